@@ -0,0 +1,217 @@
+// Copyright 2015-2025 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Provisioning of member zones from catalog zones, [RFC 9432](https://www.rfc-editor.org/rfc/rfc9432)
+
+use std::{collections::HashMap, net::SocketAddr, sync::Arc, time::Duration};
+
+use serde::Deserialize;
+use tracing::{info, warn};
+
+use hickory_proto::rr::{LowerName, Name};
+use hickory_server::authority::{
+    AuthorityObject, Views,
+    catalog_zone::{CatalogZone, MemberZones},
+};
+#[cfg(feature = "resolver")]
+use hickory_server::store::forwarder::{ForwardAuthority, ForwardConfig};
+
+use crate::{Config, secondary::load_secondary};
+
+static DEFAULT_REFRESH: u64 = 60;
+
+/// Configuration of a catalog zone, the zone itself must also be configured in `zones`
+#[derive(Clone, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct CatalogZoneConfig {
+    /// name of the catalog zone
+    pub zone: String,
+    /// seconds between reading the catalog zone again, defaults to 60
+    pub refresh: Option<u64>,
+    /// how the member zones are served
+    pub members: MemberZoneConfig,
+    /// how the member zones of a group are served, instead of `members`
+    #[serde(default)]
+    pub groups: HashMap<String, MemberZoneConfig>,
+}
+
+impl CatalogZoneConfig {
+    /// returns the name of the catalog zone
+    pub fn zone(&self) -> Result<Name, String> {
+        Name::parse(&self.zone, Some(&Name::root()))
+            .map_err(|err| format!("invalid catalog zone name {}: {err}", self.zone))
+    }
+
+    /// time between reading the catalog zone again
+    pub fn refresh(&self) -> Duration {
+        Duration::from_secs(self.refresh.unwrap_or(DEFAULT_REFRESH))
+    }
+}
+
+/// How the member zones of a catalog zone are served
+#[derive(Clone, Deserialize, Debug)]
+#[serde(tag = "zone_type")]
+#[serde(deny_unknown_fields)]
+pub enum MemberZoneConfig {
+    /// Secondary zones transferred from their primaries
+    Secondary {
+        /// addresses of the primaries, tried in order
+        primaries: Vec<SocketAddr>,
+        /// Allow AXFR of the zones
+        allow_axfr: Option<bool>,
+    },
+    /// Zones forwarded to other name servers
+    #[cfg(feature = "resolver")]
+    Forward(Box<ForwardConfig>),
+}
+
+impl MemberZoneConfig {
+    /// loads the authorities serving the member zone `zone`
+    pub async fn load(&self, zone: &Name) -> Result<Vec<Arc<dyn AuthorityObject>>, String> {
+        let authority: Arc<dyn AuthorityObject> = match self {
+            Self::Secondary {
+                primaries,
+                allow_axfr,
+            } => load_secondary(zone, primaries, allow_axfr.unwrap_or(false)).await?,
+            #[cfg(feature = "resolver")]
+            Self::Forward(config) => Arc::new(
+                ForwardAuthority::builder_tokio(ForwardConfig::clone(config))
+                    .with_origin(zone.clone())
+                    .build()?,
+            ),
+        };
+
+        Ok(vec![authority])
+    }
+}
+
+/// Keeps the member zones of a catalog zone in sync with the catalog zone
+pub struct CatalogZoneConsumer {
+    config: CatalogZoneConfig,
+    view: String,
+    authority: Arc<dyn AuthorityObject>,
+    member_zones: Arc<MemberZones>,
+}
+
+impl CatalogZoneConsumer {
+    /// Creates the consumers of the catalog zones configured in `config`
+    ///
+    /// The member zones are served by the views serving the catalog zones, they are added by
+    /// [`Self::refresh`].
+    pub fn from_config(views: &mut Views, config: &Config) -> Result<Vec<Self>, String> {
+        let mut consumers = Vec::new();
+        for catalog_zone in config.catalog_zones() {
+            let name = LowerName::from(catalog_zone.zone()?);
+
+            let mut found = false;
+            for view in views.views_mut() {
+                if !view.catalog().contains(&name) {
+                    continue;
+                }
+
+                let Some(authority) = view
+                    .catalog()
+                    .find(&name)
+                    .and_then(|authorities| authorities.first().cloned())
+                else {
+                    continue;
+                };
+
+                // catalog zones served by the same view share their member zones, so that a zone
+                // can change its owner
+                let member_zones = match consumers
+                    .iter()
+                    .find(|consumer: &&Self| consumer.view == view.name())
+                {
+                    Some(consumer) => consumer.member_zones.clone(),
+                    None => {
+                        let member_zones = Arc::new(MemberZones::default());
+                        view.catalog_mut().set_member_zones(member_zones.clone());
+                        member_zones
+                    }
+                };
+
+                found = true;
+                consumers.push(Self {
+                    config: catalog_zone.clone(),
+                    view: view.name().to_owned(),
+                    authority,
+                    member_zones,
+                });
+            }
+
+            if !found {
+                return Err(format!(
+                    "catalog zone {name} is not configured in zones or views"
+                ));
+            }
+        }
+
+        Ok(consumers)
+    }
+
+    /// Reads the catalog zone and adds or removes its member zones
+    pub async fn refresh(&self) {
+        let catalog = match CatalogZone::from_authority(&*self.authority).await {
+            Ok(catalog) => catalog,
+            Err(e) => {
+                warn!("{e}, keeping its member zones");
+                return;
+            }
+        };
+
+        let changes = self.member_zones.changes(&catalog);
+        for zone in &changes.removed {
+            info!(
+                "removing member zone {zone} of catalog zone {} in view {}",
+                catalog.origin(),
+                self.view
+            );
+            self.member_zones.remove(zone);
+        }
+
+        for member in &changes.added {
+            let config = member
+                .group
+                .as_ref()
+                .and_then(|group| self.config.groups.get(group))
+                .unwrap_or(&self.config.members);
+
+            match config.load(&member.zone).await {
+                Ok(authorities) => {
+                    info!(
+                        "adding member zone {} of catalog zone {} in view {}",
+                        member.zone,
+                        catalog.origin(),
+                        self.view
+                    );
+                    self.member_zones
+                        .insert(catalog.origin(), member, authorities);
+                }
+                Err(e) => warn!("failed to load member zone {}: {e}", member.zone),
+            }
+        }
+    }
+
+    /// The member zones served by the view of the catalog zone
+    pub fn member_zones(&self) -> &MemberZones {
+        &self.member_zones
+    }
+
+    /// Spawns a task refreshing the member zones periodically
+    pub fn spawn(self) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.config.refresh());
+            // the first tick completes immediately
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                self.refresh().await;
+            }
+        });
+    }
+}
@@ -0,0 +1,796 @@
+// Copyright 2015-2025 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Runtime control channel of the server, used by `hickory-ctl`
+//!
+//! A client connects to the Unix socket or the TCP address of the channel and receives a greeting
+//! line carrying a random nonce. It then sends a single command line and reads the response until
+//! the server closes the connection. Over TCP the command is prefixed by the hex encoded HMAC of
+//! the nonce and the command, computed with the key shared by the server and the client.
+
+use std::{
+    fmt::Write as _,
+    io,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, RwLock,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+
+use data_encoding::HEXLOWER;
+#[cfg(feature = "__dnssec")]
+use data_encoding::HEXLOWER_PERMISSIVE;
+use serde::Deserialize;
+#[cfg(unix)]
+use tokio::net::UnixListener;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader},
+    net::TcpListener,
+    time::timeout,
+};
+use tracing::{debug, info, warn};
+
+#[cfg(feature = "__dnssec")]
+use hickory_proto::dnssec::rdata::tsig::TsigAlgorithm;
+use hickory_proto::{
+    op::ResponseCode,
+    rr::{LowerName, Name, RData, RecordType},
+};
+#[cfg(feature = "__dnssec")]
+use hickory_server::{authority::Nsec3QueryInfo, dnssec::NxProofKind};
+use hickory_server::{
+    authority::{
+        AuthorityObject, LookupControlFlow, LookupObject, LookupOptions, MessageRequest,
+        UpdateResult, Views, ZoneType,
+    },
+    server::RequestInfo,
+};
+
+use crate::{Config, ZoneConfig};
+
+/// First word of the line sent by the server when a client connects, followed by the nonce
+pub const GREETING: &str = "hickory-dns-control";
+
+/// Prefix of the responses to failed commands
+pub const ERROR_PREFIX: &str = "error: ";
+
+/// Time given to a client to send its command
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Maximum length of a command line
+const MAX_COMMAND_LEN: u64 = 4096;
+
+/// Function changing the log filter of the server, e.g. to `debug` or `hickory_server=trace`
+pub type LogFilter = Box<dyn Fn(&str) -> Result<(), String> + Send + Sync>;
+
+/// Configuration of the control channel
+///
+/// At least one of `unix_socket` and `listen_addr` must be set.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ControlConfig {
+    /// Unix socket to listen on, only the owner of the server process may connect to it
+    pub unix_socket: Option<PathBuf>,
+    /// TCP address to listen on, commands must be authenticated with `key`
+    pub listen_addr: Option<SocketAddr>,
+    /// key authenticating the commands received over TCP
+    #[cfg(feature = "__dnssec")]
+    pub key: Option<ControlKeyConfig>,
+}
+
+impl ControlConfig {
+    /// binds the listeners of the channel, the key is read relative to `zone_dir`
+    #[cfg_attr(not(feature = "__dnssec"), allow(unused_variables))]
+    pub async fn bind(&self, zone_dir: &Path) -> Result<ControlListeners, String> {
+        if self.unix_socket.is_none() && self.listen_addr.is_none() {
+            return Err("the control channel requires a unix_socket or a listen_addr".to_string());
+        }
+
+        #[cfg(unix)]
+        let unix = match &self.unix_socket {
+            Some(path) => Some(bind_unix_socket(path)?),
+            None => None,
+        };
+        #[cfg(not(unix))]
+        if self.unix_socket.is_some() {
+            return Err("control sockets are only supported on Unix".to_string());
+        }
+
+        let tcp = match self.listen_addr {
+            #[cfg(feature = "__dnssec")]
+            Some(addr) => {
+                let key = self
+                    .key
+                    .as_ref()
+                    .ok_or("the control channel requires a key to listen on TCP")?
+                    .load(zone_dir)?;
+                let listener = TcpListener::bind(addr).await.map_err(|e| {
+                    format!("failed to bind control channel to TCP address {addr}: {e}")
+                })?;
+                info!("listening for control commands on TCP {addr}");
+                Some((listener, key))
+            }
+            #[cfg(not(feature = "__dnssec"))]
+            Some(_) => {
+                return Err(
+                    "the control channel requires DNSSEC support to listen on TCP".to_string(),
+                );
+            }
+            None => None,
+        };
+
+        Ok(ControlListeners {
+            #[cfg(unix)]
+            unix,
+            tcp,
+        })
+    }
+}
+
+#[cfg(unix)]
+fn bind_unix_socket(path: &Path) -> Result<UnixListener, String> {
+    use std::{
+        fs,
+        os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt},
+    };
+
+    // a socket left behind by a previous run would make the bind fail, anything else at the path
+    // is left alone
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => {
+            fs::remove_file(path)
+                .map_err(|e| format!("failed to remove control socket {}: {e}", path.display()))?;
+        }
+        Ok(_) => {
+            return Err(format!(
+                "control socket path {} exists and is not a socket",
+                path.display()
+            ));
+        }
+        Err(_) => {}
+    }
+
+    // the socket is bound in a directory only the owner can enter, and moved into place once its
+    // permissions are restricted, so that nobody else can connect in between
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| format!("control socket path {} has no file name", path.display()))?;
+    let mut private_dir = path.to_path_buf();
+    private_dir.set_file_name(format!(
+        ".{}.{}",
+        file_name.to_string_lossy(),
+        std::process::id()
+    ));
+    fs::DirBuilder::new()
+        .mode(0o700)
+        .create(&private_dir)
+        .map_err(|e| format!("failed to create {}: {e}", private_dir.display()))?;
+
+    let private_path = private_dir.join("control.sock");
+    let listener = UnixListener::bind(&private_path)
+        .map_err(|e| format!("failed to bind control socket {}: {e}", path.display()))
+        .and_then(|listener| {
+            fs::set_permissions(&private_path, fs::Permissions::from_mode(0o600)).map_err(|e| {
+                format!(
+                    "failed to set permissions of control socket {}: {e}",
+                    path.display()
+                )
+            })?;
+            fs::rename(&private_path, path)
+                .map_err(|e| format!("failed to move control socket to {}: {e}", path.display()))?;
+            Ok(listener)
+        });
+
+    let _ = fs::remove_file(&private_path);
+    if let Err(e) = fs::remove_dir(&private_dir) {
+        warn!("failed to remove {}: {e}", private_dir.display());
+    }
+
+    let listener = listener?;
+    info!("listening for control commands on {}", path.display());
+    Ok(listener)
+}
+
+/// Configuration of the key authenticating control commands
+#[cfg(feature = "__dnssec")]
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ControlKeyConfig {
+    /// the MAC algorithm, e.g. hmac-sha256
+    pub algorithm: TsigAlgorithm,
+    /// file path to the raw key bytes, relative to the zone directory
+    pub key_path: PathBuf,
+}
+
+#[cfg(feature = "__dnssec")]
+impl ControlKeyConfig {
+    /// reads the key from `key_path`
+    pub fn load(&self, zone_dir: &Path) -> Result<ControlKey, String> {
+        ControlKey::from_file(self.algorithm.clone(), &zone_dir.join(&self.key_path))
+    }
+}
+
+/// Key shared by the server and the clients of the control channel
+#[cfg(feature = "__dnssec")]
+#[derive(Clone)]
+pub struct ControlKey {
+    algorithm: TsigAlgorithm,
+    key: Vec<u8>,
+}
+
+#[cfg(feature = "__dnssec")]
+impl ControlKey {
+    /// Reads the raw key bytes from `path`
+    pub fn from_file(algorithm: TsigAlgorithm, path: &Path) -> Result<Self, String> {
+        info!("reading control key: {}", path.display());
+        let key = std::fs::read(path)
+            .map_err(|e| format!("error reading control key from {}: {e}", path.display()))?;
+        Ok(Self { algorithm, key })
+    }
+
+    /// Returns the hex encoded MAC of the command for the nonce sent by the server
+    pub fn sign(&self, nonce: &str, command: &str) -> Result<String, String> {
+        self.algorithm
+            .mac_data(&self.key, &Self::message(nonce, command))
+            .map(|mac| HEXLOWER.encode(&mac))
+            .map_err(|e| format!("failed to sign command: {e}"))
+    }
+
+    fn verify(&self, nonce: &str, command: &str, mac: &str) -> bool {
+        let Ok(mac) = HEXLOWER_PERMISSIVE.decode(mac.as_bytes()) else {
+            return false;
+        };
+
+        self.algorithm
+            .verify_mac(&self.key, &Self::message(nonce, command), &mac)
+            .is_ok()
+    }
+
+    fn message(nonce: &str, command: &str) -> Vec<u8> {
+        format!("{nonce} {command}").into_bytes()
+    }
+}
+
+/// Listeners of the control channel, see [`ControlConfig::bind`]
+pub struct ControlListeners {
+    #[cfg(unix)]
+    unix: Option<UnixListener>,
+    tcp: Option<(TcpListener, Key)>,
+}
+
+#[cfg(feature = "__dnssec")]
+type Key = ControlKey;
+#[cfg(not(feature = "__dnssec"))]
+type Key = std::convert::Infallible;
+
+/// Sends a command over a connection to the control channel and returns the response
+///
+/// The command is signed with `key` if it's set, which is required for TCP connections.
+pub async fn send_command<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: S,
+    command: &str,
+    #[cfg(feature = "__dnssec")] key: Option<&ControlKey>,
+) -> Result<String, String> {
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut reader = BufReader::new(reader);
+
+    let mut greeting = String::new();
+    reader
+        .read_line(&mut greeting)
+        .await
+        .map_err(|e| format!("failed to read greeting: {e}"))?;
+    #[cfg_attr(not(feature = "__dnssec"), allow(unused_variables))]
+    let nonce = match greeting.trim_end().split_once(' ') {
+        Some((GREETING, nonce)) => nonce,
+        _ => return Err(format!("unexpected greeting: {}", greeting.trim_end())),
+    };
+
+    #[cfg(feature = "__dnssec")]
+    let line = match key {
+        Some(key) => format!("{} {command}\n", key.sign(nonce, command)?),
+        None => format!("{command}\n"),
+    };
+    #[cfg(not(feature = "__dnssec"))]
+    let line = format!("{command}\n");
+    writer
+        .write_all(line.as_bytes())
+        .await
+        .map_err(|e| format!("failed to send command: {e}"))?;
+
+    let mut response = String::new();
+    reader
+        .read_to_string(&mut response)
+        .await
+        .map_err(|e| format!("failed to read response: {e}"))?;
+    Ok(response)
+}
+
+/// Server of the control channel, executing the commands it receives
+pub struct ControlServer {
+    config: Config,
+    zone_dir: PathBuf,
+    zones: ControlledZones,
+    log_filter: LogFilter,
+}
+
+impl ControlServer {
+    /// Creates a server controlling the `zones` loaded from `config`
+    pub fn new(
+        config: Config,
+        zone_dir: PathBuf,
+        zones: ControlledZones,
+        log_filter: LogFilter,
+    ) -> Self {
+        Self {
+            config,
+            zone_dir,
+            zones,
+            log_filter,
+        }
+    }
+
+    /// Spawns tasks accepting connections on the listeners
+    pub fn spawn(self, listeners: ControlListeners) {
+        let server = Arc::new(self);
+
+        #[cfg(unix)]
+        if let Some(listener) = listeners.unix {
+            let server = server.clone();
+            tokio::spawn(async move {
+                loop {
+                    match listener.accept().await {
+                        Ok((stream, _)) => {
+                            let server = server.clone();
+                            tokio::spawn(async move { server.serve(stream, None).await });
+                        }
+                        Err(e) => warn!("error accepting control connection: {e}"),
+                    }
+                }
+            });
+        }
+
+        if let Some((listener, key)) = listeners.tcp {
+            let key = Arc::new(key);
+            tokio::spawn(async move {
+                loop {
+                    match listener.accept().await {
+                        Ok((stream, src)) => {
+                            debug!("control connection from {src}");
+                            let server = server.clone();
+                            let key = key.clone();
+                            tokio::spawn(async move { server.serve(stream, Some(&key)).await });
+                        }
+                        Err(e) => warn!("error accepting control connection: {e}"),
+                    }
+                }
+            });
+        }
+    }
+
+    async fn serve<S: AsyncRead + AsyncWrite + Unpin>(&self, stream: S, key: Option<&Key>) {
+        if let Err(e) = self.serve_inner(stream, key).await {
+            debug!("control connection failed: {e}");
+        }
+    }
+
+    async fn serve_inner<S: AsyncRead + AsyncWrite + Unpin>(
+        &self,
+        stream: S,
+        key: Option<&Key>,
+    ) -> io::Result<()> {
+        let (reader, mut writer) = tokio::io::split(stream);
+
+        let nonce = HEXLOWER.encode(&rand::random::<[u8; 16]>());
+        writer
+            .write_all(format!("{GREETING} {nonce}\n").as_bytes())
+            .await?;
+
+        let mut line = String::new();
+        let mut reader = BufReader::new(reader).take(MAX_COMMAND_LEN);
+        timeout(COMMAND_TIMEOUT, reader.read_line(&mut line))
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "no command received"))??;
+        let line = line.trim_end();
+
+        let command = match key {
+            #[cfg(feature = "__dnssec")]
+            Some(key) => match line.split_once(' ') {
+                Some((mac, command)) if key.verify(&nonce, command, mac) => Some(command),
+                _ => None,
+            },
+            #[cfg(not(feature = "__dnssec"))]
+            Some(key) => match *key {},
+            None => Some(line),
+        };
+
+        let response = match command {
+            Some(command) => {
+                info!("executing control command: {command}");
+                match self.execute(command).await {
+                    Ok(response) => response,
+                    Err(e) => format!("{ERROR_PREFIX}{e}\n"),
+                }
+            }
+            None => {
+                warn!("rejected control command with an invalid MAC");
+                format!("{ERROR_PREFIX}authentication failed\n")
+            }
+        };
+
+        writer.write_all(response.as_bytes()).await?;
+        writer.shutdown().await
+    }
+
+    /// Executes a command and returns its output
+    async fn execute(&self, command: &str) -> Result<String, String> {
+        let mut args = command.split_whitespace();
+        match (args.next(), args.next(), args.next(), args.next()) {
+            (Some("status"), None, _, _) => Ok(self.status().await),
+            (Some("reload"), Some(zone), view, None) => self.reload(zone, view).await,
+            (Some("freeze"), Some(zone), view, None) => self.freeze(zone, view, true),
+            (Some("thaw"), Some(zone), view, None) => self.freeze(zone, view, false),
+            (Some("flush"), name, None, _) => self.flush(name),
+            (Some("dump-cache"), None, _, _) => Ok(self.dump_cache()),
+            (Some("loglevel"), Some(filter), None, _) => {
+                (self.log_filter)(filter)?;
+                Ok(format!("log filter set to {filter}\n"))
+            }
+            _ => Err(format!("unknown command: {command}")),
+        }
+    }
+
+    /// Lists the zones with their type, serial and signing status
+    async fn status(&self) -> String {
+        let mut output = String::new();
+        for zone in &self.zones.zones {
+            let mut serial = None;
+            let mut signed = false;
+            let mut frozen = false;
+            let mut zone_type = ZoneType::External;
+            for authority in &zone.authorities {
+                let inner = authority.inner();
+                zone_type = inner.zone_type();
+                frozen |= authority.is_frozen();
+                if !matches!(zone_type, ZoneType::Primary | ZoneType::Secondary) {
+                    // lookups on forwarders and recursors would be sent upstream
+                    continue;
+                }
+
+                if let Some(Ok(lookup)) = inner.soa().await.map_result() {
+                    serial = serial.or(lookup.iter().find_map(|record| match record.data() {
+                        RData::SOA(soa) => Some(soa.serial()),
+                        _ => None,
+                    }));
+                }
+
+                let dnskeys = inner
+                    .lookup(inner.origin(), RecordType::DNSKEY, LookupOptions::default())
+                    .await;
+                signed |= matches!(dnskeys.map_result(), Some(Ok(lookup)) if !lookup.is_empty());
+            }
+
+            let _ = write!(
+                output,
+                "{} view {} type {zone_type:?}",
+                zone.name, zone.view
+            );
+            if let Some(serial) = serial {
+                let _ = write!(output, " serial {serial}");
+            }
+            if matches!(zone_type, ZoneType::Primary | ZoneType::Secondary) {
+                output.push_str(if signed { " signed" } else { " unsigned" });
+            }
+            if frozen {
+                output.push_str(" frozen");
+            }
+            output.push('\n');
+        }
+
+        output
+    }
+
+    /// Loads the zone again from its configuration
+    async fn reload(&self, zone: &str, view: Option<&str>) -> Result<String, String> {
+        let mut output = String::new();
+        for zone in self.zones.find(zone, view)? {
+            let authorities = self.zone_config(zone).load(&self.zone_dir).await?;
+            if authorities.len() != zone.authorities.len() {
+                return Err(format!("the stores of zone {} changed", zone.name));
+            }
+
+            for (controlled, authority) in zone.authorities.iter().zip(authorities) {
+                controlled.replace(authority);
+            }
+
+            info!("reloaded zone {} in view {}", zone.name, zone.view);
+            let _ = writeln!(output, "reloaded zone {} in view {}", zone.name, zone.view);
+        }
+
+        Ok(output)
+    }
+
+    /// Refuses (or allows again) dynamic updates of the zone
+    fn freeze(&self, zone: &str, view: Option<&str>, frozen: bool) -> Result<String, String> {
+        let zones = self.zones.find(zone, view)?;
+        if let Some(zone) = zones.iter().find(|zone| {
+            zone.authorities
+                .iter()
+                .any(|a| !matches!(a.inner().zone_type(), ZoneType::Primary))
+        }) {
+            return Err(format!("zone {} is not a primary zone", zone.name));
+        }
+
+        let mut output = String::new();
+        for zone in zones {
+            for authority in &zone.authorities {
+                authority.frozen.store(frozen, Ordering::Relaxed);
+            }
+
+            let action = if frozen { "froze" } else { "thawed" };
+            info!("{action} zone {} in view {}", zone.name, zone.view);
+            let _ = writeln!(output, "{action} zone {} in view {}", zone.name, zone.view);
+        }
+
+        Ok(output)
+    }
+
+    /// Removes the cached records of `name` and below, or all of them
+    fn flush(&self, name: Option<&str>) -> Result<String, String> {
+        let name = name.map(parse_name).transpose()?;
+
+        let mut flushed = 0;
+        for zone in &self.zones.zones {
+            for authority in &zone.authorities {
+                if authority.inner().clear_cache(name.as_ref()) {
+                    flushed += 1;
+                }
+            }
+        }
+
+        match flushed {
+            0 => Err("no zone caches records".to_string()),
+            _ => Ok(format!("flushed {flushed} caches\n")),
+        }
+    }
+
+    /// Lists the cached records of every zone caching records
+    fn dump_cache(&self) -> String {
+        let mut output = String::new();
+        for zone in &self.zones.zones {
+            for authority in &zone.authorities {
+                let Some(records) = authority.inner().cached_records() else {
+                    continue;
+                };
+
+                let _ = writeln!(
+                    output,
+                    "; cache of zone {} in view {}",
+                    zone.name, zone.view
+                );
+                for record in records {
+                    let _ = writeln!(output, "{record}");
+                }
+            }
+        }
+
+        output
+    }
+
+    fn zone_config(&self, zone: &ControlledZone) -> &ZoneConfig {
+        match zone.view_index {
+            Some(view) => &self.config.views()[view].zones[zone.zone_index],
+            None => &self.config.zones()[zone.zone_index],
+        }
+    }
+}
+
+/// The zones of the server, wrapped to be reloaded and frozen by the control channel
+#[derive(Default)]
+pub struct ControlledZones {
+    zones: Vec<ControlledZone>,
+}
+
+impl ControlledZones {
+    /// Wraps the authorities of the zones configured in `config` which are served by `views`
+    pub fn wrap(views: &mut Views, config: &Config) -> Result<Self, String> {
+        let mut zones = Vec::new();
+        for view in views.views_mut() {
+            let view_index = config.views().iter().position(|v| v.name == view.name());
+            let configs = match view_index {
+                Some(index) => &config.views()[index].zones[..],
+                None => config.zones(),
+            };
+
+            for (zone_index, zone) in configs.iter().enumerate() {
+                let name = zone
+                    .zone()
+                    .map_err(|err| format!("failed to read zone name: {err}"))?;
+                let lower = LowerName::from(name.clone());
+                let Some(authorities) = view.catalog().find(&lower) else {
+                    continue;
+                };
+
+                let authorities = authorities
+                    .iter()
+                    .map(|authority| Arc::new(ControlledAuthority::new(authority.clone())))
+                    .collect::<Vec<_>>();
+                view.catalog_mut().upsert(
+                    lower,
+                    authorities
+                        .iter()
+                        .map(|authority| authority.clone() as Arc<dyn AuthorityObject>)
+                        .collect(),
+                );
+
+                zones.push(ControlledZone {
+                    view: view.name().to_owned(),
+                    view_index,
+                    zone_index,
+                    name,
+                    authorities,
+                });
+            }
+        }
+
+        Ok(Self { zones })
+    }
+
+    /// Returns the zones named `zone`, only the one of `view` if it's set
+    fn find(&self, zone: &str, view: Option<&str>) -> Result<Vec<&ControlledZone>, String> {
+        let name = parse_name(zone)?;
+        let zones = self
+            .zones
+            .iter()
+            .filter(|zone| zone.name == name && view.map_or(true, |view| zone.view == view))
+            .collect::<Vec<_>>();
+
+        match (zones.is_empty(), view) {
+            (true, Some(view)) => Err(format!("no zone {name} in view {view}")),
+            (true, None) => Err(format!("no zone {name}")),
+            (false, _) => Ok(zones),
+        }
+    }
+}
+
+struct ControlledZone {
+    view: String,
+    /// index of the view in the configuration, `None` if the zones aren't in views
+    view_index: Option<usize>,
+    zone_index: usize,
+    name: Name,
+    authorities: Vec<Arc<ControlledAuthority>>,
+}
+
+fn parse_name(name: &str) -> Result<Name, String> {
+    Name::parse(name, Some(&Name::root())).map_err(|e| format!("invalid name {name}: {e}"))
+}
+
+/// An authority which can be replaced when its zone is reloaded and refuse updates when frozen
+struct ControlledAuthority {
+    origin: LowerName,
+    #[cfg(feature = "__dnssec")]
+    nx_proof_kind: Option<NxProofKind>,
+    inner: RwLock<Arc<dyn AuthorityObject>>,
+    frozen: AtomicBool,
+}
+
+impl ControlledAuthority {
+    fn new(inner: Arc<dyn AuthorityObject>) -> Self {
+        Self {
+            origin: inner.origin().clone(),
+            #[cfg(feature = "__dnssec")]
+            nx_proof_kind: inner.nx_proof_kind().cloned(),
+            inner: RwLock::new(inner),
+            frozen: AtomicBool::new(false),
+        }
+    }
+
+    fn inner(&self) -> Arc<dyn AuthorityObject> {
+        self.inner.read().expect("control lock poisoned").clone()
+    }
+
+    fn replace(&self, inner: Arc<dyn AuthorityObject>) {
+        *self.inner.write().expect("control lock poisoned") = inner;
+    }
+
+    fn is_frozen(&self) -> bool {
+        self.frozen.load(Ordering::Relaxed)
+    }
+}
+
+#[async_trait::async_trait]
+impl AuthorityObject for ControlledAuthority {
+    fn zone_type(&self) -> ZoneType {
+        self.inner().zone_type()
+    }
+
+    fn is_axfr_allowed(&self) -> bool {
+        self.inner().is_axfr_allowed()
+    }
+
+    fn can_validate_dnssec(&self) -> bool {
+        self.inner().can_validate_dnssec()
+    }
+
+    async fn update(&self, update: &MessageRequest) -> UpdateResult<bool> {
+        if self.is_frozen() {
+            return Err(ResponseCode::Refused);
+        }
+
+        self.inner().update(update).await
+    }
+
+    fn origin(&self) -> &LowerName {
+        &self.origin
+    }
+
+    async fn lookup(
+        &self,
+        name: &LowerName,
+        rtype: RecordType,
+        lookup_options: LookupOptions,
+    ) -> LookupControlFlow<Box<dyn LookupObject>> {
+        self.inner().lookup(name, rtype, lookup_options).await
+    }
+
+    async fn consult(
+        &self,
+        name: &LowerName,
+        rtype: RecordType,
+        lookup_options: LookupOptions,
+        last_result: LookupControlFlow<Box<dyn LookupObject>>,
+    ) -> LookupControlFlow<Box<dyn LookupObject>> {
+        self.inner()
+            .consult(name, rtype, lookup_options, last_result)
+            .await
+    }
+
+    async fn search(
+        &self,
+        request_info: RequestInfo<'_>,
+        lookup_options: LookupOptions,
+    ) -> LookupControlFlow<Box<dyn LookupObject>> {
+        self.inner().search(request_info, lookup_options).await
+    }
+
+    async fn get_nsec_records(
+        &self,
+        name: &LowerName,
+        lookup_options: LookupOptions,
+    ) -> LookupControlFlow<Box<dyn LookupObject>> {
+        self.inner().get_nsec_records(name, lookup_options).await
+    }
+
+    #[cfg(feature = "__dnssec")]
+    async fn get_nsec3_records(
+        &self,
+        info: Nsec3QueryInfo<'_>,
+        lookup_options: LookupOptions,
+    ) -> LookupControlFlow<Box<dyn LookupObject>> {
+        self.inner().get_nsec3_records(info, lookup_options).await
+    }
+
+    #[cfg(feature = "__dnssec")]
+    fn nx_proof_kind(&self) -> Option<&NxProofKind> {
+        self.nx_proof_kind.as_ref()
+    }
+
+    fn clear_cache(&self, name: Option<&Name>) -> bool {
+        self.inner().clear_cache(name)
+    }
+
+    fn cached_records(&self) -> Option<Vec<hickory_proto::rr::Record>> {
+        self.inner().cached_records()
+    }
+
+    fn save_cache(&self) -> Option<std::io::Result<usize>> {
+        self.inner().save_cache()
+    }
+}
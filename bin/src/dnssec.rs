@@ -18,7 +18,15 @@ use tracing::info;
 use hickory_proto::rr::domain::Name;
 use hickory_proto::{
     ProtoError,
-    dnssec::{Algorithm, SigSigner, SigningKey, rdata::DNSKEY, rdata::KEY, rdata::key::KeyUsage},
+    dnssec::{
+        Algorithm, SigSigner, SigningKey,
+        rdata::DNSKEY,
+        rdata::KEY,
+        rdata::key::KeyUsage,
+        rdata::tsig::TsigAlgorithm,
+        remote::{RemoteSignerAddr, RemoteSigningKey},
+        tsig::TSigner,
+    },
     rr::domain::IntoName,
 };
 use hickory_server::authority::DnssecAuthority;
@@ -27,13 +35,17 @@ use hickory_server::authority::DnssecAuthority;
 #[derive(Deserialize, PartialEq, Eq, Debug)]
 #[serde(deny_unknown_fields)]
 pub struct KeyConfig {
-    /// file path to the key
+    /// file path to the key, not used if `remote_signer` is set
+    #[serde(default)]
     pub key_path: PathBuf,
     /// the type of key stored
     pub algorithm: Algorithm,
     /// the name to use when signing records, e.g. ns.example.com
     pub signer_name: Option<String>,
     pub purpose: KeyPurpose,
+    /// an external signer holding the private key, instead of `key_path`
+    #[serde(default)]
+    pub remote_signer: Option<RemoteSignerConfig>,
 }
 
 impl KeyConfig {
@@ -69,7 +81,10 @@ impl KeyConfig {
         };
 
         // read the key in
-        let key = key_from_file(&self.key_path, self.algorithm)?;
+        let key = match &self.remote_signer {
+            Some(remote_signer) => remote_signer.connect(self.algorithm)?,
+            None => key_from_file(&self.key_path, self.algorithm)?,
+        };
 
         // add the key to the zone
         // TODO: allow the duration of signatures to be customized
@@ -133,6 +148,40 @@ impl KeyConfig {
     }
 }
 
+/// Configuration for a key held by an external signer
+///
+/// See [`hickory_proto::dnssec::remote`] for the protocol spoken with the signer.
+#[derive(Deserialize, PartialEq, Eq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct RemoteSignerConfig {
+    /// address of the signer, e.g. unix:/run/signer.sock or tcp:127.0.0.1:5300
+    pub address: String,
+    /// identifier of the key in the signer
+    pub key_id: String,
+}
+
+impl RemoteSignerConfig {
+    fn connect(&self, algorithm: Algorithm) -> Result<Box<dyn SigningKey>, String> {
+        let addr = self
+            .address
+            .parse::<RemoteSignerAddr>()
+            .map_err(|e| format!("bad remote signer address: {e}"))?;
+
+        info!("connecting to signer: {addr}, key: {}", self.key_id);
+        let key = RemoteSigningKey::connect(addr, self.key_id.clone())
+            .map_err(|e| format!("error loading key {} from signer: {e}", self.key_id))?;
+        if key.algorithm() != algorithm {
+            return Err(format!(
+                "signer key {} is {}, expected {algorithm}",
+                self.key_id,
+                key.algorithm()
+            ));
+        }
+
+        Ok(Box::new(key))
+    }
+}
+
 /// What a key will be used for
 #[derive(Clone, Copy, Deserialize, PartialEq, Eq, Debug)]
 pub enum KeyPurpose {
@@ -150,6 +199,36 @@ pub enum KeyPurpose {
     ZoneUpdateAuth,
 }
 
+/// A TSIG key shared with clients, used to select the view answering their requests
+#[derive(Deserialize, PartialEq, Eq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct TsigKeyConfig {
+    /// the name of the key, which must match the name used by clients
+    pub name: String,
+    /// the MAC algorithm, e.g. hmac-sha256
+    pub algorithm: TsigAlgorithm,
+    /// file path to the raw key bytes, relative to the zone directory
+    pub key_path: PathBuf,
+    /// allowed difference in seconds between the client's clock and ours, defaults to 300
+    pub fudge: Option<u16>,
+}
+
+impl TsigKeyConfig {
+    /// reads the key from `key_path`
+    pub fn try_into_signer(&self, zone_dir: &Path) -> Result<TSigner, String> {
+        let name = Name::parse(&self.name, Some(&Name::root()))
+            .map_err(|e| format!("bad TSIG key name {}: {e}", self.name))?;
+
+        let key_path = zone_dir.join(&self.key_path);
+        info!("reading TSIG key: {}", key_path.display());
+        let key = std::fs::read(&key_path)
+            .map_err(|e| format!("error reading TSIG key from {}: {e}", key_path.display()))?;
+
+        TSigner::new(key, self.algorithm.clone(), name, self.fudge.unwrap_or(300))
+            .map_err(|e| format!("error loading TSIG key {}: {e}", self.name))
+    }
+}
+
 pub fn key_from_file(path: &Path, algorithm: Algorithm) -> Result<Box<dyn SigningKey>, String> {
     use std::fs::File;
     use std::io::Read;
@@ -0,0 +1,186 @@
+// Copyright 2015-2025 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! The `hickory-ctl` binary for controlling a running `hickory-dns` server
+//!
+//! ```text
+//! Usage: hickory-ctl [options] <command>
+//!
+//! Commands:
+//!    status                Show the zones with their serial and signing status
+//!    reload ZONE [VIEW]    Load a zone again from its configuration
+//!    freeze ZONE [VIEW]    Refuse dynamic updates of a zone
+//!    thaw ZONE [VIEW]      Allow dynamic updates of a frozen zone again
+//!    flush [NAME]          Flush the cache of forwarder and recursor zones
+//!    dump-cache            Print the cached records of forwarder and recursor zones
+//!    loglevel FILTER       Change the log filter, e.g. `debug`
+//!
+//! Options:
+//!    -s PATH, --socket=PATH    Unix socket of the control channel
+//!    -a ADDR, --server=ADDR    TCP address of the control channel, requires a key
+//!    -k FILE, --key-file=FILE  File holding the key shared with the server
+//!    --algorithm=ALGORITHM     MAC algorithm of the key, default is hmac-sha256
+//! ```
+
+use std::{fmt, path::PathBuf};
+#[cfg(feature = "__dnssec")]
+use std::{net::SocketAddr, str::FromStr};
+
+use clap::{Parser, Subcommand};
+use tokio::runtime;
+
+#[cfg(feature = "__dnssec")]
+use hickory_dns::control::ControlKey;
+use hickory_dns::control::{ERROR_PREFIX, send_command};
+#[cfg(feature = "__dnssec")]
+use hickory_proto::{dnssec::rdata::tsig::TsigAlgorithm, rr::Name};
+
+/// Cli struct for all options managed with clap derive api.
+#[derive(Debug, Parser)]
+#[clap(name = "Hickory DNS control", version, about)]
+struct Cli {
+    /// Unix socket of the control channel
+    #[clap(short = 's', long = "socket", value_name = "PATH", value_hint=clap::ValueHint::FilePath)]
+    socket: Option<PathBuf>,
+
+    /// TCP address of the control channel, requires a key
+    #[cfg(feature = "__dnssec")]
+    #[clap(
+        short = 'a',
+        long = "server",
+        value_name = "ADDR",
+        requires = "key_file"
+    )]
+    server: Option<SocketAddr>,
+
+    /// File holding the key shared with the server
+    #[cfg(feature = "__dnssec")]
+    #[clap(short = 'k', long = "key-file", value_name = "FILE", value_hint=clap::ValueHint::FilePath)]
+    key_file: Option<PathBuf>,
+
+    /// MAC algorithm of the key
+    #[cfg(feature = "__dnssec")]
+    #[clap(
+        long = "algorithm",
+        value_name = "ALGORITHM",
+        default_value = "hmac-sha256"
+    )]
+    algorithm: String,
+
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Show the zones with their serial and signing status
+    Status,
+    /// Load a zone again from its configuration
+    Reload { zone: String, view: Option<String> },
+    /// Refuse dynamic updates of a zone
+    Freeze { zone: String, view: Option<String> },
+    /// Allow dynamic updates of a frozen zone again
+    Thaw { zone: String, view: Option<String> },
+    /// Flush the cache of forwarder and recursor zones, only of NAME and below if it's set
+    Flush { name: Option<String> },
+    /// Print the cached records of forwarder and recursor zones
+    DumpCache,
+    /// Change the log filter, e.g. `debug` or `hickory_server=trace`
+    Loglevel { filter: String },
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let with_view = |f: &mut fmt::Formatter<'_>, command, zone, view: &Option<String>| {
+            write!(f, "{command} {zone}")?;
+            match view {
+                Some(view) => write!(f, " {view}"),
+                None => Ok(()),
+            }
+        };
+
+        match self {
+            Self::Status => f.write_str("status"),
+            Self::Reload { zone, view } => with_view(f, "reload", zone, view),
+            Self::Freeze { zone, view } => with_view(f, "freeze", zone, view),
+            Self::Thaw { zone, view } => with_view(f, "thaw", zone, view),
+            Self::Flush { name: Some(name) } => write!(f, "flush {name}"),
+            Self::Flush { name: None } => f.write_str("flush"),
+            Self::DumpCache => f.write_str("dump-cache"),
+            Self::Loglevel { filter } => write!(f, "loglevel {filter}"),
+        }
+    }
+}
+
+/// Main method for running the control client.
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("Error: {e}");
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), String> {
+    let args = Cli::parse();
+    let command = args.command.to_string();
+
+    let runtime = runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|err| format!("failed to initialize Tokio runtime: {err}"))?;
+
+    let response = runtime.block_on(async {
+        #[cfg(feature = "__dnssec")]
+        if let Some(server) = args.server {
+            let name = Name::from_str(&args.algorithm)
+                .map_err(|err| format!("invalid algorithm {}: {err}", args.algorithm))?;
+            let key_file = args
+                .key_file
+                .as_ref()
+                .ok_or("--server requires --key-file")?;
+            let key = ControlKey::from_file(TsigAlgorithm::from_name(name), key_file)?;
+
+            let stream = tokio::net::TcpStream::connect(server)
+                .await
+                .map_err(|err| format!("failed to connect to {server}: {err}"))?;
+            return send_command(stream, &command, Some(&key)).await;
+        }
+
+        let Some(socket) = &args.socket else {
+            return Err("either --socket or --server is required".to_string());
+        };
+        connect_unix(socket, &command).await
+    })?;
+
+    match response.strip_prefix(ERROR_PREFIX) {
+        Some(error) => Err(error.trim_end().to_string()),
+        None => {
+            print!("{response}");
+            Ok(())
+        }
+    }
+}
+
+#[cfg(unix)]
+async fn connect_unix(socket: &std::path::Path, command: &str) -> Result<String, String> {
+    let stream = tokio::net::UnixStream::connect(socket)
+        .await
+        .map_err(|err| format!("failed to connect to {}: {err}", socket.display()))?;
+
+    send_command(
+        stream,
+        command,
+        #[cfg(feature = "__dnssec")]
+        None,
+    )
+    .await
+}
+
+#[cfg(not(unix))]
+async fn connect_unix(_: &std::path::Path, _: &str) -> Result<String, String> {
+    Err("control sockets are only supported on Unix".to_string())
+}
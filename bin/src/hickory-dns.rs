@@ -42,21 +42,25 @@ use tokio::{
     net::{TcpListener, UdpSocket},
     runtime,
 };
-#[cfg(any(feature = "__tls", feature = "__https", feature = "__quic"))]
-use tracing::warn;
-use tracing::{Event, Level, Subscriber, error, info};
+use tracing::{Event, Level, Subscriber, error, info, warn};
 use tracing_subscriber::{
     EnvFilter,
     fmt::{FmtContext, FormatEvent, FormatFields, FormattedFields, format},
     layer::SubscriberExt,
     registry::LookupSpan,
+    reload,
     util::SubscriberInitExt,
 };
 
-use hickory_dns::Config;
 #[cfg(feature = "__tls")]
 use hickory_dns::TlsCertConfig;
-use hickory_server::{authority::Catalog, server::ServerFuture};
+use hickory_dns::{
+    Config,
+    catalog_zone::CatalogZoneConsumer,
+    control::{ControlServer, ControlledZones, LogFilter},
+    load_catalog,
+};
+use hickory_server::{authority::Views, server::ServerFuture};
 
 /// Cli struct for all options managed with clap derive api.
 #[derive(Debug, Parser)]
@@ -183,19 +187,26 @@ fn run() -> Result<(), String> {
         _ => Level::INFO,
     };
 
-    // Setup tracing for logging based on input
+    // Setup tracing for logging based on input, the filter can be changed by the control channel
+    let (filter, filter_handle) = reload::Layer::new(
+        EnvFilter::builder()
+            .with_default_directive(level.into())
+            .from_env()
+            .map_err(|err| format!("failed to parse environment variable for tracing: {err}"))?,
+    );
     tracing_subscriber::registry()
         .with(tracing_subscriber::fmt::layer().event_format(TdnsFormatter))
-        .with(
-            EnvFilter::builder()
-                .with_default_directive(level.into())
-                .from_env()
-                .map_err(|err| {
-                    format!("failed to parse environment variable for tracing: {err}")
-                })?,
-        )
+        .with(filter)
         .init();
 
+    let log_filter: LogFilter = Box::new(move |directives| {
+        let filter = EnvFilter::try_new(directives)
+            .map_err(|err| format!("invalid log filter {directives}: {err}"))?;
+        filter_handle
+            .reload(filter)
+            .map_err(|err| format!("failed to set log filter: {err}"))
+    });
+
     info!("Hickory DNS {} starting...", hickory_client::version());
 
     let mut runtime = runtime::Builder::new_multi_thread();
@@ -207,10 +218,10 @@ fn run() -> Result<(), String> {
         .build()
         .map_err(|err| format!("failed to initialize Tokio runtime: {err}"))?;
 
-    runtime.block_on(async_run(args))
+    runtime.block_on(async_run(args, log_filter))
 }
 
-async fn async_run(args: Cli) -> Result<(), String> {
+async fn async_run(args: Cli, log_filter: LogFilter) -> Result<(), String> {
     // Load configuration files
 
     let config = args.config.clone();
@@ -218,7 +229,8 @@ async fn async_run(args: Cli) -> Result<(), String> {
 
     info!("loading configuration from: {config_path:?}");
 
-    let config = Config::read_config(config_path)
+    #[cfg_attr(not(feature = "dnstap"), allow(unused_mut))]
+    let mut config = Config::read_config(config_path)
         .map_err(|err| format!("failed to read config file from {config_path:?}: {err}"))?;
     let directory_config = config.directory().to_path_buf();
     let zonedir = args.zonedir.clone();
@@ -250,19 +262,55 @@ async fn async_run(args: Cli) -> Result<(), String> {
     let mut signal = signal(SignalKind::terminate())
         .map_err(|e| format!("failed to register signal handler: {e}"))?;
 
-    let mut catalog: Catalog = Catalog::new();
+    #[cfg(feature = "dnstap")]
+    let dnstap = match config.dnstap() {
+        Some(dnstap_config) if !args.validate => {
+            let dnstap = dnstap_config.open()?;
+            config.set_resolver_dnstap(&dnstap);
+            Some(dnstap)
+        }
+        _ => None,
+    };
+
     // configure our server based on the config_path
-    for zone in config.zones() {
-        let zone_name = zone
-            .zone()
-            .map_err(|err| format!("failed to read zone name from {config_path:?}: {err}"))?;
-
-        match zone.load(&zone_dir).await {
-            Ok(authority) => catalog.upsert(zone_name.into(), authority),
-            Err(err) => return Err(format!("could not load zone {zone_name}: {err}")),
+    let mut views = if config.views().is_empty() {
+        Views::from(load_catalog(config.zones(), &zone_dir).await?)
+    } else {
+        if !config.zones().is_empty() {
+            return Err(format!(
+                "{config_path:?} configures both zones and views, zones must be moved into views"
+            ));
+        }
+
+        let mut views = Views::new();
+        for view in config.views() {
+            views.push(
+                view.load(&zone_dir)
+                    .await
+                    .map_err(|err| format!("could not load view {}: {err}", view.name))?,
+            );
+        }
+        views
+    };
+
+    if !config.deny_recursion().is_empty() || !config.allow_recursion().is_empty() {
+        for view in views.views_mut() {
+            view.catalog_mut()
+                .set_recursion_access(config.deny_recursion(), config.allow_recursion());
+        }
+    }
+
+    if let Some(minimal_responses) = config.minimal_responses() {
+        for view in views.views_mut() {
+            view.catalog_mut().set_minimal_responses(minimal_responses);
         }
     }
 
+    let controlled_zones = match config.control() {
+        Some(_) => Some(ControlledZones::wrap(&mut views, &config)?),
+        None => None,
+    };
+
     let v4addr = config
         .listen_addrs_ipv4()
         .map_err(|err| format!("failed to parse IPv4 addresses from {config_path:?}: {err}"))?;
@@ -287,13 +335,46 @@ async fn async_run(args: Cli) -> Result<(), String> {
         return Ok(());
     }
 
+    for consumer in CatalogZoneConsumer::from_config(&mut views, &config)? {
+        consumer.refresh().await;
+        consumer.spawn();
+    }
+
+    let control = match (config.control(), controlled_zones) {
+        (Some(control_config), Some(zones)) => Some((control_config.bind(&zone_dir).await?, zones)),
+        _ => None,
+    };
+
     let deny_networks = config.deny_networks();
     let allow_networks = config.allow_networks();
     let tcp_request_timeout = config.tcp_request_timeout();
 
+    // the caches of these authorities are saved when the server stops
+    let cached_authorities = views
+        .views()
+        .iter()
+        .flat_map(|view| view.catalog().authorities().cloned())
+        .collect::<Vec<_>>();
+
     // now, run the server, based on the config
     #[cfg_attr(not(feature = "__tls"), allow(unused_mut))]
-    let mut server = ServerFuture::with_access(catalog, deny_networks, allow_networks);
+    let mut server = ServerFuture::with_access(views, deny_networks, allow_networks);
+    if let Some(rate_limit) = config.response_rate_limit() {
+        info!("response rate limiting is enabled for UDP");
+        server.set_response_rate_limit(rate_limit.clone());
+    }
+    if let Some(connection_limits) = config.connection_limits() {
+        server.set_connection_limits(*connection_limits);
+    }
+    if let Some(proxy_protocol) = config.proxy_protocol() {
+        info!("PROXY protocol is enabled for connections from trusted proxies");
+        server.set_proxy_protocol(proxy_protocol.clone());
+    }
+    #[cfg(feature = "dnstap")]
+    if let Some(dnstap) = dnstap {
+        info!("dnstap logging is enabled");
+        server.set_dnstap(dnstap);
+    }
 
     if !args.disable_udp && !config.disable_udp() {
         // load all udp listeners
@@ -399,6 +480,10 @@ async fn async_run(args: Cli) -> Result<(), String> {
         return Err("dropping privileges is only supported on Unix systems".to_string());
     }
 
+    if let Some((listeners, zones)) = control {
+        ControlServer::new(config, zone_dir, zones, log_filter).spawn(listeners);
+    }
+
     #[cfg(unix)]
     {
         let token = server.shutdown_token().clone();
@@ -419,6 +504,17 @@ async fn async_run(args: Cli) -> Result<(), String> {
         Ok(()) => {
             // we're exiting for some reason...
             info!("Hickory DNS {} stopping", hickory_client::version());
+            for authority in &cached_authorities {
+                match authority.save_cache() {
+                    Some(Ok(saved)) => {
+                        info!(origin = %authority.origin(), "saved {saved} cache entries")
+                    }
+                    Some(Err(e)) => {
+                        warn!(origin = %authority.origin(), "failed to save cache: {e}")
+                    }
+                    None => {}
+                }
+            }
         }
         Err(e) => {
             let error_msg = format!(
@@ -438,7 +534,7 @@ async fn async_run(args: Cli) -> Result<(), String> {
 #[cfg(feature = "__tls")]
 fn config_tls(
     tls_port: Option<u16>,
-    server: &mut ServerFuture<Catalog>,
+    server: &mut ServerFuture<Views>,
     config: &Config,
     tls_cert_config: &TlsCertConfig,
     zone_dir: &Path,
@@ -481,7 +577,7 @@ fn config_tls(
 #[cfg(feature = "__https")]
 fn config_https(
     https_port: Option<u16>,
-    server: &mut ServerFuture<Catalog>,
+    server: &mut ServerFuture<Views>,
     config: &Config,
     tls_cert_config: &TlsCertConfig,
     zone_dir: &Path,
@@ -536,7 +632,7 @@ fn config_https(
 #[cfg(feature = "__quic")]
 fn config_quic(
     quic_port: Option<u16>,
-    server: &mut ServerFuture<Catalog>,
+    server: &mut ServerFuture<Views>,
     config: &Config,
     tls_cert_config: &TlsCertConfig,
     zone_dir: &Path,
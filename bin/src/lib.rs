@@ -7,8 +7,11 @@
 
 //! Configuration module for the server binary, `named`.
 
+pub mod catalog_zone;
+pub mod control;
 #[cfg(feature = "__dnssec")]
 pub mod dnssec;
+pub mod secondary;
 
 #[cfg(feature = "prometheus-metrics")]
 use std::net::SocketAddr;
@@ -36,6 +39,8 @@ use rustls::{
 use serde::de::{self, MapAccess, SeqAccess, Visitor};
 use serde::{self, Deserialize, Deserializer};
 
+#[cfg(feature = "dnstap")]
+use hickory_proto::dnstap::Dnstap;
 #[cfg(feature = "__tls")]
 use hickory_proto::rustls::default_provider;
 use hickory_proto::{ProtoError, rr::Name};
@@ -60,7 +65,8 @@ use hickory_server::store::recursor::RecursiveConfig;
 use hickory_server::store::sqlite::{SqliteAuthority, SqliteConfig};
 use hickory_server::{
     ConfigError,
-    authority::{AuthorityObject, ZoneType},
+    authority::{AuthorityObject, Catalog, MinimalResponses, View, ZoneType},
+    server::{ConnectionLimitConfig, ProxyProtocolConfig, ResponseRateLimitConfig},
     store::file::FileAuthority,
 };
 use tracing::{debug, info, warn};
@@ -129,6 +135,12 @@ pub struct Config {
     #[serde(default)]
     #[serde(deserialize_with = "deserialize_with_file")]
     zones: Vec<ZoneConfig>,
+    /// Views serving different zones depending on the client, instead of `zones`
+    #[serde(default)]
+    views: Vec<ViewConfig>,
+    /// Catalog zones listing member zones to serve, in addition to `zones`
+    #[serde(default)]
+    catalog_zones: Vec<catalog_zone::CatalogZoneConfig>,
     /// Certificate to associate to TLS connections (currently the same is used for HTTPS and TLS)
     #[cfg(feature = "__tls")]
     tls_cert: Option<TlsCertConfig>,
@@ -142,6 +154,25 @@ pub struct Config {
     /// Networks allowed to access the server
     #[serde(default)]
     allow_networks: Vec<IpNet>,
+    /// Rate limits for UDP responses
+    response_rate_limit: Option<ResponseRateLimitConfig>,
+    /// Limits on TCP, TLS and HTTPS connections
+    connection_limits: Option<ConnectionLimitConfig>,
+    /// PROXY protocol on TCP, TLS and HTTPS listeners, for connections from trusted proxies
+    proxy_protocol: Option<ProxyProtocolConfig>,
+    /// Networks denied recursion, i.e. answers from forwarder and recursor zones
+    #[serde(default)]
+    deny_recursion: Vec<IpNet>,
+    /// Networks allowed recursion, i.e. answers from forwarder and recursor zones
+    #[serde(default)]
+    allow_recursion: Vec<IpNet>,
+    /// Omit records not needed to answer the queries, by default all sections are filled
+    minimal_responses: Option<MinimalResponses>,
+    /// dnstap logging of queries and responses
+    #[cfg(feature = "dnstap")]
+    dnstap: Option<DnstapConfig>,
+    /// Control channel used by `hickory-ctl`
+    control: Option<control::ControlConfig>,
 }
 
 impl Config {
@@ -260,6 +291,16 @@ impl Config {
         &self.zones
     }
 
+    /// the views to select the zones answering a request, empty if `zones` are served to everyone
+    pub fn views(&self) -> &[ViewConfig] {
+        &self.views
+    }
+
+    /// the catalog zones listing member zones to serve
+    pub fn catalog_zones(&self) -> &[catalog_zone::CatalogZoneConfig] {
+        &self.catalog_zones
+    }
+
     /// the tls certificate to use for accepting tls connections
     pub fn tls_cert(&self) -> Option<&TlsCertConfig> {
         cfg_if! {
@@ -288,6 +329,181 @@ impl Config {
     pub fn allow_networks(&self) -> &[IpNet] {
         &self.allow_networks
     }
+
+    /// get the networks denied recursion
+    pub fn deny_recursion(&self) -> &[IpNet] {
+        &self.deny_recursion
+    }
+
+    /// get the networks allowed recursion
+    pub fn allow_recursion(&self) -> &[IpNet] {
+        &self.allow_recursion
+    }
+
+    /// get the sections omitted from responses, if configured
+    pub fn minimal_responses(&self) -> Option<MinimalResponses> {
+        self.minimal_responses
+    }
+
+    /// get the response rate limiting configuration for UDP, if enabled
+    pub fn response_rate_limit(&self) -> Option<&ResponseRateLimitConfig> {
+        self.response_rate_limit.as_ref()
+    }
+
+    /// get the limits on TCP, TLS and HTTPS connections, if configured
+    pub fn connection_limits(&self) -> Option<&ConnectionLimitConfig> {
+        self.connection_limits.as_ref()
+    }
+
+    /// get the PROXY protocol configuration, if enabled
+    pub fn proxy_protocol(&self) -> Option<&ProxyProtocolConfig> {
+        self.proxy_protocol.as_ref()
+    }
+
+    /// get the dnstap configuration, if enabled
+    #[cfg(feature = "dnstap")]
+    pub fn dnstap(&self) -> Option<&DnstapConfig> {
+        self.dnstap.as_ref()
+    }
+
+    /// get the control channel configuration, if enabled
+    pub fn control(&self) -> Option<&control::ControlConfig> {
+        self.control.as_ref()
+    }
+
+    /// log the queries sent upstream by forwarder and recursor zones to dnstap
+    #[cfg(feature = "dnstap")]
+    pub fn set_resolver_dnstap(&mut self, dnstap: &Dnstap) {
+        let view_zones = self.views.iter_mut().flat_map(|view| view.zones.iter_mut());
+        for zone in self.zones.iter_mut().chain(view_zones) {
+            zone.set_resolver_dnstap(dnstap);
+        }
+    }
+}
+
+/// Configuration for dnstap logging, to a file or to the Unix socket of a collector
+#[cfg(feature = "dnstap")]
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct DnstapConfig {
+    /// file to write the messages to, it's truncated on start
+    pub file: Option<PathBuf>,
+    /// Unix socket of a collector to send the messages to
+    pub socket: Option<PathBuf>,
+    /// identity of the server included in every message, e.g. its hostname
+    pub identity: Option<String>,
+    /// version included in every message, defaults to the version of hickory-dns
+    pub version: Option<String>,
+    /// number of messages queued for writing, further messages are dropped
+    pub queue_size: Option<usize>,
+}
+
+#[cfg(feature = "dnstap")]
+impl DnstapConfig {
+    /// opens the configured output
+    pub fn open(&self) -> Result<Dnstap, String> {
+        let mut builder = Dnstap::builder().version(
+            self.version
+                .clone()
+                .unwrap_or_else(|| format!("hickory-dns {}", env!("CARGO_PKG_VERSION"))),
+        );
+        if let Some(identity) = &self.identity {
+            builder = builder.identity(identity.clone());
+        }
+        if let Some(queue_size) = self.queue_size {
+            builder = builder.queue_size(queue_size);
+        }
+
+        match (&self.file, &self.socket) {
+            (Some(file), None) => builder
+                .to_file(file)
+                .map_err(|e| format!("failed to open dnstap file {}: {e}", file.display())),
+            #[cfg(unix)]
+            (None, Some(socket)) => builder.to_unix_socket(socket).map_err(|e| {
+                format!(
+                    "failed to connect to dnstap socket {}: {e}",
+                    socket.display()
+                )
+            }),
+            #[cfg(not(unix))]
+            (None, Some(_)) => Err("dnstap sockets are only supported on Unix".to_string()),
+            _ => Err("dnstap requires exactly one of file or socket".to_string()),
+        }
+    }
+}
+
+/// Configuration for a view, the zones served to the clients it matches
+///
+/// A request is answered by the first view matching all of the configured criteria.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ViewConfig {
+    /// name of the view
+    pub name: String,
+    /// networks the client address must be in
+    #[serde(default)]
+    pub match_clients: Vec<IpNet>,
+    /// networks the address the request was received on must be in
+    #[serde(default)]
+    pub match_destinations: Vec<IpNet>,
+    /// TSIG keys, one of which the request must be signed with
+    #[cfg(feature = "__dnssec")]
+    #[serde(default)]
+    pub match_keys: Vec<dnssec::TsigKeyConfig>,
+    /// zones served in the view
+    #[serde(default)]
+    #[serde(deserialize_with = "deserialize_with_file")]
+    pub zones: Vec<ZoneConfig>,
+}
+
+impl ViewConfig {
+    /// loads the zones and keys of the view
+    pub async fn load(&self, zone_dir: &Path) -> Result<View, String> {
+        let catalog = load_catalog(&self.zones, zone_dir).await?;
+        let view = View::new(self.name.clone(), catalog)
+            .with_match_clients(self.match_clients.clone())
+            .with_match_destinations(self.match_destinations.clone());
+
+        #[cfg(feature = "__dnssec")]
+        let view = view.with_match_keys(
+            self.match_keys
+                .iter()
+                .map(|key| key.try_into_signer(zone_dir))
+                .collect::<Result<_, _>>()?,
+        );
+
+        info!("view successfully loaded: {}", self.name);
+        Ok(view)
+    }
+}
+
+/// Loads all the zones into a new [`Catalog`]
+pub async fn load_catalog(zones: &[ZoneConfig], zone_dir: &Path) -> Result<Catalog, String> {
+    let mut catalog = Catalog::new();
+    for zone in zones {
+        let zone_name = zone
+            .zone()
+            .map_err(|err| format!("failed to read zone name: {err}"))?;
+
+        match zone.load(zone_dir).await {
+            Ok(authority) => catalog.upsert(zone_name.clone().into(), authority),
+            Err(err) => return Err(format!("could not load zone {zone_name}: {err}")),
+        }
+
+        if !zone.allow_query.is_empty() || !zone.deny_query.is_empty() {
+            catalog.set_query_access(
+                zone_name.clone().into(),
+                &zone.deny_query,
+                &zone.allow_query,
+            );
+        }
+
+        if let Some(minimal_responses) = zone.minimal_responses {
+            catalog.set_zone_minimal_responses(zone_name.into(), minimal_responses);
+        }
+    }
+
+    Ok(catalog)
 }
 
 #[derive(Deserialize, Debug)]
@@ -346,12 +562,46 @@ where
 pub struct ZoneConfig {
     /// name of the zone
     pub zone: String, // TODO: make Domain::Name decodable
+    /// Networks allowed to query the zone, evaluated like `allow_networks`
+    #[serde(default)]
+    pub allow_query: Vec<IpNet>,
+    /// Networks denied to query the zone, evaluated like `deny_networks`
+    #[serde(default)]
+    pub deny_query: Vec<IpNet>,
+    /// Omit records not needed to answer the queries of the zone, overrides `minimal_responses`
+    /// of the server
+    pub minimal_responses: Option<MinimalResponses>,
     /// type of the zone
     #[serde(flatten)]
     pub zone_type_config: ZoneTypeConfig,
 }
 
 impl ZoneConfig {
+    /// log the queries sent upstream by the zone's forwarder and recursor stores to dnstap
+    #[cfg(feature = "dnstap")]
+    #[cfg_attr(
+        not(any(feature = "resolver", feature = "recursor")),
+        allow(unused_variables, clippy::match_single_binding)
+    )]
+    fn set_resolver_dnstap(&mut self, dnstap: &Dnstap) {
+        let ZoneTypeConfig::External { stores } = &mut self.zone_type_config else {
+            return;
+        };
+
+        for store in stores {
+            match store {
+                #[cfg(feature = "resolver")]
+                ExternalStoreConfig::Forward(config) => {
+                    config.options.get_or_insert_with(Default::default).dnstap =
+                        Some(dnstap.clone());
+                }
+                #[cfg(feature = "recursor")]
+                ExternalStoreConfig::Recursor(config) => config.dnstap = Some(dnstap.clone()),
+                _ => {}
+            }
+        }
+    }
+
     #[warn(clippy::wildcard_enum_match_arm)] // make sure all cases are handled despite of non_exhaustive
     pub async fn load(&self, zone_dir: &Path) -> Result<Vec<Arc<dyn AuthorityObject>>, String> {
         debug!("loading zone with config: {self:#?}");
@@ -452,7 +702,10 @@ impl ZoneConfig {
                         }
                         #[cfg(feature = "resolver")]
                         ExternalStoreConfig::Forward(config) => {
-                            let forwarder = ForwardAuthority::builder_tokio(config.clone())
+                            let mut config = config.clone();
+                            // the cache file is relative to the zone directory
+                            config.cache_file = config.cache_file.map(|path| zone_dir.join(path));
+                            let forwarder = ForwardAuthority::builder_tokio(config)
                                 .with_origin(zone_name.clone())
                                 .build()?;
 
@@ -0,0 +1,406 @@
+// Copyright 2015-2025 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Secondary zones, transferred from their primaries with AXFR and IXFR
+
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Weak},
+    time::Duration,
+};
+
+use futures_util::TryStreamExt;
+use tracing::{debug, info, warn};
+
+use hickory_client::client::{Client, ClientHandle};
+use hickory_proto::{
+    op::ResponseCode,
+    rr::{DNSClass, LowerName, Name, Record, RecordType, RrKey, rdata::SOA},
+    runtime::TokioRuntimeProvider,
+    tcp::TcpClientStream,
+};
+use hickory_server::{
+    authority::{Authority, ZoneType},
+    store::in_memory::InMemoryAuthority,
+};
+
+/// The time allowed for a zone transfer
+const TRANSFER_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Transfers the secondary zone `zone` from the first of `primaries` serving it
+///
+/// The zone is refreshed in the background as its SOA record asks for, until the returned
+/// authority is dropped.
+pub async fn load_secondary(
+    zone: &Name,
+    primaries: &[SocketAddr],
+    allow_axfr: bool,
+) -> Result<Arc<InMemoryAuthority>, String> {
+    if primaries.is_empty() {
+        return Err(format!("secondary zone {zone} has no primaries"));
+    }
+
+    let authority = Arc::new(InMemoryAuthority::empty(
+        zone.clone(),
+        ZoneType::Secondary,
+        allow_axfr,
+        #[cfg(feature = "__dnssec")]
+        None,
+    ));
+    refresh(&authority, primaries).await?;
+
+    let primaries = primaries.to_vec();
+    let weak = Arc::downgrade(&authority);
+    tokio::spawn(refresh_periodically(weak, primaries));
+    Ok(authority)
+}
+
+/// Refreshes the zone after the refresh interval of its SOA, or its retry interval after a failure
+async fn refresh_periodically(authority: Weak<InMemoryAuthority>, primaries: Vec<SocketAddr>) {
+    let mut failed = false;
+    loop {
+        let Some(strong) = authority.upgrade() else {
+            return;
+        };
+        let Some(soa) = current_soa(&strong).await else {
+            return;
+        };
+        drop(strong);
+
+        let interval = match failed {
+            true => soa.retry(),
+            false => soa.refresh(),
+        };
+        tokio::time::sleep(Duration::from_secs(interval.max(0) as u64)).await;
+
+        // the zone isn't served anymore
+        let Some(authority) = authority.upgrade() else {
+            return;
+        };
+
+        failed = match refresh(&authority, &primaries).await {
+            Ok(()) => false,
+            Err(e) => {
+                warn!("{e}, retrying in {}s", soa.retry());
+                true
+            }
+        };
+    }
+}
+
+/// Updates the zone from the first primary it can be transferred from
+async fn refresh(authority: &InMemoryAuthority, primaries: &[SocketAddr]) -> Result<(), String> {
+    let zone = Name::from(authority.origin());
+    for primary in primaries {
+        match refresh_from(authority, &zone, *primary).await {
+            Ok(()) => return Ok(()),
+            Err(e) => warn!("failed to transfer zone {zone} from {primary}: {e}"),
+        }
+    }
+
+    Err(format!("failed to transfer zone {zone} from its primaries"))
+}
+
+async fn refresh_from(
+    authority: &InMemoryAuthority,
+    zone: &Name,
+    primary: SocketAddr,
+) -> Result<(), String> {
+    let (stream, sender) = TcpClientStream::new(
+        primary,
+        None,
+        Some(TRANSFER_TIMEOUT),
+        TokioRuntimeProvider::new(),
+    );
+    let (mut client, bg) = Client::with_timeout(stream, sender, TRANSFER_TIMEOUT, None)
+        .await
+        .map_err(|e| format!("failed to connect: {e}"))?;
+    tokio::spawn(bg);
+
+    let current = current_soa(authority).await;
+    if let Some(current) = &current {
+        let response = client
+            .query(zone.clone(), DNSClass::IN, RecordType::SOA)
+            .await
+            .map_err(|e| format!("SOA query failed: {e}"))?;
+        let serial = response
+            .answers()
+            .iter()
+            .find_map(|record| record.data().as_soa())
+            .map(SOA::serial)
+            .ok_or_else(|| format!("no SOA record, {}", response.response_code()))?;
+        if !is_newer(serial, current.serial()) {
+            debug!("zone {zone} is up to date with serial {serial}");
+            return Ok(());
+        }
+
+        // primaries not supporting IXFR are asked for an AXFR instead
+        match transfer(&mut client, zone, Some(current.clone())).await {
+            Ok(answers) if !answers.is_empty() => {
+                return apply(authority, zone, current, answers).await;
+            }
+            Ok(_) => debug!("IXFR of zone {zone} returned no records, requesting an AXFR"),
+            Err(e) => debug!("{e}, requesting an AXFR of zone {zone}"),
+        }
+    }
+
+    let answers = transfer(&mut client, zone, None).await?;
+    match current {
+        Some(current) => apply(authority, zone, &current, answers).await,
+        None => replace(authority, zone, answers).await,
+    }
+}
+
+/// Requests an IXFR if `current` is given, an AXFR otherwise, and returns the transferred records
+async fn transfer(
+    client: &mut Client,
+    zone: &Name,
+    current: Option<SOA>,
+) -> Result<Vec<Record>, String> {
+    let responses = client
+        .zone_transfer(zone.clone(), current)
+        .try_collect::<Vec<_>>()
+        .await
+        .map_err(|e| format!("zone transfer failed: {e}"))?;
+
+    let mut answers = Vec::new();
+    for response in responses {
+        if response.response_code() != ResponseCode::NoError {
+            return Err(format!(
+                "zone transfer failed: {}",
+                response.response_code()
+            ));
+        }
+
+        answers.extend(response.into_message().take_answers());
+    }
+
+    Ok(answers)
+}
+
+/// Applies the answers of an IXFR, which may also be a complete zone
+async fn apply(
+    authority: &InMemoryAuthority,
+    zone: &Name,
+    current: &SOA,
+    answers: Vec<Record>,
+) -> Result<(), String> {
+    // a lone SOA record means that the zone is up to date
+    if answers.len() == 1 {
+        return Ok(());
+    }
+
+    // a complete zone doesn't start with the SOA record of the current version after the new one
+    let incremental = answers
+        .get(1)
+        .and_then(|record| record.data().as_soa())
+        .is_some_and(|soa| answers.len() > 2 && soa.serial() == current.serial());
+    if !incremental {
+        return replace(authority, zone, answers).await;
+    }
+
+    let mut records = authority
+        .records()
+        .await
+        .into_values()
+        .flat_map(|rrset| rrset.records_without_rrsigs().cloned().collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+
+    // each difference is the old SOA followed by the deleted records, and the new SOA followed by
+    // the added ones, ending with the SOA of the newest version
+    let mut deleting = false;
+    for record in &answers[1..answers.len() - 1] {
+        if record.record_type() == RecordType::SOA {
+            deleting = !deleting;
+            if deleting {
+                continue;
+            }
+        }
+
+        if deleting {
+            records.retain(|existing| !same_record(existing, record));
+        } else if record.record_type() == RecordType::SOA {
+            records.retain(|existing| existing.record_type() != RecordType::SOA);
+            records.push(record.clone());
+        } else if !records.iter().any(|existing| same_record(existing, record)) {
+            records.push(record.clone());
+        }
+    }
+
+    store(authority, zone, records).await
+}
+
+/// Replaces the zone with the answers of an AXFR, ending with a copy of the SOA record
+async fn replace(
+    authority: &InMemoryAuthority,
+    zone: &Name,
+    mut answers: Vec<Record>,
+) -> Result<(), String> {
+    if answers.len() > 1 {
+        answers.pop();
+    }
+
+    store(authority, zone, answers).await
+}
+
+async fn store(
+    authority: &InMemoryAuthority,
+    zone: &Name,
+    records: Vec<Record>,
+) -> Result<(), String> {
+    let serial = records
+        .iter()
+        .find(|record| record.name() == zone)
+        .and_then(|record| record.data().as_soa())
+        .map(SOA::serial)
+        .ok_or_else(|| format!("zone transfer of {zone} has no SOA record"))?;
+
+    // the records are checked the same way as when loading a zone
+    let mut transferred = InMemoryAuthority::empty(
+        zone.clone(),
+        ZoneType::Secondary,
+        false,
+        #[cfg(feature = "__dnssec")]
+        None,
+    );
+    for record in records {
+        if !transferred.upsert_mut(record.clone(), serial) {
+            warn!("ignoring record {record} of zone {zone}");
+        }
+    }
+
+    *authority.records_mut().await = std::mem::take(transferred.records_get_mut());
+    info!("transferred zone {zone} with serial {serial}");
+    Ok(())
+}
+
+async fn current_soa(authority: &InMemoryAuthority) -> Option<SOA> {
+    let key = RrKey::new(authority.origin().clone(), RecordType::SOA);
+    let rrset = authority.records().await.remove(&key)?;
+    rrset
+        .records_without_rrsigs()
+        .find_map(|record| record.data().as_soa())
+        .cloned()
+}
+
+fn same_record(a: &Record, b: &Record) -> bool {
+    LowerName::from(a.name()) == LowerName::from(b.name()) && a.data() == b.data()
+}
+
+/// Compares serial numbers with the arithmetic of RFC 1982
+fn is_newer(serial: u32, current: u32) -> bool {
+    (serial.wrapping_sub(current) as i32) > 0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::Ipv4Addr, str::FromStr};
+
+    use futures_executor::block_on;
+    use hickory_proto::rr::{RData, rdata::A};
+
+    use super::*;
+
+    fn soa(serial: u32) -> Record {
+        let soa = SOA::new(
+            Name::from_str("ns.example.com.").unwrap(),
+            Name::from_str("hostmaster.example.com.").unwrap(),
+            serial,
+            3600,
+            600,
+            86400,
+            0,
+        );
+        Record::from_rdata(
+            Name::from_str("example.com.").unwrap(),
+            3600,
+            RData::SOA(soa),
+        )
+    }
+
+    fn a(name: &str, ip: [u8; 4]) -> Record {
+        Record::from_rdata(
+            Name::from_str(name).unwrap(),
+            3600,
+            RData::A(A(Ipv4Addr::from(ip))),
+        )
+    }
+
+    async fn records(authority: &InMemoryAuthority) -> Vec<Record> {
+        let mut records = authority
+            .records()
+            .await
+            .into_values()
+            .flat_map(|rrset| rrset.records_without_rrsigs().cloned().collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        records.sort();
+        records
+    }
+
+    #[test]
+    fn test_apply_transfers() {
+        block_on(async {
+            let zone = Name::from_str("example.com.").unwrap();
+            let authority = InMemoryAuthority::empty(
+                zone.clone(),
+                ZoneType::Secondary,
+                false,
+                #[cfg(feature = "__dnssec")]
+                None,
+            );
+
+            let www = a("www.example.com.", [192, 0, 2, 1]);
+            let old = a("old.example.com.", [192, 0, 2, 2]);
+            let new = a("new.example.com.", [192, 0, 2, 3]);
+
+            replace(
+                &authority,
+                &zone,
+                vec![soa(1), www.clone(), old.clone(), soa(1)],
+            )
+            .await
+            .unwrap();
+            let current = current_soa(&authority).await.unwrap();
+            assert_eq!(current.serial(), 1);
+
+            // an incremental transfer deletes and adds records
+            let ixfr = vec![soa(2), soa(1), old, soa(2), new.clone(), soa(2)];
+            apply(&authority, &zone, &current, ixfr).await.unwrap();
+            let mut expected = vec![soa(2), www.clone(), new];
+            expected.sort();
+            assert_eq!(records(&authority).await, expected);
+
+            // a lone SOA record leaves the zone up to date
+            let current = current_soa(&authority).await.unwrap();
+            apply(&authority, &zone, &current, vec![soa(2)])
+                .await
+                .unwrap();
+            assert_eq!(records(&authority).await, expected);
+
+            // and the complete zone replaces it
+            apply(
+                &authority,
+                &zone,
+                &current,
+                vec![soa(3), www.clone(), soa(3)],
+            )
+            .await
+            .unwrap();
+            let mut expected = vec![soa(3), www];
+            expected.sort();
+            assert_eq!(records(&authority).await, expected);
+        });
+    }
+
+    #[test]
+    fn test_serial_arithmetic() {
+        assert!(is_newer(2, 1));
+        assert!(!is_newer(1, 1));
+        assert!(!is_newer(1, 2));
+        assert!(is_newer(0, u32::MAX));
+    }
+}
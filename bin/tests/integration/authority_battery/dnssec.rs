@@ -379,6 +379,7 @@ pub fn add_signers<A: DnssecAuthority>(authority: &mut A) -> Vec<DNSKEY> {
             algorithm: Algorithm::RSASHA512,
             signer_name: Some(signer_name.to_string()),
             purpose: KeyPurpose::ZoneSigning,
+            remote_signer: None,
         };
 
         let signer = key_config
@@ -432,6 +433,7 @@ pub fn add_signers<A: DnssecAuthority>(authority: &mut A) -> Vec<DNSKEY> {
             algorithm: Algorithm::ED25519,
             signer_name: Some(signer_name.to_string()),
             purpose: KeyPurpose::ZoneSigning,
+            remote_signer: None,
         };
 
         let signer = key_config
@@ -802,6 +802,7 @@ pub fn add_auth<A: DnssecAuthority>(authority: &mut A) -> Vec<SigSigner> {
                 algorithm: algo,
                 signer_name: Some(update_name.to_string()),
                 purpose: KeyPurpose::ZoneSigning,
+                remote_signer: None,
             };
 
             let signer = key_config
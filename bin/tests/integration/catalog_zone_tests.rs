@@ -0,0 +1,86 @@
+use std::{env, net::Ipv4Addr, path::PathBuf, str::FromStr, time::Duration};
+
+use tokio::{net::TcpListener, runtime::Runtime};
+
+use hickory_dns::{Config, catalog_zone::CatalogZoneConsumer, load_catalog};
+use hickory_proto::rr::Name;
+use hickory_server::{ServerFuture, authority::Views};
+use test_support::subscribe;
+
+#[test]
+fn test_catalog_zone_members() {
+    subscribe();
+
+    let workspace = env::var("TDNS_WORKSPACE_ROOT").unwrap_or_else(|_| "..".to_owned());
+    let zone_dir = PathBuf::from(workspace).join("tests/test-data/test_configs");
+
+    let primary_config = Config::from_toml(
+        "
+[[zones]]
+zone = \"example.com\"
+zone_type = \"Primary\"
+file = \"example.com.zone\"
+allow_axfr = true
+",
+    )
+    .unwrap();
+
+    let runtime = Runtime::new().expect("failed to create Tokio Runtime");
+    runtime.block_on(async {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let primary = listener.local_addr().unwrap();
+        let mut server = ServerFuture::new(
+            load_catalog(primary_config.zones(), &zone_dir)
+                .await
+                .unwrap(),
+        );
+        server.register_listener(listener, Duration::from_secs(5));
+
+        let config = Config::from_toml(&format!(
+            "
+[[zones]]
+zone = \"catalog.example\"
+zone_type = \"Primary\"
+file = \"catalog.example.zone\"
+
+[[catalog_zones]]
+zone = \"catalog.example\"
+members = {{ zone_type = \"Secondary\", primaries = [\"{primary}\"] }}
+"
+        ))
+        .unwrap();
+
+        let mut views = Views::from(load_catalog(config.zones(), &zone_dir).await.unwrap());
+        let consumers = CatalogZoneConsumer::from_config(&mut views, &config).unwrap();
+        assert_eq!(consumers.len(), 1);
+
+        consumers[0].refresh().await;
+
+        // missing.example. isn't served by the primary, it's added on a later refresh once it
+        // can be transferred
+        assert_eq!(
+            consumers[0].member_zones().zones(),
+            vec![(
+                Name::from_str("example.com.").unwrap(),
+                Name::from_str("catalog.example.").unwrap()
+            )]
+        );
+
+        server.shutdown_gracefully().await.unwrap();
+    });
+}
+
+#[test]
+fn test_catalog_zone_not_served() {
+    let config = Config::from_toml(
+        "
+[[catalog_zones]]
+zone = \"catalog.example\"
+members = { zone_type = \"Secondary\", primaries = [\"192.0.2.1:53\"] }
+",
+    )
+    .unwrap();
+
+    let mut views = Views::new();
+    assert!(CatalogZoneConsumer::from_config(&mut views, &config).is_err());
+}
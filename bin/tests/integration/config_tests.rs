@@ -17,7 +17,7 @@
 use std::env;
 use std::fs::{File, read_dir};
 use std::io::Read;
-use std::net::{Ipv4Addr, Ipv6Addr};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::ops::Range;
 use std::path::{Path, PathBuf};
 use std::time::Duration;
@@ -26,8 +26,9 @@ use toml::map::Keys;
 use toml::value::Array;
 use toml::{Table, Value};
 
-use hickory_dns::{Config, ServerZoneConfig};
-use hickory_server::authority::ZoneType;
+use hickory_dns::{Config, ServerZoneConfig, catalog_zone::MemberZoneConfig};
+use hickory_server::authority::{MinimalResponses, ZoneType};
+use hickory_server::server::{ConnectionLimitConfig, ProxyProtocolConfig, ResponseRateLimitConfig};
 
 #[test]
 fn test_read_config() {
@@ -131,6 +132,472 @@ fn test_parse_toml() {
     assert_eq!(config.directory(), Path::new("/dev/null"));
 }
 
+#[test]
+fn test_parse_response_rate_limit() {
+    let config = Config::from_toml("").unwrap();
+    assert!(config.response_rate_limit().is_none());
+
+    let config = Config::from_toml(
+        "[response_rate_limit]
+responses_per_second = 5
+errors_per_second = 1
+slip = 0
+exempt_clients = [\"127.0.0.0/8\"]",
+    )
+    .unwrap();
+    assert_eq!(
+        config.response_rate_limit(),
+        Some(&ResponseRateLimitConfig {
+            responses_per_second: 5,
+            errors_per_second: Some(1),
+            slip: 0,
+            exempt_clients: vec!["127.0.0.0/8".parse().unwrap()],
+            ..ResponseRateLimitConfig::default()
+        })
+    );
+}
+
+#[test]
+fn test_parse_connection_limits() {
+    let config = Config::from_toml("").unwrap();
+    assert!(config.connection_limits().is_none());
+
+    let config = Config::from_toml(
+        "[connection_limits]
+max_connections_per_ip = 20
+keepalive_timeout = 120",
+    )
+    .unwrap();
+    assert_eq!(
+        config.connection_limits(),
+        Some(&ConnectionLimitConfig {
+            max_connections_per_ip: 20,
+            keepalive_timeout: Some(120),
+            ..ConnectionLimitConfig::default()
+        })
+    );
+}
+
+#[test]
+fn test_parse_proxy_protocol() {
+    let config = Config::from_toml("").unwrap();
+    assert!(config.proxy_protocol().is_none());
+
+    let config = Config::from_toml(
+        "[proxy_protocol]
+trusted_proxies = [\"10.0.0.0/8\", \"fd00::/8\"]",
+    )
+    .unwrap();
+    assert_eq!(
+        config.proxy_protocol(),
+        Some(&ProxyProtocolConfig {
+            trusted_proxies: vec!["10.0.0.0/8".parse().unwrap(), "fd00::/8".parse().unwrap()],
+        })
+    );
+}
+
+#[test]
+fn test_parse_catalog_zones() {
+    let config = Config::from_toml(
+        "
+[[catalog_zones]]
+zone = \"catalog.example\"
+refresh = 300
+members = { zone_type = \"Secondary\", primaries = [\"192.0.2.1:53\"] }
+",
+    )
+    .unwrap();
+
+    let catalog_zone = &config.catalog_zones()[0];
+    assert_eq!(catalog_zone.zone().unwrap().to_string(), "catalog.example.");
+    assert_eq!(catalog_zone.refresh(), Duration::from_secs(300));
+    assert!(matches!(
+        &catalog_zone.members,
+        MemberZoneConfig::Secondary { primaries, allow_axfr: None }
+            if primaries == &[SocketAddr::from(([192, 0, 2, 1], 53))]
+    ));
+    assert!(catalog_zone.groups.is_empty());
+}
+
+#[cfg(feature = "resolver")]
+#[test]
+fn test_parse_catalog_zone_groups() {
+    let config = Config::from_toml(
+        "
+[[catalog_zones]]
+zone = \"catalog.example\"
+members = { zone_type = \"Secondary\", primaries = [\"192.0.2.1:53\"] }
+
+[catalog_zones.groups.external]
+zone_type = \"Forward\"
+name_servers = [{ socket_addr = \"192.0.2.1:53\", protocol = \"udp\" }]
+",
+    )
+    .unwrap();
+
+    assert!(matches!(
+        config.catalog_zones()[0].groups.get("external"),
+        Some(MemberZoneConfig::Forward(_))
+    ));
+}
+
+#[test]
+fn test_parse_control() {
+    let config = Config::from_toml("").unwrap();
+    assert!(config.control().is_none());
+
+    let config = Config::from_toml(
+        "[control]
+unix_socket = \"/run/hickory-dns/control.sock\"",
+    )
+    .unwrap();
+    let control = config.control().unwrap();
+    assert_eq!(
+        control.unix_socket,
+        Some(PathBuf::from("/run/hickory-dns/control.sock"))
+    );
+    assert_eq!(control.listen_addr, None);
+}
+
+#[cfg(feature = "__dnssec")]
+#[test]
+fn test_parse_control_key() {
+    let config = Config::from_toml(
+        "[control]
+listen_addr = \"127.0.0.1:953\"
+key = { algorithm = \"hmac-sha256\", key_path = \"control.key\" }",
+    )
+    .unwrap();
+    let control = config.control().unwrap();
+    assert_eq!(control.listen_addr, Some("127.0.0.1:953".parse().unwrap()));
+    assert_eq!(
+        control.key.as_ref().unwrap().key_path,
+        PathBuf::from("control.key")
+    );
+}
+
+#[cfg(feature = "dnstap")]
+#[test]
+fn test_parse_dnstap() {
+    let config = Config::from_toml("").unwrap();
+    assert!(config.dnstap().is_none());
+
+    let config = Config::from_toml(
+        "[dnstap]
+file = \"/var/log/dnstap.fstrm\"
+identity = \"ns1\"",
+    )
+    .unwrap();
+    let dnstap = config.dnstap().unwrap();
+    assert_eq!(dnstap.file, Some(PathBuf::from("/var/log/dnstap.fstrm")));
+    assert_eq!(dnstap.socket, None);
+    assert_eq!(dnstap.identity.as_deref(), Some("ns1"));
+
+    let config = Config::from_toml(
+        "[dnstap]
+file = \"/var/log/dnstap.fstrm\"
+socket = \"/var/run/dnstap.sock\"",
+    )
+    .unwrap();
+    assert!(config.dnstap().unwrap().open().is_err());
+}
+
+#[test]
+fn test_parse_query_and_recursion_access() {
+    let config = Config::from_toml(
+        "
+allow_recursion = [\"10.0.0.0/8\"]
+deny_recursion = [\"10.0.0.0/24\"]
+
+[[zones]]
+zone = \"example.com\"
+zone_type = \"Primary\"
+file = \"example.com.zone\"
+allow_query = [\"192.0.2.0/24\"]
+",
+    )
+    .unwrap();
+
+    assert_eq!(
+        config.allow_recursion(),
+        &["10.0.0.0/8".parse::<ipnet::IpNet>().unwrap()]
+    );
+    assert_eq!(
+        config.deny_recursion(),
+        &["10.0.0.0/24".parse::<ipnet::IpNet>().unwrap()]
+    );
+    assert_eq!(
+        config.zones()[0].allow_query,
+        vec!["192.0.2.0/24".parse::<ipnet::IpNet>().unwrap()]
+    );
+    assert!(config.zones()[0].deny_query.is_empty());
+}
+
+#[test]
+fn test_parse_minimal_responses() {
+    let config = Config::from_toml(
+        "
+minimal_responses = \"no-auth-recursive\"
+
+[[zones]]
+zone = \"example.com\"
+zone_type = \"Primary\"
+file = \"example.com.zone\"
+minimal_responses = \"yes\"
+
+[[zones]]
+zone = \"example.net\"
+zone_type = \"Primary\"
+file = \"example.net.zone\"
+",
+    )
+    .unwrap();
+
+    assert_eq!(
+        config.minimal_responses(),
+        Some(MinimalResponses::NoAuthRecursive)
+    );
+    assert_eq!(
+        config.zones()[0].minimal_responses,
+        Some(MinimalResponses::Yes)
+    );
+    assert_eq!(config.zones()[1].minimal_responses, None);
+}
+
+#[test]
+#[cfg(feature = "resolver")]
+fn test_parse_forward_dns64() {
+    use hickory_dns::{ExternalStoreConfig, ZoneTypeConfig};
+
+    let config = Config::from_toml(
+        "
+[[zones]]
+zone = \".\"
+zone_type = \"External\"
+[zones.stores]
+type = \"forward\"
+name_servers = [{ socket_addr = \"192.0.2.53:53\", protocol = \"udp\" }]
+options = { dns64 = { prefix = \"2001:db8:64::/96\", exclude_ipv4 = [\"192.0.2.0/24\"] } }
+",
+    )
+    .unwrap();
+
+    let ZoneTypeConfig::External { stores } = &config.zones()[0].zone_type_config else {
+        panic!("expected external zone");
+    };
+    let ExternalStoreConfig::Forward(forward) = &stores[0] else {
+        panic!("expected forward store");
+    };
+    let dns64 = forward.options.as_ref().unwrap().dns64.as_ref().unwrap();
+    assert_eq!(dns64.prefix.net(), "2001:db8:64::/96".parse().unwrap());
+    assert_eq!(dns64.exclude, vec!["::ffff:0:0/96".parse().unwrap()]);
+    assert_eq!(dns64.exclude_ipv4, vec!["192.0.2.0/24".parse().unwrap()]);
+}
+
+#[test]
+#[cfg(feature = "resolver")]
+fn test_parse_forward_routes() {
+    use hickory_dns::{ExternalStoreConfig, ZoneTypeConfig};
+    use hickory_proto::rr::Name;
+
+    let config = Config::from_toml(
+        "
+[[zones]]
+zone = \".\"
+zone_type = \"External\"
+[zones.stores]
+type = \"forward\"
+name_servers = [{ socket_addr = \"192.0.2.53:53\", protocol = \"udp\" }]
+
+[[zones.stores.routes]]
+domains = [\"corp.example.\"]
+name_servers = [{ socket_addr = \"10.0.0.53:53\", protocol = \"tcp\" }]
+options = { attempts = 1 }
+exclusive = true
+",
+    )
+    .unwrap();
+
+    let ZoneTypeConfig::External { stores } = &config.zones()[0].zone_type_config else {
+        panic!("expected external zone");
+    };
+    let ExternalStoreConfig::Forward(forward) = &stores[0] else {
+        panic!("expected forward store");
+    };
+    let route = &forward.routes[0];
+    assert_eq!(
+        route.domains,
+        vec![Name::from_ascii("corp.example.").unwrap()]
+    );
+    assert_eq!(
+        route.name_servers[0].socket_addr,
+        "10.0.0.53:53".parse().unwrap()
+    );
+    assert_eq!(route.options.as_ref().unwrap().attempts, 1);
+    assert!(route.exclusive);
+}
+
+#[test]
+#[cfg(feature = "resolver")]
+fn test_parse_forward_name_server_urls() {
+    use hickory_dns::{ExternalStoreConfig, ZoneTypeConfig};
+    use hickory_proto::xfer::Protocol;
+
+    let config = Config::from_toml(
+        "
+[[zones]]
+zone = \".\"
+zone_type = \"External\"
+[zones.stores]
+type = \"forward\"
+name_servers = [
+    \"udp://192.0.2.53\",
+    \"tcp://[2001:db8::53]:5353\",
+    { socket_addr = \"192.0.2.54:53\", protocol = \"udp\" },
+]
+",
+    )
+    .unwrap();
+
+    let ZoneTypeConfig::External { stores } = &config.zones()[0].zone_type_config else {
+        panic!("expected external zone");
+    };
+    let ExternalStoreConfig::Forward(forward) = &stores[0] else {
+        panic!("expected forward store");
+    };
+    let name_servers = &forward.name_servers;
+    assert_eq!(name_servers.len(), 3);
+    assert_eq!(name_servers[0].protocol, Protocol::Udp);
+    assert_eq!(
+        name_servers[0].socket_addr,
+        "192.0.2.53:53".parse().unwrap()
+    );
+    assert_eq!(name_servers[1].protocol, Protocol::Tcp);
+    assert_eq!(
+        name_servers[1].socket_addr,
+        "[2001:db8::53]:5353".parse().unwrap()
+    );
+    assert_eq!(
+        name_servers[2].socket_addr,
+        "192.0.2.54:53".parse().unwrap()
+    );
+
+    assert!(
+        Config::from_toml(
+            "
+[[zones]]
+zone = \".\"
+zone_type = \"External\"
+[zones.stores]
+type = \"forward\"
+name_servers = [\"ftp://192.0.2.53\"]
+",
+        )
+        .is_err()
+    );
+}
+
+#[test]
+#[cfg(feature = "recursor")]
+fn test_parse_recursor_cache_file() {
+    use hickory_dns::{ExternalStoreConfig, ZoneTypeConfig};
+
+    let config = Config::from_toml(
+        "
+[[zones]]
+zone = \".\"
+zone_type = \"External\"
+[zones.stores]
+type = \"recursor\"
+roots = \"default/root.zone\"
+cache_file = \"recursor.cache\"
+preserve_dnssec_proofs = true
+",
+    )
+    .unwrap();
+
+    let ZoneTypeConfig::External { stores } = &config.zones()[0].zone_type_config else {
+        panic!("expected external zone");
+    };
+    let ExternalStoreConfig::Recursor(recursor) = &stores[0] else {
+        panic!("expected recursor store");
+    };
+    assert_eq!(
+        recursor.cache_file.as_deref(),
+        Some(Path::new("recursor.cache"))
+    );
+    assert!(recursor.preserve_dnssec_proofs);
+}
+
+#[test]
+fn test_parse_views() {
+    let config = Config::from_toml(
+        "
+[[views]]
+name = \"internal\"
+match_clients = [\"10.0.0.0/8\"]
+match_destinations = [\"10.0.0.53/32\"]
+
+[[views.zones]]
+zone = \"example.com\"
+zone_type = \"Primary\"
+file = \"internal/example.com.zone\"
+
+[[views]]
+name = \"external\"
+
+[[views.zones]]
+zone = \"example.com\"
+zone_type = \"Primary\"
+file = \"example.com.zone\"
+",
+    )
+    .unwrap();
+
+    assert!(config.zones().is_empty());
+    let views = config.views();
+    assert_eq!(views.len(), 2);
+    assert_eq!(views[0].name, "internal");
+    assert_eq!(
+        views[0].match_clients,
+        vec!["10.0.0.0/8".parse::<ipnet::IpNet>().unwrap()]
+    );
+    assert_eq!(
+        views[0].match_destinations,
+        vec!["10.0.0.53/32".parse::<ipnet::IpNet>().unwrap()]
+    );
+    assert_eq!(views[0].zones[0].zone, "example.com");
+    assert_eq!(views[1].name, "external");
+    assert!(views[1].match_clients.is_empty());
+    assert_eq!(views[1].zones.len(), 1);
+}
+
+#[cfg(feature = "__dnssec")]
+#[test]
+fn test_parse_view_keys() {
+    use hickory_dns::dnssec::TsigKeyConfig;
+    use hickory_proto::dnssec::rdata::tsig::TsigAlgorithm;
+
+    let config = Config::from_toml(
+        "
+[[views]]
+name = \"internal\"
+match_keys = [{ name = \"internal-key\", algorithm = \"hmac-sha256\", key_path = \"internal.key\" }]
+",
+    )
+    .unwrap();
+
+    assert_eq!(
+        config.views()[0].match_keys,
+        vec![TsigKeyConfig {
+            name: "internal-key".to_owned(),
+            algorithm: TsigAlgorithm::HmacSha256,
+            key_path: PathBuf::from("internal.key"),
+            fudge: None,
+        }]
+    );
+}
+
 #[cfg(feature = "__dnssec")]
 #[test]
 fn test_parse_zone_keys() {
@@ -205,6 +672,39 @@ purpose = \"ZoneSigning\"
     );
 }
 
+#[test]
+#[cfg(feature = "__dnssec")]
+fn test_parse_remote_signer() {
+    use hickory_dns::dnssec::RemoteSignerConfig;
+
+    let config = Config::from_toml(
+        "
+[[zones]]
+zone = \"example.com\"
+zone_type = \"Primary\"
+[zones.stores]
+type = \"file\"
+zone_file_path = \"example.com.zone\"
+
+[[zones.keys]]
+algorithm = \"ED25519\"
+purpose = \"ZoneSigning\"
+remote_signer = { address = \"unix:/run/signer.sock\", key_id = \"zsk\" }
+",
+    )
+    .unwrap();
+
+    let key = &server_zone(&config, 0).keys[0];
+    assert_eq!(key.key_path, Path::new(""));
+    assert_eq!(
+        key.remote_signer,
+        Some(RemoteSignerConfig {
+            address: "unix:/run/signer.sock".to_string(),
+            key_id: "zsk".to_string(),
+        })
+    );
+}
+
 #[test]
 #[cfg(feature = "__tls")]
 fn test_parse_tls() {
@@ -0,0 +1,98 @@
+#![cfg(unix)]
+
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+use tokio::{net::UnixStream, runtime::Runtime};
+
+use hickory_dns::{
+    Config,
+    control::{ControlServer, ControlledZones, send_command},
+    load_catalog,
+};
+use hickory_server::authority::Views;
+use test_support::subscribe;
+
+#[test]
+fn test_control_commands() {
+    subscribe();
+
+    let workspace = env::var("TDNS_WORKSPACE_ROOT").unwrap_or_else(|_| "..".to_owned());
+    let zone_dir = PathBuf::from(workspace).join("tests/test-data/test_configs");
+    let socket = env::temp_dir().join(format!("hickory-control-{}.sock", std::process::id()));
+
+    let config = Config::from_toml(&format!(
+        "
+[control]
+unix_socket = \"{}\"
+
+[[zones]]
+zone = \"example.com\"
+zone_type = \"Primary\"
+file = \"example.com.zone\"
+",
+        socket.display()
+    ))
+    .unwrap();
+
+    let runtime = Runtime::new().expect("failed to create Tokio Runtime");
+    runtime.block_on(async {
+        let mut views = Views::from(load_catalog(config.zones(), &zone_dir).await.unwrap());
+        let zones = ControlledZones::wrap(&mut views, &config).unwrap();
+        let listeners = config.control().unwrap().bind(&zone_dir).await.unwrap();
+        ControlServer::new(config, zone_dir, zones, Box::new(|_| Ok(()))).spawn(listeners);
+
+        assert_eq!(
+            command(&socket, "status").await,
+            "example.com. view default type Primary serial 199609203 unsigned\n"
+        );
+        assert_eq!(
+            command(&socket, "freeze example.com").await,
+            "froze zone example.com. in view default\n"
+        );
+        assert_eq!(
+            command(&socket, "status").await,
+            "example.com. view default type Primary serial 199609203 unsigned frozen\n"
+        );
+        assert_eq!(
+            command(&socket, "reload example.com default").await,
+            "reloaded zone example.com. in view default\n"
+        );
+        assert_eq!(
+            command(&socket, "thaw example.com").await,
+            "thawed zone example.com. in view default\n"
+        );
+        assert_eq!(
+            command(&socket, "reload example.net").await,
+            "error: no zone example.net.\n"
+        );
+        assert_eq!(
+            command(&socket, "flush").await,
+            "error: no zone caches records\n"
+        );
+        assert_eq!(
+            command(&socket, "loglevel debug").await,
+            "log filter set to debug\n"
+        );
+        assert_eq!(
+            command(&socket, "shutdown").await,
+            "error: unknown command: shutdown\n"
+        );
+    });
+
+    let _ = std::fs::remove_file(&socket);
+}
+
+async fn command(socket: &Path, command: &str) -> String {
+    let stream = UnixStream::connect(socket).await.unwrap();
+    send_command(
+        stream,
+        command,
+        #[cfg(feature = "__dnssec")]
+        None,
+    )
+    .await
+    .unwrap()
+}
@@ -1,6 +1,8 @@
 #[macro_use]
 mod authority_battery;
+mod catalog_zone_tests;
 mod config_tests;
+mod control_tests;
 mod forwarder;
 mod in_memory;
 mod named_https_tests;
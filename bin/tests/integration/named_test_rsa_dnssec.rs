@@ -12,8 +12,8 @@ use crate::server_harness::{named_test_harness, query_a, query_all_dnssec};
 use hickory_client::client::Client;
 use hickory_dns::dnssec::key_from_file;
 use hickory_proto::dnssec::{Algorithm, DnssecDnsHandle, TrustAnchors};
-use hickory_proto::runtime::{RuntimeProvider, TokioRuntimeProvider, TokioTime};
-use hickory_proto::tcp::TcpClientStream;
+use hickory_proto::runtime::{RuntimeProvider, TokioRuntimeProvider};
+use hickory_proto::tcp::{DnsTcpStream, TcpClientStream};
 use hickory_proto::xfer::{DnsExchangeBackground, DnsMultiplexer, Protocol};
 use test_support::subscribe;
 
@@ -37,7 +37,7 @@ async fn standard_tcp_conn<P: RuntimeProvider>(
     provider: P,
 ) -> (
     Client,
-    DnsExchangeBackground<DnsMultiplexer<TcpClientStream<P::Tcp>>, TokioTime>,
+    DnsExchangeBackground<DnsMultiplexer<TcpClientStream<P::Tcp>>, <P::Tcp as DnsTcpStream>::Time>,
 ) {
     let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
     let (stream, sender) = TcpClientStream::new(addr, None, None, provider);
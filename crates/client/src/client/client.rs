@@ -21,11 +21,13 @@ use rand;
 use tracing::debug;
 
 use crate::{ClientError, ClientErrorKind};
+#[cfg(feature = "tokio")]
+use hickory_proto::runtime::TokioTime;
 use hickory_proto::{
     ProtoError, ProtoErrorKind,
     op::{Edns, Message, MessageFinalizer, MessageType, OpCode, Query, update_message},
     rr::{DNSClass, Name, Record, RecordSet, RecordType, rdata::SOA},
-    runtime::TokioTime,
+    runtime::Time,
     xfer::{
         BufDnsStreamHandle, DnsClientStream, DnsExchange, DnsExchangeBackground, DnsExchangeSend,
         DnsHandle, DnsMultiplexer, DnsRequest, DnsRequestOptions, DnsRequestSender, DnsResponse,
@@ -60,7 +62,7 @@ impl Client {
         stream: F,
         stream_handle: BufDnsStreamHandle,
         signer: Option<Arc<dyn MessageFinalizer>>,
-    ) -> Result<(Self, DnsExchangeBackground<DnsMultiplexer<S>, TokioTime>), ProtoError>
+    ) -> Result<(Self, DnsExchangeBackground<DnsMultiplexer<S>, S::Time>), ProtoError>
     where
         F: Future<Output = Result<S, ProtoError>> + Send + Unpin + 'static,
         S: DnsClientStream + 'static + Unpin,
@@ -83,13 +85,13 @@ impl Client {
         stream_handle: BufDnsStreamHandle,
         timeout_duration: Duration,
         signer: Option<Arc<dyn MessageFinalizer>>,
-    ) -> Result<(Self, DnsExchangeBackground<DnsMultiplexer<S>, TokioTime>), ProtoError>
+    ) -> Result<(Self, DnsExchangeBackground<DnsMultiplexer<S>, S::Time>), ProtoError>
     where
         F: Future<Output = Result<S, ProtoError>> + 'static + Send + Unpin,
         S: DnsClientStream + 'static + Unpin,
     {
         let mp = DnsMultiplexer::with_timeout(stream, stream_handle, timeout_duration, signer);
-        Self::connect(mp).await
+        Self::connect_with_timer(mp).await
     }
 
     /// Returns a future, which itself wraps a future which is awaiting connection.
@@ -101,12 +103,28 @@ impl Client {
     /// This returns a tuple of Self a handle to send dns messages and an optional background.
     ///  The background task must be run on an executor before handle is used, if it is Some.
     ///  If it is None, then another thread has already run the background.
+    #[cfg(feature = "tokio")]
     pub async fn connect<F, S>(
         connect_future: F,
     ) -> Result<(Self, DnsExchangeBackground<S, TokioTime>), ProtoError>
     where
         S: DnsRequestSender,
         F: Future<Output = Result<S, ProtoError>> + 'static + Send + Unpin,
+    {
+        Self::connect_with_timer(connect_future).await
+    }
+
+    /// Like [`Client::connect`], for any async runtime
+    ///
+    /// The background task uses the timer `TE`, which should be the timer of the runtime it is
+    /// spawned on, e.g. `SmolTime` for smol.
+    pub async fn connect_with_timer<F, S, TE>(
+        connect_future: F,
+    ) -> Result<(Self, DnsExchangeBackground<S, TE>), ProtoError>
+    where
+        S: DnsRequestSender,
+        F: Future<Output = Result<S, ProtoError>> + 'static + Send + Unpin,
+        TE: Time + Unpin,
     {
         let result = DnsExchange::connect(connect_future).await;
         let use_edns = true;
@@ -571,7 +589,7 @@ pub trait ClientHandle: 'static + Clone + DnsHandle + Send {
     ///
     /// # Arguments
     /// * `zone_origin` - the zone name to update, i.e. SOA name
-    /// * `last_soa` - the last SOA known, if any. If provided, name must match `zone_origin`
+    /// * `last_soa` - the last SOA of the zone known, if any
     fn zone_transfer(
         &mut self,
         zone_origin: Name,
@@ -15,6 +15,8 @@ use crate::client::Client;
 use crate::proto::ProtoError;
 use crate::proto::dnssec::DnssecDnsHandle;
 use crate::proto::dnssec::TrustAnchors;
+use crate::proto::runtime::Time;
+#[cfg(feature = "tokio")]
 use crate::proto::runtime::TokioTime;
 use crate::proto::xfer::{
     DnsExchangeBackground, DnsHandle, DnsRequest, DnsRequestSender, DnsResponse,
@@ -42,6 +44,7 @@ impl DnssecClient {
     }
 
     /// Returns a DNSSEC verifying client with the default TrustAnchor
+    #[cfg(feature = "tokio")]
     pub async fn connect<F, S>(
         connect_future: F,
     ) -> Result<(Self, DnsExchangeBackground<S, TokioTime>), ProtoError>
@@ -102,11 +105,19 @@ where
     }
 
     /// Construct the new client
+    #[cfg(feature = "tokio")]
     pub async fn build(
-        mut self,
+        self,
     ) -> Result<(DnssecClient, DnsExchangeBackground<S, TokioTime>), ProtoError> {
+        self.build_with_timer().await
+    }
+
+    /// Construct the new client for any async runtime, see [`Client::connect_with_timer`]
+    pub async fn build_with_timer<TE: Time + Unpin>(
+        mut self,
+    ) -> Result<(DnssecClient, DnsExchangeBackground<S, TE>), ProtoError> {
         let trust_anchor = Arc::new(self.trust_anchor.take().unwrap_or_default());
-        let result = Client::connect(self.connect_future).await;
+        let result = Client::connect_with_timer(self.connect_future).await;
 
         result.map(|(client, bg)| (DnssecClient::from_client(client, trust_anchor), bg))
     }
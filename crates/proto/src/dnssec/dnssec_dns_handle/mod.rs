@@ -23,7 +23,7 @@ use tracing::{debug, trace, warn};
 
 use crate::{
     dnssec::{
-        Algorithm, Proof, ProofError, ProofErrorKind, TrustAnchors, Verifier,
+        Algorithm, NegativeTrustAnchors, Proof, ProofError, ProofErrorKind, TrustAnchors, Verifier,
         rdata::{DNSKEY, DS, RRSIG},
     },
     error::{ProtoError, ProtoErrorKind},
@@ -37,6 +37,9 @@ use self::rrset::Rrset;
 mod nsec3_validation;
 use nsec3_validation::verify_nsec3;
 
+mod trace;
+pub use trace::{ValidationEvent, ValidationTrace};
+
 use super::rdata::NSEC;
 
 /// Performs DNSSEC validation of all DNS responses from the wrapped DnsHandle
@@ -52,9 +55,11 @@ where
 {
     handle: H,
     trust_anchor: Arc<TrustAnchors>,
+    negative_trust_anchors: NegativeTrustAnchors,
     request_depth: usize,
     minimum_key_len: usize,
     minimum_algorithm: Algorithm, // used to prevent down grade attacks...
+    trace: Option<ValidationTrace>,
 }
 
 impl<H> DnssecDnsHandle<H>
@@ -82,12 +87,37 @@ where
         Self {
             handle,
             trust_anchor,
+            negative_trust_anchors: NegativeTrustAnchors::default(),
             request_depth: 0,
             minimum_key_len: 0,
             minimum_algorithm: Algorithm::RSASHA256,
+            trace: None,
         }
     }
 
+    /// Skips validation at and below the names in `negative_trust_anchors`, see RFC 7646
+    ///
+    /// Records covered by an active negative trust anchor are marked as `Insecure` without any
+    /// validation, and negative responses for them don't need NSEC or NSEC3 proofs. The set is
+    /// shared, changes made to it after this call are picked up by the handle.
+    pub fn with_negative_trust_anchors(
+        mut self,
+        negative_trust_anchors: NegativeTrustAnchors,
+    ) -> Self {
+        self.negative_trust_anchors = negative_trust_anchors;
+        self
+    }
+
+    /// Records every step of the validation of the responses to `trace`
+    ///
+    /// This includes every DS, DNSKEY and RRSIG that was considered while walking the chain of
+    /// trust, and the outcome of each check. The trace is shared by all clones of this handle,
+    /// so a dedicated handle should be used per traced query.
+    pub fn with_validation_trace(mut self, trace: ValidationTrace) -> Self {
+        self.trace = Some(trace);
+        self
+    }
+
     /// An internal function used to clone the handle, but maintain some information back to the
     ///  original handle, such as the request_depth such that infinite recursion does
     ///  not occur.
@@ -95,9 +125,18 @@ where
         Self {
             handle: self.handle.clone(),
             trust_anchor: Arc::clone(&self.trust_anchor),
+            negative_trust_anchors: self.negative_trust_anchors.clone(),
             request_depth: self.request_depth + 1,
             minimum_key_len: self.minimum_key_len,
             minimum_algorithm: self.minimum_algorithm,
+            trace: self.trace.clone(),
+        }
+    }
+
+    /// Records the event to the validation trace, if there is one
+    fn trace(&self, event: impl FnOnce() -> ValidationEvent) {
+        if let Some(trace) = &self.trace {
+            trace.record(event());
         }
     }
 }
@@ -141,6 +180,8 @@ where
         };
 
         let handle = self.clone_with_context();
+        let trace = self.trace.clone();
+        let negative_trust_anchors = self.negative_trust_anchors.clone();
         request
             .extensions_mut()
             .get_or_insert_with(Edns::new)
@@ -191,7 +232,12 @@ where
                     verify_response(handle.clone(), message_response, options)
                 })
                 .and_then(move |verified_message| {
-                    future::ready(check_nsec(verified_message, &query))
+                    // there is nothing to prove below a negative trust anchor
+                    if negative_trust_anchors.contains(query.name()) {
+                        return future::ok(verified_message);
+                    }
+
+                    future::ready(check_nsec(verified_message, &query, trace.as_ref()))
                 }),
         )
     }
@@ -202,7 +248,11 @@ where
 ///
 /// at this point all of the message is verified.
 /// This is where NSEC and NSEC3 validation occurs
-fn check_nsec(verified_message: DnsResponse, query: &Query) -> Result<DnsResponse, ProtoError> {
+fn check_nsec(
+    verified_message: DnsResponse,
+    query: &Query,
+    trace: Option<&ValidationTrace>,
+) -> Result<DnsResponse, ProtoError> {
     if !verified_message.answers().is_empty() {
         return Ok(verified_message);
     }
@@ -278,6 +328,13 @@ fn check_nsec(verified_message: DnsResponse, query: &Query) -> Result<DnsRespons
         }
     };
 
+    if let Some(trace) = trace {
+        trace.record(ValidationEvent::Nsec {
+            query: query.clone(),
+            proof: nsec_proof,
+        });
+    }
+
     if !nsec_proof.is_secure() {
         debug!("returning Nsec error for {} {nsec_proof}", query.name());
         // TODO change this to remove the NSECs, like we do for the others?
@@ -402,8 +459,24 @@ where
             rrsig_len = rrsigs.len()
         );
 
-        // verify this rrset
-        let proof = verify_rrset(handle.clone_with_context(), &rrset, rrsigs, options).await;
+        // verify this rrset, unless validation is disabled for it by a negative trust anchor
+        let proof = match handle.negative_trust_anchors.covering(&name) {
+            Some(anchor) => {
+                debug!("negative trust anchor {anchor} covers: {name} record_type: {record_type}");
+                handle.trace(|| ValidationEvent::NegativeTrustAnchor {
+                    name: name.clone(),
+                    record_type,
+                    anchor,
+                });
+                Ok((Proof::Insecure, None, None))
+            }
+            None => verify_rrset(handle.clone_with_context(), &rrset, rrsigs, options).await,
+        };
+        handle.trace(|| ValidationEvent::Rrset {
+            name: name.clone(),
+            record_type,
+            result: proof.as_ref().map(|proof| proof.0).map_err(Clone::clone),
+        });
 
         let proof = match proof {
             Ok(proof) => {
@@ -560,6 +633,15 @@ where
         };
 
         proof.0 = is_dnskey_in_root_store(&handle, &dnskey);
+        if proof.0.is_secure() {
+            if let Ok(key_tag) = dnskey.data().calculate_key_tag() {
+                handle.trace(|| ValidationEvent::TrustAnchor {
+                    name: dnskey.name().clone(),
+                    key_tag,
+                    algorithm: dnskey.data().algorithm(),
+                });
+            }
+        }
     }
 
     // if not all of the DNSKEYs are in the root store, then we need to look for DS records to verify
@@ -572,6 +654,16 @@ where
         Vec::default()
     };
 
+    for ds in &ds_records {
+        handle.trace(|| ValidationEvent::Ds {
+            name: ds.name().clone(),
+            key_tag: ds.data().key_tag(),
+            algorithm: ds.data().algorithm(),
+            digest_type: ds.data().digest_type(),
+            proof: ds.proof(),
+        });
+    }
+
     // if the DS records are not empty and they also have no supported algorithms, then this is INSECURE
     // for secure DS records the BOGUS check happens after DNSKEYs are evaluated against the DS
     if ds_records
@@ -601,7 +693,17 @@ where
         }
 
         // need to track each proof on each dnskey to ensure they are all validated
-        match verify_dnskey(&dnskey, &ds_records) {
+        let result = verify_dnskey(&dnskey, &ds_records);
+        if let Ok(key_tag) = dnskey.data().calculate_key_tag() {
+            handle.trace(|| ValidationEvent::Dnskey {
+                name: dnskey.name().clone(),
+                key_tag,
+                algorithm: dnskey.data().algorithm(),
+                result: result.clone(),
+            });
+        }
+
+        match result {
             Ok(pf) => {
                 *proof = (pf, None, None);
             }
@@ -629,7 +731,9 @@ where
                     .map(|r| (r, proof))
             })
             .find_map(|(dnskey, proof)| {
-                verify_rrset_with_dnskey(dnskey, *proof, rrsig, rrset, current_time).ok()
+                let result = verify_rrset_with_dnskey(dnskey, *proof, rrsig, rrset, current_time);
+                trace_rrsig(handle.trace.as_ref(), rrset, rrsig, &result);
+                result.ok()
             });
 
         if let Some(rrset_proof) = rrset_proof {
@@ -944,6 +1048,7 @@ where
         .enumerate()
         .filter_map(|(i, rrsig)| {
             let handle = handle.clone_with_context();
+            let trace = handle.trace.clone();
             let query = Query::query(rrsig.data().signer_name().clone(), RecordType::DNSKEY);
 
             if i > MAX_RRSIGS_PER_RRSET {
@@ -995,9 +1100,9 @@ where
                         match dnskey.proof() {
                             Proof::Secure => {
                                 all_insecure = Some(false);
-                                if let Ok(proof) =
-                                    verify_rrset_with_dnskey(dnskey, dnskey.proof(), rrsig, rrset, current_time)
-                                {
+                                let result = verify_rrset_with_dnskey(dnskey, dnskey.proof(), rrsig, rrset, current_time);
+                                trace_rrsig(trace.as_ref(), rrset, rrsig, &result);
+                                if let Ok(proof) = result {
                                     return Some((proof.0, proof.1, Some(i)));
                                 }
                             }
@@ -1042,6 +1147,30 @@ where
     )
 }
 
+/// Records the outcome of `verify_rrset_with_dnskey` to the validation trace, if there is one
+fn trace_rrsig(
+    trace: Option<&ValidationTrace>,
+    rrset: &Rrset<'_>,
+    rrsig: &RecordRef<'_, RRSIG>,
+    result: &Result<(Proof, Option<u32>), ProofError>,
+) {
+    let Some(trace) = trace else {
+        return;
+    };
+
+    let rrsig = rrsig.data();
+    trace.record(ValidationEvent::Rrsig {
+        name: rrset.name().clone(),
+        record_type: rrset.record_type(),
+        signer_name: rrsig.signer_name().clone(),
+        key_tag: rrsig.key_tag(),
+        algorithm: rrsig.algorithm(),
+        inception: rrsig.sig_inception(),
+        expiration: rrsig.sig_expiration(),
+        result: result.as_ref().map(|proof| proof.0).map_err(Clone::clone),
+    });
+}
+
 /// Verifies the given SIG of the RRSET with the DNSKEY.
 fn verify_rrset_with_dnskey(
     dnskey: RecordRef<'_, DNSKEY>,
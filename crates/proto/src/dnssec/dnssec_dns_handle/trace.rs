@@ -0,0 +1,232 @@
+//! Tracing of the chain of trust walked during DNSSEC validation
+
+use alloc::{sync::Arc, vec::Vec};
+use core::fmt;
+use std::sync::Mutex;
+
+use time::OffsetDateTime;
+
+use crate::{
+    dnssec::{Algorithm, DigestType, Proof, ProofError},
+    op::Query,
+    rr::{Name, RecordType, SerialNumber},
+};
+
+/// A record of every step taken while validating a response, see
+/// [`DnssecDnsHandle::with_validation_trace`](super::DnssecDnsHandle::with_validation_trace)
+///
+/// This is a cheap handle to a shared list of events, clones observe the same events. The
+/// `Display` implementation prints one event per line, in the order they occurred.
+#[derive(Clone, Debug, Default)]
+pub struct ValidationTrace {
+    events: Arc<Mutex<Vec<ValidationEvent>>>,
+}
+
+impl ValidationTrace {
+    /// Creates a new empty trace
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a copy of the events recorded so far
+    pub fn events(&self) -> Vec<ValidationEvent> {
+        self.events
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    pub(super) fn record(&self, event: ValidationEvent) {
+        self.events
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .push(event);
+    }
+}
+
+impl fmt::Display for ValidationTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for event in self.events() {
+            writeln!(f, "{event}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A single step of DNSSEC validation
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum ValidationEvent {
+    /// A DNSKEY is one of the configured trust anchors
+    TrustAnchor {
+        /// Owner name of the DNSKEY
+        name: Name,
+        /// Key tag of the DNSKEY
+        key_tag: u16,
+        /// Algorithm of the DNSKEY
+        algorithm: Algorithm,
+    },
+    /// A DS record was found in the parent zone
+    Ds {
+        /// Owner name of the DS record, i.e. the child zone
+        name: Name,
+        /// Key tag of the DNSKEY the DS record refers to
+        key_tag: u16,
+        /// Algorithm of the DNSKEY the DS record refers to
+        algorithm: Algorithm,
+        /// Digest type of the DS record
+        digest_type: DigestType,
+        /// Validation status of the DS record
+        proof: Proof,
+    },
+    /// A DNSKEY was checked against the DS records of its zone
+    Dnskey {
+        /// Owner name of the DNSKEY
+        name: Name,
+        /// Key tag of the DNSKEY
+        key_tag: u16,
+        /// Algorithm of the DNSKEY
+        algorithm: Algorithm,
+        /// The outcome of the check
+        result: Result<Proof, ProofError>,
+    },
+    /// An RRSIG was checked with a DNSKEY
+    Rrsig {
+        /// Owner name of the RRset
+        name: Name,
+        /// Type of the RRset
+        record_type: RecordType,
+        /// Name of the zone that signed the RRset
+        signer_name: Name,
+        /// Key tag of the DNSKEY used
+        key_tag: u16,
+        /// Algorithm of the signature
+        algorithm: Algorithm,
+        /// Start of the validity period of the signature
+        inception: SerialNumber,
+        /// End of the validity period of the signature
+        expiration: SerialNumber,
+        /// The outcome of the check
+        result: Result<Proof, ProofError>,
+    },
+    /// Validation of an RRset completed
+    Rrset {
+        /// Owner name of the RRset
+        name: Name,
+        /// Type of the RRset
+        record_type: RecordType,
+        /// The outcome of the validation
+        result: Result<Proof, ProofError>,
+    },
+    /// Validation of an RRset was skipped because of a negative trust anchor
+    NegativeTrustAnchor {
+        /// Owner name of the RRset
+        name: Name,
+        /// Type of the RRset
+        record_type: RecordType,
+        /// The negative trust anchor covering the RRset
+        anchor: Name,
+    },
+    /// The NSEC or NSEC3 records of a negative response were checked
+    Nsec {
+        /// The query that had a negative response
+        query: Query,
+        /// The outcome of the check
+        proof: Proof,
+    },
+}
+
+impl fmt::Display for ValidationEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TrustAnchor {
+                name,
+                key_tag,
+                algorithm,
+            } => write!(f, "DNSKEY {name} key {key_tag} ({algorithm}): trust anchor"),
+            Self::Ds {
+                name,
+                key_tag,
+                algorithm,
+                digest_type,
+                proof,
+            } => write!(
+                f,
+                "DS {name} key {key_tag} ({algorithm}) digest {digest_type:?}: {proof}"
+            ),
+            Self::Dnskey {
+                name,
+                key_tag,
+                algorithm,
+                result,
+            } => {
+                write!(f, "DNSKEY {name} key {key_tag} ({algorithm}): ")?;
+                fmt_result(f, result)
+            }
+            Self::Rrsig {
+                name,
+                record_type,
+                signer_name,
+                key_tag,
+                algorithm,
+                inception,
+                expiration,
+                result,
+            } => {
+                write!(
+                    f,
+                    "RRSIG {name} {record_type} by {signer_name} key {key_tag} ({algorithm}) valid {}-{}: ",
+                    Timestamp(*inception),
+                    Timestamp(*expiration),
+                )?;
+                fmt_result(f, result)
+            }
+            Self::Rrset {
+                name,
+                record_type,
+                result,
+            } => {
+                write!(f, "RRset {name} {record_type}: ")?;
+                fmt_result(f, result)
+            }
+            Self::NegativeTrustAnchor {
+                name,
+                record_type,
+                anchor,
+            } => write!(f, "NTA {anchor} covers RRset {name} {record_type}"),
+            Self::Nsec { query, proof } => {
+                write!(f, "NSEC {} {}: {proof}", query.name(), query.query_type())
+            }
+        }
+    }
+}
+
+fn fmt_result(f: &mut fmt::Formatter<'_>, result: &Result<Proof, ProofError>) -> fmt::Result {
+    match result {
+        Ok(proof) => write!(f, "{proof}"),
+        Err(err) => write!(f, "{err}"),
+    }
+}
+
+/// Formats a signature timestamp like the RRSIG presentation format, YYYYMMDDHHmmSS
+struct Timestamp(SerialNumber);
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Ok(time) = OffsetDateTime::from_unix_timestamp(self.0.get().into()) else {
+            return write!(f, "{}", self.0.get());
+        };
+
+        write!(
+            f,
+            "{:04}{:02}{:02}{:02}{:02}{:02}",
+            time.year(),
+            u8::from(time.month()),
+            time.day(),
+            time.hour(),
+            time.minute(),
+            time.second()
+        )
+    }
+}
@@ -7,33 +7,39 @@
 
 //! dns security extension related modules
 
+use alloc::boxed::Box;
 use alloc::string::String;
 use alloc::vec::Vec;
 use core::fmt;
 
 #[cfg(feature = "backtrace")]
 use backtrace::Backtrace;
+use futures_util::future::{self, BoxFuture};
 use rdata::tsig::TsigAlgorithm;
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 use crate::error::{ProtoError, ProtoErrorKind};
+use crate::serialize::binary::DecodeError;
 #[cfg(feature = "backtrace")]
 use crate::trace;
 
 mod algorithm;
 mod dnssec_dns_handle;
-pub use dnssec_dns_handle::DnssecDnsHandle;
 #[doc(hidden)]
 pub use dnssec_dns_handle::verify_nsec;
+pub use dnssec_dns_handle::{DnssecDnsHandle, ValidationEvent, ValidationTrace};
 /// Cryptographic backend implementations of DNSSEC traits.
 pub mod crypto;
 mod ec_public_key;
+mod negative_trust_anchor;
 mod nsec3;
 pub mod proof;
 pub mod public_key;
 pub mod rdata;
+#[cfg(feature = "tokio")]
+pub mod remote;
 mod rsa_public_key;
 mod signer;
 mod supported_algorithm;
@@ -43,6 +49,7 @@ pub mod tsig;
 mod verifier;
 
 pub use self::algorithm::Algorithm;
+pub use self::negative_trust_anchor::{NegativeTrustAnchor, NegativeTrustAnchors};
 pub use self::nsec3::Nsec3HashAlgorithm;
 pub use self::proof::{Proof, ProofError, ProofErrorKind, ProofFlags, Proven};
 pub use self::public_key::{PublicKey, PublicKeyBuf};
@@ -113,6 +120,12 @@ impl From<DigestType> for u8 {
 }
 
 /// A key that can be used to sign records.
+///
+/// This is the extension point for keys that don't live in this process, e.g. keys held by an
+/// HSM through a PKCS#11 module or by a separate signing service. Such implementations should
+/// override [`SigningKey::sign_batch`], which is used when a whole zone or a dynamic update is
+/// signed, see `RemoteSigningKey` in the `remote` module (with the `tokio` feature) for an
+/// example.
 pub trait SigningKey: Send + Sync + 'static {
     /// Sign DNS records.
     ///
@@ -121,6 +134,20 @@ pub trait SigningKey: Send + Sync + 'static {
     /// The signature, ready to be stored in an `RData::RRSIG`.
     fn sign(&self, tbs: &TBS) -> DnsSecResult<Vec<u8>>;
 
+    /// Sign many sets of DNS records at once.
+    ///
+    /// The default implementation calls [`SigningKey::sign`] for each entry. Keys backed by an
+    /// external signer should override this to submit the batch without blocking the caller.
+    ///
+    /// # Return value
+    ///
+    /// The signatures, in the same order as `tbs`.
+    fn sign_batch<'a>(&'a self, tbs: &'a [TBS]) -> BoxFuture<'a, DnsSecResult<Vec<Vec<u8>>>> {
+        Box::pin(future::ready(
+            tbs.iter().map(|tbs| self.sign(tbs)).collect(),
+        ))
+    }
+
     /// Returns a [`PublicKeyBuf`] for this [`SigningKey`].
     fn to_public_key(&self) -> DnsSecResult<PublicKeyBuf>;
 
@@ -205,6 +232,12 @@ impl From<ProtoError> for DnsSecError {
     }
 }
 
+impl From<DecodeError> for DnsSecError {
+    fn from(e: DecodeError) -> Self {
+        ProtoError::from(e).into()
+    }
+}
+
 impl From<ring_like::KeyRejected> for DnsSecError {
     fn from(e: ring_like::KeyRejected) -> Self {
         DnsSecErrorKind::from(e).into()
@@ -0,0 +1,226 @@
+// Copyright 2015-2025 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Negative trust anchors, [RFC 7646](https://datatracker.ietf.org/doc/html/rfc7646)
+//!
+//! A negative trust anchor disables DNSSEC validation for a domain and everything below it, so
+//! that a domain with a broken DNSSEC deployment stays reachable from a validating resolver.
+
+use alloc::{sync::Arc, vec::Vec};
+use core::time::Duration;
+use std::{collections::HashMap, sync::RwLock, time::Instant};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::rr::Name;
+
+/// A single negative trust anchor
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
+pub struct NegativeTrustAnchor {
+    /// Domain at and below which validation is skipped
+    pub name: Name,
+    /// How long the negative trust anchor stays in place, `None` for no expiry
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub lifetime: Option<Duration>,
+}
+
+impl NegativeTrustAnchor {
+    /// Creates a negative trust anchor for `name` that expires after `lifetime`
+    pub fn new(name: Name, lifetime: Option<Duration>) -> Self {
+        Self { name, lifetime }
+    }
+}
+
+/// The set of negative trust anchors in use by a validator
+///
+/// This is a cheap handle to a shared set, clones observe the same anchors, so the set can be
+/// changed at runtime while it's in use by a [`DnssecDnsHandle`](super::DnssecDnsHandle). Records
+/// at or below an active anchor are not validated and are given a proof of
+/// [`Proof::Insecure`](super::Proof::Insecure).
+#[derive(Clone, Debug, Default)]
+pub struct NegativeTrustAnchors {
+    /// Lowercased names to the instant at which they expire
+    anchors: Arc<RwLock<HashMap<Name, Option<Instant>>>>,
+}
+
+impl NegativeTrustAnchors {
+    /// Creates a new empty set of negative trust anchors
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a negative trust anchor for `name`, replacing any existing one for the same name
+    pub fn insert(&self, name: Name, lifetime: Option<Duration>) {
+        let now = Instant::now();
+        let expires = lifetime.and_then(|lifetime| now.checked_add(lifetime));
+        let mut anchors = self
+            .anchors
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        // drop the anchors that have expired, so that the set doesn't grow unbounded
+        anchors.retain(|_, expires| is_active(*expires, now));
+        anchors.insert(name.to_lowercase(), expires);
+    }
+
+    /// Removes the negative trust anchor for exactly `name`, returns `true` if there was one
+    pub fn remove(&self, name: &Name) -> bool {
+        self.anchors
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .remove(&name.to_lowercase())
+            .is_some()
+    }
+
+    /// Returns the active negative trust anchor covering `name`, if any
+    ///
+    /// An anchor covers its own name and every name below it.
+    pub fn covering(&self, name: &Name) -> Option<Name> {
+        self.covering_at(name, Instant::now())
+    }
+
+    /// Returns `true` if there is an active negative trust anchor at or above `name`
+    pub fn contains(&self, name: &Name) -> bool {
+        self.covering(name).is_some()
+    }
+
+    /// Returns the active negative trust anchors, with their remaining lifetime
+    pub fn anchors(&self) -> Vec<NegativeTrustAnchor> {
+        let now = Instant::now();
+        let mut anchors = self
+            .anchors
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .iter()
+            .filter(|(_, expires)| is_active(**expires, now))
+            .map(|(name, expires)| NegativeTrustAnchor {
+                name: name.clone(),
+                lifetime: expires.map(|expires| expires.saturating_duration_since(now)),
+            })
+            .collect::<Vec<_>>();
+
+        anchors.sort_by(|a, b| a.name.cmp(&b.name));
+        anchors
+    }
+
+    /// Returns `true` if there are no active negative trust anchors
+    pub fn is_empty(&self) -> bool {
+        let now = Instant::now();
+        !self
+            .anchors
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .values()
+            .any(|expires| is_active(*expires, now))
+    }
+
+    fn covering_at(&self, name: &Name, now: Instant) -> Option<Name> {
+        let anchors = self
+            .anchors
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if anchors.is_empty() {
+            return None;
+        }
+
+        let mut name = name.to_lowercase();
+        loop {
+            if let Some(expires) = anchors.get(&name) {
+                if is_active(*expires, now) {
+                    return Some(name);
+                }
+            }
+
+            if name.is_root() {
+                return None;
+            }
+            name = name.base_name();
+        }
+    }
+}
+
+impl FromIterator<NegativeTrustAnchor> for NegativeTrustAnchors {
+    fn from_iter<T: IntoIterator<Item = NegativeTrustAnchor>>(iter: T) -> Self {
+        let anchors = Self::new();
+        for anchor in iter {
+            anchors.insert(anchor.name, anchor.lifetime);
+        }
+
+        anchors
+    }
+}
+
+fn is_active(expires: Option<Instant>, now: Instant) -> bool {
+    expires.map_or(true, |expires| now < expires)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_covers_subdomains() {
+        let anchors = NegativeTrustAnchors::new();
+        anchors.insert(Name::from_ascii("Example.com.").unwrap(), None);
+
+        let covering = Some(Name::from_ascii("example.com.").unwrap());
+        for name in ["example.com.", "www.example.com.", "A.B.EXAMPLE.COM."] {
+            assert_eq!(
+                anchors.covering(&Name::from_ascii(name).unwrap()),
+                covering,
+                "{name}"
+            );
+        }
+
+        for name in ["com.", ".", "example.net.", "notexample.com."] {
+            assert!(
+                !anchors.contains(&Name::from_ascii(name).unwrap()),
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_expiry() {
+        let anchors = NegativeTrustAnchors::new();
+        let name = Name::from_ascii("example.com.").unwrap();
+        anchors.insert(name.clone(), Some(Duration::from_secs(60)));
+
+        let now = Instant::now();
+        assert!(anchors.covering_at(&name, now).is_some());
+        assert!(
+            anchors
+                .covering_at(&name, now + Duration::from_secs(61))
+                .is_none()
+        );
+
+        anchors.insert(name.clone(), Some(Duration::ZERO));
+        assert!(!anchors.contains(&name));
+        assert!(anchors.is_empty());
+        assert!(anchors.anchors().is_empty());
+    }
+
+    #[test]
+    fn test_insert_remove_shared() {
+        let anchors = NegativeTrustAnchors::new();
+        let shared = anchors.clone();
+        let name = Name::from_ascii("example.com.").unwrap();
+
+        shared.insert(name.clone(), None);
+        assert_eq!(
+            anchors.anchors(),
+            vec![NegativeTrustAnchor::new(name.clone(), None)]
+        );
+
+        assert!(anchors.remove(&Name::from_ascii("EXAMPLE.com.").unwrap()));
+        assert!(!shared.remove(&name));
+        assert!(!shared.contains(&name));
+    }
+}
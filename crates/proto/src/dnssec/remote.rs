@@ -0,0 +1,521 @@
+//! Signing keys held by an external signer, reached over a local socket
+//!
+//! This allows zones to be signed without the private keys ever being readable by this process,
+//! the signer can be a small service in front of an HSM, a PKCS#11 module or a cloud KMS.
+//!
+//! # Protocol
+//!
+//! Every request opens a new connection, and consists of one request frame answered by one
+//! response frame. A frame is a 4 byte big-endian length followed by that many bytes.
+//!
+//! A request starts with the length of the key identifier (1 byte), the UTF-8 key identifier,
+//! and an operation code (1 byte):
+//!
+//! * `0x01`, public key: no further data. A successful response is a status of `0x00`, the
+//!   DNSSEC algorithm number (1 byte) and the public key in DNSKEY format.
+//! * `0x02`, sign: the number of entries (2 bytes), followed by each TBS buffer prefixed by its
+//!   length (4 bytes). A successful response is a status of `0x00`, the number of signatures
+//!   (2 bytes), followed by each signature prefixed by its length (4 bytes), in request order.
+//!
+//! A failed request is answered with a status of `0x01` followed by a UTF-8 error message.
+//!
+//! [`serve_signer`] implements the signer side of the protocol on top of any [`SigningKey`], it
+//! can be used as a reference, or as a stub signer in tests.
+
+use alloc::{
+    boxed::Box,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{fmt, slice, str::FromStr, time::Duration};
+use std::{
+    collections::HashMap,
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpStream},
+};
+#[cfg(unix)]
+use std::{os::unix::net::UnixStream, path::PathBuf};
+
+use futures_util::future::BoxFuture;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::runtime::{Handle, RuntimeFlavor};
+use tracing::{debug, warn};
+
+use super::{
+    Algorithm, DnsSecError, DnsSecErrorKind, DnsSecResult, PublicKey, PublicKeyBuf, SigningKey, TBS,
+};
+use crate::serialize::binary::BinDecoder;
+
+const OP_PUBLIC_KEY: u8 = 0x01;
+const OP_SIGN: u8 = 0x02;
+const STATUS_OK: u8 = 0x00;
+const STATUS_ERROR: u8 = 0x01;
+
+/// The largest frame that will be accepted from the peer
+const MAX_FRAME_LEN: usize = 16 * 1024 * 1024;
+/// The largest number of entries sent in a single sign request
+const MAX_BATCH_LEN: usize = 1024;
+/// The default timeout for a request to the signer
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The address of an external signer
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RemoteSignerAddr {
+    /// A TCP socket, this should only be used on the loopback interface or a trusted network
+    Tcp(SocketAddr),
+    /// A Unix domain socket
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+impl FromStr for RemoteSignerAddr {
+    type Err = DnsSecError;
+
+    /// Parses `unix:/path/to/socket`, `tcp:127.0.0.1:5353` or `127.0.0.1:5353`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        #[cfg(unix)]
+        if let Some(path) = s.strip_prefix("unix:") {
+            return Ok(Self::Unix(PathBuf::from(path)));
+        }
+
+        s.strip_prefix("tcp:")
+            .unwrap_or(s)
+            .parse()
+            .map(Self::Tcp)
+            .map_err(|e| format!("invalid signer address {s}: {e}").into())
+    }
+}
+
+impl fmt::Display for RemoteSignerAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tcp(addr) => write!(f, "tcp:{addr}"),
+            #[cfg(unix)]
+            Self::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+/// A [`SigningKey`] whose private key is held by an external signer
+///
+/// [`SigningKey::sign_batch`] sends whole batches to the signer without blocking the runtime,
+/// this is what's used when zones are signed. [`SigningKey::sign`] and
+/// [`RemoteSigningKey::connect`] block the current thread until the signer answers, on a worker
+/// of a multi-threaded tokio runtime this is done with [`tokio::task::block_in_place`], so that
+/// the other tasks of the worker keep running.
+pub struct RemoteSigningKey {
+    addr: RemoteSignerAddr,
+    key_id: String,
+    public_key: PublicKeyBuf,
+    timeout: Duration,
+}
+
+impl RemoteSigningKey {
+    /// Connects to the signer and retrieves the public part of the key
+    ///
+    /// This blocks until the signer answers, or until the default timeout of 5 seconds expires.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - the address of the signer
+    /// * `key_id` - identifies the key to the signer, e.g. a PKCS#11 label, at most 255 bytes
+    pub fn connect(addr: RemoteSignerAddr, key_id: impl Into<String>) -> DnsSecResult<Self> {
+        Self::with_timeout(addr, key_id, DEFAULT_TIMEOUT)
+    }
+
+    /// Same as [`RemoteSigningKey::connect`], `timeout` applies to every request to the signer
+    pub fn with_timeout(
+        addr: RemoteSignerAddr,
+        key_id: impl Into<String>,
+        timeout: Duration,
+    ) -> DnsSecResult<Self> {
+        let key_id = key_id.into();
+        let request = encode_request(&key_id, OP_PUBLIC_KEY, &[])?;
+        let response = block_in_place(|| request_blocking(&addr, timeout, &request))?;
+        let public_key = decode_public_key_response(&response)?;
+
+        debug!(
+            "loaded {} key {key_id} from signer: {addr}",
+            public_key.algorithm()
+        );
+        Ok(Self {
+            addr,
+            key_id,
+            public_key,
+            timeout,
+        })
+    }
+
+    /// The address of the signer
+    pub fn addr(&self) -> &RemoteSignerAddr {
+        &self.addr
+    }
+
+    /// The identifier of the key in the signer
+    pub fn key_id(&self) -> &str {
+        &self.key_id
+    }
+}
+
+impl SigningKey for RemoteSigningKey {
+    fn sign(&self, tbs: &TBS) -> DnsSecResult<Vec<u8>> {
+        let request = encode_sign_request(&self.key_id, slice::from_ref(tbs))?;
+        let response = block_in_place(|| request_blocking(&self.addr, self.timeout, &request))?;
+        let mut signatures = decode_sign_response(&response, 1)?;
+        Ok(signatures.remove(0))
+    }
+
+    fn sign_batch<'a>(&'a self, tbs: &'a [TBS]) -> BoxFuture<'a, DnsSecResult<Vec<Vec<u8>>>> {
+        Box::pin(async move {
+            let mut signatures = Vec::with_capacity(tbs.len());
+            for chunk in tbs.chunks(MAX_BATCH_LEN) {
+                let request = encode_sign_request(&self.key_id, chunk)?;
+                let response =
+                    tokio::time::timeout(self.timeout, request_async(&self.addr, &request))
+                        .await
+                        .map_err(|_| DnsSecError::from(DnsSecErrorKind::Timeout))??;
+
+                signatures.extend(decode_sign_response(&response, chunk.len())?);
+            }
+
+            Ok(signatures)
+        })
+    }
+
+    fn to_public_key(&self) -> DnsSecResult<PublicKeyBuf> {
+        Ok(self.public_key.clone())
+    }
+
+    fn algorithm(&self) -> Algorithm {
+        self.public_key.algorithm()
+    }
+}
+
+/// Answers the requests of a [`RemoteSigningKey`] on `stream`, using `keys` by identifier
+///
+/// This is a reference implementation of the signer side of the protocol, see the
+/// [module documentation](self). It handles a single request, as each request from a
+/// [`RemoteSigningKey`] uses a new connection.
+pub async fn serve_signer<S, K>(mut stream: S, keys: &HashMap<String, K>) -> io::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    K: SigningKey,
+{
+    let request = read_frame_async(&mut stream).await?;
+    let response = match handle_request(&request, keys) {
+        Ok(response) => response,
+        Err(e) => {
+            warn!("signer request failed: {e}");
+            let mut response = vec![STATUS_ERROR];
+            response.extend_from_slice(e.to_string().as_bytes());
+            response
+        }
+    };
+
+    write_frame_async(&mut stream, &response).await?;
+    stream.shutdown().await
+}
+
+fn handle_request<K: SigningKey>(
+    request: &[u8],
+    keys: &HashMap<String, K>,
+) -> DnsSecResult<Vec<u8>> {
+    let mut decoder = BinDecoder::new(request);
+    let key_id_len = decoder.read_u8()?.unverified() as usize;
+    let key_id = decoder.read_slice(key_id_len)?.unverified();
+    let key_id = core::str::from_utf8(key_id).map_err(|_| "key identifier is not UTF-8")?;
+    let Some(key) = keys.get(key_id) else {
+        return Err(format!("unknown key: {key_id}").into());
+    };
+
+    let mut response = vec![STATUS_OK];
+    match decoder.read_u8()?.unverified() {
+        OP_PUBLIC_KEY => {
+            let public_key = key.to_public_key()?;
+            response.push(u8::from(public_key.algorithm()));
+            response.extend_from_slice(public_key.public_bytes());
+        }
+        OP_SIGN => {
+            let count = decoder.read_u16()?.unverified();
+            response.extend_from_slice(&count.to_be_bytes());
+            for _ in 0..count {
+                let tbs = read_entry(&mut decoder)?;
+                emit_entry(&mut response, &key.sign(&TBS::from(tbs))?)?;
+            }
+        }
+        op => return Err(format!("unknown operation: {op}").into()),
+    }
+
+    Ok(response)
+}
+
+fn encode_request(key_id: &str, op: u8, body: &[u8]) -> DnsSecResult<Vec<u8>> {
+    let key_id_len = u8::try_from(key_id.len()).map_err(|_| "key identifier is too long")?;
+
+    let mut request = Vec::with_capacity(2 + key_id.len() + body.len());
+    request.push(key_id_len);
+    request.extend_from_slice(key_id.as_bytes());
+    request.push(op);
+    request.extend_from_slice(body);
+    Ok(request)
+}
+
+fn encode_sign_request(key_id: &str, tbs: &[TBS]) -> DnsSecResult<Vec<u8>> {
+    let count = u16::try_from(tbs.len()).map_err(|_| "too many entries in sign request")?;
+
+    let mut body = Vec::new();
+    body.extend_from_slice(&count.to_be_bytes());
+    for tbs in tbs {
+        emit_entry(&mut body, tbs.as_ref())?;
+    }
+
+    encode_request(key_id, OP_SIGN, &body)
+}
+
+fn decode_public_key_response(response: &[u8]) -> DnsSecResult<PublicKeyBuf> {
+    let mut decoder = decode_status(response)?;
+    let algorithm = Algorithm::from_u8(decoder.read_u8()?.unverified());
+    let key = decoder.read_slice(decoder.len())?.unverified();
+    Ok(PublicKeyBuf::new(key.to_vec(), algorithm))
+}
+
+fn decode_sign_response(response: &[u8], expected: usize) -> DnsSecResult<Vec<Vec<u8>>> {
+    let mut decoder = decode_status(response)?;
+    let count = decoder.read_u16()?.unverified() as usize;
+    if count != expected {
+        return Err(format!("signer returned {count} signatures, expected {expected}").into());
+    }
+
+    (0..count)
+        .map(|_| read_entry(&mut decoder).map(<[u8]>::to_vec))
+        .collect()
+}
+
+fn decode_status(response: &[u8]) -> DnsSecResult<BinDecoder<'_>> {
+    let mut decoder = BinDecoder::new(response);
+    match decoder.read_u8()?.unverified() {
+        STATUS_OK => Ok(decoder),
+        STATUS_ERROR => Err(format!(
+            "signer error: {}",
+            String::from_utf8_lossy(decoder.read_slice(decoder.len())?.unverified())
+        )
+        .into()),
+        status => Err(format!("unknown signer status: {status}").into()),
+    }
+}
+
+fn emit_entry(buf: &mut Vec<u8>, entry: &[u8]) -> DnsSecResult<()> {
+    let len = u32::try_from(entry.len()).map_err(|_| "entry is too long")?;
+    buf.extend_from_slice(&len.to_be_bytes());
+    buf.extend_from_slice(entry);
+    Ok(())
+}
+
+fn read_entry<'a>(decoder: &mut BinDecoder<'a>) -> DnsSecResult<&'a [u8]> {
+    let len = decoder.read_u32()?.unverified() as usize;
+    Ok(decoder.read_slice(len)?.unverified())
+}
+
+/// Runs `f` with [`tokio::task::block_in_place`] if this is a multi-threaded runtime
+///
+/// `block_in_place` panics on a current thread runtime, there `f` blocks the runtime until it
+/// returns.
+fn block_in_place<T>(f: impl FnOnce() -> T) -> T {
+    match Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+            tokio::task::block_in_place(f)
+        }
+        _ => f(),
+    }
+}
+
+fn request_blocking(
+    addr: &RemoteSignerAddr,
+    timeout: Duration,
+    request: &[u8],
+) -> DnsSecResult<Vec<u8>> {
+    fn exchange(mut stream: impl Read + Write, request: &[u8]) -> io::Result<Vec<u8>> {
+        stream.write_all(&frame_len(request)?)?;
+        stream.write_all(request)?;
+
+        let mut len = [0; 4];
+        stream.read_exact(&mut len)?;
+        let mut response = vec![0; checked_frame_len(len)?];
+        stream.read_exact(&mut response)?;
+        Ok(response)
+    }
+
+    let response = match addr {
+        RemoteSignerAddr::Tcp(socket_addr) => TcpStream::connect_timeout(socket_addr, timeout)
+            .and_then(|stream| {
+                stream.set_read_timeout(Some(timeout))?;
+                stream.set_write_timeout(Some(timeout))?;
+                exchange(stream, request)
+            }),
+        #[cfg(unix)]
+        RemoteSignerAddr::Unix(path) => UnixStream::connect(path).and_then(|stream| {
+            stream.set_read_timeout(Some(timeout))?;
+            stream.set_write_timeout(Some(timeout))?;
+            exchange(stream, request)
+        }),
+    };
+
+    response.map_err(|e| io_error(addr, e))
+}
+
+async fn request_async(addr: &RemoteSignerAddr, request: &[u8]) -> DnsSecResult<Vec<u8>> {
+    async fn exchange<S>(mut stream: S, request: &[u8]) -> io::Result<Vec<u8>>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        write_frame_async(&mut stream, request).await?;
+        read_frame_async(&mut stream).await
+    }
+
+    let response = match addr {
+        RemoteSignerAddr::Tcp(socket_addr) => {
+            match tokio::net::TcpStream::connect(socket_addr).await {
+                Ok(stream) => exchange(stream, request).await,
+                Err(e) => Err(e),
+            }
+        }
+        #[cfg(unix)]
+        RemoteSignerAddr::Unix(path) => match tokio::net::UnixStream::connect(path).await {
+            Ok(stream) => exchange(stream, request).await,
+            Err(e) => Err(e),
+        },
+    };
+
+    response.map_err(|e| io_error(addr, e))
+}
+
+async fn read_frame_async<S: AsyncRead + Unpin>(stream: &mut S) -> io::Result<Vec<u8>> {
+    let mut len = [0; 4];
+    stream.read_exact(&mut len).await?;
+    let mut frame = vec![0; checked_frame_len(len)?];
+    stream.read_exact(&mut frame).await?;
+    Ok(frame)
+}
+
+async fn write_frame_async<S: AsyncWrite + Unpin>(stream: &mut S, frame: &[u8]) -> io::Result<()> {
+    stream.write_all(&frame_len(frame)?).await?;
+    stream.write_all(frame).await?;
+    stream.flush().await
+}
+
+fn frame_len(frame: &[u8]) -> io::Result<[u8; 4]> {
+    match u32::try_from(frame.len()) {
+        Ok(len) if frame.len() <= MAX_FRAME_LEN => Ok(len.to_be_bytes()),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "frame too long",
+        )),
+    }
+}
+
+fn checked_frame_len(len: [u8; 4]) -> io::Result<usize> {
+    match u32::from_be_bytes(len) as usize {
+        len if len <= MAX_FRAME_LEN => Ok(len),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidData, "frame too long")),
+    }
+}
+
+fn io_error(addr: &RemoteSignerAddr, e: io::Error) -> DnsSecError {
+    match e.kind() {
+        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => DnsSecErrorKind::Timeout.into(),
+        _ => format!("error talking to signer {addr}: {e}").into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use test_support::subscribe;
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::dnssec::crypto::Ed25519SigningKey;
+
+    /// Spawns a stub signer on a loopback TCP socket, serving one key named "zsk"
+    async fn stub_signer(requests: &'static AtomicUsize) -> RemoteSignerAddr {
+        let key =
+            Ed25519SigningKey::from_pkcs8(&Ed25519SigningKey::generate_pkcs8().unwrap()).unwrap();
+        let keys = HashMap::from([("zsk".to_string(), key)]);
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = RemoteSignerAddr::Tcp(listener.local_addr().unwrap());
+
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                requests.fetch_add(1, Ordering::SeqCst);
+                serve_signer(stream, &keys).await.unwrap();
+            }
+        });
+
+        addr
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_remote_signing_key() {
+        subscribe();
+        static REQUESTS: AtomicUsize = AtomicUsize::new(0);
+        let addr = stub_signer(&REQUESTS).await;
+
+        // blocking requests are moved off the worker, so the stub signer can answer
+        let key = RemoteSigningKey::connect(addr, "zsk").unwrap();
+        assert_eq!(key.algorithm(), Algorithm::ED25519);
+        let public_key = key.to_public_key().unwrap();
+
+        let tbs = (0..2000u32)
+            .map(|i| TBS::from(i.to_be_bytes().as_slice()))
+            .collect::<Vec<_>>();
+
+        REQUESTS.store(0, Ordering::SeqCst);
+        let signatures = key.sign_batch(&tbs).await.unwrap();
+        // two batches of at most MAX_BATCH_LEN entries
+        assert_eq!(REQUESTS.load(Ordering::SeqCst), 2);
+        assert_eq!(signatures.len(), tbs.len());
+        for (tbs, signature) in tbs.iter().zip(&signatures) {
+            public_key.verify(tbs.as_ref(), signature).unwrap();
+        }
+
+        let tbs = TBS::from(b"single".as_slice());
+        let signature = key.sign(&tbs).unwrap();
+        public_key.verify(tbs.as_ref(), &signature).unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_remote_signing_key_unknown() {
+        subscribe();
+        static REQUESTS: AtomicUsize = AtomicUsize::new(0);
+        let addr = stub_signer(&REQUESTS).await;
+
+        let error = tokio::task::spawn_blocking(move || RemoteSigningKey::connect(addr, "ksk"))
+            .await
+            .unwrap()
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("unknown key: ksk"), "{error}");
+    }
+
+    #[test]
+    fn test_remote_signer_addr() {
+        assert_eq!(
+            "127.0.0.1:5353".parse::<RemoteSignerAddr>().unwrap(),
+            RemoteSignerAddr::Tcp(SocketAddr::from(([127, 0, 0, 1], 5353)))
+        );
+        assert_eq!(
+            "tcp:[::1]:5353".parse::<RemoteSignerAddr>().unwrap(),
+            RemoteSignerAddr::Tcp(SocketAddr::from(([0, 0, 0, 0, 0, 0, 0, 1], 5353)))
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            "unix:/run/signer.sock".parse::<RemoteSignerAddr>().unwrap(),
+            RemoteSignerAddr::Unix(PathBuf::from("/run/signer.sock"))
+        );
+        assert!("signer".parse::<RemoteSignerAddr>().is_err());
+    }
+}
@@ -332,6 +332,34 @@ impl SigSigner {
             .map_err(|e| ProtoErrorKind::Msg(format!("signing error: {e}")).into())
     }
 
+    /// Signs many hashes at once, see [`SigningKey::sign_batch`].
+    ///
+    /// # Arguments
+    ///
+    /// * `tbs` - the hashed resource record sets, see `rrset_tbs`.
+    ///
+    /// # Return value
+    ///
+    /// The signatures, in the same order as `tbs`.
+    pub async fn sign_batch(&self, tbs: &[TBS]) -> ProtoResult<Vec<Vec<u8>>> {
+        let signatures = self
+            .key
+            .sign_batch(tbs)
+            .await
+            .map_err(|e| ProtoErrorKind::Msg(format!("signing error: {e}")))?;
+
+        if signatures.len() != tbs.len() {
+            return Err(ProtoErrorKind::Msg(format!(
+                "signing error: expected {} signatures, got {}",
+                tbs.len(),
+                signatures.len()
+            ))
+            .into());
+        }
+
+        Ok(signatures)
+    }
+
     /// The name of the signing entity, e.g. the DNS server name.
     ///
     /// This should match the name on key in the zone.
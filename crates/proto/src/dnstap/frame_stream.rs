@@ -0,0 +1,203 @@
+//! Frame Streams, the framing protocol dnstap messages are written with
+//!
+//! See <https://farsightsec.github.io/fstrm/> for the protocol. Data frames are the length of the
+//! payload followed by the payload, control frames are escaped with a zero length.
+
+use alloc::vec::Vec;
+use std::io::{self, BufWriter, Read, Write};
+
+/// The content type of dnstap data frames
+const CONTENT_TYPE: &[u8] = b"protobuf:dnstap.Dnstap";
+
+const CONTROL_ACCEPT: u32 = 0x01;
+const CONTROL_START: u32 = 0x02;
+const CONTROL_STOP: u32 = 0x03;
+const CONTROL_READY: u32 = 0x04;
+
+const FIELD_CONTENT_TYPE: u32 = 0x01;
+
+/// The largest control frame accepted from a receiver
+const MAX_CONTROL_FRAME: usize = 512;
+
+/// Writes dnstap payloads as a unidirectional Frame Stream
+pub(super) struct FrameStreamWriter<W: Write> {
+    writer: BufWriter<W>,
+}
+
+impl<W: Write> FrameStreamWriter<W> {
+    /// Starts a stream, writing the START frame
+    pub(super) fn new(writer: W) -> io::Result<Self> {
+        let mut writer = BufWriter::new(writer);
+        writer.write_all(&control_frame(CONTROL_START, true))?;
+        Ok(Self { writer })
+    }
+
+    /// Writes a data frame
+    pub(super) fn write_frame(&mut self, payload: &[u8]) -> io::Result<()> {
+        let len = u32::try_from(payload.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "frame too large"))?;
+        self.writer.write_all(&len.to_be_bytes())?;
+        self.writer.write_all(payload)
+    }
+
+    /// Flushes buffered frames to the underlying writer
+    pub(super) fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// Ends the stream, writing the STOP frame
+    pub(super) fn finish(mut self) -> io::Result<()> {
+        self.writer.write_all(&control_frame(CONTROL_STOP, false))?;
+        self.writer.flush()
+    }
+}
+
+/// Performs the handshake of a bidirectional stream, as used on sockets
+///
+/// The receiver must accept the dnstap content type in response to our READY frame, the START
+/// frame is written by [`FrameStreamWriter::new`] afterwards.
+pub(super) fn handshake(stream: &mut (impl Read + Write)) -> io::Result<()> {
+    stream.write_all(&control_frame(CONTROL_READY, true))?;
+    stream.flush()?;
+
+    let (control_type, content_types) = read_control_frame(stream)?;
+    if control_type != CONTROL_ACCEPT {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("expected an ACCEPT frame, got control type {control_type}"),
+        ));
+    }
+
+    if !content_types.is_empty() && !content_types.iter().any(|ty| ty == CONTENT_TYPE) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "receiver does not accept dnstap",
+        ));
+    }
+
+    Ok(())
+}
+
+fn control_frame(control_type: u32, content_type: bool) -> Vec<u8> {
+    let mut payload = control_type.to_be_bytes().to_vec();
+    if content_type {
+        payload.extend_from_slice(&FIELD_CONTENT_TYPE.to_be_bytes());
+        payload.extend_from_slice(&(CONTENT_TYPE.len() as u32).to_be_bytes());
+        payload.extend_from_slice(CONTENT_TYPE);
+    }
+
+    let mut frame = Vec::with_capacity(payload.len() + 8);
+    frame.extend_from_slice(&0u32.to_be_bytes());
+    frame.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    frame.extend_from_slice(&payload);
+    frame
+}
+
+/// Reads a control frame, returning its type and content types
+fn read_control_frame(reader: &mut impl Read) -> io::Result<(u32, Vec<Vec<u8>>)> {
+    let mut word = [0; 4];
+    reader.read_exact(&mut word)?;
+    if u32::from_be_bytes(word) != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "expected a control frame",
+        ));
+    }
+
+    reader.read_exact(&mut word)?;
+    let len = u32::from_be_bytes(word) as usize;
+    if !(4..=MAX_CONTROL_FRAME).contains(&len) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("bad control frame length: {len}"),
+        ));
+    }
+
+    let mut frame = vec![0; len];
+    reader.read_exact(&mut frame)?;
+    let control_type = u32::from_be_bytes([frame[0], frame[1], frame[2], frame[3]]);
+
+    let mut content_types = Vec::new();
+    let mut fields = &frame[4..];
+    while fields.len() >= 8 {
+        let field = u32::from_be_bytes([fields[0], fields[1], fields[2], fields[3]]);
+        let len = u32::from_be_bytes([fields[4], fields[5], fields[6], fields[7]]) as usize;
+        let Some(value) = fields.get(8..8 + len) else {
+            break;
+        };
+
+        if field == FIELD_CONTENT_TYPE {
+            content_types.push(value.to_vec());
+        }
+        fields = &fields[8 + len..];
+    }
+
+    Ok((control_type, content_types))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn test_unidirectional_stream() {
+        let mut buf = Vec::new();
+        let mut writer = FrameStreamWriter::new(&mut buf).unwrap();
+        writer.write_frame(b"hello").unwrap();
+        writer.finish().unwrap();
+
+        let mut reader = Cursor::new(buf);
+        let (control_type, content_types) = read_control_frame(&mut reader).unwrap();
+        assert_eq!(control_type, CONTROL_START);
+        assert_eq!(content_types, vec![CONTENT_TYPE.to_vec()]);
+
+        let mut len = [0; 4];
+        reader.read_exact(&mut len).unwrap();
+        let mut payload = vec![0; u32::from_be_bytes(len) as usize];
+        reader.read_exact(&mut payload).unwrap();
+        assert_eq!(payload, b"hello");
+
+        let (control_type, _) = read_control_frame(&mut reader).unwrap();
+        assert_eq!(control_type, CONTROL_STOP);
+        assert_eq!(reader.position() as usize, reader.get_ref().len());
+    }
+
+    #[test]
+    fn test_handshake() {
+        struct Peer {
+            input: Cursor<Vec<u8>>,
+            output: Vec<u8>,
+        }
+
+        impl Read for Peer {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                self.input.read(buf)
+            }
+        }
+
+        impl Write for Peer {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.output.write(buf)
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut peer = Peer {
+            input: Cursor::new(control_frame(CONTROL_ACCEPT, true)),
+            output: Vec::new(),
+        };
+        handshake(&mut peer).unwrap();
+        assert_eq!(peer.output, control_frame(CONTROL_READY, true));
+
+        let mut peer = Peer {
+            input: Cursor::new(control_frame(CONTROL_STOP, false)),
+            output: Vec::new(),
+        };
+        assert!(handshake(&mut peer).is_err());
+    }
+}
@@ -0,0 +1,297 @@
+//! Protobuf encoding of dnstap messages
+//!
+//! The schema is small enough to encode by hand, see
+//! <https://github.com/dnstap/dnstap.pb/blob/master/dnstap.proto> for the definitions.
+
+use alloc::vec::Vec;
+use std::{
+    net::{IpAddr, SocketAddr},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::xfer::Protocol;
+
+/// The kind of a dnstap message, identifying who sent it to whom
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DnstapMessageType {
+    /// A query received by an authoritative server
+    AuthQuery,
+    /// A response sent by an authoritative server
+    AuthResponse,
+    /// A query sent upstream by a recursive resolver
+    ResolverQuery,
+    /// A response received from upstream by a recursive resolver
+    ResolverResponse,
+    /// A query received from a client
+    ClientQuery,
+    /// A response sent to a client
+    ClientResponse,
+    /// A query sent upstream by a forwarder
+    ForwarderQuery,
+    /// A response received from upstream by a forwarder
+    ForwarderResponse,
+    /// A query sent by a stub resolver
+    StubQuery,
+    /// A response received by a stub resolver
+    StubResponse,
+    /// A query sent by a tool
+    ToolQuery,
+    /// A response received by a tool
+    ToolResponse,
+    /// A dynamic update received
+    UpdateQuery,
+    /// A response to a dynamic update
+    UpdateResponse,
+}
+
+impl DnstapMessageType {
+    fn value(self) -> u64 {
+        match self {
+            Self::AuthQuery => 1,
+            Self::AuthResponse => 2,
+            Self::ResolverQuery => 3,
+            Self::ResolverResponse => 4,
+            Self::ClientQuery => 5,
+            Self::ClientResponse => 6,
+            Self::ForwarderQuery => 7,
+            Self::ForwarderResponse => 8,
+            Self::StubQuery => 9,
+            Self::StubResponse => 10,
+            Self::ToolQuery => 11,
+            Self::ToolResponse => 12,
+            Self::UpdateQuery => 13,
+            Self::UpdateResponse => 14,
+        }
+    }
+}
+
+/// A DNS message and the context it was sent or received in
+///
+/// `query_address` is the address of the initiator of the exchange, e.g. the client for
+/// [`DnstapMessageType::ClientQuery`] or this resolver for [`DnstapMessageType::ResolverQuery`],
+/// and `response_address` the address of the responder.
+#[derive(Clone, Debug)]
+pub struct DnstapMessage {
+    message_type: DnstapMessageType,
+    protocol: Protocol,
+    query_address: Option<SocketAddr>,
+    response_address: Option<SocketAddr>,
+    query_time: Option<SystemTime>,
+    query_message: Option<Vec<u8>>,
+    response_time: Option<SystemTime>,
+    response_message: Option<Vec<u8>>,
+}
+
+impl DnstapMessage {
+    /// A query, sent or received now
+    pub fn query(message_type: DnstapMessageType, protocol: Protocol, message: Vec<u8>) -> Self {
+        Self {
+            message_type,
+            protocol,
+            query_address: None,
+            response_address: None,
+            query_time: Some(SystemTime::now()),
+            query_message: Some(message),
+            response_time: None,
+            response_message: None,
+        }
+    }
+
+    /// A response, sent or received now
+    pub fn response(message_type: DnstapMessageType, protocol: Protocol, message: Vec<u8>) -> Self {
+        Self {
+            message_type,
+            protocol,
+            query_address: None,
+            response_address: None,
+            query_time: None,
+            query_message: None,
+            response_time: Some(SystemTime::now()),
+            response_message: Some(message),
+        }
+    }
+
+    /// Sets the address of the initiator of the exchange
+    pub fn with_query_address(mut self, address: SocketAddr) -> Self {
+        self.query_address = Some(address);
+        self
+    }
+
+    /// Sets the address of the responder
+    pub fn with_response_address(mut self, address: SocketAddr) -> Self {
+        self.response_address = Some(address);
+        self
+    }
+
+    /// Sets the time the query was sent or received, for responses
+    pub fn with_query_time(mut self, time: SystemTime) -> Self {
+        self.query_time = Some(time);
+        self
+    }
+
+    /// The kind of message
+    pub fn message_type(&self) -> DnstapMessageType {
+        self.message_type
+    }
+
+    /// Encodes a `Dnstap` protobuf message, wrapping this message
+    pub(super) fn encode(&self, identity: Option<&[u8]>, version: Option<&[u8]>) -> Vec<u8> {
+        let mut message = Vec::with_capacity(
+            64 + self.query_message.as_ref().map_or(0, Vec::len)
+                + self.response_message.as_ref().map_or(0, Vec::len),
+        );
+        put_varint_field(&mut message, 1, self.message_type.value());
+
+        let family = self
+            .query_address
+            .or(self.response_address)
+            .map(|addr| match addr.ip() {
+                IpAddr::V4(_) => 1,
+                IpAddr::V6(_) => 2,
+            });
+        if let Some(family) = family {
+            put_varint_field(&mut message, 2, family);
+        }
+        put_varint_field(&mut message, 3, socket_protocol(self.protocol));
+
+        if let Some(addr) = self.query_address {
+            put_bytes_field(&mut message, 4, &ip_bytes(addr.ip()));
+        }
+        if let Some(addr) = self.response_address {
+            put_bytes_field(&mut message, 5, &ip_bytes(addr.ip()));
+        }
+        if let Some(addr) = self.query_address {
+            put_varint_field(&mut message, 6, u64::from(addr.port()));
+        }
+        if let Some(addr) = self.response_address {
+            put_varint_field(&mut message, 7, u64::from(addr.port()));
+        }
+        if let Some(time) = self.query_time {
+            put_time_fields(&mut message, 8, 9, time);
+        }
+        if let Some(query) = &self.query_message {
+            put_bytes_field(&mut message, 10, query);
+        }
+        if let Some(time) = self.response_time {
+            put_time_fields(&mut message, 12, 13, time);
+        }
+        if let Some(response) = &self.response_message {
+            put_bytes_field(&mut message, 14, response);
+        }
+
+        let mut dnstap = Vec::with_capacity(message.len() + 32);
+        if let Some(identity) = identity {
+            put_bytes_field(&mut dnstap, 1, identity);
+        }
+        if let Some(version) = version {
+            put_bytes_field(&mut dnstap, 2, version);
+        }
+        put_bytes_field(&mut dnstap, 14, &message);
+        // Dnstap.Type MESSAGE
+        put_varint_field(&mut dnstap, 15, 1);
+        dnstap
+    }
+}
+
+fn socket_protocol(protocol: Protocol) -> u64 {
+    match protocol {
+        Protocol::Udp => 1,
+        Protocol::Tcp => 2,
+        #[cfg(feature = "__tls")]
+        Protocol::Tls => 3,
+        #[cfg(feature = "__https")]
+        Protocol::Https => 4,
+        #[cfg(feature = "__quic")]
+        Protocol::Quic => 7,
+        #[cfg(feature = "__h3")]
+        Protocol::H3 => 4,
+    }
+}
+
+fn ip_bytes(ip: IpAddr) -> Vec<u8> {
+    match ip {
+        IpAddr::V4(ip) => ip.octets().to_vec(),
+        IpAddr::V6(ip) => ip.octets().to_vec(),
+    }
+}
+
+fn put_time_fields(buf: &mut Vec<u8>, sec_field: u32, nsec_field: u32, time: SystemTime) {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    put_varint_field(buf, sec_field, since_epoch.as_secs());
+    // fixed32, wire type 5
+    put_varint(buf, u64::from(field_key(nsec_field, 5)));
+    buf.extend_from_slice(&since_epoch.subsec_nanos().to_le_bytes());
+}
+
+fn put_varint_field(buf: &mut Vec<u8>, field: u32, value: u64) {
+    put_varint(buf, u64::from(field_key(field, 0)));
+    put_varint(buf, value);
+}
+
+fn put_bytes_field(buf: &mut Vec<u8>, field: u32, value: &[u8]) {
+    put_varint(buf, u64::from(field_key(field, 2)));
+    put_varint(buf, value.len() as u64);
+    buf.extend_from_slice(value);
+}
+
+fn field_key(field: u32, wire_type: u32) -> u32 {
+    (field << 3) | wire_type
+}
+
+fn put_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_varint() {
+        let mut buf = Vec::new();
+        put_varint(&mut buf, 1);
+        put_varint(&mut buf, 300);
+        put_varint(&mut buf, u64::from(u32::MAX));
+        assert_eq!(buf, [0x01, 0xac, 0x02, 0xff, 0xff, 0xff, 0xff, 0x0f]);
+    }
+
+    #[test]
+    fn test_encode_client_query() {
+        let time = UNIX_EPOCH + Duration::new(1_700_000_000, 5);
+        let mut message = DnstapMessage::query(
+            DnstapMessageType::ClientQuery,
+            Protocol::Udp,
+            vec![0xab, 0xcd],
+        )
+        .with_query_address("192.0.2.1:5353".parse().unwrap())
+        .with_response_address("192.0.2.53:53".parse().unwrap());
+        message.query_time = Some(time);
+
+        let encoded = message.encode(Some(b"ns1"), None);
+
+        let mut expected = vec![
+            0x08, 5, // type CLIENT_QUERY
+            0x10, 1, // socket_family INET
+            0x18, 1, // socket_protocol UDP
+            0x22, 4, 192, 0, 2, 1, // query_address
+            0x2a, 4, 192, 0, 2, 53, // response_address
+            0x30, 0xe9, 0x29, // query_port 5353
+            0x38, 53, // response_port
+            0x40, 0x80, 0xe2, 0xcf, 0xaa, 0x06, // query_time_sec
+            0x4d, 5, 0, 0, 0, // query_time_nsec
+            0x52, 2, 0xab, 0xcd, // query_message
+        ];
+        let message_len = expected.len() as u8;
+        expected.splice(0..0, [0x0a, 3, b'n', b's', b'1', 0x72, message_len]);
+        expected.extend_from_slice(&[0x78, 1]);
+
+        assert_eq!(encoded, expected);
+    }
+}
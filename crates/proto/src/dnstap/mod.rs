@@ -0,0 +1,440 @@
+//! dnstap, structured logging of DNS traffic
+//!
+//! Messages are encoded as dnstap protobuf messages and written as a Frame Stream to a file or to
+//! a Unix socket, where a collector such as `fstrm_capture` or `dnstap-read` can pick them up. See
+//! <https://dnstap.info> for the format.
+//!
+//! Logging never blocks the caller: messages are queued to a background thread doing the writing,
+//! and dropped when the queue is full or while the output is unavailable. A Unix socket output is
+//! reconnected with backoff after writing to it failed.
+
+use alloc::{boxed::Box, string::String, sync::Arc, vec::Vec};
+use core::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::{
+    fs::File,
+    io::{self, Write},
+    path::Path,
+    sync::mpsc::{self, Receiver, SyncSender, TryRecvError},
+    thread::{self, JoinHandle},
+    time::Instant,
+};
+
+use tracing::{debug, warn};
+
+mod frame_stream;
+mod message;
+
+use frame_stream::FrameStreamWriter;
+pub use message::{DnstapMessage, DnstapMessageType};
+
+/// The default number of messages queued for writing
+const DEFAULT_QUEUE_SIZE: usize = 10_000;
+
+/// The delay before the second attempt to reconnect a failed output, doubled after each failure
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+/// The longest delay between attempts to reconnect a failed output
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Opens a new connection to an output after writing to it failed
+type Reconnect<W> = Box<dyn FnMut() -> io::Result<W> + Send>;
+
+/// Configuration of a [`Dnstap`] output
+#[derive(Clone, Debug, Default)]
+pub struct DnstapBuilder {
+    identity: Option<String>,
+    version: Option<String>,
+    queue_size: Option<usize>,
+}
+
+impl DnstapBuilder {
+    /// Creates a builder with no identity or version
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the identity of the server included in every message, e.g. its hostname
+    pub fn identity(mut self, identity: impl Into<String>) -> Self {
+        self.identity = Some(identity.into());
+        self
+    }
+
+    /// Sets the version of the server included in every message
+    pub fn version(mut self, version: impl Into<String>) -> Self {
+        self.version = Some(version.into());
+        self
+    }
+
+    /// Sets the number of messages queued for writing, defaults to 10,000
+    pub fn queue_size(mut self, queue_size: usize) -> Self {
+        self.queue_size = Some(queue_size);
+        self
+    }
+
+    /// Writes messages to a file, truncating it
+    pub fn to_file(self, path: impl AsRef<Path>) -> io::Result<Dnstap> {
+        let file = File::create(path)?;
+        self.to_writer(file)
+    }
+
+    /// Writes messages to the Unix socket a collector is listening on
+    ///
+    /// The socket is reconnected if writing to it fails, e.g. because the collector restarted,
+    /// messages logged until then are dropped.
+    #[cfg(unix)]
+    pub fn to_unix_socket(self, path: impl AsRef<Path>) -> io::Result<Dnstap> {
+        let path = path.as_ref().to_path_buf();
+        let stream = connect_unix_socket(&path)?;
+        self.spawn(stream, Some(Box::new(move || connect_unix_socket(&path))))
+    }
+
+    /// Writes messages to any writer
+    ///
+    /// Messages logged after writing failed are dropped.
+    pub fn to_writer<W: Write + Send + 'static>(self, writer: W) -> io::Result<Dnstap> {
+        self.spawn(writer, None)
+    }
+
+    fn spawn<W: Write + Send + 'static>(
+        self,
+        writer: W,
+        reconnect: Option<Reconnect<W>>,
+    ) -> io::Result<Dnstap> {
+        let dropped = Arc::new(AtomicU64::new(0));
+        let output = Output {
+            writer: Some(FrameStreamWriter::new(writer)?),
+            unflushed: 0,
+            reconnect,
+            retry_at: Instant::now(),
+            backoff: INITIAL_BACKOFF,
+            dropped: dropped.clone(),
+        };
+        let (sender, receiver) = mpsc::sync_channel(self.queue_size.unwrap_or(DEFAULT_QUEUE_SIZE));
+        let thread = thread::Builder::new()
+            .name("hickory-dnstap".into())
+            .spawn(move || write_frames(output, receiver))?;
+
+        Ok(Dnstap {
+            inner: Arc::new(DnstapInner {
+                sender: Some(sender),
+                identity: self.identity.map(String::into_bytes),
+                version: self.version.map(String::into_bytes),
+                dropped,
+                thread: Some(thread),
+            }),
+        })
+    }
+}
+
+#[cfg(unix)]
+fn connect_unix_socket(path: &Path) -> io::Result<UnixStream> {
+    let mut stream = UnixStream::connect(path)?;
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    stream.set_write_timeout(Some(Duration::from_secs(5)))?;
+    frame_stream::handshake(&mut stream)?;
+    Ok(stream)
+}
+
+/// A handle to a dnstap output, cheap to clone
+///
+/// The output is finished, writing the remaining messages, when the last handle is dropped.
+#[derive(Clone, Debug)]
+pub struct Dnstap {
+    inner: Arc<DnstapInner>,
+}
+
+impl Dnstap {
+    /// Returns a builder for a dnstap output
+    pub fn builder() -> DnstapBuilder {
+        DnstapBuilder::new()
+    }
+
+    /// Queues a message for writing
+    pub fn log(&self, message: DnstapMessage) {
+        let Some(sender) = &self.inner.sender else {
+            return;
+        };
+
+        let frame = message.encode(
+            self.inner.identity.as_deref(),
+            self.inner.version.as_deref(),
+        );
+        if sender.try_send(frame).is_err() {
+            self.inner.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// The number of messages dropped so far, because the queue was full or writing failed
+    pub fn dropped(&self) -> u64 {
+        self.inner.dropped.load(Ordering::Relaxed)
+    }
+}
+
+/// Handles are equal if they write to the same output
+impl PartialEq for Dnstap {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl Eq for Dnstap {}
+
+#[derive(Debug)]
+struct DnstapInner {
+    sender: Option<SyncSender<Vec<u8>>>,
+    identity: Option<Vec<u8>>,
+    version: Option<Vec<u8>>,
+    dropped: Arc<AtomicU64>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for DnstapInner {
+    fn drop(&mut self) {
+        // closing the channel lets the writer finish the stream
+        drop(self.sender.take());
+        let Some(thread) = self.thread.take() else {
+            return;
+        };
+
+        // waiting for the remaining messages to be written must not block an async worker, the
+        // runtime waits for blocking tasks when it shuts down
+        #[cfg(feature = "tokio")]
+        if let Ok(handle) = tokio::runtime::Handle::try_current() {
+            handle.spawn_blocking(move || thread.join());
+            return;
+        }
+
+        let _ = thread.join();
+    }
+}
+
+fn write_frames<W: Write>(mut output: Output<W>, receiver: Receiver<Vec<u8>>) {
+    loop {
+        let frame = match receiver.try_recv() {
+            Ok(frame) => frame,
+            Err(TryRecvError::Empty) => {
+                output.flush();
+                match receiver.recv() {
+                    Ok(frame) => frame,
+                    Err(_) => break,
+                }
+            }
+            Err(TryRecvError::Disconnected) => break,
+        };
+
+        output.write_frame(&frame);
+    }
+
+    output.finish();
+}
+
+/// The writing side of a dnstap output, owned by the writer thread
+struct Output<W: Write> {
+    /// The stream being written, `None` after writing failed
+    writer: Option<FrameStreamWriter<W>>,
+    /// The number of frames written to the stream since it was last flushed
+    unflushed: u64,
+    reconnect: Option<Reconnect<W>>,
+    retry_at: Instant,
+    backoff: Duration,
+    dropped: Arc<AtomicU64>,
+}
+
+impl<W: Write> Output<W> {
+    fn write_frame(&mut self, frame: &[u8]) {
+        let Some(writer) = self.connected() else {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return;
+        };
+
+        let result = writer.write_frame(frame);
+        self.unflushed += 1;
+        if let Err(e) = result {
+            self.failed(e);
+        }
+    }
+
+    fn flush(&mut self) {
+        let Some(writer) = &mut self.writer else {
+            return;
+        };
+
+        match writer.flush() {
+            Ok(()) => self.unflushed = 0,
+            Err(e) => self.failed(e),
+        }
+    }
+
+    fn finish(mut self) {
+        let Some(writer) = self.writer.take() else {
+            return;
+        };
+
+        match writer.finish() {
+            Ok(()) => debug!("dnstap output finished"),
+            Err(e) => warn!("failed to finish dnstap output: {e}"),
+        }
+    }
+
+    /// Returns the stream, reconnecting it if writing failed and the backoff has elapsed
+    fn connected(&mut self) -> Option<&mut FrameStreamWriter<W>> {
+        if self.writer.is_none() {
+            let reconnect = self.reconnect.as_mut()?;
+            let now = Instant::now();
+            if now < self.retry_at {
+                return None;
+            }
+
+            match reconnect().and_then(FrameStreamWriter::new) {
+                Ok(writer) => {
+                    debug!("dnstap output reconnected");
+                    self.writer = Some(writer);
+                    self.backoff = INITIAL_BACKOFF;
+                }
+                Err(e) => {
+                    warn!(
+                        "failed to reconnect dnstap output, retrying in {:?}: {e}",
+                        self.backoff
+                    );
+                    self.retry_at = now + self.backoff;
+                    self.backoff = (self.backoff * 2).min(MAX_BACKOFF);
+                    return None;
+                }
+            }
+        }
+
+        self.writer.as_mut()
+    }
+
+    /// Drops the failed stream, the frames not yet flushed are lost with it
+    fn failed(&mut self, error: io::Error) {
+        self.writer = None;
+        self.dropped.fetch_add(self.unflushed, Ordering::Relaxed);
+        self.unflushed = 0;
+        match self.reconnect {
+            Some(_) => warn!("dnstap output failed, reconnecting: {error}"),
+            None => warn!("dnstap output failed, dropping further messages: {error}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::Read,
+        sync::{Mutex, PoisonError},
+    };
+
+    use super::*;
+    use crate::xfer::Protocol;
+
+    #[derive(Clone, Default)]
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn data_frames(mut buf: &[u8]) -> Vec<Vec<u8>> {
+        let mut frames = Vec::new();
+        let mut len = [0; 4];
+        while buf.read_exact(&mut len).is_ok() {
+            let len = u32::from_be_bytes(len) as usize;
+            if len == 0 {
+                // control frame
+                let mut control_len = [0; 4];
+                buf.read_exact(&mut control_len).unwrap();
+                buf = &buf[u32::from_be_bytes(control_len) as usize..];
+                continue;
+            }
+
+            frames.push(buf[..len].to_vec());
+            buf = &buf[len..];
+        }
+        frames
+    }
+
+    #[test]
+    fn test_log_written_on_drop() {
+        let buf = SharedBuf::default();
+        let dnstap = Dnstap::builder()
+            .identity("ns1")
+            .to_writer(buf.clone())
+            .unwrap();
+
+        let message =
+            DnstapMessage::query(DnstapMessageType::ClientQuery, Protocol::Udp, vec![1, 2, 3]);
+        let expected = message.encode(Some(b"ns1"), None);
+        dnstap.clone().log(message);
+        drop(dnstap);
+
+        let written = buf.0.lock().unwrap().clone();
+        assert_eq!(data_frames(&written), vec![expected]);
+    }
+
+    struct FailingWriter;
+
+    impl Write for FailingWriter {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::ErrorKind::BrokenPipe.into())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_reconnect_after_failure() {
+        let buf = SharedBuf::default();
+        let mut outputs = vec![Ok(buf.clone()), Err(io::ErrorKind::NotFound.into())];
+        let dnstap = Dnstap::builder()
+            .spawn::<Box<dyn Write + Send>>(
+                Box::new(FailingWriter),
+                Some(Box::new(move || {
+                    outputs
+                        .pop()
+                        .unwrap()
+                        .map(|buf| Box::new(buf) as Box<dyn Write + Send>)
+                })),
+            )
+            .unwrap();
+
+        let message =
+            || DnstapMessage::query(DnstapMessageType::ClientQuery, Protocol::Udp, vec![1, 2, 3]);
+        let last = message();
+        let expected = last.encode(None, None);
+        // the first message is lost with the failed stream, the second one when reconnecting fails
+        for dropped in 1..=2 {
+            dnstap.log(message());
+            while dnstap.dropped() < dropped {
+                thread::sleep(Duration::from_millis(10));
+            }
+        }
+
+        // the third one is written once the backoff has elapsed
+        thread::sleep(INITIAL_BACKOFF);
+        dnstap.log(last);
+        let dropped = dnstap.dropped();
+        drop(dnstap);
+
+        let written = buf.0.lock().unwrap().clone();
+        assert_eq!(data_frames(&written), vec![expected]);
+        assert_eq!(dropped, 2);
+    }
+}
@@ -54,6 +54,8 @@ macro_rules! try_ready_stream {
 
 #[cfg(any(feature = "dnssec-aws-lc-rs", feature = "dnssec-ring"))]
 pub mod dnssec;
+#[cfg(feature = "dnstap")]
+pub mod dnstap;
 mod error;
 #[cfg(feature = "__https")]
 pub mod h2;
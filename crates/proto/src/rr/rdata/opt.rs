@@ -13,6 +13,7 @@ use core::fmt;
 #[cfg(not(feature = "std"))]
 use core::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use core::str::FromStr;
+use core::time::Duration;
 #[cfg(feature = "std")]
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 
@@ -475,6 +476,9 @@ pub enum EdnsOption {
     /// [RFC 7871, Client Subnet, Optional](https://tools.ietf.org/html/rfc7871)
     Subnet(ClientSubnet),
 
+    /// [RFC 7828, edns-tcp-keepalive](https://tools.ietf.org/html/rfc7828)
+    Keepalive(TcpKeepalive),
+
     /// Unknown, used to deal with unknown or unsupported codes
     Unknown(u16, Vec<u8>),
 }
@@ -486,6 +490,7 @@ impl EdnsOption {
             #[cfg(feature = "__dnssec")]
             EdnsOption::DAU(algorithms) => algorithms.len(),
             EdnsOption::Subnet(subnet) => subnet.len(),
+            EdnsOption::Keepalive(keepalive) => keepalive.len(),
             EdnsOption::Unknown(_, data) => data.len() as u16, // TODO: should we verify?
         }
     }
@@ -496,6 +501,7 @@ impl EdnsOption {
             #[cfg(feature = "__dnssec")]
             EdnsOption::DAU(algorithms) => algorithms.is_empty(),
             EdnsOption::Subnet(subnet) => subnet.is_empty(),
+            EdnsOption::Keepalive(keepalive) => keepalive.is_empty(),
             EdnsOption::Unknown(_, data) => data.is_empty(),
         }
     }
@@ -507,6 +513,7 @@ impl BinEncodable for EdnsOption {
             #[cfg(feature = "__dnssec")]
             EdnsOption::DAU(algorithms) => algorithms.emit(encoder),
             EdnsOption::Subnet(subnet) => subnet.emit(encoder),
+            EdnsOption::Keepalive(keepalive) => keepalive.emit(encoder),
             EdnsOption::Unknown(_, data) => encoder.emit_vec(data), // gah, clone needed or make a crazy api.
         }
     }
@@ -522,6 +529,7 @@ impl<'a> TryFrom<(EdnsCode, &'a [u8])> for EdnsOption {
             #[cfg(feature = "__dnssec")]
             EdnsCode::DAU => Self::DAU(value.1.into()),
             EdnsCode::Subnet => Self::Subnet(value.1.try_into()?),
+            EdnsCode::Keepalive => Self::Keepalive(value.1.try_into()?),
             _ => Self::Unknown(value.0.into(), value.1.to_vec()),
         })
     }
@@ -535,6 +543,7 @@ impl<'a> TryFrom<&'a EdnsOption> for Vec<u8> {
             #[cfg(feature = "__dnssec")]
             EdnsOption::DAU(algorithms) => algorithms.into(),
             EdnsOption::Subnet(subnet) => subnet.try_into()?,
+            EdnsOption::Keepalive(keepalive) => keepalive.into(),
             EdnsOption::Unknown(_, data) => data.clone(), // gah, clone needed or make a crazy api.
         })
     }
@@ -546,6 +555,7 @@ impl<'a> From<&'a EdnsOption> for EdnsCode {
             #[cfg(feature = "__dnssec")]
             EdnsOption::DAU(..) => Self::DAU,
             EdnsOption::Subnet(..) => Self::Subnet,
+            EdnsOption::Keepalive(..) => Self::Keepalive,
             EdnsOption::Unknown(code, _) => (*code).into(),
         }
     }
@@ -781,6 +791,95 @@ impl FromStr for ClientSubnet {
     }
 }
 
+/// [RFC 7828, edns-tcp-keepalive](https://tools.ietf.org/html/rfc7828)
+///
+/// ```text
+///                      1                   2                   3
+///  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+/// +-------------------------------+-------------------------------+
+/// !         OPTION-CODE           !         OPTION-LENGTH         !
+/// +-------------------------------+-------------------------------+
+/// |           TIMEOUT             !
+/// +-------------------------------+
+///
+/// o  TIMEOUT: an idle timeout value for the TCP connection, specified in
+///    units of 100 milliseconds, encoded in network byte order.
+///
+/// Clients signal support by sending the option without a TIMEOUT, servers answer with the idle
+/// timeout they will apply to the connection.
+/// ```
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[derive(Debug, Default, PartialOrd, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct TcpKeepalive {
+    timeout: Option<u16>,
+}
+
+impl TcpKeepalive {
+    /// Construct the option with a timeout in units of 100 milliseconds, `None` for queries
+    pub fn new(timeout: Option<u16>) -> Self {
+        Self { timeout }
+    }
+
+    /// Construct the option advertising `timeout`, rounded down to 100 milliseconds
+    pub fn with_timeout(timeout: Duration) -> Self {
+        let timeout = (timeout.as_millis() / 100).min(u16::MAX as u128) as u16;
+        Self {
+            timeout: Some(timeout),
+        }
+    }
+
+    /// Returns the idle timeout, present in responses
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+            .map(|timeout| Duration::from_millis(u64::from(timeout) * 100))
+    }
+
+    /// Returns the length in bytes of the EdnsOption
+    pub fn len(&self) -> u16 {
+        match self.timeout {
+            Some(_) => 2,
+            None => 0,
+        }
+    }
+
+    /// Returns `true` if the option carries no timeout
+    pub fn is_empty(&self) -> bool {
+        self.timeout.is_none()
+    }
+}
+
+impl BinEncodable for TcpKeepalive {
+    fn emit(&self, encoder: &mut BinEncoder<'_>) -> ProtoResult<()> {
+        if let Some(timeout) = self.timeout {
+            encoder.emit_u16(timeout)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> From<&'a TcpKeepalive> for Vec<u8> {
+    fn from(value: &'a TcpKeepalive) -> Self {
+        value
+            .timeout
+            .map(|timeout| timeout.to_be_bytes().to_vec())
+            .unwrap_or_default()
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for TcpKeepalive {
+    type Error = ProtoError;
+
+    fn try_from(value: &'a [u8]) -> Result<Self, Self::Error> {
+        match *value {
+            [] => Ok(Self { timeout: None }),
+            [high, low] => Ok(Self {
+                timeout: Some(u16::from_be_bytes([high, low])),
+            }),
+            _ => Err(ProtoErrorKind::Message("Invalid edns-tcp-keepalive length").into()),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     #![allow(clippy::dbg_macro, clippy::print_stdout)]
@@ -835,7 +934,10 @@ mod tests {
                 EdnsCode::Cookie,
                 EdnsOption::Unknown(10, vec![0x0b, 0x64, 0xb4, 0xdc, 0xd7, 0xb0, 0xcc, 0x8f]),
             ),
-            (EdnsCode::Keepalive, EdnsOption::Unknown(11, vec![])),
+            (
+                EdnsCode::Keepalive,
+                EdnsOption::Keepalive(TcpKeepalive::default()),
+            ),
         ];
         let options = OPT::new(options);
         assert_eq!(opt, options);
@@ -893,4 +995,24 @@ mod tests {
         let ecs = ClientSubnet::try_from(bytes.as_slice()).unwrap();
         assert_eq!(ecs, "172.1.1.0/24".parse().unwrap());
     }
+
+    #[test]
+    fn test_tcp_keepalive() {
+        let keepalive = TcpKeepalive::with_timeout(Duration::from_millis(120_050));
+        assert_eq!(keepalive.timeout(), Some(Duration::from_secs(120)));
+
+        let mut opt = OPT::default();
+        opt.insert(EdnsOption::Keepalive(keepalive));
+        let mut bytes = Vec::new();
+        let mut encoder = BinEncoder::new(&mut bytes);
+        opt.emit(&mut encoder).unwrap();
+        assert_eq!(bytes, [0x00, 0x0b, 0x00, 0x02, 0x04, 0xb0]);
+
+        let mut decoder = BinDecoder::new(&bytes);
+        let read = OPT::read_data(&mut decoder, Restrict::new(bytes.len() as u16)).unwrap();
+        assert_eq!(read, opt);
+
+        assert_eq!(TcpKeepalive::try_from(&[][..]).unwrap().timeout(), None);
+        assert!(TcpKeepalive::try_from(&[0x00][..]).is_err());
+    }
 }
@@ -236,6 +236,14 @@ mod tokio_runtime {
 #[cfg(feature = "tokio")]
 pub use tokio_runtime::{TokioHandle, TokioRuntimeProvider};
 
+#[cfg(all(feature = "tokio", any(test, feature = "testing")))]
+pub mod sim;
+
+#[cfg(feature = "smol")]
+mod smol_runtime;
+#[cfg(feature = "smol")]
+pub use smol_runtime::{SmolHandle, SmolRuntimeProvider, SmolTime};
+
 /// RuntimeProvider defines which async runtime that handles IO and timers.
 pub trait RuntimeProvider: Clone + Send + Sync + Unpin + 'static {
     /// Handle to the executor;
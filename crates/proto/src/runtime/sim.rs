@@ -0,0 +1,725 @@
+//! A deterministic, in-process network simulator implementing [`RuntimeProvider`]
+//!
+//! A [`SimNetwork`] connects virtual hosts, each with its own IP address. A host is represented by
+//! a [`SimRuntimeProvider`], which can be handed to anything generic over [`RuntimeProvider`] to
+//! send UDP datagrams and open TCP connections to the other hosts of the network, without touching
+//! real sockets. Servers are mounted on a host by binding a UDP socket or listening for TCP
+//! connections on one of its addresses.
+//!
+//! The conditions of the links between hosts are configurable with [`LinkConditions`]: latency of
+//! UDP packets and TCP data, UDP packet loss and truncation of UDP responses above a maximum
+//! payload size. Packet loss is
+//! decided by a random number generator seeded when the network is created, so the same sequence
+//! of packets sees the same losses on every run.
+//!
+//! Timers and latency use Tokio's clock. Running the simulation on a current thread runtime with
+//! paused time, e.g. `#[tokio::test(start_paused = true)]`, makes time virtual: it only advances
+//! when every task is waiting on a timer, so timeouts and latencies of any length complete
+//! instantly and in a deterministic order.
+
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use std::{
+    collections::HashMap,
+    io,
+    net::{IpAddr, SocketAddr},
+    sync::Mutex,
+};
+
+use futures_channel::mpsc;
+use futures_util::{StreamExt, future, stream::Stream};
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt, DuplexStream, ReadHalf, WriteHalf},
+    time::Instant,
+};
+use tracing::{debug, trace};
+
+use super::{RuntimeProvider, TokioHandle, TokioTime, iocompat::AsyncIoTokioAsStd};
+use crate::{op::Message, udp::DnsUdpSocket};
+
+/// Size of the buffers of simulated TCP connections, in each direction
+const TCP_BUFFER_SIZE: usize = 64 * 1024;
+
+/// First port handed out to sockets bound to port 0
+const FIRST_EPHEMERAL_PORT: u16 = 49152;
+
+/// An in-process network of virtual hosts
+///
+/// This is a cheap handle to the shared network state, clones refer to the same network.
+#[derive(Clone)]
+pub struct SimNetwork {
+    state: Arc<Mutex<NetworkState>>,
+}
+
+impl SimNetwork {
+    /// Creates a new network without any hosts, packet loss is decided by a generator seeded with
+    /// `seed`
+    pub fn new(seed: u64) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(NetworkState {
+                udp: HashMap::new(),
+                tcp: HashMap::new(),
+                default_link: LinkConditions::default(),
+                links: HashMap::new(),
+                rng: StdRng::seed_from_u64(seed),
+                next_port: FIRST_EPHEMERAL_PORT,
+                stats: SimStats::default(),
+            })),
+        }
+    }
+
+    /// Returns a runtime provider for the host with address `ip`
+    ///
+    /// Hosts don't need to be declared, any number of providers can be created for the same
+    /// address.
+    pub fn host(&self, ip: IpAddr) -> SimRuntimeProvider {
+        SimRuntimeProvider {
+            network: self.clone(),
+            ip,
+            handle: TokioHandle::default(),
+        }
+    }
+
+    /// Sets the conditions of every link without more specific conditions
+    pub fn set_default_link(&self, conditions: LinkConditions) {
+        self.lock().default_link = conditions;
+    }
+
+    /// Sets the conditions of the link from `from` to `to`
+    ///
+    /// Links are directional: this only applies to the packets sent by `from` to `to`.
+    pub fn set_link(&self, from: IpAddr, to: IpAddr, conditions: LinkConditions) {
+        self.lock().links.insert((from, to), conditions);
+    }
+
+    /// Returns the counters of the traffic that went through the network so far
+    pub fn stats(&self) -> SimStats {
+        self.lock().stats
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, NetworkState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn send_udp(&self, from: SocketAddr, to: SocketAddr, mut payload: Vec<u8>) {
+        let mut state = self.lock();
+        let conditions = state.link(from.ip(), to.ip());
+        state.stats.udp_sent += 1;
+
+        if conditions.loss > 0.0 && state.rng.random_bool(conditions.loss.min(1.0)) {
+            trace!("dropping udp packet from {from} to {to}");
+            state.stats.udp_dropped += 1;
+            return;
+        }
+
+        if let Some(max_payload) = conditions.max_udp_payload {
+            if payload.len() > max_payload {
+                match Message::from_vec(&payload).and_then(|message| message.truncate().to_vec()) {
+                    Ok(truncated) if truncated.len() <= max_payload => {
+                        trace!("truncating udp packet from {from} to {to}");
+                        state.stats.udp_truncated += 1;
+                        payload = truncated;
+                    }
+                    _ => {
+                        trace!("dropping oversized udp packet from {from} to {to}");
+                        state.stats.udp_dropped += 1;
+                        return;
+                    }
+                }
+            }
+        }
+        drop(state);
+
+        if conditions.latency.is_zero() {
+            self.deliver_udp(from, to, payload);
+            return;
+        }
+
+        let network = self.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(conditions.latency).await;
+            network.deliver_udp(from, to, payload);
+        });
+    }
+
+    fn deliver_udp(&self, from: SocketAddr, to: SocketAddr, payload: Vec<u8>) {
+        let mut state = self.lock();
+        let delivered = state
+            .udp
+            .get(&to)
+            .is_some_and(|sender| sender.unbounded_send((payload, from)).is_ok());
+
+        if delivered {
+            state.stats.udp_delivered += 1;
+        } else {
+            trace!("no udp socket bound to {to}, dropping packet from {from}");
+            state.stats.udp_dropped += 1;
+        }
+    }
+
+    async fn connect_tcp(
+        &self,
+        from: IpAddr,
+        to: SocketAddr,
+    ) -> io::Result<AsyncIoTokioAsStd<DuplexStream>> {
+        let (sent, received, local_addr) = {
+            let mut state = self.lock();
+            let sent = state.link(from, to.ip()).latency;
+            let received = state.link(to.ip(), from).latency;
+            let port = state.ephemeral_port(from, |state, addr| state.tcp.contains_key(addr))?;
+            (sent, received, SocketAddr::new(from, port))
+        };
+
+        // the handshake takes a round trip
+        if !(sent + received).is_zero() {
+            tokio::time::sleep(sent + received).await;
+        }
+
+        let mut state = self.lock();
+        let Some(listener) = state.tcp.get(&to) else {
+            return Err(io::Error::new(
+                io::ErrorKind::ConnectionRefused,
+                format!("no simulated listener on {to}"),
+            ));
+        };
+
+        let (client, server) = match sent.is_zero() && received.is_zero() {
+            true => tokio::io::duplex(TCP_BUFFER_SIZE),
+            false => delayed_duplex(sent, received),
+        };
+        listener
+            .unbounded_send((AsyncIoTokioAsStd(server), local_addr))
+            .map_err(|_| io::Error::new(io::ErrorKind::ConnectionRefused, "listener closed"))?;
+
+        debug!("simulated tcp connection from {local_addr} to {to}");
+        state.stats.tcp_connections += 1;
+        Ok(AsyncIoTokioAsStd(client))
+    }
+}
+
+/// Creates a pair of connected streams, delaying the data written to the first one by `sent` and
+/// the data written to the second one by `received`
+fn delayed_duplex(sent: Duration, received: Duration) -> (DuplexStream, DuplexStream) {
+    let (client, client_relay) = tokio::io::duplex(TCP_BUFFER_SIZE);
+    let (server, server_relay) = tokio::io::duplex(TCP_BUFFER_SIZE);
+    let (client_read, client_write) = tokio::io::split(client_relay);
+    let (server_read, server_write) = tokio::io::split(server_relay);
+    tokio::spawn(delay_stream(client_read, server_write, sent));
+    tokio::spawn(delay_stream(server_read, client_write, received));
+    (client, server)
+}
+
+/// Writes the data read from `from` to `to`, each chunk `latency` after it was read
+///
+/// The data in flight doesn't wait for the chunks before it, like on a real link.
+async fn delay_stream(
+    mut from: ReadHalf<DuplexStream>,
+    mut to: WriteHalf<DuplexStream>,
+    latency: Duration,
+) {
+    let (sender, mut receiver) = mpsc::unbounded::<(Instant, Vec<u8>)>();
+    let read = async move {
+        let mut buf = vec![0; TCP_BUFFER_SIZE];
+        while let Ok(len @ 1..) = from.read(&mut buf).await {
+            if sender
+                .unbounded_send((Instant::now() + latency, buf[..len].to_vec()))
+                .is_err()
+            {
+                break;
+            }
+        }
+    };
+
+    let write = async move {
+        while let Some((deliver_at, data)) = receiver.next().await {
+            tokio::time::sleep_until(deliver_at).await;
+            if to.write_all(&data).await.is_err() {
+                return;
+            }
+        }
+
+        // the stream was closed after all its data was delivered
+        let _ = to.shutdown().await;
+    };
+
+    future::join(read, write).await;
+}
+
+struct NetworkState {
+    udp: HashMap<SocketAddr, mpsc::UnboundedSender<(Vec<u8>, SocketAddr)>>,
+    tcp: HashMap<SocketAddr, mpsc::UnboundedSender<(SimTcpStream, SocketAddr)>>,
+    default_link: LinkConditions,
+    links: HashMap<(IpAddr, IpAddr), LinkConditions>,
+    rng: StdRng,
+    next_port: u16,
+    stats: SimStats,
+}
+
+impl NetworkState {
+    fn link(&self, from: IpAddr, to: IpAddr) -> LinkConditions {
+        self.links
+            .get(&(from, to))
+            .copied()
+            .unwrap_or(self.default_link)
+    }
+
+    /// Hands out the next free port of `ip` in the ephemeral range
+    fn ephemeral_port(
+        &mut self,
+        ip: IpAddr,
+        in_use: impl Fn(&Self, &SocketAddr) -> bool,
+    ) -> io::Result<u16> {
+        for _ in FIRST_EPHEMERAL_PORT..=u16::MAX {
+            let port = self.next_port;
+            self.next_port = match port {
+                u16::MAX => FIRST_EPHEMERAL_PORT,
+                port => port + 1,
+            };
+
+            if !in_use(self, &SocketAddr::new(ip, port)) {
+                return Ok(port);
+            }
+        }
+
+        Err(io::Error::new(
+            io::ErrorKind::AddrInUse,
+            format!("no free simulated port on {ip}"),
+        ))
+    }
+}
+
+/// Conditions of a link between two hosts of a [`SimNetwork`]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LinkConditions {
+    /// Time it takes for a packet, or data written to a TCP connection, to reach the other host
+    pub latency: Duration,
+    /// Probability for a UDP packet to be lost, between 0 and 1
+    pub loss: f64,
+    /// UDP DNS messages larger than this are truncated, as with [`Message::truncate`]
+    ///
+    /// Packets that still don't fit, or that aren't DNS messages, are dropped.
+    pub max_udp_payload: Option<usize>,
+}
+
+/// Counters of the traffic that went through a [`SimNetwork`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SimStats {
+    /// UDP packets sent
+    pub udp_sent: usize,
+    /// UDP packets that reached a bound socket
+    pub udp_delivered: usize,
+    /// UDP packets lost, oversized or sent to an address where nothing is bound
+    pub udp_dropped: usize,
+    /// UDP packets that were truncated
+    pub udp_truncated: usize,
+    /// TCP connections established
+    pub tcp_connections: usize,
+}
+
+/// A host of a [`SimNetwork`]
+#[derive(Clone)]
+pub struct SimRuntimeProvider {
+    network: SimNetwork,
+    ip: IpAddr,
+    handle: TokioHandle,
+}
+
+impl SimRuntimeProvider {
+    /// The address of this host
+    pub fn ip(&self) -> IpAddr {
+        self.ip
+    }
+
+    /// The network this host is part of
+    pub fn network(&self) -> &SimNetwork {
+        &self.network
+    }
+
+    /// Listens for TCP connections on `port` of this host, or an ephemeral port if `port` is 0
+    pub fn listen_tcp(&self, port: u16) -> io::Result<SimTcpListener> {
+        let mut state = self.network.lock();
+        let port = match port {
+            0 => state.ephemeral_port(self.ip, |state, addr| state.tcp.contains_key(addr))?,
+            port => port,
+        };
+
+        let local_addr = SocketAddr::new(self.ip, port);
+        if state.tcp.contains_key(&local_addr) {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                format!("simulated tcp address in use: {local_addr}"),
+            ));
+        }
+
+        let (sender, receiver) = mpsc::unbounded();
+        state.tcp.insert(local_addr, sender);
+        Ok(SimTcpListener {
+            network: self.network.clone(),
+            local_addr,
+            incoming: receiver,
+        })
+    }
+
+    fn bind(&self, local_addr: SocketAddr) -> io::Result<SimUdpSocket> {
+        if !local_addr.ip().is_unspecified() && local_addr.ip() != self.ip {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrNotAvailable,
+                format!(
+                    "{} is not the address of this host: {}",
+                    local_addr.ip(),
+                    self.ip
+                ),
+            ));
+        }
+
+        let mut state = self.network.lock();
+        let port = match local_addr.port() {
+            0 => state.ephemeral_port(self.ip, |state, addr| state.udp.contains_key(addr))?,
+            port => port,
+        };
+
+        let local_addr = SocketAddr::new(self.ip, port);
+        if state.udp.contains_key(&local_addr) {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                format!("simulated udp address in use: {local_addr}"),
+            ));
+        }
+
+        let (sender, receiver) = mpsc::unbounded();
+        state.udp.insert(local_addr, sender);
+        Ok(SimUdpSocket {
+            network: self.network.clone(),
+            local_addr,
+            incoming: Mutex::new(receiver),
+        })
+    }
+}
+
+impl RuntimeProvider for SimRuntimeProvider {
+    type Handle = TokioHandle;
+    type Timer = TokioTime;
+    type Udp = SimUdpSocket;
+    type Tcp = SimTcpStream;
+
+    fn create_handle(&self) -> Self::Handle {
+        self.handle.clone()
+    }
+
+    fn connect_tcp(
+        &self,
+        server_addr: SocketAddr,
+        _bind_addr: Option<SocketAddr>,
+        timeout: Option<Duration>,
+    ) -> Pin<Box<dyn Send + Future<Output = io::Result<Self::Tcp>>>> {
+        let network = self.network.clone();
+        let ip = self.ip;
+        Box::pin(async move {
+            let wait_for = timeout.unwrap_or(crate::xfer::CONNECT_TIMEOUT);
+            match tokio::time::timeout(wait_for, network.connect_tcp(ip, server_addr)).await {
+                Ok(result) => result,
+                Err(_) => Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("connection to {server_addr:?} timed out after {wait_for:?}"),
+                )),
+            }
+        })
+    }
+
+    fn bind_udp(
+        &self,
+        local_addr: SocketAddr,
+        _server_addr: SocketAddr,
+    ) -> Pin<Box<dyn Send + Future<Output = io::Result<Self::Udp>>>> {
+        let result = self.bind(local_addr);
+        Box::pin(async move { result })
+    }
+}
+
+/// A UDP socket bound to an address of a [`SimNetwork`] host
+pub struct SimUdpSocket {
+    network: SimNetwork,
+    local_addr: SocketAddr,
+    incoming: Mutex<mpsc::UnboundedReceiver<(Vec<u8>, SocketAddr)>>,
+}
+
+impl SimUdpSocket {
+    /// The address this socket is bound to
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
+impl DnsUdpSocket for SimUdpSocket {
+    type Time = TokioTime;
+
+    fn poll_recv_from(
+        &self,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<(usize, SocketAddr)>> {
+        let mut incoming = self
+            .incoming
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        match incoming.poll_next_unpin(cx) {
+            Poll::Ready(Some((payload, from))) => {
+                // like a real socket, the part of the datagram that doesn't fit is discarded
+                let len = payload.len().min(buf.len());
+                buf[..len].copy_from_slice(&payload[..len]);
+                Poll::Ready(Ok((len, from)))
+            }
+            Poll::Ready(None) => Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "simulated udp socket closed",
+            ))),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    fn poll_send_to(
+        &self,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+        target: SocketAddr,
+    ) -> Poll<io::Result<usize>> {
+        self.network.send_udp(self.local_addr, target, buf.to_vec());
+        Poll::Ready(Ok(buf.len()))
+    }
+}
+
+impl Drop for SimUdpSocket {
+    fn drop(&mut self) {
+        self.network.lock().udp.remove(&self.local_addr);
+    }
+}
+
+/// A TCP connection between two hosts of a [`SimNetwork`]
+pub type SimTcpStream = AsyncIoTokioAsStd<DuplexStream>;
+
+/// Listens for TCP connections on an address of a [`SimNetwork`] host
+pub struct SimTcpListener {
+    network: SimNetwork,
+    local_addr: SocketAddr,
+    incoming: mpsc::UnboundedReceiver<(SimTcpStream, SocketAddr)>,
+}
+
+impl SimTcpListener {
+    /// The address this listener is bound to
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Accepts the next connection, returning the stream and the address of the peer
+    pub async fn accept(&mut self) -> io::Result<(SimTcpStream, SocketAddr)> {
+        self.incoming.next().await.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotConnected, "simulated tcp listener closed")
+        })
+    }
+}
+
+impl Stream for SimTcpListener {
+    type Item = io::Result<(SimTcpStream, SocketAddr)>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.incoming.poll_next_unpin(cx).map(|next| next.map(Ok))
+    }
+}
+
+impl Drop for SimTcpListener {
+    fn drop(&mut self) {
+        self.network.lock().tcp.remove(&self.local_addr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::net::Ipv4Addr;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+    use crate::{
+        op::{Message, Query},
+        rr::{Name, RData, Record, RecordType, rdata::TXT},
+    };
+
+    const CLIENT: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+    const SERVER: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+
+    async fn udp_pair(network: &SimNetwork) -> (SimUdpSocket, SimUdpSocket) {
+        let server_addr = SocketAddr::new(SERVER, 53);
+        let server = network
+            .host(SERVER)
+            .bind_udp(server_addr, server_addr)
+            .await
+            .unwrap();
+        let client = network
+            .host(CLIENT)
+            .bind_udp((Ipv4Addr::UNSPECIFIED, 0).into(), server_addr)
+            .await
+            .unwrap();
+        (client, server)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_udp_latency() {
+        let network = SimNetwork::new(0);
+        network.set_default_link(LinkConditions {
+            latency: Duration::from_secs(3),
+            ..LinkConditions::default()
+        });
+
+        let (client, server) = udp_pair(&network).await;
+        assert_eq!(client.local_addr().ip(), CLIENT);
+
+        let start = tokio::time::Instant::now();
+        client.send_to(b"ping", server.local_addr()).await.unwrap();
+
+        let mut buf = [0; 512];
+        let (len, from) = server.recv_from(&mut buf).await.unwrap();
+        assert_eq!(&buf[..len], b"ping");
+        assert_eq!(from, client.local_addr());
+        assert_eq!(start.elapsed(), Duration::from_secs(3));
+
+        // nothing is bound there anymore
+        drop(server);
+        client
+            .send_to(b"ping", SocketAddr::new(SERVER, 53))
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        assert_eq!(network.stats().udp_dropped, 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_udp_loss_is_deterministic() {
+        async fn delivered(seed: u64) -> usize {
+            let network = SimNetwork::new(seed);
+            network.set_link(
+                CLIENT,
+                SERVER,
+                LinkConditions {
+                    loss: 0.5,
+                    ..LinkConditions::default()
+                },
+            );
+
+            let (client, server) = udp_pair(&network).await;
+            for _ in 0..100 {
+                client.send_to(b"ping", server.local_addr()).await.unwrap();
+                // the link back isn't lossy
+                server.send_to(b"pong", client.local_addr()).await.unwrap();
+            }
+
+            let stats = network.stats();
+            assert_eq!(stats.udp_sent, 200);
+            assert_eq!(stats.udp_delivered + stats.udp_dropped, 200);
+            stats.udp_delivered
+        }
+
+        let first = delivered(7).await;
+        assert!(first > 100 && first < 200, "{first}");
+        assert_eq!(first, delivered(7).await);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_udp_truncation() {
+        let network = SimNetwork::new(0);
+        network.set_default_link(LinkConditions {
+            max_udp_payload: Some(512),
+            ..LinkConditions::default()
+        });
+
+        let (client, server) = udp_pair(&network).await;
+
+        let name = Name::from_ascii("example.com.").unwrap();
+        let mut message = Message::new();
+        message.add_query(Query::query(name.clone(), RecordType::TXT));
+        for _ in 0..10 {
+            message.add_answer(Record::from_rdata(
+                name.clone(),
+                60,
+                RData::TXT(TXT::new(vec!["x".repeat(100)])),
+            ));
+        }
+
+        server
+            .send_to(&message.to_vec().unwrap(), client.local_addr())
+            .await
+            .unwrap();
+
+        let mut buf = [0; 4096];
+        let (len, _) = client.recv_from(&mut buf).await.unwrap();
+        let received = Message::from_vec(&buf[..len]).unwrap();
+        assert!(received.truncated());
+        assert!(received.answers().is_empty());
+        assert_eq!(received.queries(), message.queries());
+        assert_eq!(network.stats().udp_truncated, 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_tcp() {
+        let network = SimNetwork::new(0);
+        network.set_default_link(LinkConditions {
+            latency: Duration::from_millis(50),
+            ..LinkConditions::default()
+        });
+
+        let server = network.host(SERVER);
+        let mut listener = server.listen_tcp(53).unwrap();
+        assert!(server.listen_tcp(53).is_err());
+
+        let client = network.host(CLIENT);
+        let server_addr = listener.local_addr();
+        let start = tokio::time::Instant::now();
+        let (accepted, connected) = tokio::join!(
+            listener.accept(),
+            client.connect_tcp(server_addr, None, None)
+        );
+        let (mut stream, peer) = accepted.unwrap();
+        let mut client_stream = connected.unwrap();
+        assert_eq!(start.elapsed(), Duration::from_millis(100));
+        assert_eq!(peer.ip(), CLIENT);
+
+        // data takes the latency of the link in each direction
+        let start = tokio::time::Instant::now();
+        client_stream.0.write_all(b"ping").await.unwrap();
+        let mut buf = [0; 4];
+        stream.0.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"ping");
+        assert_eq!(start.elapsed(), Duration::from_millis(50));
+
+        stream.0.write_all(b"pong").await.unwrap();
+        client_stream.0.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"pong");
+        assert_eq!(start.elapsed(), Duration::from_millis(100));
+
+        // closing a stream is seen by the peer once the data in flight arrived
+        client_stream.0.write_all(b"bye").await.unwrap();
+        drop(client_stream);
+        let mut rest = Vec::new();
+        stream.0.read_to_end(&mut rest).await.unwrap();
+        assert_eq!(rest, b"bye");
+        assert_eq!(start.elapsed(), Duration::from_millis(150));
+
+        drop(listener);
+        let Err(err) = client
+            .connect_tcp(SocketAddr::new(SERVER, 53), None, None)
+            .await
+        else {
+            panic!("connected without a listener");
+        };
+        assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused);
+        assert_eq!(network.stats().tcp_connections, 1);
+    }
+}
@@ -0,0 +1,245 @@
+//! A [`RuntimeProvider`] for the [smol](https://docs.rs/smol) async runtime
+//!
+//! Background tasks are spawned on smol's global executor, so the provider works from within
+//! `smol::block_on` or any other executor driving the returned futures. UDP, TCP and DNS over TLS
+//! are supported, HTTPS and QUIC still require Tokio.
+
+use alloc::boxed::Box;
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use std::{
+    io,
+    net::{SocketAddr, TcpStream, UdpSocket},
+};
+
+use async_trait::async_trait;
+use futures_util::ready;
+use smol::{Async, Timer};
+use socket2::{Domain, Protocol, Socket, Type};
+
+use super::{RuntimeProvider, Spawn, Time};
+use crate::{error::ProtoError, tcp::DnsTcpStream, udp::DnsUdpSocket, xfer::CONNECT_TIMEOUT};
+
+/// A handle to smol's global executor
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SmolHandle;
+
+impl Spawn for SmolHandle {
+    fn spawn_bg<F>(&mut self, future: F)
+    where
+        F: Future<Output = Result<(), ProtoError>> + Send + 'static,
+    {
+        smol::spawn(future).detach();
+    }
+}
+
+/// The smol runtime for async execution
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SmolRuntimeProvider(SmolHandle);
+
+impl SmolRuntimeProvider {
+    /// Create a smol runtime provider
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl RuntimeProvider for SmolRuntimeProvider {
+    type Handle = SmolHandle;
+    type Timer = SmolTime;
+    type Udp = Async<UdpSocket>;
+    type Tcp = Async<TcpStream>;
+
+    fn create_handle(&self) -> Self::Handle {
+        self.0
+    }
+
+    fn connect_tcp(
+        &self,
+        server_addr: SocketAddr,
+        bind_addr: Option<SocketAddr>,
+        wait_for: Option<Duration>,
+    ) -> Pin<Box<dyn Send + Future<Output = io::Result<Self::Tcp>>>> {
+        Box::pin(async move {
+            let wait_for = wait_for.unwrap_or(CONNECT_TIMEOUT);
+            let connect = async move {
+                let stream = match bind_addr {
+                    // async-io can't bind a socket before connecting it, so connect on the
+                    // blocking thread pool instead
+                    Some(bind_addr) => {
+                        let stream =
+                            smol::unblock(move || connect_from(server_addr, bind_addr, wait_for))
+                                .await?;
+                        Async::new(stream)?
+                    }
+                    None => Async::<TcpStream>::connect(server_addr).await?,
+                };
+
+                stream.get_ref().set_nodelay(true)?;
+                Ok(stream)
+            };
+
+            match SmolTime::timeout(wait_for, connect).await {
+                Ok(result) => result,
+                Err(_) => Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("connection to {server_addr:?} timed out after {wait_for:?}"),
+                )),
+            }
+        })
+    }
+
+    fn bind_udp(
+        &self,
+        local_addr: SocketAddr,
+        _server_addr: SocketAddr,
+    ) -> Pin<Box<dyn Send + Future<Output = io::Result<Self::Udp>>>> {
+        let socket = Async::<UdpSocket>::bind(local_addr);
+        Box::pin(async move { socket })
+    }
+}
+
+/// Connects a TCP socket bound to `bind_addr`, blocking the current thread
+fn connect_from(
+    server_addr: SocketAddr,
+    bind_addr: SocketAddr,
+    timeout: Duration,
+) -> io::Result<TcpStream> {
+    let socket = Socket::new(
+        Domain::for_address(server_addr),
+        Type::STREAM,
+        Some(Protocol::TCP),
+    )?;
+    socket.bind(&bind_addr.into())?;
+    socket.connect_timeout(&server_addr.into(), timeout)?;
+    Ok(socket.into())
+}
+
+impl DnsUdpSocket for Async<UdpSocket> {
+    type Time = SmolTime;
+
+    fn poll_recv_from(
+        &self,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<(usize, SocketAddr)>> {
+        loop {
+            match self.get_ref().recv_from(buf) {
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => ready!(self.poll_readable(cx))?,
+                result => return Poll::Ready(result),
+            }
+        }
+    }
+
+    fn poll_send_to(
+        &self,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+        target: SocketAddr,
+    ) -> Poll<io::Result<usize>> {
+        loop {
+            match self.get_ref().send_to(buf, target) {
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => ready!(self.poll_writable(cx))?,
+                result => return Poll::Ready(result),
+            }
+        }
+    }
+}
+
+impl DnsTcpStream for Async<TcpStream> {
+    type Time = SmolTime;
+}
+
+/// Timers backed by smol's reactor
+#[derive(Clone, Copy, Debug)]
+pub struct SmolTime;
+
+#[async_trait]
+impl Time for SmolTime {
+    async fn delay_for(duration: Duration) {
+        Timer::after(duration).await;
+    }
+
+    async fn timeout<F: 'static + Future + Send>(
+        duration: Duration,
+        future: F,
+    ) -> Result<F::Output, io::Error> {
+        let expired = async move {
+            Timer::after(duration).await;
+            Err(io::Error::new(io::ErrorKind::TimedOut, "future timed out"))
+        };
+
+        smol::future::or(async move { Ok(future.await) }, expired).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use test_support::subscribe;
+
+    use super::*;
+    use crate::tests::{
+        next_random_socket_test, tcp_client_stream_test, tcp_stream_test, udp_client_stream_test,
+        udp_stream_test,
+    };
+
+    #[test]
+    fn test_next_random_socket() {
+        subscribe();
+        smol::block_on(next_random_socket_test(SmolRuntimeProvider::new()));
+    }
+
+    #[test]
+    fn test_udp_stream_ipv4() {
+        subscribe();
+        smol::block_on(udp_stream_test(
+            IpAddr::V4(Ipv4Addr::LOCALHOST),
+            SmolRuntimeProvider::new(),
+        ));
+    }
+
+    #[test]
+    fn test_udp_client_stream_ipv4() {
+        subscribe();
+        smol::block_on(udp_client_stream_test(
+            IpAddr::V4(Ipv4Addr::LOCALHOST),
+            SmolRuntimeProvider::new(),
+        ));
+    }
+
+    #[test]
+    fn test_tcp_stream_ipv4() {
+        subscribe();
+        smol::block_on(tcp_stream_test(
+            IpAddr::V4(Ipv4Addr::LOCALHOST),
+            SmolRuntimeProvider::new(),
+        ));
+    }
+
+    #[test]
+    fn test_tcp_client_stream_ipv4() {
+        subscribe();
+        smol::block_on(tcp_client_stream_test(
+            IpAddr::V4(Ipv4Addr::LOCALHOST),
+            SmolRuntimeProvider::new(),
+        ));
+    }
+
+    #[test]
+    fn test_timeout() {
+        let result = smol::block_on(SmolTime::timeout(
+            Duration::from_millis(10),
+            Timer::after(Duration::from_secs(10)),
+        ));
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::TimedOut);
+
+        let result = smol::block_on(SmolTime::timeout(Duration::from_secs(10), async { 42 }));
+        assert_eq!(result.unwrap(), 42);
+    }
+}
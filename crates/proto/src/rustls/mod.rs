@@ -7,20 +7,25 @@
 
 //! TLS protocol related components for DNS over TLS
 
+#[cfg(not(feature = "rustls-platform-verifier"))]
+use alloc::string::ToString;
 use alloc::sync::Arc;
 
-#[cfg(not(feature = "rustls-platform-verifier"))]
-use rustls::RootCertStore;
 use rustls::{
     ClientConfig,
+    client::danger::ServerCertVerifier,
     crypto::{self, CryptoProvider},
 };
+#[cfg(not(feature = "rustls-platform-verifier"))]
+use rustls::{RootCertStore, client::WebPkiServerVerifier};
 #[cfg(feature = "rustls-platform-verifier")]
 use rustls_platform_verifier::BuilderVerifierExt;
 
+pub mod pinned_verifier;
 pub mod tls_client_stream;
 pub mod tls_stream;
 
+pub use self::pinned_verifier::{PinnedServerVerifier, pin_certificates};
 pub use self::tls_client_stream::{
     TlsClientStream, tls_client_connect, tls_client_connect_with_bind_addr,
 };
@@ -46,6 +51,34 @@ pub fn client_config() -> ClientConfig {
     builder.with_no_client_auth()
 }
 
+/// Make a new certificate verifier with the same roots as [`client_config()`]
+///
+/// This fails without the `webpki-roots` and `rustls-platform-verifier` features, as there are no
+/// roots then.
+pub fn default_verifier(
+    provider: Arc<CryptoProvider>,
+) -> Result<Arc<dyn ServerCertVerifier>, rustls::Error> {
+    #[cfg(feature = "rustls-platform-verifier")]
+    {
+        Ok(Arc::new(
+            rustls_platform_verifier::Verifier::new().with_provider(provider),
+        ))
+    }
+
+    #[cfg(not(feature = "rustls-platform-verifier"))]
+    {
+        #[cfg_attr(not(feature = "webpki-roots"), allow(unused_mut))]
+        let mut root_store = RootCertStore::empty();
+        #[cfg(feature = "webpki-roots")]
+        root_store.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+
+        let verifier = WebPkiServerVerifier::builder_with_provider(Arc::new(root_store), provider)
+            .build()
+            .map_err(|e| rustls::Error::General(e.to_string()))?;
+        Ok(verifier)
+    }
+}
+
 /// Instantiate a new [`CryptoProvider`] for use with rustls
 #[cfg(all(feature = "tls-aws-lc-rs", not(feature = "tls-ring")))]
 pub fn default_provider() -> CryptoProvider {
@@ -0,0 +1,196 @@
+// Copyright 2015-2025 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Pinning of server certificates
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::CryptoProvider;
+use rustls::crypto::hash::{Hash, HashAlgorithm};
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{CertificateError, ClientConfig, DigitallySignedStruct, Error, SignatureScheme};
+
+/// Verifies server certificates with another verifier, and additionally requires a certificate of
+/// the chain to match one of the pins
+///
+/// A pin is the SHA-256 digest of the DER encoded `TBSCertificate` of a certificate, the signed
+/// part of it, as used by [DNS Stamps](https://dnscrypt.info/stamps-specifications). Usually the
+/// certificate of an intermediate CA is pinned.
+pub struct PinnedServerVerifier {
+    inner: Arc<dyn ServerCertVerifier>,
+    pins: Vec<[u8; 32]>,
+    sha256: &'static dyn Hash,
+}
+
+impl PinnedServerVerifier {
+    /// Creates a verifier for the pins, which first verifies certificates with `inner`
+    ///
+    /// `provider` is used to compute the digests of certificates.
+    pub fn new(
+        inner: Arc<dyn ServerCertVerifier>,
+        pins: Vec<[u8; 32]>,
+        provider: &CryptoProvider,
+    ) -> Result<Self, Error> {
+        let sha256 = provider
+            .cipher_suites
+            .iter()
+            .filter_map(|suite| suite.tls13())
+            .map(|suite| suite.common.hash_provider)
+            .find(|hash| hash.algorithm() == HashAlgorithm::SHA256)
+            .ok_or_else(|| Error::General("the crypto provider does not support SHA-256".into()))?;
+
+        Ok(Self {
+            inner,
+            pins,
+            sha256,
+        })
+    }
+
+    fn is_pinned(&self, cert: &CertificateDer<'_>) -> bool {
+        tbs_certificate(cert).is_some_and(|tbs| {
+            let digest = self.sha256.hash(tbs);
+            self.pins.iter().any(|pin| pin[..] == *digest.as_ref())
+        })
+    }
+}
+
+/// Replaces the certificate verifier of `config` with a [`PinnedServerVerifier`] for the pins,
+/// which wraps `verifier`
+///
+/// rustls doesn't expose the verifier of a [`ClientConfig`], so `verifier` has to be passed in,
+/// usually the one `config` was built with, e.g. [`super::default_verifier`] for
+/// [`super::client_config`].
+pub fn pin_certificates(
+    config: &mut ClientConfig,
+    verifier: Arc<dyn ServerCertVerifier>,
+    pins: Vec<[u8; 32]>,
+) -> Result<(), Error> {
+    let verifier = PinnedServerVerifier::new(verifier, pins, config.crypto_provider())?;
+    config
+        .dangerous()
+        .set_certificate_verifier(Arc::new(verifier));
+    Ok(())
+}
+
+impl ServerCertVerifier for PinnedServerVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, Error> {
+        let verified = self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            ocsp_response,
+            now,
+        )?;
+
+        if core::iter::once(end_entity)
+            .chain(intermediates)
+            .any(|cert| self.is_pinned(cert))
+        {
+            Ok(verified)
+        } else {
+            Err(Error::InvalidCertificate(
+                CertificateError::ApplicationVerificationFailure,
+            ))
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+impl fmt::Debug for PinnedServerVerifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PinnedServerVerifier")
+            .field("inner", &self.inner)
+            .field("pins", &self.pins.len())
+            .finish()
+    }
+}
+
+/// Returns the DER encoded `TBSCertificate` of a certificate, RFC 5280 section 4.1
+fn tbs_certificate(cert: &[u8]) -> Option<&[u8]> {
+    let (header_len, len) = sequence_header(cert)?;
+    let certificate = cert.get(header_len..header_len.checked_add(len)?)?;
+
+    let (header_len, len) = sequence_header(certificate)?;
+    certificate.get(..header_len.checked_add(len)?)
+}
+
+/// Returns the length of the header and of the contents of a DER encoded SEQUENCE
+fn sequence_header(der: &[u8]) -> Option<(usize, usize)> {
+    const SEQUENCE: u8 = 0x30;
+
+    match der {
+        [SEQUENCE, len, ..] if *len < 0x80 => Some((2, usize::from(*len))),
+        [SEQUENCE, len_bytes, rest @ ..] => {
+            let count = usize::from(len_bytes & 0x7f);
+            if count == 0 || count > 4 {
+                return None;
+            }
+
+            let len = rest
+                .get(..count)?
+                .iter()
+                .fold(0, |len, byte| len << 8 | usize::from(*byte));
+            Some((2 + count, len))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tbs_certificate() {
+        // SEQUENCE { SEQUENCE { INTEGER 1 }, SEQUENCE {}, BIT STRING }
+        let cert = [
+            0x30, 0x0b, 0x30, 0x03, 0x02, 0x01, 0x01, 0x30, 0x00, 0x03, 0x02, 0x00, 0x00,
+        ];
+        assert_eq!(tbs_certificate(&cert), Some(&cert[2..7]));
+
+        // long form lengths
+        let mut cert = vec![0x30, 0x82, 0x01, 0x05, 0x30, 0x81, 0x80];
+        cert.extend([0; 0x80]);
+        cert.extend([0x30, 0x00]);
+        cert.extend([0; 0x80]);
+        assert_eq!(tbs_certificate(&cert), Some(&cert[4..0x83 + 4]));
+
+        assert_eq!(tbs_certificate(&[0x30, 0x05, 0x30, 0x03, 0x02]), None);
+        assert_eq!(tbs_certificate(&[0x31, 0x02, 0x30, 0x00]), None);
+    }
+}
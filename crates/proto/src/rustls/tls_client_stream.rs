@@ -20,14 +20,18 @@ use rustls::ClientConfig;
 
 use crate::error::ProtoError;
 use crate::runtime::RuntimeProvider;
-use crate::runtime::iocompat::{AsyncIoStdAsTokio, AsyncIoTokioAsStd};
-use crate::rustls::tls_stream::{tls_connect_with_bind_addr, tls_connect_with_future};
+use crate::rustls::tls_stream::{
+    RustlsClientStream, tls_connect_with_bind_addr, tls_connect_with_future,
+};
 use crate::tcp::{DnsTcpStream, TcpClientStream};
 use crate::xfer::BufDnsStreamHandle;
 
 /// Type of TlsClientStream used with Rustls
-pub type TlsClientStream<S> =
-    TcpClientStream<AsyncIoTokioAsStd<tokio_rustls::client::TlsStream<AsyncIoStdAsTokio<S>>>>;
+pub type TlsClientStream<S> = TcpClientStream<RustlsClientStream<S>>;
+
+impl<S: DnsTcpStream> DnsTcpStream for RustlsClientStream<S> {
+    type Time = S::Time;
+}
 
 /// Creates a new TlsStream to the specified name_server
 ///
@@ -15,27 +15,35 @@ use core::pin::Pin;
 use std::io;
 use std::net::SocketAddr;
 
+use futures_rustls::TlsConnector;
 use rustls::ClientConfig;
 use rustls::pki_types::ServerName;
+#[cfg(feature = "tokio")]
 use tokio::net::TcpStream as TokioTcpStream;
-use tokio::{self, time::timeout};
-use tokio_rustls::TlsConnector;
 
-use crate::runtime::RuntimeProvider;
-use crate::runtime::iocompat::{AsyncIoStdAsTokio, AsyncIoTokioAsStd};
+#[cfg(feature = "tokio")]
+use crate::runtime::iocompat::AsyncIoStdAsTokio;
+use crate::runtime::{RuntimeProvider, Time};
 use crate::tcp::{DnsTcpStream, TcpStream};
 use crate::xfer::{BufDnsStreamHandle, CONNECT_TIMEOUT, StreamReceiver};
 
 /// Predefined type for abstracting the TlsClientStream with TokioTls
+#[cfg(feature = "tokio")]
 pub type TokioTlsClientStream<S> = tokio_rustls::client::TlsStream<AsyncIoStdAsTokio<S>>;
 
 /// Predefined type for abstracting the TlsServerStream with TokioTls
+#[cfg(feature = "tokio")]
 pub type TokioTlsServerStream = tokio_rustls::server::TlsStream<TokioTcpStream>;
 
-/// Predefined type for abstracting the base I/O TlsStream with TokioTls
+/// Predefined type for the client side of a TLS connection over a `DnsTcpStream`
+///
+/// This is independent of the async runtime, the handshake and all I/O go through `S`.
+pub type RustlsClientStream<S> = futures_rustls::client::TlsStream<S>;
+
+/// Predefined type for abstracting the base I/O TlsStream
 pub type TlsStream<S> = TcpStream<S>;
 
-/// Initializes a TlsStream with an existing tokio_tls::TlsStream.
+/// Initializes a TlsStream with an existing TLS stream.
 ///
 /// This is intended for use with a TlsListener and Incoming connections
 pub fn tls_from_stream<S: DnsTcpStream>(
@@ -80,16 +88,7 @@ pub fn tls_connect<P: RuntimeProvider>(
     client_config: Arc<ClientConfig>,
     provider: P,
 ) -> (
-    Pin<
-        Box<
-            dyn Future<
-                    Output = Result<
-                        TlsStream<AsyncIoTokioAsStd<TokioTlsClientStream<P::Tcp>>>,
-                        io::Error,
-                    >,
-                > + Send,
-        >,
-    >,
+    Pin<Box<dyn Future<Output = Result<TlsStream<RustlsClientStream<P::Tcp>>, io::Error>> + Send>>,
     BufDnsStreamHandle,
 ) {
     tls_connect_with_bind_addr(name_server, None, dns_name, client_config, provider)
@@ -110,16 +109,7 @@ pub fn tls_connect_with_bind_addr<P: RuntimeProvider>(
     client_config: Arc<ClientConfig>,
     provider: P,
 ) -> (
-    Pin<
-        Box<
-            dyn Future<
-                    Output = Result<
-                        TlsStream<AsyncIoTokioAsStd<TokioTlsClientStream<P::Tcp>>>,
-                        io::Error,
-                    >,
-                > + Send,
-        >,
-    >,
+    Pin<Box<dyn Future<Output = Result<TlsStream<RustlsClientStream<P::Tcp>>, io::Error>> + Send>>,
     BufDnsStreamHandle,
 ) {
     let (message_sender, outbound_messages) = BufDnsStreamHandle::new(name_server);
@@ -154,16 +144,7 @@ pub fn tls_connect_with_future<S, F>(
     dns_name: String,
     client_config: Arc<ClientConfig>,
 ) -> (
-    Pin<
-        Box<
-            dyn Future<
-                    Output = Result<
-                        TlsStream<AsyncIoTokioAsStd<TokioTlsClientStream<S>>>,
-                        io::Error,
-                    >,
-                > + Send,
-        >,
-    >,
+    Pin<Box<dyn Future<Output = Result<TlsStream<RustlsClientStream<S>>, io::Error>> + Send>>,
     BufDnsStreamHandle,
 )
 where
@@ -194,7 +175,7 @@ async fn connect_tls<P: RuntimeProvider>(
     dns_name: String,
     outbound_messages: StreamReceiver,
     provider: P,
-) -> io::Result<TcpStream<AsyncIoTokioAsStd<TokioTlsClientStream<P::Tcp>>>> {
+) -> io::Result<TcpStream<RustlsClientStream<P::Tcp>>> {
     let tcp = provider.connect_tcp(name_server, bind_addr, None);
     connect_tls_with_future(tls_connector, tcp, name_server, dns_name, outbound_messages).await
 }
@@ -205,7 +186,7 @@ async fn connect_tls_with_future<S, F>(
     name_server: SocketAddr,
     server_name: String,
     outbound_messages: StreamReceiver,
-) -> io::Result<TcpStream<AsyncIoTokioAsStd<TokioTlsClientStream<S>>>>
+) -> io::Result<TcpStream<RustlsClientStream<S>>>
 where
     S: DnsTcpStream,
     F: Future<Output = io::Result<S>> + Send + Unpin,
@@ -215,8 +196,9 @@ where
         Err(_) => return Err(io::Error::new(io::ErrorKind::InvalidInput, "bad dns_name")),
     };
 
-    let stream = AsyncIoStdAsTokio(future.await?);
-    let s = match timeout(CONNECT_TIMEOUT, tls_connector.connect(dns_name, stream)).await {
+    let stream = future.await?;
+    let handshake = tls_connector.connect(dns_name, stream);
+    let s = match <S::Time as Time>::timeout(CONNECT_TIMEOUT, handshake).await {
         Ok(Ok(s)) => s,
         Ok(Err(e)) => {
             return Err(io::Error::new(
@@ -233,7 +215,7 @@ where
     };
 
     Ok(TcpStream::from_stream_with_receiver(
-        AsyncIoTokioAsStd(s),
+        s,
         name_server,
         outbound_messages,
     ))
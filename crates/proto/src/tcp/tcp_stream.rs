@@ -27,7 +27,7 @@ use crate::xfer::{SerialMessage, StreamReceiver};
 /// Trait for TCP connection
 pub trait DnsTcpStream: AsyncRead + AsyncWrite + Unpin + Send + Sync + Sized + 'static {
     /// Timer type to use with this TCP stream type
-    type Time: Time;
+    type Time: Time + Send + Unpin;
 }
 
 /// Current state while writing to the remote of the TCP connection
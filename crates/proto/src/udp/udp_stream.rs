@@ -30,7 +30,7 @@ where
     Self: Send + Sync + Sized + Unpin,
 {
     /// Time implementation used for this type
-    type Time: Time;
+    type Time: Time + Send + Unpin;
 
     /// Poll once Receive data from the socket and returns the number of bytes read and the address from
     /// where the data came on success.
@@ -74,7 +74,7 @@ pub trait DnsClientStream:
     Stream<Item = Result<SerialMessage, ProtoError>> + Display + Send
 {
     /// Time implementation for this impl
-    type Time: Time;
+    type Time: Time + Send + Unpin;
 
     /// The remote name server address
     fn name_server_addr(&self) -> SocketAddr;
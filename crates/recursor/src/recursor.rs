@@ -5,32 +5,47 @@
 // https://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+#[cfg(feature = "__dnssec")]
+use std::time::Duration;
 use std::{
     collections::HashSet,
+    io,
     net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    path::Path,
     sync::{Arc, atomic::AtomicU8},
     time::Instant,
 };
 
 use ipnet::IpNet;
 
+#[cfg(feature = "dnstap")]
+use crate::proto::dnstap::Dnstap;
 use crate::{
     DnssecPolicy, Error,
-    proto::op::Query,
+    proto::{
+        op::Query,
+        rr::{Name, Record},
+        runtime::{RuntimeProvider, TokioRuntimeProvider},
+    },
     recursor_dns_handle::RecursorDnsHandle,
-    resolver::{config::NameServerConfigGroup, dns_lru::TtlConfig, lookup::Lookup},
+    resolver::{
+        cache_file::{self, CacheEntry},
+        config::NameServerConfigGroup,
+        dns_lru::{DnsLru, TtlConfig},
+        lookup::Lookup,
+        name_server::GenericConnector,
+    },
 };
 #[cfg(feature = "__dnssec")]
 use crate::{
     ErrorKind,
     proto::{
         ProtoError,
-        dnssec::{DnssecDnsHandle, TrustAnchors},
+        dnssec::{DnssecDnsHandle, NegativeTrustAnchor, NegativeTrustAnchors, TrustAnchors},
         op::ResponseCode,
-        rr::{Record, RecordType, resource::RecordRef},
+        rr::{RecordType, resource::RecordRef},
         xfer::{DnsHandle as _, DnsRequestOptions, FirstAnswer as _},
     },
-    resolver::dns_lru::DnsLru,
 };
 
 /// A `Recursor` builder
@@ -50,6 +65,10 @@ pub struct RecursorBuilder {
     avoid_local_udp_ports: HashSet<u16>,
     ttl_config: TtlConfig,
     case_randomization: bool,
+    #[cfg(feature = "__dnssec")]
+    negative_trust_anchors: Vec<NegativeTrustAnchor>,
+    #[cfg(feature = "dnstap")]
+    dnstap: Option<Dnstap>,
 }
 
 impl RecursorBuilder {
@@ -121,21 +140,54 @@ impl RecursorBuilder {
         self
     }
 
+    /// Sets the negative trust anchors, domains for which DNSSEC validation is skipped
+    ///
+    /// See [RFC 7646](https://datatracker.ietf.org/doc/html/rfc7646). These can be changed later
+    /// with [`Recursor::add_negative_trust_anchor`] and [`Recursor::remove_negative_trust_anchor`].
+    #[cfg(feature = "__dnssec")]
+    pub fn negative_trust_anchors(
+        mut self,
+        negative_trust_anchors: impl IntoIterator<Item = NegativeTrustAnchor>,
+    ) -> Self {
+        self.negative_trust_anchors = negative_trust_anchors.into_iter().collect();
+        self
+    }
+
+    /// Logs the queries sent to authoritative servers and their responses to dnstap
+    #[cfg(feature = "dnstap")]
+    pub fn dnstap(mut self, dnstap: Dnstap) -> Self {
+        self.dnstap = Some(dnstap);
+        self
+    }
+
     /// Construct a new recursor using the list of NameServerConfigs for the root node list
     ///
     /// # Panics
     ///
     /// This will panic if the roots are empty.
     pub fn build(self, roots: impl Into<NameServerConfigGroup>) -> Result<Recursor, Error> {
-        Recursor::build(roots, self)
+        Recursor::build(roots, self, TokioRuntimeProvider::default())
+    }
+
+    /// Construct a new recursor that sends its queries through the given runtime provider
+    ///
+    /// # Panics
+    ///
+    /// This will panic if the roots are empty.
+    pub fn build_with_provider<P: RuntimeProvider>(
+        self,
+        roots: impl Into<NameServerConfigGroup>,
+        provider: P,
+    ) -> Result<Recursor<P>, Error> {
+        Recursor::build(roots, self, provider)
     }
 }
 
 /// A top down recursive resolver which operates off a list of roots for initial recursive requests.
 ///
 /// This is the well known root nodes, referred to as hints in RFCs. See the IANA [Root Servers](https://www.iana.org/domains/root/servers) list.
-pub struct Recursor {
-    mode: RecursorMode,
+pub struct Recursor<P: RuntimeProvider = TokioRuntimeProvider> {
+    mode: RecursorMode<P>,
 }
 
 impl Recursor {
@@ -143,7 +195,9 @@ impl Recursor {
     pub fn builder() -> RecursorBuilder {
         RecursorBuilder::default()
     }
+}
 
+impl<P: RuntimeProvider> Recursor<P> {
     /// Whether the recursive resolver is a validating resolver
     pub fn is_validating(&self) -> bool {
         // matching on `NonValidating` to avoid conditional compilation (`#[cfg]`)
@@ -154,6 +208,7 @@ impl Recursor {
     fn build(
         roots: impl Into<NameServerConfigGroup>,
         builder: RecursorBuilder,
+        provider: P,
     ) -> Result<Self, Error> {
         let RecursorBuilder {
             ns_cache_size,
@@ -166,6 +221,10 @@ impl Recursor {
             avoid_local_udp_ports,
             ttl_config,
             case_randomization,
+            #[cfg(feature = "__dnssec")]
+            negative_trust_anchors,
+            #[cfg(feature = "dnstap")]
+            dnstap,
         } = builder;
 
         let handle = RecursorDnsHandle::new(
@@ -180,6 +239,9 @@ impl Recursor {
             Arc::new(avoid_local_udp_ports),
             ttl_config,
             case_randomization,
+            #[cfg(feature = "dnstap")]
+            dnstap,
+            GenericConnector::new(provider),
         );
 
         let mode = match dnssec_policy {
@@ -190,7 +252,7 @@ impl Recursor {
 
             #[cfg(feature = "__dnssec")]
             DnssecPolicy::ValidateWithStaticKey { trust_anchor } => {
-                let record_cache = handle.record_cache().clone();
+                let recursor = handle.clone();
                 let trust_anchor = match trust_anchor {
                     Some(anchor) if anchor.is_empty() => {
                         return Err(Error::from("trust anchor must not be empty"));
@@ -199,9 +261,16 @@ impl Recursor {
                     None => Arc::new(TrustAnchors::default()),
                 };
 
+                let negative_trust_anchors = negative_trust_anchors
+                    .into_iter()
+                    .collect::<NegativeTrustAnchors>();
                 RecursorMode::Validating {
-                    record_cache,
-                    handle: DnssecDnsHandle::with_trust_anchor(handle, trust_anchor),
+                    recursor,
+                    handle: Box::new(
+                        DnssecDnsHandle::with_trust_anchor(handle, trust_anchor)
+                            .with_negative_trust_anchors(negative_trust_anchors.clone()),
+                    ),
+                    negative_trust_anchors,
                 }
             }
         };
@@ -209,6 +278,130 @@ impl Recursor {
         Ok(Self { mode })
     }
 
+    /// Adds a negative trust anchor for `name`, replacing any existing one for the same name
+    ///
+    /// DNSSEC validation is skipped for `name` and everything below it until `lifetime` has
+    /// elapsed, or until it's removed if `lifetime` is `None`. Cached responses for those names
+    /// are discarded, so previously bogus answers are resolved again.
+    ///
+    /// This has no effect if the recursor is not validating.
+    #[cfg(feature = "__dnssec")]
+    pub fn add_negative_trust_anchor(&self, name: Name, lifetime: Option<Duration>) {
+        if let RecursorMode::Validating {
+            recursor,
+            negative_trust_anchors,
+            ..
+        } = &self.mode
+        {
+            negative_trust_anchors.insert(name.clone(), lifetime);
+            recursor.record_cache().remove_subdomains(&name);
+        }
+    }
+
+    /// Removes the negative trust anchor for exactly `name`, returns `true` if there was one
+    ///
+    /// Cached responses for names below it are discarded, so they are validated again.
+    #[cfg(feature = "__dnssec")]
+    pub fn remove_negative_trust_anchor(&self, name: &Name) -> bool {
+        let RecursorMode::Validating {
+            recursor,
+            negative_trust_anchors,
+            ..
+        } = &self.mode
+        else {
+            return false;
+        };
+
+        let removed = negative_trust_anchors.remove(name);
+        if removed {
+            recursor.record_cache().remove_subdomains(name);
+        }
+
+        removed
+    }
+
+    /// Returns the active negative trust anchors, with their remaining lifetime
+    #[cfg(feature = "__dnssec")]
+    pub fn negative_trust_anchors(&self) -> Vec<NegativeTrustAnchor> {
+        match &self.mode {
+            RecursorMode::Validating {
+                negative_trust_anchors,
+                ..
+            } => negative_trust_anchors.anchors(),
+            RecursorMode::NonValidating { .. } => Vec::new(),
+        }
+    }
+
+    /// Flushes/Removes all records and name server pools from the cache
+    pub fn clear_cache(&self) {
+        self.record_cache().clear();
+        self.handle().clear_name_servers();
+    }
+
+    /// Removes the cached records and name server pools for `name` and the names below it
+    pub fn clear_cache_subdomains(&self, name: &Name) {
+        self.record_cache().remove_subdomains(name);
+        self.handle().clear_name_servers_subdomains(name);
+    }
+
+    /// Returns the records of all cached positive responses, with their remaining TTL
+    pub fn cached_records(&self) -> Vec<Record> {
+        self.record_cache().records()
+    }
+
+    /// Writes the cached records and name server pools to the file at `path`, returns the number
+    /// of written entries
+    ///
+    /// The entries are stored in wire format with their absolute expiry time, the file is
+    /// replaced atomically.
+    pub fn save_cache(&self, path: &Path) -> io::Result<usize> {
+        cache_file::write_file(path, |file| {
+            Ok(self.record_cache().save(file)? + self.handle().save_name_servers(file)?)
+        })
+    }
+
+    /// Inserts the entries of a file written by [`Self::save_cache`] into the caches, returns the
+    /// number of restored entries
+    ///
+    /// Expired entries are discarded. Unless `preserve_proofs` is set, the DNSSEC validation state
+    /// of the records is not restored, so a validating recursor validates them again when they are
+    /// used.
+    pub fn load_cache(&self, path: &Path, preserve_proofs: bool) -> io::Result<usize> {
+        let handle = self.handle();
+        let mut restored = 0;
+        cache_file::read_file(path, |entry| {
+            let is_current = match entry {
+                CacheEntry::Response(response) => {
+                    handle.record_cache().restore(response, preserve_proofs)
+                }
+                CacheEntry::NameServers {
+                    zone,
+                    addresses,
+                    expires,
+                } => handle.restore_name_servers(zone, addresses, expires),
+                _ => false,
+            };
+
+            if is_current {
+                restored += 1;
+            }
+        })?;
+
+        Ok(restored)
+    }
+
+    fn record_cache(&self) -> &DnsLru {
+        self.handle().record_cache()
+    }
+
+    fn handle(&self) -> &RecursorDnsHandle<P> {
+        match &self.mode {
+            RecursorMode::NonValidating { handle } => handle,
+            #[cfg(feature = "__dnssec")]
+            RecursorMode::Validating { recursor, .. } => recursor,
+        }
+    }
+
     /// Perform a recursive resolution
     ///
     /// [RFC 1034](https://datatracker.ietf.org/doc/html/rfc1034#section-5.3.3), Domain Concepts and Facilities, November 1987
@@ -395,9 +588,9 @@ impl Recursor {
 
             #[cfg(feature = "__dnssec")]
             RecursorMode::Validating {
-                handle,
-                record_cache,
+                handle, recursor, ..
             } => {
+                let record_cache = recursor.record_cache();
                 if let Some(Ok(lookup)) = record_cache.get(&query, request_time) {
                     let none_indeterminate = lookup
                         .records()
@@ -503,20 +696,26 @@ impl Default for RecursorBuilder {
             avoid_local_udp_ports: HashSet::new(),
             ttl_config: TtlConfig::default(),
             case_randomization: false,
+            #[cfg(feature = "__dnssec")]
+            negative_trust_anchors: Vec::new(),
+            #[cfg(feature = "dnstap")]
+            dnstap: None,
         }
     }
 }
 
-enum RecursorMode {
+enum RecursorMode<P: RuntimeProvider> {
     NonValidating {
-        handle: RecursorDnsHandle,
+        handle: RecursorDnsHandle<P>,
     },
 
     #[cfg(feature = "__dnssec")]
     Validating {
-        handle: DnssecDnsHandle<RecursorDnsHandle>,
-        // this is a handle to the record cache in `RecursorDnsHandle`; not a whole separate cache
-        record_cache: DnsLru,
+        handle: Box<DnssecDnsHandle<RecursorDnsHandle<P>>>,
+        // this is a handle to the `RecursorDnsHandle` in `handle`, sharing its caches
+        recursor: RecursorDnsHandle<P>,
+        // shared with `handle`
+        negative_trust_anchors: NegativeTrustAnchors,
     },
 }
 
@@ -536,12 +735,13 @@ mod for_dnssec {
     use crate::proto::{
         ProtoError,
         op::{Message, OpCode},
+        runtime::RuntimeProvider,
         xfer::DnsHandle,
         xfer::DnsResponse,
     };
     use crate::recursor_dns_handle::RecursorDnsHandle;
 
-    impl DnsHandle for RecursorDnsHandle {
+    impl<P: RuntimeProvider> DnsHandle for RecursorDnsHandle<P> {
         type Response = BoxStream<'static, Result<DnsResponse, ProtoError>>;
 
         fn send<R: Into<hickory_proto::xfer::DnsRequest> + Unpin + Send + 'static>(
@@ -651,4 +851,44 @@ mod tests {
 
         Ok(())
     }
+
+    #[cfg(feature = "__dnssec")]
+    #[test]
+    fn negative_trust_anchors() -> Result<(), Error> {
+        use std::time::Duration;
+
+        use crate::{DnssecPolicy, proto::dnssec::NegativeTrustAnchor};
+
+        subscribe();
+
+        let example_com = Name::from_ascii("example.com.")?;
+        let example_net = Name::from_ascii("example.net.")?;
+        let recursor = Recursor::builder()
+            .dnssec_policy(DnssecPolicy::ValidateWithStaticKey { trust_anchor: None })
+            .negative_trust_anchors([NegativeTrustAnchor::new(example_com.clone(), None)])
+            .build(NameServerConfigGroup::cloudflare())?;
+
+        recursor.add_negative_trust_anchor(example_net.clone(), Some(Duration::from_secs(60)));
+        let anchors = recursor.negative_trust_anchors();
+        assert_eq!(anchors.len(), 2);
+        assert_eq!(
+            anchors[0],
+            NegativeTrustAnchor::new(example_com.clone(), None)
+        );
+        assert_eq!(anchors[1].name, example_net);
+        assert!(anchors[1].lifetime.unwrap() <= Duration::from_secs(60));
+
+        assert!(recursor.remove_negative_trust_anchor(&example_com));
+        assert!(!recursor.remove_negative_trust_anchor(&example_com));
+        assert_eq!(recursor.negative_trust_anchors().len(), 1);
+
+        // a non-validating recursor has nothing to skip
+        let recursor = Recursor::builder()
+            .negative_trust_anchors([NegativeTrustAnchor::new(example_com, None)])
+            .build(NameServerConfigGroup::cloudflare())?;
+        recursor.add_negative_trust_anchor(example_net, None);
+        assert!(recursor.negative_trust_anchors().is_empty());
+
+        Ok(())
+    }
 }
@@ -1,11 +1,12 @@
 use std::{
     collections::{HashMap, HashSet},
+    io::{self, Write},
     net::IpAddr,
     sync::{
         Arc,
         atomic::{AtomicU8, Ordering},
     },
-    time::Instant,
+    time::{Instant, SystemTime},
 };
 
 use async_recursion::async_recursion;
@@ -16,6 +17,8 @@ use parking_lot::Mutex;
 use prefix_trie::PrefixSet;
 use tracing::{debug, info, trace, warn};
 
+#[cfg(feature = "dnstap")]
+use crate::proto::dnstap::Dnstap;
 use crate::{
     Error, ErrorKind,
     proto::{
@@ -27,23 +30,25 @@ use crate::{
             Record, RecordType,
             rdata::{A, AAAA, NS},
         },
-        runtime::TokioRuntimeProvider,
+        runtime::RuntimeProvider,
         xfer::DnsResponse,
     },
     recursor_pool::RecursorPool,
     resolver::{
         Name,
+        cache_file::CacheFileWriter,
         config::{NameServerConfigGroup, ResolverOpts},
         dns_lru::{DnsLru, TtlConfig},
         lookup::Lookup,
-        name_server::{GenericNameServerPool, TokioConnectionProvider},
+        name_server::{GenericConnector, GenericNameServerPool},
     },
 };
 
 #[derive(Clone)]
-pub(crate) struct RecursorDnsHandle {
-    roots: RecursorPool<TokioRuntimeProvider>,
-    name_server_cache: Arc<Mutex<LruCache<Name, RecursorPool<TokioRuntimeProvider>>>>,
+pub(crate) struct RecursorDnsHandle<P: RuntimeProvider> {
+    conn_provider: GenericConnector<P>,
+    roots: RecursorPool<P>,
+    name_server_cache: Arc<Mutex<LruCache<Name, RecursorPool<P>>>>,
     record_cache: DnsLru,
     recursion_limit: Option<u8>,
     ns_recursion_limit: Option<u8>,
@@ -54,9 +59,11 @@ pub(crate) struct RecursorDnsHandle {
     allow_server_v6: PrefixSet<Ipv6Net>,
     avoid_local_udp_ports: Arc<HashSet<u16>>,
     case_randomization: bool,
+    #[cfg(feature = "dnstap")]
+    dnstap: Option<Dnstap>,
 }
 
-impl RecursorDnsHandle {
+impl<P: RuntimeProvider> RecursorDnsHandle<P> {
     #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
         roots: impl Into<NameServerConfigGroup>,
@@ -70,6 +77,8 @@ impl RecursorDnsHandle {
         avoid_local_udp_ports: Arc<HashSet<u16>>,
         ttl_config: TtlConfig,
         case_randomization: bool,
+        #[cfg(feature = "dnstap")] dnstap: Option<Dnstap>,
+        conn_provider: GenericConnector<P>,
     ) -> Self {
         // configure the hickory-resolver
         let roots: NameServerConfigGroup = roots.into();
@@ -77,9 +86,13 @@ impl RecursorDnsHandle {
         assert!(!roots.is_empty(), "roots must not be empty");
 
         debug!("Using cache sizes {}/{}", ns_cache_size, record_cache_size);
-        let opts = recursor_opts(avoid_local_udp_ports.clone(), case_randomization);
-        let roots =
-            GenericNameServerPool::from_config(roots, opts, TokioConnectionProvider::default());
+        #[allow(unused_mut)]
+        let mut opts = recursor_opts(avoid_local_udp_ports.clone(), case_randomization);
+        #[cfg(feature = "dnstap")]
+        {
+            opts.dnstap = dnstap.clone();
+        }
+        let roots = GenericNameServerPool::from_config(roots, opts, conn_provider.clone());
         let roots = RecursorPool::from(Name::root(), roots);
         let name_server_cache = Arc::new(Mutex::new(LruCache::new(ns_cache_size)));
         let record_cache = DnsLru::new(record_cache_size, ttl_config);
@@ -115,6 +128,7 @@ impl RecursorDnsHandle {
         }
 
         Self {
+            conn_provider,
             roots,
             name_server_cache,
             record_cache,
@@ -127,6 +141,8 @@ impl RecursorDnsHandle {
             allow_server_v6,
             avoid_local_udp_ports,
             case_randomization,
+            #[cfg(feature = "dnstap")]
+            dnstap,
         }
     }
 
@@ -380,7 +396,7 @@ impl RecursorDnsHandle {
     async fn lookup(
         &self,
         query: Query,
-        ns: RecursorPool<TokioRuntimeProvider>,
+        ns: RecursorPool<P>,
         now: Instant,
         expect_dnssec_in_cached_response: bool,
     ) -> Result<(Lookup, Option<DnsResponse>), Error> {
@@ -427,7 +443,7 @@ impl RecursorDnsHandle {
         zone: Name,
         request_time: Instant,
         mut depth: u8,
-    ) -> Result<(u8, RecursorPool<TokioRuntimeProvider>), Error> {
+    ) -> Result<(u8, RecursorPool<P>), Error> {
         // TODO: need to check TTLs here.
         if let Some(ns) = self.name_server_cache.lock().get_mut(&zone) {
             debug!("returning cached pool for {zone}");
@@ -555,7 +571,7 @@ impl RecursorDnsHandle {
         let ns = GenericNameServerPool::from_config(
             config_group,
             self.recursor_opts(),
-            TokioConnectionProvider::default(),
+            self.conn_provider.clone(),
         );
         let ns = RecursorPool::from(zone.clone(), ns);
 
@@ -610,7 +626,7 @@ impl RecursorDnsHandle {
         nameservers: Arc<[ForwardNSData]>,
         request_time: Instant,
         mut depth: u8,
-    ) -> Result<(u8, RecursorPool<TokioRuntimeProvider>), Error> {
+    ) -> Result<(u8, RecursorPool<P>), Error> {
         let query_name = query.name().clone();
 
         depth += 1;
@@ -706,7 +722,7 @@ impl RecursorDnsHandle {
         let ns = GenericNameServerPool::from_config(
             config_group,
             self.recursor_opts(),
-            TokioConnectionProvider::default(),
+            self.conn_provider.clone(),
         );
         let ns = RecursorPool::from(query_name.clone(), ns);
 
@@ -731,17 +747,101 @@ impl RecursorDnsHandle {
         }
     }
 
-    #[cfg(feature = "__dnssec")]
     pub(crate) fn record_cache(&self) -> &DnsLru {
         &self.record_cache
     }
 
+    /// Removes all cached name server pools
+    pub(crate) fn clear_name_servers(&self) {
+        self.name_server_cache.lock().clear();
+    }
+
+    /// Removes the cached name server pools of `zone` and the zones below it
+    pub(crate) fn clear_name_servers_subdomains(&self, zone: &Name) {
+        let mut name_server_cache = self.name_server_cache.lock();
+        let covered = name_server_cache
+            .iter()
+            .map(|(name, _)| name)
+            .filter(|name| zone.zone_of(name))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        for name in covered {
+            name_server_cache.remove(&name);
+        }
+    }
+
+    /// Writes the cached name server pools to `file`, returns the number of written pools
+    ///
+    /// A pool expires with the cached NS records of its zone, pools without them are skipped.
+    pub(crate) fn save_name_servers<W: Write>(
+        &self,
+        file: &mut CacheFileWriter<W>,
+    ) -> io::Result<usize> {
+        // least recently used first, so that restoring them keeps the order
+        let pools = self
+            .name_server_cache
+            .lock()
+            .iter()
+            .map(|(zone, pool)| (zone.clone(), pool.addresses()))
+            .collect::<Vec<_>>();
+
+        let now = Instant::now();
+        let system_now = SystemTime::now();
+        let mut saved = 0;
+        for (zone, addresses) in pools {
+            let query = Query::query(zone.clone(), RecordType::NS);
+            let Some(Ok(lookup)) = self.record_cache.get(&query, now) else {
+                continue;
+            };
+
+            let expires = system_now + lookup.valid_until().saturating_duration_since(now);
+            if file.write_name_servers(&zone, &addresses, expires)? {
+                saved += 1;
+            }
+        }
+
+        Ok(saved)
+    }
+
+    /// Inserts a name server pool read from a cache file, returns `false` if it has expired
+    pub(crate) fn restore_name_servers(
+        &self,
+        zone: Name,
+        addresses: Vec<IpAddr>,
+        expires: SystemTime,
+    ) -> bool {
+        if expires <= SystemTime::now() {
+            return false;
+        }
+
+        let mut config_group = NameServerConfigGroup::new();
+        config_group.append_ips(
+            addresses
+                .into_iter()
+                .filter(|ip| !self.matches_nameserver_filter(*ip)),
+            true,
+        );
+        if config_group.is_empty() {
+            return false;
+        }
+
+        let ns = GenericNameServerPool::from_config(
+            config_group,
+            self.recursor_opts(),
+            self.conn_provider.clone(),
+        );
+        let ns = RecursorPool::from(zone.clone(), ns);
+        self.name_server_cache.lock().insert(zone, ns);
+        true
+    }
+
     async fn append_ips_from_lookup<'a, I: Iterator<Item = &'a NS>>(
         &self,
         zone: &Name,
         depth: u8,
         request_time: Instant,
-        nameserver_pool: RecursorPool<TokioRuntimeProvider>,
+        nameserver_pool: RecursorPool<P>,
         nameservers: I,
         config: &mut NameServerConfigGroup,
     ) -> Result<u8, Error> {
@@ -803,7 +903,14 @@ impl RecursorDnsHandle {
     }
 
     fn recursor_opts(&self) -> ResolverOpts {
-        recursor_opts(self.avoid_local_udp_ports.clone(), self.case_randomization)
+        #[allow(unused_mut)]
+        let mut options =
+            recursor_opts(self.avoid_local_udp_ports.clone(), self.case_randomization);
+        #[cfg(feature = "dnstap")]
+        {
+            options.dnstap = self.dnstap.clone();
+        }
+        options
     }
 }
 
@@ -820,6 +927,10 @@ fn recursor_opts(
     options.num_concurrent_reqs = 1;
     options.avoid_local_udp_ports = avoid_local_udp_ports;
     options.case_randomization = case_randomization;
+    #[cfg(feature = "metrics")]
+    {
+        options.per_upstream_metrics = false;
+    }
 
     options
 }
@@ -829,6 +940,8 @@ fn recursor_opts(
 fn test_nameserver_filter() {
     use std::net::Ipv4Addr;
 
+    use crate::resolver::name_server::TokioConnectionProvider;
+
     let allow_server = vec![IpNet::new(IpAddr::from([192, 168, 0, 1]), 32).unwrap()];
     let deny_server = vec![
         IpNet::new(IpAddr::from(Ipv4Addr::LOCALHOST), 8).unwrap(),
@@ -848,6 +961,9 @@ fn test_nameserver_filter() {
         Arc::new(HashSet::new()),
         TtlConfig::default(),
         false,
+        #[cfg(feature = "dnstap")]
+        None,
+        TokioConnectionProvider::default(),
     );
 
     for addr in [
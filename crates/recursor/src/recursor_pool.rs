@@ -7,29 +7,31 @@
 
 use std::{
     collections::HashMap,
+    net::IpAddr,
     pin::Pin,
     sync::Arc,
     task::{Context, Poll},
 };
 
-use futures_util::{Future, FutureExt, StreamExt, future::Shared};
+use futures_util::{
+    Future, FutureExt, StreamExt,
+    future::{Shared, WeakShared},
+};
 use hickory_proto::{
     DnsHandle,
     op::Query,
-    runtime::{RuntimeProvider, TokioRuntimeProvider},
+    runtime::RuntimeProvider,
     xfer::{DnsRequestOptions, DnsResponse},
 };
 use hickory_resolver::{Name, ResolveError, ResolveErrorKind, name_server::GenericNameServerPool};
-use parking_lot::Mutex;
+use parking_lot::{Mutex, MutexGuard};
 use tracing::info;
 
-#[allow(clippy::type_complexity)]
+type LookupFuture =
+    Pin<Box<dyn Future<Output = Option<Result<DnsResponse, ResolveError>>> + Send + 'static>>;
+
 #[derive(Clone)]
-pub(crate) struct SharedLookup(
-    Shared<
-        Pin<Box<dyn Future<Output = Option<Result<DnsResponse, ResolveError>>> + Send + 'static>>,
-    >,
-);
+pub(crate) struct SharedLookup(Shared<LookupFuture>);
 
 impl Future for SharedLookup {
     type Output = Result<DnsResponse, ResolveError>;
@@ -46,12 +48,15 @@ impl Future for SharedLookup {
 pub(crate) struct RecursorPool<P: RuntimeProvider + Send + 'static> {
     zone: Name,
     ns: GenericNameServerPool<P>,
-    active_requests: Arc<Mutex<HashMap<Query, SharedLookup>>>,
+    active_requests: ActiveRequests,
 }
 
-impl RecursorPool<TokioRuntimeProvider> {
-    pub(crate) fn from(zone: Name, ns: GenericNameServerPool<TokioRuntimeProvider>) -> Self {
-        let active_requests = Arc::new(Mutex::new(HashMap::default()));
+impl<P> RecursorPool<P>
+where
+    P: RuntimeProvider + Send + 'static,
+{
+    pub(crate) fn from(zone: Name, ns: GenericNameServerPool<P>) -> Self {
+        let active_requests = ActiveRequests::default();
 
         Self {
             zone,
@@ -59,16 +64,24 @@ impl RecursorPool<TokioRuntimeProvider> {
             active_requests,
         }
     }
-}
 
-impl<P> RecursorPool<P>
-where
-    P: RuntimeProvider + Send + 'static,
-{
     pub(crate) fn zone(&self) -> &Name {
         &self.zone
     }
 
+    /// Returns the distinct addresses of the name servers
+    pub(crate) fn addresses(&self) -> Vec<IpAddr> {
+        let mut addresses = Vec::new();
+        for config in self.ns.name_server_configs() {
+            let ip = config.socket_addr.ip();
+            if !addresses.contains(&ip) {
+                addresses.push(ip);
+            }
+        }
+
+        addresses
+    }
+
     pub(crate) async fn lookup(
         &self,
         query: Query,
@@ -80,40 +93,88 @@ where
         let case_randomization = self.ns.options().case_randomization;
 
         // block concurrent requests
-        let lookup = self
-            .active_requests
-            .lock()
-            .entry(query.clone())
-            .or_insert_with(move || {
-                info!("querying {} for {}", self.zone, query_cpy);
-
-                let mut options = DnsRequestOptions::default();
-                options.use_edns = security_aware;
-                options.edns_set_dnssec_ok = security_aware;
-                options.case_randomization = case_randomization;
-
-                // Set RD=0 in queries made by the recursive resolver. See the last figure in
-                // section 2.2 of RFC 1035, for example. Failure to do so may allow for loops
-                // between recursive resolvers following referrals to each other.
-                options.recursion_desired = false;
-
-                // convert the lookup into a shared future
-                let lookup = ns
-                    .lookup(query_cpy, options)
-                    .into_future()
-                    .map(|(next, _)| next.map(|r| r.map_err(ResolveError::from)))
-                    .boxed()
-                    .shared();
-
-                SharedLookup(lookup)
-            })
-            .clone();
-
-        let result = lookup.await;
-
-        // remove the concurrent request marker
-        self.active_requests.lock().remove(&query);
-
-        result
+        let lookup = {
+            let mut active_requests = self.active_requests.lock();
+            match active_requests.get(&query).and_then(WeakShared::upgrade) {
+                Some(lookup) => lookup,
+                None => {
+                    info!("querying {} for {}", self.zone, query_cpy);
+
+                    let mut options = DnsRequestOptions::default();
+                    options.use_edns = security_aware;
+                    options.edns_set_dnssec_ok = security_aware;
+                    options.case_randomization = case_randomization;
+
+                    // Set RD=0 in queries made by the recursive resolver. See the last figure in
+                    // section 2.2 of RFC 1035, for example. Failure to do so may allow for loops
+                    // between recursive resolvers following referrals to each other.
+                    options.recursion_desired = false;
+
+                    // removes the request once it completes, or when all waiting lookups are dropped
+                    let active_request = ActiveRequest {
+                        active_requests: self.active_requests.clone(),
+                        query: query.clone(),
+                    };
+
+                    // convert the lookup into a shared future
+                    let lookup: LookupFuture = ns
+                        .lookup(query_cpy, options)
+                        .into_future()
+                        .map(move |(next, _)| {
+                            active_request.complete();
+                            next.map(|r| r.map_err(ResolveError::from))
+                        })
+                        .boxed();
+                    let lookup = lookup.shared();
+
+                    if let Some(weak) = lookup.downgrade() {
+                        active_requests.insert(query, weak);
+                    }
+                    lookup
+                }
+            }
+        };
+
+        SharedLookup(lookup).await
+    }
+}
+
+/// The requests sent to the name servers of a pool, which identical requests wait for
+#[derive(Clone, Default)]
+struct ActiveRequests(Arc<Mutex<HashMap<Query, WeakShared<LookupFuture>>>>);
+
+impl ActiveRequests {
+    fn lock(&self) -> MutexGuard<'_, HashMap<Query, WeakShared<LookupFuture>>> {
+        self.0.lock()
+    }
+}
+
+/// Removes the query from the active requests when the request is done or cancelled
+struct ActiveRequest {
+    active_requests: ActiveRequests,
+    query: Query,
+}
+
+impl ActiveRequest {
+    fn complete(self) {
+        // while the request is polled nothing else can replace its entry
+        self.active_requests.lock().remove(&self.query);
+    }
+}
+
+impl Drop for ActiveRequest {
+    fn drop(&mut self) {
+        // a new request may have replaced the entry of the cancelled one already
+        let mut active_requests = self.active_requests.lock();
+        let current = active_requests
+            .get(&self.query)
+            .and_then(WeakShared::upgrade);
+        if current.is_none() {
+            active_requests.remove(&self.query);
+        }
+
+        // dropping the upgraded request must not happen with the lock held
+        drop(active_requests);
+        drop(current);
     }
 }
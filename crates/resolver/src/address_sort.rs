@@ -0,0 +1,433 @@
+// Copyright 2015-2025 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Destination address selection, [RFC 6724](https://www.rfc-editor.org/rfc/rfc6724)
+//!
+//! Addresses are ordered by how well they match the source address the local machine would use
+//! to reach them, which is taken from the routing table of the operating system.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+#[cfg(feature = "tokio")]
+use std::panic;
+use std::time::{Duration, Instant};
+
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+
+/// The default policy table of RFC 6724, section 2.1, as (prefix, prefix length, precedence, label)
+///
+/// The entries are ordered by prefix length, so that the first match is the longest one.
+const POLICY_TABLE: [(u128, u32, u8, u8); 9] = [
+    // ::1/128
+    (1, 128, 50, 0),
+    // ::ffff:0:0/96, IPv4-mapped addresses
+    (0xffff_0000_0000, 96, 35, 4),
+    // ::/96, IPv4-compatible addresses
+    (0, 96, 1, 3),
+    // 2001::/32, Teredo
+    (0x2001 << 112, 32, 5, 5),
+    // 2002::/16, 6to4
+    (0x2002 << 112, 16, 30, 2),
+    // 3ffe::/16, 6bone
+    (0x3ffe << 112, 16, 1, 12),
+    // fec0::/10, site-local
+    (0xfec0 << 112, 10, 1, 11),
+    // fc00::/7, unique local
+    (0xfc00 << 112, 7, 3, 13),
+    // ::/0
+    (0, 0, 40, 1),
+];
+
+/// How long the source address used for a destination is cached, so that changes of the routes
+/// are picked up
+const SOURCE_CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// Maximum number of destinations with a cached source address
+const SOURCE_CACHE_SIZE: usize = 1024;
+
+/// The source addresses of recently sorted destinations, with the time they were looked up
+static SOURCE_CACHE: Lazy<Mutex<SourceCache>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+type SourceCache = HashMap<IpAddr, (Option<IpAddr>, Instant)>;
+
+const SCOPE_LINK_LOCAL: u8 = 0x2;
+const SCOPE_SITE_LOCAL: u8 = 0x5;
+const SCOPE_GLOBAL: u8 = 0xe;
+
+/// Sorts the addresses by the destination address selection of RFC 6724
+///
+/// Addresses the local machine has no route to are moved to the end, the order of addresses
+/// which are equally preferred is kept.
+///
+/// The source address for each destination is looked up by binding and connecting a UDP socket,
+/// these system calls block. The results are cached for a minute. The resolver sorts the results
+/// of its lookups on the blocking thread pool of the tokio runtime when a lookup is needed.
+pub fn sort_addresses(addresses: &mut [IpAddr]) {
+    sort_by_address(addresses, |address| *address);
+}
+
+/// Sorts the items by the destination address selection of RFC 6724 of their addresses
+pub(crate) fn sort_by_address<T: Clone>(items: &mut [T], address: impl Fn(&T) -> IpAddr) {
+    if items.len() < 2 {
+        return;
+    }
+
+    let destinations = items
+        .iter()
+        .map(|item| {
+            let address = address(item);
+            Destination::new(address, cached_source_address(address))
+        })
+        .collect::<Vec<_>>();
+
+    let mut order = (0..items.len()).collect::<Vec<_>>();
+    order.sort_by(|a, b| destinations[*a].cmp_preference(&destinations[*b]));
+
+    let sorted = order.iter().map(|i| items[*i].clone()).collect::<Vec<_>>();
+    items.clone_from_slice(&sorted);
+}
+
+/// Runs `sort`, which sorts `addresses`, without blocking the async runtime
+///
+/// Unless the source addresses of all `addresses` are cached, `sort` runs on the blocking thread
+/// pool of the current tokio runtime, if there is one.
+pub(crate) async fn sort_without_blocking<T: Send + 'static>(
+    addresses: Vec<IpAddr>,
+    sort: impl FnOnce() -> T + Send + 'static,
+) -> T {
+    #[cfg(feature = "tokio")]
+    if addresses.len() >= 2 && !all_cached(&addresses) {
+        if let Ok(handle) = tokio::runtime::Handle::try_current() {
+            return match handle.spawn_blocking(sort).await {
+                Ok(sorted) => sorted,
+                Err(e) => panic::resume_unwind(e.into_panic()),
+            };
+        }
+    }
+
+    #[cfg(not(feature = "tokio"))]
+    let _ = addresses;
+    sort()
+}
+
+/// Returns true if the source addresses of all `destinations` are cached
+#[cfg(feature = "tokio")]
+fn all_cached(destinations: &[IpAddr]) -> bool {
+    let now = Instant::now();
+    let cache = SOURCE_CACHE.lock();
+    destinations.iter().all(|destination| {
+        cache.get(destination).is_some_and(|(_, looked_up)| {
+            now.saturating_duration_since(*looked_up) < SOURCE_CACHE_TTL
+        })
+    })
+}
+
+/// Returns the source address used for packets to `destination` from the cache, or looks it up
+fn cached_source_address(destination: IpAddr) -> Option<IpAddr> {
+    let now = Instant::now();
+    if let Some((source, looked_up)) = SOURCE_CACHE.lock().get(&destination) {
+        if now.saturating_duration_since(*looked_up) < SOURCE_CACHE_TTL {
+            return *source;
+        }
+    }
+
+    // the lock isn't held while the socket is connected
+    let source = source_address(destination);
+
+    let mut cache = SOURCE_CACHE.lock();
+    if cache.len() >= SOURCE_CACHE_SIZE {
+        cache.retain(|_, (_, looked_up)| {
+            now.saturating_duration_since(*looked_up) < SOURCE_CACHE_TTL
+        });
+        if cache.len() >= SOURCE_CACHE_SIZE {
+            cache.clear();
+        }
+    }
+    cache.insert(destination, (source, now));
+
+    source
+}
+
+/// Returns the source address used for packets to `destination`, if there is a route to it
+///
+/// Connecting a UDP socket only selects the route, no packets are sent.
+fn source_address(destination: IpAddr) -> Option<IpAddr> {
+    let unspecified = match destination {
+        IpAddr::V4(_) => IpAddr::from(Ipv4Addr::UNSPECIFIED),
+        IpAddr::V6(_) => IpAddr::from(Ipv6Addr::UNSPECIFIED),
+    };
+
+    let socket = UdpSocket::bind(SocketAddr::new(unspecified, 0)).ok()?;
+    socket.connect(SocketAddr::new(destination, 9)).ok()?;
+    socket.local_addr().ok().map(|addr| addr.ip())
+}
+
+/// A destination address with the properties compared by RFC 6724, section 6
+#[derive(Debug)]
+struct Destination {
+    address: Ipv6Addr,
+    scope: u8,
+    precedence: u8,
+    label: u8,
+    source: Option<Source>,
+}
+
+#[derive(Debug)]
+struct Source {
+    address: Ipv6Addr,
+    scope: u8,
+    label: u8,
+}
+
+impl Destination {
+    fn new(destination: IpAddr, source: Option<IpAddr>) -> Self {
+        let (precedence, label) = policy(&destination);
+        Self {
+            address: to_ipv6(destination),
+            scope: scope(&destination),
+            precedence,
+            label,
+            source: source.map(|source| Source {
+                address: to_ipv6(source),
+                scope: scope(&source),
+                label: policy(&source).1,
+            }),
+        }
+    }
+
+    /// Returns `Ordering::Less` if `self` is preferred over `other`
+    fn cmp_preference(&self, other: &Self) -> Ordering {
+        let (a_source, b_source) = match (&self.source, &other.source) {
+            (Some(a), Some(b)) => (a, b),
+            // Rule 1: Avoid unusable destinations
+            (a, b) => return b.is_some().cmp(&a.is_some()),
+        };
+
+        // Rule 2: Prefer matching scope
+        (b_source.scope == other.scope)
+            .cmp(&(a_source.scope == self.scope))
+            // Rule 5: Prefer matching label
+            .then_with(|| (b_source.label == other.label).cmp(&(a_source.label == self.label)))
+            // Rule 6: Prefer higher precedence
+            .then_with(|| other.precedence.cmp(&self.precedence))
+            // Rule 8: Prefer smaller scope
+            .then_with(|| self.scope.cmp(&other.scope))
+            // Rule 9: Use longest matching prefix, for IPv6 addresses only
+            .then_with(|| {
+                if self.address.to_ipv4_mapped().is_some()
+                    || other.address.to_ipv4_mapped().is_some()
+                {
+                    return Ordering::Equal;
+                }
+
+                common_prefix_len(&other.address, &b_source.address)
+                    .cmp(&common_prefix_len(&self.address, &a_source.address))
+            })
+        // Rules 3, 4 and 7 need information about the addresses of the interfaces which isn't
+        // available, Rule 10 is the stable sort.
+    }
+}
+
+fn to_ipv6(address: IpAddr) -> Ipv6Addr {
+    match address {
+        IpAddr::V4(address) => address.to_ipv6_mapped(),
+        IpAddr::V6(address) => address,
+    }
+}
+
+/// Returns the precedence and label of the address from the policy table
+fn policy(address: &IpAddr) -> (u8, u8) {
+    let bits = u128::from(to_ipv6(*address));
+    POLICY_TABLE
+        .iter()
+        .find(|(prefix, len, _, _)| match len {
+            0 => true,
+            len => (bits ^ prefix) >> (128 - len) == 0,
+        })
+        .map_or((40, 1), |(_, _, precedence, label)| (*precedence, *label))
+}
+
+/// Returns the scope of the address, RFC 6724 section 3.1 and 3.2
+fn scope(address: &IpAddr) -> u8 {
+    match address {
+        IpAddr::V4(address) if address.is_loopback() || address.is_link_local() => SCOPE_LINK_LOCAL,
+        IpAddr::V4(_) => SCOPE_GLOBAL,
+        IpAddr::V6(address) if address.is_multicast() => (address.segments()[0] & 0xf) as u8,
+        IpAddr::V6(address)
+            if address.is_loopback() || address.segments()[0] & 0xffc0 == 0xfe80 =>
+        {
+            SCOPE_LINK_LOCAL
+        }
+        IpAddr::V6(address) if address.segments()[0] & 0xffc0 == 0xfec0 => SCOPE_SITE_LOCAL,
+        IpAddr::V6(_) => SCOPE_GLOBAL,
+    }
+}
+
+/// Returns the length of the common prefix, at most the length of the interface identifier
+fn common_prefix_len(a: &Ipv6Addr, b: &Ipv6Addr) -> u32 {
+    (u128::from(*a) ^ u128::from(*b)).leading_zeros().min(64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sort(addresses: &[(&str, Option<&str>)]) -> Vec<IpAddr> {
+        let mut destinations = addresses
+            .iter()
+            .map(|(destination, source)| {
+                Destination::new(
+                    destination.parse().unwrap(),
+                    source.map(|source| source.parse().unwrap()),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        destinations.sort_by(Destination::cmp_preference);
+        destinations
+            .into_iter()
+            .map(|destination| match destination.address.to_ipv4_mapped() {
+                Some(address) => IpAddr::from(address),
+                None => IpAddr::from(destination.address),
+            })
+            .collect()
+    }
+
+    fn addresses(addresses: &[&str]) -> Vec<IpAddr> {
+        addresses
+            .iter()
+            .map(|address| address.parse().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_policy() {
+        assert_eq!(policy(&"::1".parse().unwrap()), (50, 0));
+        assert_eq!(policy(&"192.0.2.1".parse().unwrap()), (35, 4));
+        assert_eq!(policy(&"2001:db8::1".parse().unwrap()), (40, 1));
+        assert_eq!(policy(&"2001::1".parse().unwrap()), (5, 5));
+        assert_eq!(policy(&"2002:c000:0201::1".parse().unwrap()), (30, 2));
+        assert_eq!(policy(&"fd00::1".parse().unwrap()), (3, 13));
+    }
+
+    // the examples of RFC 6724, section 10.2
+    #[test]
+    fn test_sort_rfc_examples() {
+        // prefer matching scope
+        assert_eq!(
+            sort(&[
+                ("198.51.100.121", Some("169.254.13.78")),
+                ("2001:db8:1::1", Some("2001:db8:1::2")),
+            ]),
+            addresses(&["2001:db8:1::1", "198.51.100.121"])
+        );
+        assert_eq!(
+            sort(&[
+                ("2001:db8:1::1", Some("fe80::1")),
+                ("198.51.100.121", Some("198.51.100.117")),
+            ]),
+            addresses(&["198.51.100.121", "2001:db8:1::1"])
+        );
+
+        // prefer higher precedence
+        assert_eq!(
+            sort(&[
+                ("10.1.2.3", Some("10.1.2.4")),
+                ("2001:db8:1::1", Some("2001:db8:1::2")),
+            ]),
+            addresses(&["2001:db8:1::1", "10.1.2.3"])
+        );
+        assert_eq!(
+            sort(&[
+                ("2002:c633:6401::1", Some("2002:c633:6401::2")),
+                ("2001:db8:1::1", Some("2001:db8:1::2")),
+            ]),
+            addresses(&["2001:db8:1::1", "2002:c633:6401::1"])
+        );
+
+        // prefer smaller scope
+        assert_eq!(
+            sort(&[
+                ("2001:db8:1::1", Some("2001:db8:1::2")),
+                ("fe80::1", Some("fe80::2")),
+            ]),
+            addresses(&["fe80::1", "2001:db8:1::1"])
+        );
+
+        // prefer matching label
+        assert_eq!(
+            sort(&[
+                ("2001:db8:1::1", Some("2002:c633:6401::2")),
+                ("2002:c633:6401::1", Some("2002:c633:6401::2")),
+            ]),
+            addresses(&["2002:c633:6401::1", "2001:db8:1::1"])
+        );
+
+        // use longest matching prefix
+        assert_eq!(
+            sort(&[
+                ("2001:db8:3ffe::1", Some("2001:db8:3::2")),
+                ("2001:db8:1::1", Some("2001:db8:1::2")),
+            ]),
+            addresses(&["2001:db8:1::1", "2001:db8:3ffe::1"])
+        );
+    }
+
+    #[test]
+    fn test_sort_unusable_last() {
+        assert_eq!(
+            sort(&[
+                ("2001:db8:1::1", None),
+                ("198.51.100.121", Some("198.51.100.117")),
+            ]),
+            addresses(&["198.51.100.121", "2001:db8:1::1"])
+        );
+    }
+
+    #[test]
+    fn test_sort_loopback() {
+        let mut loopback = addresses(&["127.0.0.1", "::1"]);
+        sort_addresses(&mut loopback);
+
+        // ::1 is preferred, unless IPv6 is not available
+        if source_address("::1".parse().unwrap()).is_some() {
+            assert_eq!(loopback, addresses(&["::1", "127.0.0.1"]));
+        } else {
+            assert_eq!(loopback, addresses(&["127.0.0.1", "::1"]));
+        }
+    }
+
+    #[test]
+    fn test_source_cache() {
+        let loopback = IpAddr::from(Ipv4Addr::LOCALHOST);
+        assert_eq!(cached_source_address(loopback), Some(loopback));
+
+        let cached = SOURCE_CACHE
+            .lock()
+            .get(&loopback)
+            .map(|(source, _)| *source);
+        assert_eq!(cached, Some(Some(loopback)));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_sort_without_blocking() {
+        let unique_local = addresses(&["fd00::1", "fd00::2"]);
+        assert!(!all_cached(&unique_local));
+
+        let mut to_sort = unique_local.clone();
+        let sorted = sort_without_blocking(unique_local.clone(), move || {
+            sort_addresses(&mut to_sort);
+            to_sort
+        })
+        .await;
+        assert_eq!(sorted.len(), 2);
+        assert!(all_cached(&unique_local));
+    }
+}
@@ -0,0 +1,474 @@
+// Copyright 2015-2025 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Saving caches to files, to restore them after a restart
+//!
+//! A cache file starts with a header, followed by entries which each start with their kind and
+//! length. Names, queries and records are stored in wire format, expiry times are stored as
+//! seconds since the Unix epoch, so entries which expired while the cache was not in use are
+//! discarded when the file is read.
+
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "__dnssec")]
+use crate::proto::dnssec::Proof;
+use crate::proto::op::{Query, ResponseCode};
+use crate::proto::rr::{Name, Record};
+use crate::proto::serialize::binary::{BinDecodable, BinDecoder, BinEncodable, BinEncoder};
+use crate::proto::{ProtoError, ProtoErrorKind};
+
+/// The first bytes of a cache file, the last one is the version of the format
+const HEADER: &[u8; 8] = b"HDNSCCH\x01";
+
+/// The maximum length of an entry, longer entries are not written and are rejected when read
+const MAX_ENTRY_LEN: usize = 1 << 20;
+
+const RESPONSE: u8 = 1;
+const NAME_SERVERS: u8 = 2;
+
+/// Writes the file at `path` with `write`
+///
+/// The entries are written to a temporary file first, which replaces the file at `path` once all
+/// of them were written, so an existing cache file is never left incomplete.
+pub fn write_file<T>(
+    path: &Path,
+    write: impl FnOnce(&mut CacheFileWriter<BufWriter<File>>) -> io::Result<T>,
+) -> io::Result<T> {
+    let mut temp_name = path.file_name().unwrap_or_default().to_owned();
+    temp_name.push(".tmp");
+    let temp_path = path.with_file_name(temp_name);
+
+    let result =
+        CacheFileWriter::new(BufWriter::new(File::create(&temp_path)?)).and_then(|mut file| {
+            let result = write(&mut file)?;
+            file.finish()?;
+            Ok(result)
+        });
+
+    match result {
+        Ok(result) => {
+            fs::rename(&temp_path, path)?;
+            Ok(result)
+        }
+        Err(e) => {
+            let _ = fs::remove_file(&temp_path);
+            Err(e)
+        }
+    }
+}
+
+/// Reads the entries of the file at `path`, passing them to `read`
+pub fn read_file(path: &Path, mut read: impl FnMut(CacheEntry)) -> io::Result<()> {
+    let mut file = CacheFileReader::new(BufReader::new(File::open(path)?))?;
+    while let Some(entry) = file.read_entry()? {
+        read(entry);
+    }
+
+    Ok(())
+}
+
+/// Writes cache entries
+pub struct CacheFileWriter<W: Write> {
+    writer: W,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> CacheFileWriter<W> {
+    /// Writes the header of the file to `writer`
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writer.write_all(HEADER)?;
+        Ok(Self {
+            writer,
+            buffer: Vec::new(),
+        })
+    }
+
+    /// Writes a cached response to `query`, which expires at `expires`
+    ///
+    /// `original_ttl` is the TTL the response was cached with. An error is a negative response,
+    /// returns `false` if the error is not one, or if the response is too large to be saved.
+    pub(crate) fn write_response(
+        &mut self,
+        query: &Query,
+        expires: SystemTime,
+        original_ttl: Duration,
+        response: Result<&[Record], &ProtoError>,
+    ) -> io::Result<bool> {
+        let negative = match response {
+            Ok(records) => Ok(records),
+            Err(error) => match error.kind() {
+                ProtoErrorKind::NoRecordsFound {
+                    soa,
+                    response_code,
+                    trusted,
+                    ..
+                } => Err((soa, *response_code, *trusted)),
+                _ => return Ok(false),
+            },
+        };
+
+        self.write_entry(RESPONSE, |encoder| {
+            query.emit(encoder)?;
+            encoder.emit_vec(&unix_seconds(expires).to_be_bytes())?;
+            encoder.emit_u32(u32::try_from(original_ttl.as_secs()).unwrap_or(u32::MAX))?;
+
+            match negative {
+                Ok(records) => {
+                    encoder.emit(1)?;
+                    encoder.emit_u16(u16::try_from(records.len()).unwrap_or(u16::MAX))?;
+                    for record in records.iter().take(usize::from(u16::MAX)) {
+                        record.emit(encoder)?;
+                        #[cfg(feature = "__dnssec")]
+                        encoder.emit(record.proof() as u8)?;
+                        #[cfg(not(feature = "__dnssec"))]
+                        encoder.emit(0)?;
+                    }
+                }
+                Err((soa, response_code, trusted)) => {
+                    encoder.emit(0)?;
+                    encoder.emit_u16(response_code.into())?;
+                    encoder.emit(u8::from(trusted))?;
+                    match soa {
+                        Some(soa) => {
+                            encoder.emit(1)?;
+                            soa.clone().into_record_of_rdata().emit(encoder)?;
+                        }
+                        None => encoder.emit(0)?,
+                    }
+                }
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Writes the addresses of the name servers of `zone`, which expire at `expires`
+    ///
+    /// Returns `false` if the entry is too large to be saved.
+    pub fn write_name_servers(
+        &mut self,
+        zone: &Name,
+        addresses: &[IpAddr],
+        expires: SystemTime,
+    ) -> io::Result<bool> {
+        self.write_entry(NAME_SERVERS, |encoder| {
+            zone.emit(encoder)?;
+            encoder.emit_vec(&unix_seconds(expires).to_be_bytes())?;
+            encoder.emit_u16(u16::try_from(addresses.len()).unwrap_or(u16::MAX))?;
+            for address in addresses.iter().take(usize::from(u16::MAX)) {
+                match address {
+                    IpAddr::V4(address) => {
+                        encoder.emit(4)?;
+                        encoder.emit_vec(&address.octets())?;
+                    }
+                    IpAddr::V6(address) => {
+                        encoder.emit(6)?;
+                        encoder.emit_vec(&address.octets())?;
+                    }
+                }
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Flushes the written entries, returning the writer
+    pub fn finish(mut self) -> io::Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn write_entry(
+        &mut self,
+        kind: u8,
+        emit: impl FnOnce(&mut BinEncoder<'_>) -> Result<(), ProtoError>,
+    ) -> io::Result<bool> {
+        self.buffer.clear();
+        emit(&mut BinEncoder::new(&mut self.buffer)).map_err(invalid_data)?;
+        if self.buffer.len() > MAX_ENTRY_LEN {
+            return Ok(false);
+        }
+
+        let len = self.buffer.len() as u32;
+        self.writer.write_all(&[kind])?;
+        self.writer.write_all(&len.to_be_bytes())?;
+        self.writer.write_all(&self.buffer)?;
+        Ok(true)
+    }
+}
+
+/// Reads cache entries
+pub struct CacheFileReader<R: Read> {
+    reader: R,
+    buffer: Vec<u8>,
+}
+
+impl<R: Read> CacheFileReader<R> {
+    /// Reads the header of the file from `reader`
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut header = [0; HEADER.len()];
+        reader.read_exact(&mut header)?;
+        if &header != HEADER {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a cache file, or an unsupported version",
+            ));
+        }
+
+        Ok(Self {
+            reader,
+            buffer: Vec::new(),
+        })
+    }
+
+    /// Reads the next entry, `None` at the end of the file
+    ///
+    /// Entries of unknown kinds are skipped.
+    pub fn read_entry(&mut self) -> io::Result<Option<CacheEntry>> {
+        loop {
+            let mut kind = [0; 1];
+            match self.reader.read_exact(&mut kind) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+                Err(e) => return Err(e),
+            }
+
+            let mut len = [0; 4];
+            self.reader.read_exact(&mut len)?;
+            let len = u32::from_be_bytes(len) as usize;
+            if len > MAX_ENTRY_LEN {
+                return Err(invalid_data(format!(
+                    "cache entry of {len} bytes is too large"
+                )));
+            }
+
+            self.buffer.resize(len, 0);
+            self.reader.read_exact(&mut self.buffer)?;
+
+            let mut decoder = BinDecoder::new(&self.buffer);
+            let entry = match kind[0] {
+                RESPONSE => {
+                    CacheEntry::Response(read_response(&mut decoder).map_err(invalid_data)?)
+                }
+                NAME_SERVERS => read_name_servers(&mut decoder).map_err(invalid_data)?,
+                _ => continue,
+            };
+
+            return Ok(Some(entry));
+        }
+    }
+}
+
+/// An entry of a cache file
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum CacheEntry {
+    /// A cached response, see [`DnsLru::restore`](crate::dns_lru::DnsLru::restore)
+    Response(SavedResponse),
+    /// The addresses of the name servers of a zone
+    NameServers {
+        /// The zone
+        zone: Name,
+        /// The addresses of the name servers
+        addresses: Vec<IpAddr>,
+        /// When the name servers expire
+        expires: SystemTime,
+    },
+}
+
+/// A cached response read from a cache file
+#[derive(Debug)]
+pub struct SavedResponse {
+    pub(crate) query: Query,
+    pub(crate) expires: SystemTime,
+    pub(crate) original_ttl: Duration,
+    /// The records and their DNSSEC proof, or the negative response
+    pub(crate) response: Result<Vec<(Record, u8)>, ProtoError>,
+}
+
+impl SavedResponse {
+    /// The query the response is cached for
+    pub fn query(&self) -> &Query {
+        &self.query
+    }
+
+    /// When the response expires
+    pub fn expires(&self) -> SystemTime {
+        self.expires
+    }
+
+    /// Returns the records, with their DNSSEC proof if `preserve_proofs` is set
+    pub(crate) fn records(records: Vec<(Record, u8)>, preserve_proofs: bool) -> Vec<Record> {
+        records
+            .into_iter()
+            .map(|(record, proof)| {
+                #[allow(unused_mut)]
+                let mut record = record;
+                #[cfg(feature = "__dnssec")]
+                if preserve_proofs {
+                    record.set_proof(match proof {
+                        3 => Proof::Secure,
+                        2 => Proof::Insecure,
+                        1 => Proof::Bogus,
+                        _ => Proof::Indeterminate,
+                    });
+                }
+                #[cfg(not(feature = "__dnssec"))]
+                let _ = (proof, preserve_proofs);
+                record
+            })
+            .collect()
+    }
+}
+
+fn read_response(decoder: &mut BinDecoder<'_>) -> Result<SavedResponse, ProtoError> {
+    let query = Query::read(decoder)?;
+    let expires = read_expires(decoder)?;
+    let original_ttl = Duration::from_secs(u64::from(decoder.read_u32()?.unverified()));
+
+    let response = match decoder.read_u8()?.unverified() {
+        0 => {
+            let response_code: ResponseCode = decoder.read_u16()?.unverified().into();
+            let trusted = decoder.read_u8()?.unverified() != 0;
+            let soa = match decoder.read_u8()?.unverified() {
+                0 => None,
+                _ => Record::try_from(Record::read(decoder)?)
+                    .map(Box::new)
+                    .map_err(|_| ProtoError::from("negative response with a non-SOA record"))
+                    .map(Some)?,
+            };
+
+            Err(ProtoError::nx_error(
+                Box::new(query.clone()),
+                soa,
+                None,
+                Some(u32::try_from(original_ttl.as_secs()).unwrap_or(u32::MAX)),
+                response_code,
+                trusted,
+                None,
+            ))
+        }
+        _ => {
+            let count = decoder.read_u16()?.unverified();
+            let mut records = Vec::with_capacity(usize::from(count));
+            for _ in 0..count {
+                let record = Record::read(decoder)?;
+                let proof = decoder.read_u8()?.unverified();
+                records.push((record, proof));
+            }
+            Ok(records)
+        }
+    };
+
+    Ok(SavedResponse {
+        query,
+        expires,
+        original_ttl,
+        response,
+    })
+}
+
+fn read_name_servers(decoder: &mut BinDecoder<'_>) -> Result<CacheEntry, ProtoError> {
+    let zone = Name::read(decoder)?;
+    let expires = read_expires(decoder)?;
+
+    let count = decoder.read_u16()?.unverified();
+    let mut addresses = Vec::with_capacity(usize::from(count));
+    for _ in 0..count {
+        let address = match decoder.read_u8()?.unverified() {
+            4 => IpAddr::from(Ipv4Addr::from(read_array::<4>(decoder)?)),
+            6 => IpAddr::from(Ipv6Addr::from(read_array::<16>(decoder)?)),
+            family => return Err(format!("unknown address family {family}").into()),
+        };
+        addresses.push(address);
+    }
+
+    Ok(CacheEntry::NameServers {
+        zone,
+        addresses,
+        expires,
+    })
+}
+
+fn read_expires(decoder: &mut BinDecoder<'_>) -> Result<SystemTime, ProtoError> {
+    let seconds = u64::from_be_bytes(read_array(decoder)?);
+    Ok(UNIX_EPOCH + Duration::from_secs(seconds))
+}
+
+fn read_array<const N: usize>(decoder: &mut BinDecoder<'_>) -> Result<[u8; N], ProtoError> {
+    let mut array = [0; N];
+    array.copy_from_slice(decoder.read_slice(N)?.unverified());
+    Ok(array)
+}
+
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+fn invalid_data(error: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn test_name_servers_round_trip() {
+        let zone = Name::from_str("example.com.").unwrap();
+        let addresses = [
+            IpAddr::from(Ipv4Addr::new(192, 0, 2, 1)),
+            IpAddr::from(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1)),
+        ];
+        let expires = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+
+        let mut file = CacheFileWriter::new(Vec::new()).unwrap();
+        file.write_name_servers(&zone, &addresses, expires).unwrap();
+        let mut bytes = file.finish().unwrap();
+
+        // entries of unknown kinds are skipped
+        bytes.extend_from_slice(&[0xff, 0, 0, 0, 2, 1, 2]);
+
+        let mut reader = CacheFileReader::new(bytes.as_slice()).unwrap();
+        match reader.read_entry().unwrap() {
+            Some(CacheEntry::NameServers {
+                zone: read_zone,
+                addresses: read_addresses,
+                expires: read_expires,
+            }) => {
+                assert_eq!(read_zone, zone);
+                assert_eq!(read_addresses, addresses);
+                assert_eq!(read_expires, expires);
+            }
+            entry => panic!("unexpected entry: {entry:?}"),
+        }
+        assert!(reader.read_entry().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_entry_too_large() {
+        let mut bytes = HEADER.to_vec();
+        bytes.extend_from_slice(&[NAME_SERVERS, 0xff, 0xff, 0xff, 0xff]);
+
+        let mut reader = CacheFileReader::new(bytes.as_slice()).unwrap();
+        let error = reader.read_entry().err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_invalid_header() {
+        let error = CacheFileReader::new(&b"HDNSCCH\x02"[..]).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -7,13 +7,25 @@
 
 //! Caching related functionality for the Resolver.
 
-use std::{borrow::Cow, future::Future, pin::Pin, sync::Arc, time::Instant};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    time::Instant,
+};
 
-use futures_util::future::TryFutureExt;
+use futures_util::future::{BoxFuture, FutureExt, Shared, TryFutureExt, WeakShared};
 use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use tracing::debug;
 
 use crate::{
-    dns_lru::{self, DnsLru, TtlConfig},
+    config::{Dns64Config, PrefetchConfig},
+    dns_lru::{self, CacheUsage, DnsLru, TtlConfig},
+    dns64,
     error::ResolveError,
     lookup::Lookup,
     proto::{
@@ -27,6 +39,7 @@ use crate::{
             rdata::{A, AAAA, CNAME, PTR, SOA},
             resource::RecordRef,
         },
+        runtime::Spawn,
         xfer::{DnsHandle, DnsRequestOptions, DnsResponse, FirstAnswer},
         {ForwardNSData, ProtoError, ProtoErrorKind},
     },
@@ -68,6 +81,9 @@ where
     lru: DnsLru,
     client: C,
     preserve_intermediates: bool,
+    dns64: Option<Arc<Dns64Config>>,
+    prefetcher: Option<Arc<Prefetcher>>,
+    in_flight: InFlightLookups,
 }
 
 impl<C> CachingClient<C>
@@ -88,14 +104,60 @@ where
             lru,
             client,
             preserve_intermediates,
+            dns64: None,
+            prefetcher: None,
+            in_flight: InFlightLookups::default(),
         }
     }
 
+    /// Synthesize AAAA records from A records, see [`Dns64Config`]
+    pub(crate) fn set_dns64(&mut self, config: Dns64Config) {
+        self.dns64 = Some(Arc::new(config));
+    }
+
+    /// Refresh popular cache entries before they expire, see [`PrefetchConfig`]
+    ///
+    /// The refreshes are spawned on `spawner`.
+    pub(crate) fn set_prefetch(
+        &mut self,
+        config: PrefetchConfig,
+        spawner: impl Spawn + Clone + Send + Sync + 'static,
+    ) {
+        self.prefetcher = Some(Arc::new(Prefetcher {
+            config,
+            in_flight: Mutex::default(),
+            spawn: Box::new(move |future| spawner.clone().spawn_bg(future)),
+        }));
+    }
+
+    /// The client used for lookups that are not answered from the cache
+    #[cfg(feature = "__dnssec")]
+    pub(crate) fn client(&self) -> &C {
+        &self.client
+    }
+
     /// Perform a lookup against this caching client, looking first in the cache for a result
     pub fn lookup(
         &self,
         query: Query,
         options: DnsRequestOptions,
+    ) -> Pin<Box<dyn Future<Output = Result<Lookup, ResolveError>> + Send>> {
+        match &self.dns64 {
+            Some(config)
+                if query.query_class() == DNSClass::IN
+                    && matches!(query.query_type(), RecordType::AAAA | RecordType::PTR) =>
+            {
+                Box::pin(dns64::lookup(self.clone(), config.clone(), query, options))
+            }
+            _ => self.cached_lookup(query, options),
+        }
+    }
+
+    /// Perform a lookup like [`Self::lookup`], without DNS64 synthesis
+    pub(crate) fn cached_lookup(
+        &self,
+        query: Query,
+        options: DnsRequestOptions,
     ) -> Pin<Box<dyn Future<Output = Result<Lookup, ResolveError>> + Send>> {
         Box::pin(
             Self::inner_lookup(
@@ -112,7 +174,7 @@ where
     async fn inner_lookup(
         query: Query,
         options: DnsRequestOptions,
-        mut client: Self,
+        client: Self,
         preserved_records: Vec<(Record, u32)>,
         depth: DepthTracker,
     ) -> Result<Lookup, ProtoError> {
@@ -173,13 +235,65 @@ where
             }
         }
 
-        let is_dnssec = client.client.is_verifying_dnssec();
-
         // first transition any polling that is needed (mutable refs...)
-        if let Some(cached_lookup) = client.lookup_from_cache(&query) {
+        if let Some(cached_lookup) = client.lookup_from_cache(&query, options) {
             return cached_lookup;
         };
 
+        // lookups following a CNAME chain are not coalesced, as their result depends on the
+        //  preserved records, and a CNAME loop would wait for itself
+        if depth.query_depth > 0 {
+            return Self::fetch(query, options, client, preserved_records, depth).await;
+        }
+
+        Self::coalesced_fetch(query, options, client).await
+    }
+
+    /// Fetches the query like [`Self::fetch`], unless the same query is already being fetched,
+    ///  in which case the result of that lookup is awaited instead
+    ///
+    /// The lookup continues as long as any of the waiting lookups is polled, if all of them are
+    ///  dropped it's cancelled.
+    async fn coalesced_fetch(
+        query: Query,
+        options: DnsRequestOptions,
+        client: Self,
+    ) -> Result<Lookup, ProtoError> {
+        let key = (query, options);
+        let lookup = client.in_flight.join_or_insert(key, |key| {
+            let guard = InFlightLookup {
+                in_flight: client.in_flight.clone(),
+                key: key.clone(),
+            };
+            let fetch = Self::fetch(
+                key.0.clone(),
+                key.1,
+                client.clone(),
+                vec![],
+                DepthTracker::default(),
+            );
+
+            async move {
+                let result = fetch.await;
+                guard.complete();
+                result
+            }
+            .boxed()
+        });
+
+        lookup.await
+    }
+
+    /// Sends the query to the name servers and caches the response
+    async fn fetch(
+        query: Query,
+        options: DnsRequestOptions,
+        mut client: Self,
+        preserved_records: Vec<(Record, u32)>,
+        depth: DepthTracker,
+    ) -> Result<Lookup, ProtoError> {
+        let is_dnssec = client.client.is_verifying_dnssec();
+
         let response_message = client
             .client
             .lookup(query.clone(), options)
@@ -254,8 +368,54 @@ where
     }
 
     /// Check if this query is already cached
-    fn lookup_from_cache(&self, query: &Query) -> Option<Result<Lookup, ProtoError>> {
-        self.lru.get(query, Instant::now())
+    fn lookup_from_cache(
+        &self,
+        query: &Query,
+        options: DnsRequestOptions,
+    ) -> Option<Result<Lookup, ProtoError>> {
+        let (lookup, usage) = self.lru.get_with_usage(query, Instant::now())?;
+        self.prefetch(query, options, usage);
+        Some(lookup)
+    }
+
+    /// Refreshes the cached response to `query` in the background, if it's due for a prefetch
+    fn prefetch(&self, query: &Query, options: DnsRequestOptions, usage: CacheUsage) {
+        let Some(prefetcher) = &self.prefetcher else {
+            return;
+        };
+        if !prefetcher.is_due(&usage) {
+            return;
+        }
+
+        {
+            let mut in_flight = prefetcher.in_flight.lock();
+            if in_flight.len() >= prefetcher.config.max_concurrent
+                || !in_flight.insert(query.clone())
+            {
+                return;
+            }
+        }
+
+        debug!(%query, "prefetching cached response");
+        let in_flight = InFlightPrefetch {
+            prefetcher: prefetcher.clone(),
+            query: query.clone(),
+        };
+        let refresh = Self::fetch(
+            query.clone(),
+            options,
+            self.clone(),
+            vec![],
+            DepthTracker::default(),
+        );
+
+        (prefetcher.spawn)(Box::pin(async move {
+            if let Err(e) = refresh.await {
+                debug!(query = %in_flight.query, "prefetch failed: {e}");
+            }
+            drop(in_flight);
+            Ok(())
+        }));
     }
 
     /// See https://tools.ietf.org/html/rfc2308
@@ -496,6 +656,129 @@ where
     pub fn clear_cache(&self) {
         self.lru.clear();
     }
+
+    /// Removes the cached entries for `name` and the names below it
+    pub fn clear_cache_subdomains(&self, name: &Name) {
+        self.lru.remove_subdomains(name);
+    }
+
+    /// Returns the records of all cached positive responses
+    pub fn cached_records(&self) -> Vec<Record> {
+        self.lru.records()
+    }
+
+    pub(crate) fn lru(&self) -> &DnsLru {
+        &self.lru
+    }
+}
+
+/// Refreshes popular cache entries in the background before they expire
+struct Prefetcher {
+    config: PrefetchConfig,
+    /// The queries which are being refreshed
+    in_flight: Mutex<HashSet<Query>>,
+    spawn: Box<dyn Fn(PrefetchFuture) + Send + Sync>,
+}
+
+type PrefetchFuture = BoxFuture<'static, Result<(), ProtoError>>;
+
+impl Prefetcher {
+    /// Returns true if the cached value was used often enough and expires soon
+    fn is_due(&self, usage: &CacheUsage) -> bool {
+        usage.hits >= self.config.min_hits
+            && !usage.original_ttl.is_zero()
+            && usage.remaining_ttl.as_millis() * 100
+                <= usage.original_ttl.as_millis() * u128::from(self.config.remaining_ttl_percent)
+    }
+}
+
+impl fmt::Debug for Prefetcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Prefetcher")
+            .field("config", &self.config)
+            .field("in_flight", &self.in_flight)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Removes the query from the in-flight prefetches when the refresh is done or cancelled
+struct InFlightPrefetch {
+    prefetcher: Arc<Prefetcher>,
+    query: Query,
+}
+
+impl Drop for InFlightPrefetch {
+    fn drop(&mut self) {
+        self.prefetcher.in_flight.lock().remove(&self.query);
+    }
+}
+
+/// The lookups sent to the name servers, which identical lookups can wait for
+#[derive(Clone, Default)]
+struct InFlightLookups {
+    lookups: Arc<Mutex<HashMap<LookupKey, WeakShared<LookupFuture>>>>,
+}
+
+type LookupKey = (Query, DnsRequestOptions);
+
+type LookupFuture = BoxFuture<'static, Result<Lookup, ProtoError>>;
+
+impl InFlightLookups {
+    /// Returns the in-flight lookup for `key`, or inserts the lookup returned by `lookup`
+    fn join_or_insert(
+        &self,
+        key: LookupKey,
+        lookup: impl FnOnce(&LookupKey) -> LookupFuture,
+    ) -> Shared<LookupFuture> {
+        let mut lookups = self.lookups.lock();
+        if let Some(lookup) = lookups.get(&key).and_then(WeakShared::upgrade) {
+            debug!(query = %key.0, "waiting for in-flight lookup");
+            return lookup;
+        }
+
+        let lookup = lookup(&key).shared();
+        if let Some(weak) = lookup.downgrade() {
+            lookups.insert(key, weak);
+        }
+        lookup
+    }
+}
+
+impl fmt::Debug for InFlightLookups {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InFlightLookups")
+            .field("len", &self.lookups.lock().len())
+            .finish()
+    }
+}
+
+/// Removes the query from the in-flight lookups when the lookup is done or cancelled
+struct InFlightLookup {
+    in_flight: InFlightLookups,
+    key: LookupKey,
+}
+
+impl InFlightLookup {
+    /// Removes the completed lookup, later lookups start from the cache again
+    fn complete(self) {
+        // while the lookup is polled nothing else can replace its entry
+        self.in_flight.lookups.lock().remove(&self.key);
+    }
+}
+
+impl Drop for InFlightLookup {
+    fn drop(&mut self) {
+        // a new lookup may have replaced the entry of the cancelled one already
+        let mut lookups = self.in_flight.lookups.lock();
+        let current = lookups.get(&self.key).and_then(WeakShared::upgrade);
+        if current.is_none() {
+            lookups.remove(&self.key);
+        }
+
+        // dropping the upgraded lookup must not happen with the lock held
+        drop(lookups);
+        drop(current);
+    }
 }
 
 enum Records {
@@ -513,12 +796,17 @@ enum Records {
 mod tests {
     use std::net::*;
     use std::str::FromStr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::task::{Context, Poll};
     use std::time::*;
 
     use crate::proto::op::{Message, Query};
     use crate::proto::rr::rdata::{NS, SRV};
     use crate::proto::rr::{Name, Record};
+    use crate::proto::xfer::DnsRequest;
     use futures_executor::block_on;
+    use futures_util::future;
+    use futures_util::stream::{self, Stream};
     use test_support::subscribe;
 
     use super::*;
@@ -1090,4 +1378,168 @@ mod tests {
             .is_ok()
         );
     }
+
+    /// Collects the spawned futures, to run them when the test wants to
+    #[derive(Clone, Default)]
+    struct Spawned(Arc<Mutex<Vec<PrefetchFuture>>>);
+
+    impl Spawn for Spawned {
+        fn spawn_bg<F>(&mut self, future: F)
+        where
+            F: Future<Output = Result<(), ProtoError>> + Send + 'static,
+        {
+            self.0.lock().push(Box::pin(future));
+        }
+    }
+
+    #[test]
+    fn test_prefetch() {
+        subscribe();
+        let cache = DnsLru::new(1, dns_lru::TtlConfig::default());
+        let query = Query::query(Name::root(), RecordType::A);
+        cache.insert(
+            query.clone(),
+            vec![(
+                Record::from_rdata(Name::root(), 100, RData::A(A::new(127, 0, 0, 2))),
+                100,
+            )],
+            Instant::now(),
+        );
+
+        let spawned = Spawned::default();
+        let mut client = CachingClient::with_cache(cache, mock(vec![v4_message()]), false);
+        client.set_prefetch(
+            PrefetchConfig {
+                remaining_ttl_percent: 100,
+                min_hits: 2,
+                max_concurrent: 1,
+            },
+            spawned.clone(),
+        );
+
+        let lookup_ips = || {
+            block_on(client.lookup(query.clone(), DnsRequestOptions::default()))
+                .unwrap()
+                .iter()
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+
+        // the first hit is not enough for a prefetch
+        assert_eq!(lookup_ips(), vec![RData::A(A::new(127, 0, 0, 2))]);
+        assert!(spawned.0.lock().is_empty());
+
+        // the cached response is returned while the prefetch is pending, and only one is started
+        assert_eq!(lookup_ips(), vec![RData::A(A::new(127, 0, 0, 2))]);
+        assert_eq!(lookup_ips(), vec![RData::A(A::new(127, 0, 0, 2))]);
+        assert_eq!(spawned.0.lock().len(), 1);
+
+        let refresh = spawned.0.lock().pop().unwrap();
+        block_on(refresh).unwrap();
+        assert!(
+            client
+                .prefetcher
+                .as_ref()
+                .unwrap()
+                .in_flight
+                .lock()
+                .is_empty()
+        );
+
+        assert_eq!(lookup_ips(), vec![RData::A(A::new(127, 0, 0, 1))]);
+    }
+
+    /// Answers with the response after yielding twice, counting the requests sent
+    #[derive(Clone)]
+    struct YieldingHandle {
+        sends: Arc<AtomicUsize>,
+        response: fn() -> Result<DnsResponse, ProtoError>,
+    }
+
+    impl YieldingHandle {
+        fn new(response: fn() -> Result<DnsResponse, ProtoError>) -> Self {
+            Self {
+                sends: Arc::default(),
+                response,
+            }
+        }
+    }
+
+    impl DnsHandle for YieldingHandle {
+        type Response = Pin<Box<dyn Stream<Item = Result<DnsResponse, ProtoError>> + Send>>;
+
+        fn send<R: Into<DnsRequest>>(&self, _: R) -> Self::Response {
+            self.sends.fetch_add(1, Ordering::SeqCst);
+            let response = self.response;
+            let mut yields = 2;
+            Box::pin(stream::once(async move {
+                future::poll_fn(|cx| {
+                    if yields == 0 {
+                        return Poll::Ready(());
+                    }
+                    yields -= 1;
+                    cx.waker().wake_by_ref();
+                    Poll::Pending
+                })
+                .await;
+                response()
+            }))
+        }
+    }
+
+    #[test]
+    fn test_coalesce_concurrent_lookups() {
+        subscribe();
+        let handle = YieldingHandle::new(v4_message);
+        let client = CachingClient::new(1, handle.clone(), false);
+        let query = Query::query(Name::root(), RecordType::A);
+
+        let (first, second) = block_on(future::join(
+            client.lookup(query.clone(), DnsRequestOptions::default()),
+            client.lookup(query.clone(), DnsRequestOptions::default()),
+        ));
+        assert_eq!(handle.sends.load(Ordering::SeqCst), 1);
+        assert_eq!(first.unwrap().records(), second.unwrap().records());
+        assert!(client.in_flight.lookups.lock().is_empty());
+
+        // errors are returned to all waiting lookups
+        let handle = YieldingHandle::new(error);
+        let client = CachingClient::new(1, handle.clone(), false);
+        let (first, second) = block_on(future::join(
+            client.lookup(query.clone(), DnsRequestOptions::default()),
+            client.lookup(query, DnsRequestOptions::default()),
+        ));
+        assert_eq!(handle.sends.load(Ordering::SeqCst), 1);
+        assert!(first.is_err());
+        assert!(second.is_err());
+        assert!(client.in_flight.lookups.lock().is_empty());
+    }
+
+    #[test]
+    fn test_cancel_coalesced_lookup() {
+        subscribe();
+        let handle = YieldingHandle::new(v4_message);
+        let client = CachingClient::new(1, handle.clone(), false);
+        let query = Query::query(Name::root(), RecordType::A);
+
+        let mut first = client.lookup(query.clone(), DnsRequestOptions::default());
+        let mut second = client.lookup(query.clone(), DnsRequestOptions::default());
+        let waker = futures_util::task::noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        assert!(first.as_mut().poll(&mut cx).is_pending());
+        assert!(second.as_mut().poll(&mut cx).is_pending());
+
+        // the lookup continues for the remaining waiting lookup
+        drop(first);
+        assert!(block_on(second).is_ok());
+        assert_eq!(handle.sends.load(Ordering::SeqCst), 1);
+
+        // the lookup is removed once no lookup waits for it anymore
+        client.clear_cache();
+        let mut third = client.lookup(query, DnsRequestOptions::default());
+        assert!(third.as_mut().poll(&mut cx).is_pending());
+        assert_eq!(client.in_flight.lookups.lock().len(), 1);
+        drop(third);
+        assert!(client.in_flight.lookups.lock().is_empty());
+    }
 }
@@ -13,17 +13,32 @@ use std::fmt;
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::ops::{Deref, DerefMut};
 use std::path::PathBuf;
+use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Duration;
 
+use ipnet::{Ipv4Net, Ipv6Net};
+#[cfg(feature = "serde")]
+use serde::de::{self, MapAccess, Visitor, value::MapAccessDeserializer};
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use url::Url;
+
+use crate::error::ResolveError;
 
+#[cfg(feature = "__dnssec")]
+use crate::proto::dnssec::NegativeTrustAnchor;
+#[cfg(feature = "dnstap")]
+use crate::proto::dnstap::Dnstap;
 use crate::proto::rr::Name;
 #[cfg(feature = "__tls")]
 use crate::proto::rustls::client_config;
+#[cfg(all(feature = "__tls", feature = "serde"))]
+use crate::proto::rustls::{default_provider, default_verifier};
 use crate::proto::xfer::Protocol;
 
+mod stamp;
+
 /// Configuration for the upstream nameservers to use for resolution
 #[derive(Clone, Debug)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -36,6 +51,9 @@ pub struct ResolverConfig {
     search: Vec<Name>,
     // nameservers to use for resolution.
     name_servers: NameServerConfigGroup,
+    // name servers for routing domains
+    #[cfg_attr(feature = "serde", serde(default))]
+    routes: Vec<DomainRoute>,
 }
 
 impl ResolverConfig {
@@ -46,6 +64,7 @@ impl ResolverConfig {
             domain: None,
             search: vec![],
             name_servers: NameServerConfigGroup::new(),
+            routes: Vec::new(),
         }
     }
 
@@ -65,6 +84,7 @@ impl ResolverConfig {
             domain: None,
             search: vec![],
             name_servers: NameServerConfigGroup::google(),
+            routes: Vec::new(),
         }
     }
 
@@ -86,6 +106,7 @@ impl ResolverConfig {
             domain: None,
             search: vec![],
             name_servers: NameServerConfigGroup::google_tls(),
+            routes: Vec::new(),
         }
     }
 
@@ -107,6 +128,7 @@ impl ResolverConfig {
             domain: None,
             search: vec![],
             name_servers: NameServerConfigGroup::google_https(),
+            routes: Vec::new(),
         }
     }
 
@@ -128,6 +150,7 @@ impl ResolverConfig {
             domain: None,
             search: vec![],
             name_servers: NameServerConfigGroup::google_h3(),
+            routes: Vec::new(),
         }
     }
 
@@ -142,6 +165,7 @@ impl ResolverConfig {
             domain: None,
             search: vec![],
             name_servers: NameServerConfigGroup::cloudflare(),
+            routes: Vec::new(),
         }
     }
 
@@ -157,6 +181,7 @@ impl ResolverConfig {
             domain: None,
             search: vec![],
             name_servers: NameServerConfigGroup::cloudflare_tls(),
+            routes: Vec::new(),
         }
     }
 
@@ -172,6 +197,7 @@ impl ResolverConfig {
             domain: None,
             search: vec![],
             name_servers: NameServerConfigGroup::cloudflare_https(),
+            routes: Vec::new(),
         }
     }
 
@@ -186,6 +212,7 @@ impl ResolverConfig {
             domain: None,
             search: vec![],
             name_servers: NameServerConfigGroup::quad9(),
+            routes: Vec::new(),
         }
     }
 
@@ -201,6 +228,7 @@ impl ResolverConfig {
             domain: None,
             search: vec![],
             name_servers: NameServerConfigGroup::quad9_tls(),
+            routes: Vec::new(),
         }
     }
 
@@ -216,6 +244,7 @@ impl ResolverConfig {
             domain: None,
             search: vec![],
             name_servers: NameServerConfigGroup::quad9_https(),
+            routes: Vec::new(),
         }
     }
 
@@ -235,6 +264,7 @@ impl ResolverConfig {
             domain,
             search,
             name_servers: name_servers.into(),
+            routes: Vec::new(),
         }
     }
 
@@ -273,6 +303,16 @@ impl ResolverConfig {
     pub fn name_servers(&self) -> &[NameServerConfig] {
         &self.name_servers
     }
+
+    /// Add name servers for routing domains, see [`DomainRoute`]
+    pub fn add_route(&mut self, route: DomainRoute) {
+        self.routes.push(route);
+    }
+
+    /// Returns the name servers for routing domains
+    pub fn routes(&self) -> &[DomainRoute] {
+        &self.routes
+    }
 }
 
 impl Default for ResolverConfig {
@@ -288,6 +328,52 @@ impl Default for ResolverConfig {
     }
 }
 
+/// Name servers which resolve the names in routing domains, like the routing domains of
+/// systemd-resolved
+///
+/// Names in the `domains` and their subdomains are resolved with the `name_servers`, instead of the
+/// name servers of the [`ResolverConfig`]. If the domains of several routes contain a name, the
+/// route with the longest domain is used.
+#[derive(Clone, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(deny_unknown_fields)
+)]
+pub struct DomainRoute {
+    /// The routing domains
+    pub domains: Vec<Name>,
+    /// The name servers for names in the routing domains
+    pub name_servers: NameServerConfigGroup,
+    /// Options for sending queries to the name servers, e.g. `timeout` or `attempts`, defaults to
+    /// the options of the resolver
+    ///
+    /// The options which don't concern the name servers, e.g. `ndots` or the cache options, are
+    /// always taken from the resolver. Responses of all routes share the cache of the resolver, so
+    /// `validate` must match the resolver's, the resolver's is used otherwise.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub options: Option<ResolverOpts>,
+    /// Never resolve names in the routing domains with other name servers
+    ///
+    /// Otherwise queries the name servers fail to answer, because they can't be reached or respond
+    /// with SERVFAIL, are resolved with the name servers of the resolver. Their answers, including
+    /// NXDOMAIN, are always final.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub exclusive: bool,
+}
+
+impl DomainRoute {
+    /// Routes the names in `domains` to `name_servers`, with the options of the resolver
+    pub fn new(domains: Vec<Name>, name_servers: impl Into<NameServerConfigGroup>) -> Self {
+        Self {
+            domains,
+            name_servers: name_servers.into(),
+            options: None,
+            exclusive: false,
+        }
+    }
+}
+
 /// Configuration for the NameServer
 #[derive(Clone, Debug)]
 #[cfg_attr(
@@ -307,6 +393,13 @@ pub struct NameServerConfig {
     /// The HTTP endpoint where the DNS NameServer provides service. Only
     /// relevant to DNS-over-HTTPS. Defaults to `/dns-query` if unspecified.
     pub http_endpoint: Option<String>,
+    /// SHA-256 digests of the `TBSCertificate` of certificates, one of which must be part of the
+    /// certificate chain of the server. Only relevant to encrypted protocols.
+    ///
+    /// The certificate is still verified with the [`ResolverOpts::tls_verifier`], see
+    /// [`PinnedServerVerifier`](crate::proto::rustls::PinnedServerVerifier).
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub tls_pins: Vec<[u8; 32]>,
     /// Whether to trust `NXDOMAIN` responses from upstream nameservers.
     ///
     /// When this is `true`, and an empty `NXDOMAIN` response or `NOERROR`
@@ -334,6 +427,7 @@ impl NameServerConfig {
             trust_negative_responses: true,
             tls_dns_name: None,
             http_endpoint: None,
+            tls_pins: Vec::new(),
             bind_addr: None,
         }
     }
@@ -351,6 +445,92 @@ impl fmt::Display for NameServerConfig {
     }
 }
 
+impl FromStr for NameServerConfig {
+    type Err = ResolveError;
+
+    /// Parses a name server URL or a DNS Stamp
+    ///
+    /// The URL schemes are `udp`, `tcp`, `tls`, `https`, `quic` and `h3`, for the protocols
+    /// enabled by the crate features, for example `tls://1.1.1.1:853#cloudflare-dns.com` or
+    /// `https://192.0.2.1/dns-query#dns.example`. The port defaults to the standard port of the
+    /// protocol. For the encrypted protocols, the fragment is the TLS name of the server and
+    /// defaults to the host, and for DNS-over-HTTPS and DNS-over-HTTP/3, the path is the
+    /// `http_endpoint`.
+    ///
+    /// The host must be an IP address, host names like `https://dns.example/dns-query` are not
+    /// supported, as they would have to be resolved first. Put the name in the fragment instead.
+    ///
+    /// [DNS Stamps](https://dnscrypt.info/stamps-specifications) start with `sdns://` and may be
+    /// for plain DNS, DNS-over-TLS, DNS-over-HTTPS and DNS-over-QUIC. Their certificate hashes
+    /// are enforced as [`NameServerConfig::tls_pins`], stamps without an address are rejected.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(stamp) = s.strip_prefix("sdns://") {
+            return stamp::parse(stamp);
+        }
+
+        let url = Url::parse(s).map_err(|e| format!("invalid name server URL {s}: {e}"))?;
+        let (protocol, default_port) = match url.scheme() {
+            "udp" => (Protocol::Udp, 53),
+            "tcp" => (Protocol::Tcp, 53),
+            #[cfg(feature = "__tls")]
+            "tls" => (Protocol::Tls, 853),
+            #[cfg(feature = "__https")]
+            "https" => (Protocol::Https, 443),
+            #[cfg(feature = "__quic")]
+            "quic" => (Protocol::Quic, 853),
+            #[cfg(feature = "__h3")]
+            "h3" => (Protocol::H3, 443),
+            scheme => return Err(format!("unsupported name server URL scheme: {scheme}").into()),
+        };
+
+        let host = url
+            .host_str()
+            .ok_or_else(|| format!("name server URL without host: {s}"))?;
+        let port = url.port().unwrap_or(default_port);
+        let socket_addr = parse_socket_addr(host, port)
+            .ok_or_else(|| format!("the host of a name server URL must be an IP address: {s}"))?;
+
+        let mut config = Self::new(socket_addr, protocol);
+        if protocol.is_encrypted() {
+            let tls_dns_name = url.fragment().unwrap_or(host.trim_matches(['[', ']']));
+            config.tls_dns_name = Some(tls_dns_name.to_owned());
+        } else if url.fragment().is_some() {
+            return Err(format!("TLS name in unencrypted name server URL: {s}").into());
+        }
+
+        let path = url.path();
+        if matches!(url.scheme(), "https" | "h3") {
+            if !path.is_empty() && path != "/" {
+                config.http_endpoint = Some(path.to_owned());
+            }
+        } else if !path.is_empty() && path != "/" {
+            return Err(format!("path in non-HTTP name server URL: {s}").into());
+        }
+
+        Ok(config)
+    }
+}
+
+/// Parses an IP address with an optional port, IPv6 addresses may be in brackets
+fn parse_socket_addr(addr: &str, default_port: u16) -> Option<SocketAddr> {
+    if let Ok(socket_addr) = SocketAddr::from_str(addr) {
+        return Some(socket_addr);
+    }
+
+    let ip = IpAddr::from_str(addr.trim_start_matches('[').trim_end_matches(']')).ok()?;
+    Some(SocketAddr::new(ip, default_port))
+}
+
+/// Splits an optional port off a host name
+fn split_port(host: &str) -> Option<(&str, Option<u16>)> {
+    match host.rsplit_once(':') {
+        // an IPv6 address without a port
+        Some((name, _)) if name.contains(':') && !name.ends_with(']') => Some((host, None)),
+        Some((name, port)) => Some((name, Some(port.parse().ok()?))),
+        None => Some((host, None)),
+    }
+}
+
 /// A set of name_servers to associate with a [`ResolverConfig`].
 #[derive(Clone, Debug)]
 pub struct NameServerConfigGroup {
@@ -373,7 +553,43 @@ impl<'de> Deserialize<'de> for NameServerConfigGroup {
     where
         D: Deserializer<'de>,
     {
-        Vec::deserialize(deserializer).map(|servers| Self { servers })
+        let servers = Vec::<NameServerEntry>::deserialize(deserializer)?;
+        Ok(Self {
+            servers: servers.into_iter().map(|entry| entry.0).collect(),
+        })
+    }
+}
+
+/// A name server in a [`NameServerConfigGroup`], either a table of its configuration, or a URL or
+/// DNS Stamp as parsed by [`NameServerConfig::from_str`]
+#[cfg(feature = "serde")]
+struct NameServerEntry(NameServerConfig);
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for NameServerEntry {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct EntryVisitor;
+
+        impl<'de> Visitor<'de> for EntryVisitor {
+            type Value = NameServerConfig;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                formatter.write_str("a name server configuration, URL or DNS stamp")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+                NameServerConfig::from_str(value).map_err(E::custom)
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+                NameServerConfig::deserialize(MapAccessDeserializer::new(map))
+            }
+        }
+
+        deserializer.deserialize_any(EntryVisitor).map(Self)
     }
 }
 
@@ -410,6 +626,7 @@ impl NameServerConfigGroup {
                 protocol: Protocol::Udp,
                 tls_dns_name: None,
                 http_endpoint: None,
+                tls_pins: Vec::new(),
                 trust_negative_responses,
                 bind_addr: None,
             };
@@ -418,6 +635,7 @@ impl NameServerConfigGroup {
                 protocol: Protocol::Tcp,
                 tls_dns_name: None,
                 http_endpoint: None,
+                tls_pins: Vec::new(),
                 trust_negative_responses,
                 bind_addr: None,
             };
@@ -447,6 +665,7 @@ impl NameServerConfigGroup {
                 protocol,
                 tls_dns_name: Some(tls_dns_name.clone()),
                 http_endpoint: None,
+                tls_pins: Vec::new(),
                 trust_negative_responses,
                 bind_addr: None,
             };
@@ -757,6 +976,181 @@ pub enum ResolveHosts {
     Auto,
 }
 
+/// DNS64 synthesis of AAAA records from A records, [RFC 6147](https://www.rfc-editor.org/rfc/rfc6147)
+///
+/// For names without AAAA records, AAAA records are synthesized by embedding the addresses of the
+/// A records in the NAT64 `prefix`, see [RFC 6052](https://www.rfc-editor.org/rfc/rfc6052).
+/// PTR queries for synthesized addresses are answered with the PTR records of the IPv4 addresses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(deny_unknown_fields)
+)]
+pub struct Dns64Config {
+    /// The NAT64 prefix, defaults to the well-known prefix `64:ff9b::/96`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub prefix: Nat64Prefix,
+    /// AAAA records with addresses in these networks are treated as absent, defaults to the
+    /// IPv4-mapped addresses `::ffff:0:0/96`
+    #[cfg_attr(feature = "serde", serde(default = "default_dns64_exclude"))]
+    pub exclude: Vec<Ipv6Net>,
+    /// A records with addresses in these networks are not used for synthesis
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub exclude_ipv4: Vec<Ipv4Net>,
+}
+
+impl Default for Dns64Config {
+    /// Uses the well-known prefix `64:ff9b::/96`
+    fn default() -> Self {
+        Self {
+            prefix: Nat64Prefix::default(),
+            exclude: default_dns64_exclude(),
+            exclude_ipv4: Vec::new(),
+        }
+    }
+}
+
+/// A NAT64 prefix, the length of which is 32, 40, 48, 56, 64 or 96 as required by
+/// [RFC 6052](https://www.rfc-editor.org/rfc/rfc6052#section-2.2)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(try_from = "Ipv6Net", into = "Ipv6Net")
+)]
+pub struct Nat64Prefix(Ipv6Net);
+
+impl Nat64Prefix {
+    /// The network of the prefix
+    pub fn net(&self) -> Ipv6Net {
+        self.0
+    }
+}
+
+impl Default for Nat64Prefix {
+    /// The well-known prefix `64:ff9b::/96`
+    fn default() -> Self {
+        Self(Ipv6Net::new(Ipv6Addr::new(0x64, 0xff9b, 0, 0, 0, 0, 0, 0), 96).expect("valid prefix"))
+    }
+}
+
+impl TryFrom<Ipv6Net> for Nat64Prefix {
+    type Error = String;
+
+    fn try_from(prefix: Ipv6Net) -> Result<Self, Self::Error> {
+        match prefix.prefix_len() {
+            32 | 40 | 48 | 56 | 64 | 96 => Ok(Self(prefix)),
+            len => Err(format!(
+                "invalid DNS64 prefix {prefix}, the length must be 32, 40, 48, 56, 64 or 96, not {len}"
+            )),
+        }
+    }
+}
+
+impl From<Nat64Prefix> for Ipv6Net {
+    fn from(prefix: Nat64Prefix) -> Self {
+        prefix.0
+    }
+}
+
+impl fmt::Display for Nat64Prefix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+fn default_dns64_exclude() -> Vec<Ipv6Net> {
+    vec![Ipv6Net::new(Ipv6Addr::new(0, 0, 0, 0, 0, 0xffff, 0, 0), 96).expect("valid prefix")]
+}
+
+/// Prefetching of popular cache entries before they expire
+///
+/// A lookup answered from the cache starts a refresh of the entry in the background when less
+/// than `remaining_ttl_percent` of its TTL is left and it was returned from the cache at least
+/// `min_hits` times. The current entry is returned while the refresh is running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct PrefetchConfig {
+    /// The percentage of the original TTL under which entries are refreshed, defaults to 10
+    pub remaining_ttl_percent: u8,
+    /// The number of cache hits needed before an entry is refreshed, defaults to 3
+    pub min_hits: u32,
+    /// The maximum number of refreshes running at the same time, defaults to 16
+    pub max_concurrent: usize,
+}
+
+impl Default for PrefetchConfig {
+    fn default() -> Self {
+        Self {
+            remaining_ttl_percent: 10,
+            min_hits: 3,
+            max_concurrent: 16,
+        }
+    }
+}
+
+/// Hedged queries, sent to further name servers when the first ones are slow to answer
+///
+/// A query is sent to the first name server of the pool, in the order of the
+/// `server_ordering_strategy`. When no answer arrives within the `rtt_percentile` of the round-trip
+/// times recently observed for that name server, or when it fails, the query is also sent to the
+/// next name server. The first answer received is used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct HedgingConfig {
+    /// The percentile of the recent round-trip times of a name server after which the query is
+    /// sent to the next name server, defaults to 95
+    pub rtt_percentile: u8,
+    /// The delay used for name servers without recent round-trip times, defaults to 100ms
+    pub initial_delay: Duration,
+}
+
+impl Default for HedgingConfig {
+    fn default() -> Self {
+        Self {
+            rtt_percentile: 95,
+            initial_delay: Duration::from_millis(100),
+        }
+    }
+}
+
+/// A circuit breaker, taking name servers out of rotation after repeated failures
+///
+/// After `failure_threshold` consecutive connection failures or timeouts, a name server is only
+/// used when all other name servers of the pool are out of rotation too. Every `probe_interval`
+/// a query is sent to it again, and a response puts it back into rotation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct CircuitBreakerConfig {
+    /// The number of consecutive failures after which a name server is taken out of rotation,
+    /// defaults to 5
+    pub failure_threshold: u32,
+    /// The interval between queries probing whether a name server recovered, defaults to 30s
+    pub probe_interval: Duration,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            probe_interval: Duration::from_secs(30),
+        }
+    }
+}
+
 /// Configuration for the Resolver
 #[derive(Debug, Clone)]
 #[cfg_attr(
@@ -788,9 +1182,23 @@ pub struct ResolverOpts {
     pub validate: bool,
     /// The ip_strategy for the Resolver to use when lookup Ipv4 or Ipv6 addresses
     pub ip_strategy: LookupIpStrategy,
+    /// Sort the addresses of IP lookups by the destination address selection of
+    /// [RFC 6724](https://www.rfc-editor.org/rfc/rfc6724), using the routes of the local machine.
+    ///
+    /// The route to each address is looked up with a blocking system call, which is cached for a
+    /// minute per address. This is false by default, the addresses are returned in the order of
+    /// the `ip_strategy`.
+    pub sort_addresses: bool,
+    /// Synthesize AAAA records from A records for names without AAAA records, for clients on
+    /// IPv6-only networks with NAT64, see [`Dns64Config`]
+    pub dns64: Option<Dns64Config>,
     /// Cache size is in number of records (some records can be large)
     #[cfg_attr(feature = "serde", serde(default = "default_cache_size"))]
     pub cache_size: usize,
+    /// Refresh popular cache entries in the background before they expire, see
+    /// [`PrefetchConfig`]. Disabled by default, and with connection providers which can't spawn
+    /// tasks, see [`ConnectionProvider::create_handle`](crate::name_server::ConnectionProvider::create_handle).
+    pub prefetch: Option<PrefetchConfig>,
     /// Check /etc/hosts file before dns requery (only works for unix like OS)
     pub use_hosts_file: ResolveHosts,
     /// Optional minimum TTL for positive responses.
@@ -830,6 +1238,12 @@ pub struct ResolverOpts {
     pub try_tcp_on_error: bool,
     /// The server ordering strategy that the resolver should use.
     pub server_ordering_strategy: ServerOrderingStrategy,
+    /// Send queries to the name servers one after another, each time the previous ones are slow to
+    /// answer, see [`HedgingConfig`]. This replaces `num_concurrent_reqs`, disabled by default.
+    pub hedging: Option<HedgingConfig>,
+    /// Take name servers out of rotation after repeated failures, see [`CircuitBreakerConfig`].
+    /// Disabled by default.
+    pub circuit_breaker: Option<CircuitBreakerConfig>,
     /// Request upstream recursive resolvers to not perform any recursion.
     ///
     /// This is true by default, disabling this is useful for requesting single records, but may prevent successful resolution.
@@ -855,6 +1269,17 @@ pub struct ResolverOpts {
     #[cfg(feature = "__tls")]
     #[cfg_attr(feature = "serde", serde(skip, default = "client_config"))]
     pub tls_config: rustls::ClientConfig,
+    /// The certificate verifier of `tls_config`, which is wrapped to also check the
+    /// [`NameServerConfig::tls_pins`] of name servers
+    ///
+    /// rustls doesn't expose the verifier of a `ClientConfig`, so this must be set along with
+    /// `tls_config`, for the default one use [`crate::proto::rustls::default_verifier`].
+    /// Connections to name servers with pins fail if this is `None`, which is the default, so
+    /// that pins never replace the verifier of a custom `tls_config`. When deserialized,
+    /// `tls_config` is always the default one, and this is set to its verifier.
+    #[cfg(feature = "__tls")]
+    #[cfg_attr(feature = "serde", serde(skip, default = "default_tls_verifier"))]
+    pub tls_verifier: Option<Arc<dyn rustls::client::danger::ServerCertVerifier>>,
     /// Enable case randomization.
     ///
     /// Randomize the case of letters in query names, and require that responses preserve the case
@@ -867,6 +1292,25 @@ pub struct ResolverOpts {
     ///
     /// If this is provided, `validate` will automatically be set to `true`, enabling DNSSEC validation.
     pub trust_anchor: Option<PathBuf>,
+    /// Negative trust anchors, see [RFC 7646](https://datatracker.ietf.org/doc/html/rfc7646).
+    ///
+    /// DNSSEC validation is skipped for these domains and everything below them, their records
+    /// are treated as `Insecure`. Each anchor can have a lifetime after which it's removed.
+    #[cfg(feature = "__dnssec")]
+    pub negative_trust_anchors: Vec<NegativeTrustAnchor>,
+    /// Log queries sent to name servers and their responses as dnstap `RESOLVER_QUERY` and
+    /// `RESOLVER_RESPONSE` messages.
+    #[cfg(feature = "dnstap")]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub dnstap: Option<Dnstap>,
+    /// Label the metrics of the exchanges with name servers by their address.
+    ///
+    /// This is true by default. Disable it if the name servers aren't known in advance, like the
+    /// authoritative servers queried by a recursor, the metrics of all name servers are then
+    /// aggregated under `upstream="all"`, without the SRTT gauge.
+    #[cfg(feature = "metrics")]
+    #[cfg_attr(feature = "serde", serde(default = "default_per_upstream_metrics"))]
+    pub per_upstream_metrics: bool,
 }
 
 impl Default for ResolverOpts {
@@ -882,7 +1326,10 @@ impl Default for ResolverOpts {
             edns0: false,
             validate: false,
             ip_strategy: LookupIpStrategy::default(),
+            sort_addresses: false,
+            dns64: None,
             cache_size: default_cache_size(),
+            prefetch: None,
             use_hosts_file: ResolveHosts::default(),
             positive_min_ttl: None,
             negative_min_ttl: None,
@@ -895,17 +1342,32 @@ impl Default for ResolverOpts {
 
             try_tcp_on_error: false,
             server_ordering_strategy: ServerOrderingStrategy::default(),
+            hedging: None,
+            circuit_breaker: None,
             recursion_desired: default_recursion_desired(),
             avoid_local_udp_ports: Arc::default(),
             os_port_selection: false,
             #[cfg(feature = "__tls")]
             tls_config: client_config(),
+            #[cfg(feature = "__tls")]
+            tls_verifier: None,
             case_randomization: false,
             trust_anchor: None,
+            #[cfg(feature = "__dnssec")]
+            negative_trust_anchors: Vec::new(),
+            #[cfg(feature = "dnstap")]
+            dnstap: None,
+            #[cfg(feature = "metrics")]
+            per_upstream_metrics: default_per_upstream_metrics(),
         }
     }
 }
 
+#[cfg(all(feature = "__tls", feature = "serde"))]
+fn default_tls_verifier() -> Option<Arc<dyn rustls::client::danger::ServerCertVerifier>> {
+    default_verifier(Arc::new(default_provider())).ok()
+}
+
 fn default_ndots() -> usize {
     1
 }
@@ -938,6 +1400,11 @@ fn default_recursion_desired() -> bool {
     true
 }
 
+#[cfg(feature = "metrics")]
+fn default_per_upstream_metrics() -> bool {
+    true
+}
+
 /// IP addresses for Google Public DNS
 pub const GOOGLE_IPS: &[IpAddr] = &[
     IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8)),
@@ -962,10 +1429,91 @@ pub const QUAD9_IPS: &[IpAddr] = &[
     IpAddr::V6(Ipv6Addr::new(0x2620, 0x00fe, 0, 0, 0, 0, 0x00fe, 0x0009)),
 ];
 
-#[cfg(all(test, feature = "serde"))]
+#[cfg(test)]
 mod tests {
     use super::*;
 
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_dns64_prefix() {
+        let config = toml::from_str::<Dns64Config>("prefix = \"2001:db8:64::/96\"").unwrap();
+        assert_eq!(config.prefix.net(), "2001:db8:64::/96".parse().unwrap());
+        assert_eq!(
+            toml::from_str::<Dns64Config>("").unwrap().prefix,
+            Nat64Prefix::default()
+        );
+
+        let error = toml::from_str::<ResolverOpts>("dns64 = { prefix = \"2001:db8::/33\" }")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("invalid DNS64 prefix"), "{error}");
+    }
+
+    #[test]
+    fn test_name_server_from_url() {
+        let config = NameServerConfig::from_str("udp://192.0.2.1").unwrap();
+        assert_eq!(config.protocol, Protocol::Udp);
+        assert_eq!(config.socket_addr, SocketAddr::from(([192, 0, 2, 1], 53)));
+        assert_eq!(config.tls_dns_name, None);
+
+        let config = NameServerConfig::from_str("tcp://[2001:db8::1]:5353").unwrap();
+        assert_eq!(config.protocol, Protocol::Tcp);
+        assert_eq!(
+            config.socket_addr,
+            SocketAddr::new(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1).into(), 5353)
+        );
+
+        assert!(NameServerConfig::from_str("udp://192.0.2.1#dns.example").is_err());
+        assert!(NameServerConfig::from_str("tcp://192.0.2.1/dns-query").is_err());
+        assert!(NameServerConfig::from_str("ftp://192.0.2.1").is_err());
+        assert!(NameServerConfig::from_str("192.0.2.1").is_err());
+        // host names are not resolved
+        assert!(NameServerConfig::from_str("udp://dns.example").is_err());
+    }
+
+    #[cfg(feature = "__tls")]
+    #[test]
+    fn test_name_server_from_tls_url() {
+        let config = NameServerConfig::from_str("tls://1.1.1.1:853#cloudflare-dns.com").unwrap();
+        assert_eq!(config.protocol, Protocol::Tls);
+        assert_eq!(config.socket_addr, SocketAddr::from(([1, 1, 1, 1], 853)));
+        assert_eq!(config.tls_dns_name.as_deref(), Some("cloudflare-dns.com"));
+
+        let config = NameServerConfig::from_str("tls://192.0.2.1").unwrap();
+        assert_eq!(config.socket_addr, SocketAddr::from(([192, 0, 2, 1], 853)));
+        assert_eq!(config.tls_dns_name.as_deref(), Some("192.0.2.1"));
+    }
+
+    #[cfg(feature = "__https")]
+    #[test]
+    fn test_name_server_from_https_url() {
+        let config = NameServerConfig::from_str("https://192.0.2.1/resolve#dns.example").unwrap();
+        assert_eq!(config.protocol, Protocol::Https);
+        assert_eq!(config.socket_addr, SocketAddr::from(([192, 0, 2, 1], 443)));
+        assert_eq!(config.tls_dns_name.as_deref(), Some("dns.example"));
+        assert_eq!(config.http_endpoint.as_deref(), Some("/resolve"));
+
+        let config = NameServerConfig::from_str("https://192.0.2.1:8443").unwrap();
+        assert_eq!(config.socket_addr, SocketAddr::from(([192, 0, 2, 1], 8443)));
+        assert_eq!(config.http_endpoint, None);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_name_server_group_entries() {
+        let group = serde_json::from_str::<NameServerConfigGroup>(
+            r#"["udp://192.0.2.1", { "socket_addr": "192.0.2.2:53", "protocol": "tcp" }]"#,
+        )
+        .unwrap();
+
+        assert_eq!(group.len(), 2);
+        assert_eq!(group[0].protocol, Protocol::Udp);
+        assert_eq!(group[1].protocol, Protocol::Tcp);
+        assert_eq!(group[1].socket_addr, SocketAddr::from(([192, 0, 2, 2], 53)));
+
+        assert!(serde_json::from_str::<NameServerConfigGroup>(r#"["ftp://192.0.2.1"]"#).is_err());
+    }
+
     #[cfg(feature = "serde")]
     #[test]
     fn default_opts() {
@@ -993,5 +1541,7 @@ mod tests {
         assert_eq!(code.os_port_selection, json.os_port_selection);
         assert_eq!(code.case_randomization, json.case_randomization);
         assert_eq!(code.trust_anchor, json.trust_anchor);
+        #[cfg(feature = "__dnssec")]
+        assert_eq!(code.negative_trust_anchors, json.negative_trust_anchors);
     }
 }
@@ -0,0 +1,211 @@
+// Copyright 2015-2025 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Parsing of [DNS Stamps](https://dnscrypt.info/stamps-specifications)
+
+use std::str;
+
+use data_encoding::BASE64URL_NOPAD;
+
+use super::{NameServerConfig, parse_socket_addr, split_port};
+use crate::error::ResolveError;
+use crate::proto::xfer::Protocol;
+
+/// Parses the part of a DNS Stamp after `sdns://`
+///
+/// Plain DNS, DNS-over-HTTPS, DNS-over-TLS and DNS-over-QUIC stamps are supported, the name server
+/// is contacted over UDP for plain DNS stamps. Certificate hashes become the `tls_pins` of the
+/// configuration. Stamps without an address are rejected, their host name would have to be
+/// resolved first.
+pub(super) fn parse(stamp: &str) -> Result<NameServerConfig, ResolveError> {
+    let bytes = BASE64URL_NOPAD
+        .decode(stamp.trim_end_matches('=').as_bytes())
+        .map_err(|e| format!("invalid DNS stamp encoding: {e}"))?;
+    let mut reader = Reader(&bytes);
+
+    let id = reader.byte()?;
+    // the properties only describe the name server, like whether it logs queries
+    reader.bytes(8)?;
+
+    let protocol = match id {
+        0x00 => Protocol::Udp,
+        #[cfg(feature = "__https")]
+        0x02 => Protocol::Https,
+        #[cfg(feature = "__tls")]
+        0x03 => Protocol::Tls,
+        #[cfg(feature = "__quic")]
+        0x04 => Protocol::Quic,
+        _ => return Err(format!("unsupported DNS stamp protocol: {id:#04x}").into()),
+    };
+
+    let default_port = match protocol {
+        Protocol::Udp => 53,
+        #[cfg(feature = "__https")]
+        Protocol::Https => 443,
+        _ => 853,
+    };
+
+    let addr = reader.string()?;
+    if protocol == Protocol::Udp {
+        let socket_addr = parse_socket_addr(addr, default_port)
+            .ok_or_else(|| format!("invalid DNS stamp address: {addr}"))?;
+        return Ok(NameServerConfig::new(socket_addr, protocol));
+    }
+
+    let tls_pins = reader
+        .hashes()?
+        .into_iter()
+        .filter(|hash| !hash.is_empty())
+        .map(|hash| {
+            <[u8; 32]>::try_from(hash)
+                .map_err(|_| ResolveError::from("DNS stamp certificate hashes must be SHA-256"))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let (host, port) = split_port(reader.string()?)
+        .ok_or_else(|| ResolveError::from("invalid DNS stamp host name"))?;
+    let port = port.unwrap_or(default_port);
+
+    let socket_addr = match addr {
+        "" => return Err(format!("DNS stamp for {host} without an address").into()),
+        addr => parse_socket_addr(addr, port)
+            .ok_or_else(|| format!("invalid DNS stamp address: {addr}"))?,
+    };
+
+    #[cfg(feature = "__https")]
+    let http_endpoint = match protocol {
+        Protocol::Https => Some(reader.string()?.to_owned()),
+        _ => None,
+    };
+    #[cfg(not(feature = "__https"))]
+    let http_endpoint = None;
+
+    Ok(NameServerConfig {
+        tls_dns_name: Some(host.to_owned()),
+        http_endpoint,
+        tls_pins,
+        ..NameServerConfig::new(socket_addr, protocol)
+    })
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> Result<u8, ResolveError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], ResolveError> {
+        if self.0.len() < len {
+            return Err("truncated DNS stamp".into());
+        }
+
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    /// A length prefixed string
+    fn string(&mut self) -> Result<&'a str, ResolveError> {
+        let len = self.byte()?;
+        let bytes = self.bytes(usize::from(len))?;
+        str::from_utf8(bytes).map_err(|_| "invalid UTF-8 in DNS stamp".into())
+    }
+
+    /// A variable length set of length prefixed hashes, the high bit of a length is set if more
+    /// hashes follow
+    fn hashes(&mut self) -> Result<Vec<&'a [u8]>, ResolveError> {
+        let mut hashes = Vec::new();
+        loop {
+            let len = self.byte()?;
+            hashes.push(self.bytes(usize::from(len & 0x7f))?);
+            if len & 0x80 == 0 {
+                return Ok(hashes);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(any(feature = "__https", feature = "__tls"))]
+    use std::net::Ipv4Addr;
+    use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+
+    use super::*;
+
+    #[test]
+    fn test_plain() {
+        let stamp =
+            BASE64URL_NOPAD.encode(b"\x00\x01\x00\x00\x00\x00\x00\x00\x00\x0d[2001:db8::1]");
+        let config = parse(&stamp).unwrap();
+
+        assert_eq!(config.protocol, Protocol::Udp);
+        assert_eq!(
+            config.socket_addr,
+            SocketAddr::new(
+                IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1)),
+                53
+            )
+        );
+    }
+
+    #[cfg(feature = "__https")]
+    #[test]
+    fn test_https() {
+        let config =
+            parse("AgcAAAAAAAAABzEuMC4wLjEAEmRucy5jbG91ZGZsYXJlLmNvbQovZG5zLXF1ZXJ5").unwrap();
+
+        assert_eq!(config.protocol, Protocol::Https);
+        assert_eq!(
+            config.socket_addr,
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::new(1, 0, 0, 1)), 443)
+        );
+        assert_eq!(config.tls_dns_name.as_deref(), Some("dns.cloudflare.com"));
+        assert_eq!(config.http_endpoint.as_deref(), Some("/dns-query"));
+        assert!(config.tls_pins.is_empty());
+    }
+
+    #[cfg(feature = "__tls")]
+    #[test]
+    fn test_tls_pins() {
+        let mut stamp = b"\x03\x00\x00\x00\x00\x00\x00\x00\x00\x0d192.0.2.1:853".to_vec();
+        stamp.push(0x80 | 32);
+        stamp.extend([1; 32]);
+        stamp.push(32);
+        stamp.extend([2; 32]);
+        stamp.push(11);
+        stamp.extend(b"dns.example");
+
+        let config = parse(&BASE64URL_NOPAD.encode(&stamp)).unwrap();
+        assert_eq!(config.protocol, Protocol::Tls);
+        assert_eq!(
+            config.socket_addr,
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)), 853)
+        );
+        assert_eq!(config.tls_dns_name.as_deref(), Some("dns.example"));
+        assert_eq!(config.tls_pins, vec![[1; 32], [2; 32]]);
+
+        // truncated after the hashes
+        stamp.truncate(stamp.len() - 12);
+        assert!(parse(&BASE64URL_NOPAD.encode(&stamp)).is_err());
+
+        // without an address
+        let mut stamp = b"\x03\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00".to_vec();
+        stamp.push(11);
+        stamp.extend(b"dns.example");
+        assert!(parse(&BASE64URL_NOPAD.encode(&stamp)).is_err());
+    }
+
+    #[test]
+    fn test_unsupported() {
+        // DNSCrypt
+        let stamp = BASE64URL_NOPAD.encode(b"\x01\x00\x00\x00\x00\x00\x00\x00\x00\x07192.0.2.1");
+        assert!(parse(&stamp).is_err());
+        assert!(parse("not base64!").is_err());
+    }
+}
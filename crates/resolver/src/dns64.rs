@@ -0,0 +1,419 @@
+// Copyright 2015-2025 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! DNS64 synthesis of AAAA records, [RFC 6147](https://www.rfc-editor.org/rfc/rfc6147)
+
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::sync::Arc;
+
+use ipnet::IpNet;
+use tracing::debug;
+
+use crate::caching_client::CachingClient;
+use crate::config::Dns64Config;
+use crate::error::ResolveError;
+use crate::lookup::Lookup;
+use crate::proto::op::Query;
+use crate::proto::rr::rdata::AAAA;
+use crate::proto::rr::{Name, RData, Record, RecordType};
+use crate::proto::xfer::{DnsHandle, DnsRequestOptions};
+
+/// Looks up the AAAA or PTR records of the query, synthesizing them if needed
+pub(crate) async fn lookup<C: DnsHandle + Send + 'static>(
+    client: CachingClient<C>,
+    config: Arc<Dns64Config>,
+    query: Query,
+    options: DnsRequestOptions,
+) -> Result<Lookup, ResolveError> {
+    match query.query_type() {
+        RecordType::AAAA => lookup_aaaa(client, &config, query, options).await,
+        RecordType::PTR => match mapped_ipv4(&config, query.name()) {
+            Some(ipv4) => lookup_ptr(client, ipv4, query, options).await,
+            None => client.cached_lookup(query, options).await,
+        },
+        _ => client.cached_lookup(query, options).await,
+    }
+}
+
+/// RFC 6147 section 5.1, AAAA records are only synthesized if there are none
+async fn lookup_aaaa<C: DnsHandle + Send + 'static>(
+    client: CachingClient<C>,
+    config: &Dns64Config,
+    query: Query,
+    options: DnsRequestOptions,
+) -> Result<Lookup, ResolveError> {
+    let aaaa = client
+        .cached_lookup(query.clone(), options)
+        .await
+        .map(|lookup| remove_excluded(config, lookup));
+    match &aaaa {
+        Ok(lookup) if has_aaaa(lookup.records()) => return aaaa,
+        Ok(_) => {}
+        // a name that doesn't exist has no A records either
+        Err(e) if e.is_no_records_found() && !e.is_nx_domain() => {}
+        Err(_) => return aaaa,
+    }
+
+    let mut a_query = Query::query(query.name().clone(), RecordType::A);
+    a_query.set_query_class(query.query_class());
+    let a = match client.cached_lookup(a_query, options).await {
+        Ok(a) => a,
+        Err(e) => {
+            debug!(
+                "no A records to synthesize AAAA records for {}: {e}",
+                query.name()
+            );
+            return aaaa;
+        }
+    };
+
+    let records = a
+        .records()
+        .iter()
+        .filter_map(|record| match record.data() {
+            RData::A(a) => {
+                let ipv4 = Ipv4Addr::from(*a);
+                if config.exclude_ipv4.iter().any(|net| net.contains(&ipv4)) {
+                    return None;
+                }
+
+                let mut synthesized = record.clone();
+                synthesized.set_data(RData::AAAA(AAAA::from(synthesize(config, ipv4))));
+                Some(synthesized)
+            }
+            // the CNAME records leading to the A records
+            _ => Some(record.clone()),
+        })
+        .collect::<Vec<_>>();
+
+    if !has_aaaa(&records) {
+        return aaaa;
+    }
+
+    debug!("synthesized AAAA records for {}", query.name());
+    Ok(Lookup::new_with_deadline(
+        query,
+        Arc::from(records),
+        a.valid_until(),
+    ))
+}
+
+fn has_aaaa(records: &[Record]) -> bool {
+    records
+        .iter()
+        .any(|record| record.record_type() == RecordType::AAAA)
+}
+
+/// Removes the AAAA records in the excluded networks, RFC 6147 section 5.1.4
+fn remove_excluded(config: &Dns64Config, lookup: Lookup) -> Lookup {
+    let is_excluded = |record: &Record| match record.data() {
+        RData::AAAA(aaaa) => {
+            let ipv6 = Ipv6Addr::from(*aaaa);
+            config.exclude.iter().any(|net| net.contains(&ipv6))
+        }
+        _ => false,
+    };
+
+    if !lookup.records().iter().any(is_excluded) {
+        return lookup;
+    }
+
+    let records = lookup
+        .records()
+        .iter()
+        .filter(|record| !is_excluded(record))
+        .cloned()
+        .collect::<Vec<_>>();
+    Lookup::new_with_deadline(
+        lookup.query().clone(),
+        Arc::from(records),
+        lookup.valid_until(),
+    )
+}
+
+/// RFC 6147 section 5.3.1, PTR queries for synthesized addresses are answered with the PTR
+/// records of the embedded IPv4 address
+async fn lookup_ptr<C: DnsHandle + Send + 'static>(
+    client: CachingClient<C>,
+    ipv4: Ipv4Addr,
+    query: Query,
+    options: DnsRequestOptions,
+) -> Result<Lookup, ResolveError> {
+    let mut ptr_query = Query::query(Name::from(ipv4), RecordType::PTR);
+    ptr_query.set_query_class(query.query_class());
+    let lookup = client.cached_lookup(ptr_query, options).await?;
+
+    let records = lookup
+        .records()
+        .iter()
+        .map(|record| {
+            let mut record = record.clone();
+            if record.record_type() == RecordType::PTR {
+                record.set_name(query.name().clone());
+            }
+            record
+        })
+        .collect::<Vec<_>>();
+
+    Ok(Lookup::new_with_deadline(
+        query,
+        Arc::from(records),
+        lookup.valid_until(),
+    ))
+}
+
+/// Returns the IPv4 address embedded in `name`, if it's the `ip6.arpa` name of a synthesized
+/// address
+fn mapped_ipv4(config: &Dns64Config, name: &Name) -> Option<Ipv4Addr> {
+    match name.parse_arpa_name() {
+        Ok(IpNet::V6(net))
+            if net.prefix_len() == 128 && config.prefix.net().contains(&net.addr()) =>
+        {
+            Some(extract(config, net.addr()))
+        }
+        _ => None,
+    }
+}
+
+/// Returns the indexes of the octets of the IPv4 address, RFC 6052 section 2.2
+///
+/// The octets follow the prefix, skipping bits 64 to 71.
+fn ipv4_octets(config: &Dns64Config) -> impl Iterator<Item = usize> {
+    (usize::from(config.prefix.net().prefix_len()) / 8..16)
+        .filter(|index| *index != 8)
+        .take(4)
+}
+
+/// Embeds the IPv4 address in the prefix
+fn synthesize(config: &Dns64Config, ipv4: Ipv4Addr) -> Ipv6Addr {
+    let mut octets = config.prefix.net().network().octets();
+    for (index, octet) in ipv4_octets(config).zip(ipv4.octets()) {
+        octets[index] = octet;
+    }
+
+    Ipv6Addr::from(octets)
+}
+
+/// Extracts the IPv4 address embedded in the address
+fn extract(config: &Dns64Config, ipv6: Ipv6Addr) -> Ipv4Addr {
+    let octets = ipv6.octets();
+    let mut ipv4 = [0; 4];
+    for (octet, index) in ipv4.iter_mut().zip(ipv4_octets(config)) {
+        *octet = octets[index];
+    }
+
+    Ipv4Addr::from(ipv4)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use futures_executor::block_on;
+    use ipnet::Ipv6Net;
+    use test_support::subscribe;
+
+    use crate::config::Nat64Prefix;
+    use crate::lookup_ip::tests::{empty, mock};
+    use crate::proto::ProtoError;
+    use crate::proto::op::Message;
+    use crate::proto::rr::rdata::{A, PTR};
+    use crate::proto::xfer::DnsResponse;
+
+    use super::*;
+
+    fn config(prefix: &str) -> Dns64Config {
+        Dns64Config {
+            prefix: Nat64Prefix::try_from(Ipv6Net::from_str(prefix).unwrap()).unwrap(),
+            ..Dns64Config::default()
+        }
+    }
+
+    fn message(query: Query, answers: Vec<Record>) -> Result<DnsResponse, ProtoError> {
+        let mut message = Message::new();
+        message.add_query(query);
+        message.insert_answers(answers);
+        Ok(DnsResponse::from_message(message).unwrap())
+    }
+
+    fn lookup_addresses(
+        messages: Vec<Result<DnsResponse, ProtoError>>,
+        config: Dns64Config,
+    ) -> Result<Vec<Ipv6Addr>, ResolveError> {
+        let client = CachingClient::new(0, mock(messages), false);
+        let query = Query::query(
+            Name::from_str("www.example.com.").unwrap(),
+            RecordType::AAAA,
+        );
+        let lookup = block_on(lookup(
+            client,
+            Arc::new(config),
+            query,
+            DnsRequestOptions::default(),
+        ))?;
+
+        Ok(lookup
+            .iter()
+            .filter_map(|rdata| match rdata {
+                RData::AAAA(aaaa) => Some(Ipv6Addr::from(*aaaa)),
+                _ => None,
+            })
+            .collect())
+    }
+
+    fn a_message() -> Result<DnsResponse, ProtoError> {
+        let name = Name::from_str("www.example.com.").unwrap();
+        message(
+            Query::query(name.clone(), RecordType::A),
+            vec![Record::from_rdata(
+                name,
+                300,
+                RData::A(A::new(192, 0, 2, 33)),
+            )],
+        )
+    }
+
+    fn aaaa_message(aaaa: Ipv6Addr) -> Result<DnsResponse, ProtoError> {
+        let name = Name::from_str("www.example.com.").unwrap();
+        message(
+            Query::query(name.clone(), RecordType::AAAA),
+            vec![Record::from_rdata(name, 300, RData::AAAA(aaaa.into()))],
+        )
+    }
+
+    // the examples of RFC 6052, section 2.4
+    #[test]
+    fn test_synthesize() {
+        let ipv4 = Ipv4Addr::new(192, 0, 2, 33);
+        for (prefix, ipv6) in [
+            ("2001:db8::/32", "2001:db8:c000:221::"),
+            ("2001:db8:100::/40", "2001:db8:1c0:2:21::"),
+            ("2001:db8:122::/48", "2001:db8:122:c000:2:2100::"),
+            ("2001:db8:122:300::/56", "2001:db8:122:3c0:0:221::"),
+            ("2001:db8:122:344::/64", "2001:db8:122:344:c0:2:2100:0"),
+            ("2001:db8:122:344::/96", "2001:db8:122:344::192.0.2.33"),
+            ("64:ff9b::/96", "64:ff9b::192.0.2.33"),
+        ] {
+            let config = config(prefix);
+            let ipv6 = Ipv6Addr::from_str(ipv6).unwrap();
+            assert_eq!(synthesize(&config, ipv4), ipv6, "{prefix}");
+            assert_eq!(extract(&config, ipv6), ipv4, "{prefix}");
+        }
+    }
+
+    #[test]
+    fn test_mapped_ipv4() {
+        let config = Dns64Config::default();
+        let name = Name::from(std::net::IpAddr::from(
+            Ipv6Addr::from_str("64:ff9b::192.0.2.33").unwrap(),
+        ));
+        assert_eq!(
+            mapped_ipv4(&config, &name),
+            Some(Ipv4Addr::new(192, 0, 2, 33))
+        );
+
+        let name = Name::from(std::net::IpAddr::from(
+            Ipv6Addr::from_str("2001:db8::1").unwrap(),
+        ));
+        assert_eq!(mapped_ipv4(&config, &name), None);
+    }
+
+    #[test]
+    fn test_prefix_length() {
+        assert!(Nat64Prefix::try_from(Ipv6Net::from_str("2001:db8::/32").unwrap()).is_ok());
+        assert!(Nat64Prefix::try_from(Ipv6Net::from_str("2001:db8::/33").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_synthesize_without_aaaa() {
+        subscribe();
+
+        // the mock returns the messages from last to first
+        let addresses =
+            lookup_addresses(vec![a_message(), empty()], Dns64Config::default()).unwrap();
+        assert_eq!(
+            addresses,
+            vec![Ipv6Addr::from_str("64:ff9b::192.0.2.33").unwrap()]
+        );
+    }
+
+    #[test]
+    fn test_no_synthesis_with_aaaa() {
+        subscribe();
+
+        let aaaa = Ipv6Addr::from_str("2001:db8::1").unwrap();
+        let addresses = lookup_addresses(
+            vec![a_message(), aaaa_message(aaaa)],
+            Dns64Config::default(),
+        )
+        .unwrap();
+        assert_eq!(addresses, vec![aaaa]);
+    }
+
+    #[test]
+    fn test_synthesize_with_excluded_aaaa() {
+        subscribe();
+
+        let aaaa = Ipv6Addr::from_str("::ffff:192.0.2.1").unwrap();
+        let addresses = lookup_addresses(
+            vec![a_message(), aaaa_message(aaaa)],
+            Dns64Config::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            addresses,
+            vec![Ipv6Addr::from_str("64:ff9b::192.0.2.33").unwrap()]
+        );
+    }
+
+    #[test]
+    fn test_excluded_ipv4() {
+        subscribe();
+
+        let config = Dns64Config {
+            exclude_ipv4: vec!["192.0.2.0/24".parse().unwrap()],
+            ..Dns64Config::default()
+        };
+        let error = lookup_addresses(vec![a_message(), empty()], config).unwrap_err();
+        assert!(error.is_no_records_found());
+    }
+
+    #[test]
+    fn test_ptr() {
+        subscribe();
+
+        let ipv4_name = Name::from(Ipv4Addr::new(192, 0, 2, 33));
+        let target = Name::from_str("www.example.com.").unwrap();
+        let client = CachingClient::new(
+            0,
+            mock(vec![message(
+                Query::query(ipv4_name.clone(), RecordType::PTR),
+                vec![Record::from_rdata(
+                    ipv4_name,
+                    300,
+                    RData::PTR(PTR(target.clone())),
+                )],
+            )]),
+            false,
+        );
+
+        let name = Name::from(std::net::IpAddr::from(
+            Ipv6Addr::from_str("64:ff9b::192.0.2.33").unwrap(),
+        ));
+        let lookup = block_on(lookup(
+            client,
+            Arc::new(Dns64Config::default()),
+            Query::query(name.clone(), RecordType::PTR),
+            DnsRequestOptions::default(),
+        ))
+        .unwrap();
+
+        assert_eq!(lookup.records().len(), 1);
+        assert_eq!(lookup.records()[0].name(), &name);
+        assert_eq!(lookup.records()[0].data(), &RData::PTR(PTR(target)));
+    }
+}
@@ -8,14 +8,17 @@
 //! An LRU cache designed for work with DNS lookups
 
 use std::collections::HashMap;
+use std::io::{self, Write};
 use std::ops::RangeInclusive;
 use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, Instant, SystemTime};
 
 use moka::{Expiry, sync::Cache};
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Deserializer};
 
+use crate::cache_file::{CacheFileWriter, SavedResponse};
 use crate::config;
 use crate::lookup::Lookup;
 #[cfg(feature = "__dnssec")]
@@ -23,7 +26,7 @@ use crate::proto::dnssec::rdata::RRSIG;
 use crate::proto::op::Query;
 #[cfg(feature = "__dnssec")]
 use crate::proto::rr::RecordData;
-use crate::proto::rr::{Record, RecordType};
+use crate::proto::rr::{Name, Record, RecordType};
 use crate::proto::{ProtoError, ProtoErrorKind};
 
 /// Maximum TTL. This is set to one day (in seconds).
@@ -38,9 +41,22 @@ struct LruValue {
     // In the Err case, this represents an NXDomain
     lookup: Result<Lookup, ProtoError>,
     valid_until: Instant,
+    /// The TTL the value was cached with
+    original_ttl: Duration,
+    /// The number of times the value was returned from the cache, shared with its clones
+    hits: Arc<AtomicU32>,
 }
 
 impl LruValue {
+    fn new(lookup: Result<Lookup, ProtoError>, now: Instant, ttl: Duration) -> Self {
+        Self {
+            lookup,
+            valid_until: now + ttl,
+            original_ttl: ttl,
+            hits: Arc::default(),
+        }
+    }
+
     /// Returns true if this set of ips is still valid
     fn is_current(&self, now: Instant) -> bool {
         now <= self.valid_until
@@ -74,10 +90,23 @@ impl LruValue {
         Self {
             lookup,
             valid_until: self.valid_until,
+            original_ttl: self.original_ttl,
+            hits: self.hits.clone(),
         }
     }
 }
 
+/// How a value returned from the cache was used
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct CacheUsage {
+    /// The number of times the value was returned from the cache, including this time
+    pub(crate) hits: u32,
+    /// The TTL the value was cached with
+    pub(crate) original_ttl: Duration,
+    /// The remaining TTL of the value
+    pub(crate) remaining_ttl: Duration,
+}
+
 /// A cache specifically for storing DNS records.
 ///
 /// This is named `DnsLru` for historical reasons. It currently uses a "TinyLFU" policy, implemented
@@ -263,10 +292,99 @@ impl DnsLru {
         }
     }
 
-    pub(crate) fn clear(&self) {
+    /// Removes all entries from the cache
+    pub fn clear(&self) {
         self.cache.invalidate_all();
     }
 
+    /// Removes the cached responses that contain any records for `name` or the names below it
+    ///
+    /// This includes the responses to queries for those names, as well as responses that reached
+    /// them through a CNAME chain.
+    pub fn remove_subdomains(&self, name: &Name) {
+        for (query, value) in self.cache.iter() {
+            let covered = name.zone_of(query.name())
+                || value.lookup.as_ref().is_ok_and(|lookup| {
+                    lookup
+                        .records()
+                        .iter()
+                        .any(|record| name.zone_of(record.name()))
+                });
+
+            if covered {
+                self.cache.invalidate(&*query);
+            }
+        }
+    }
+
+    /// Returns the records of all cached positive responses, with their remaining TTL
+    pub fn records(&self) -> Vec<Record> {
+        let now = Instant::now();
+        self.cache
+            .iter()
+            .filter(|(_, value)| value.is_current(now))
+            .filter_map(|(_, value)| value.with_updated_ttl(now).lookup.ok())
+            .flat_map(|lookup| lookup.records().to_vec())
+            .collect()
+    }
+
+    /// Writes the current entries of the cache to `file`, returns the number of written entries
+    ///
+    /// See [`Self::restore`] to insert them into a cache again.
+    pub fn save<W: Write>(&self, file: &mut CacheFileWriter<W>) -> io::Result<usize> {
+        let now = Instant::now();
+        let system_now = SystemTime::now();
+
+        let mut saved = 0;
+        for (query, value) in self.cache.iter() {
+            if !value.is_current(now) {
+                continue;
+            }
+
+            let response = match &value.lookup {
+                // bogus records must be validated again, and fail again
+                #[cfg(feature = "__dnssec")]
+                Ok(lookup) if lookup.records().iter().any(|r| r.proof().is_bogus()) => continue,
+                Ok(lookup) => Ok(lookup.records()),
+                Err(e) => Err(e),
+            };
+            let expires = system_now + value.ttl(now);
+            if file.write_response(&query, expires, value.original_ttl, response)? {
+                saved += 1;
+            }
+        }
+
+        Ok(saved)
+    }
+
+    /// Inserts a response read from a cache file, returns `false` if it has expired
+    ///
+    /// Unless `preserve_proofs` is set, the DNSSEC validation state of the records is discarded,
+    /// they are `Indeterminate`. Records are only validated again if the cache is queried through
+    /// a validating handle, like the cache of the recursor; the cache of the resolver is above its
+    /// validation, see `Resolver::load_cache`.
+    pub fn restore(&self, response: SavedResponse, preserve_proofs: bool) -> bool {
+        let ttl = match response.expires.duration_since(SystemTime::now()) {
+            Ok(ttl) if !ttl.is_zero() => ttl,
+            _ => return false,
+        };
+
+        let now = Instant::now();
+        let lookup = match response.response {
+            Ok(records) => Ok(Lookup::new_with_deadline(
+                response.query.clone(),
+                Arc::from(SavedResponse::records(records, preserve_proofs)),
+                now + ttl,
+            )),
+            Err(e) => Err(e),
+        };
+
+        let mut value = LruValue::new(lookup, now, ttl);
+        value.original_ttl = response.original_ttl;
+        self.cache.insert(response.query, value);
+        true
+    }
+
     pub(crate) fn insert(
         &self,
         query: Query,
@@ -297,13 +415,8 @@ impl DnsLru {
 
         // insert into the LRU
         let lookup = Lookup::new_with_deadline(query.clone(), Arc::from(records), valid_until);
-        self.cache.insert(
-            query,
-            LruValue {
-                lookup: Ok(lookup.clone()),
-                valid_until,
-            },
-        );
+        self.cache
+            .insert(query, LruValue::new(Ok(lookup.clone()), now, ttl));
 
         lookup
     }
@@ -397,15 +510,8 @@ impl DnsLru {
     /// Generally for inserting a set of records that have already been cached, but with a different Query.
     pub(crate) fn duplicate(&self, query: Query, lookup: Lookup, ttl: u32, now: Instant) -> Lookup {
         let ttl = Duration::from_secs(u64::from(ttl));
-        let valid_until = now + ttl;
-
-        self.cache.insert(
-            query,
-            LruValue {
-                lookup: Ok(lookup.clone()),
-                valid_until,
-            },
-        );
+        self.cache
+            .insert(query, LruValue::new(Ok(lookup.clone()), now, ttl));
 
         lookup
     }
@@ -439,19 +545,9 @@ impl DnsLru {
                 // Clamp the TTL so that it's between the cache's configured
                 // minimum and maximum TTLs for negative responses.
                 .clamp(negative_min_ttl, negative_max_ttl);
-            let valid_until = now + ttl_duration;
-
-            {
-                let error = error.clone();
 
-                self.cache.insert(
-                    query,
-                    LruValue {
-                        lookup: Err(error),
-                        valid_until,
-                    },
-                );
-            }
+            self.cache
+                .insert(query, LruValue::new(Err(error.clone()), now, ttl_duration));
 
             Self::nx_error_with_ttl(&mut error, ttl_duration);
         }
@@ -461,15 +557,30 @@ impl DnsLru {
 
     /// Based on the query, see if there are any records available
     pub fn get(&self, query: &Query, now: Instant) -> Option<Result<Lookup, ProtoError>> {
+        self.get_with_usage(query, now).map(|(result, _)| result)
+    }
+
+    /// Like [`Self::get`], also returning how the cached value was used
+    pub(crate) fn get_with_usage(
+        &self,
+        query: &Query,
+        now: Instant,
+    ) -> Option<(Result<Lookup, ProtoError>, CacheUsage)> {
         let value = self.cache.get(query)?;
         if !value.is_current(now) {
             return None;
         }
+
+        let usage = CacheUsage {
+            hits: value.hits.fetch_add(1, Ordering::Relaxed).saturating_add(1),
+            original_ttl: value.original_ttl,
+            remaining_ttl: value.ttl(now),
+        };
         let mut result = value.with_updated_ttl(now).lookup;
         if let Err(err) = &mut result {
             Self::nx_error_with_ttl(err, value.ttl(now));
         }
-        Some(result)
+        Some((result, usage))
     }
 }
 
@@ -521,7 +632,7 @@ mod tests {
     use hickory_proto::rr::rdata::TXT;
 
     use crate::proto::op::{Query, ResponseCode};
-    use crate::proto::rr::rdata::A;
+    use crate::proto::rr::rdata::{A, CNAME};
     use crate::proto::rr::{Name, RData, RecordType};
 
     use super::*;
@@ -533,10 +644,11 @@ mod tests {
         let future = now + Duration::from_secs(5);
         let past_the_future = now + Duration::from_secs(6);
 
-        let value = LruValue {
-            lookup: Err(ProtoErrorKind::Message("test error").into()),
-            valid_until: future,
-        };
+        let value = LruValue::new(
+            Err(ProtoErrorKind::Message("test error").into()),
+            now,
+            Duration::from_secs(5),
+        );
 
         assert!(value.is_current(now));
         assert!(value.is_current(not_the_future));
@@ -764,6 +876,46 @@ mod tests {
         assert_eq!(*rc_ips.iter().next().unwrap(), ips[0]);
     }
 
+    #[test]
+    fn test_remove_subdomains() {
+        let now = Instant::now();
+        let lru = DnsLru::new(4, TtlConfig::default());
+
+        let mut queries = vec![];
+        for name in ["example.com.", "www.example.com.", "www.example.net."] {
+            let name = Name::from_str(name).unwrap();
+            let query = Query::query(name.clone(), RecordType::A);
+            let record = Record::from_rdata(name, 60, RData::A(A::new(127, 0, 0, 1)));
+            lru.insert(query.clone(), vec![(record, 60)], now);
+            queries.push(query);
+        }
+
+        // a CNAME chain from an unrelated name into the removed domain
+        let name = Name::from_str("alias.example.org.").unwrap();
+        let target = Name::from_str("cdn.example.com.").unwrap();
+        let query = Query::query(name.clone(), RecordType::A);
+        let records = vec![
+            (
+                Record::from_rdata(name, 60, RData::CNAME(CNAME(target.clone()))),
+                60,
+            ),
+            (
+                Record::from_rdata(target, 60, RData::A(A::new(127, 0, 0, 2))),
+                60,
+            ),
+        ];
+        lru.insert(query.clone(), records, now);
+        queries.push(query);
+
+        lru.remove_subdomains(&Name::from_str("EXAMPLE.com.").unwrap());
+
+        let cached = queries
+            .iter()
+            .map(|query| lru.get(query, now).is_some())
+            .collect::<Vec<_>>();
+        assert_eq!(cached, [false, false, true, false]);
+    }
+
     #[test]
     fn test_update_ttl() {
         let now = Instant::now();
@@ -997,4 +1149,104 @@ mod tests {
         // greater than the min TTL for TXT records.
         assert_eq!(rc_txt.valid_until(), now + Duration::from_secs(7));
     }
+
+    #[test]
+    fn test_get_with_usage() {
+        let now = Instant::now();
+        let name = Name::from_str("www.example.com.").unwrap();
+        let query = Query::query(name.clone(), RecordType::A);
+        let records = vec![(
+            Record::from_rdata(name, 10, RData::A(A::new(127, 0, 0, 1))),
+            10,
+        )];
+
+        let lru = DnsLru::new(1, TtlConfig::default());
+        lru.insert(query.clone(), records.clone(), now);
+
+        let (_, usage) = lru.get_with_usage(&query, now).unwrap();
+        assert_eq!(usage.hits, 1);
+        assert_eq!(usage.original_ttl, Duration::from_secs(10));
+        assert_eq!(usage.remaining_ttl, Duration::from_secs(10));
+
+        let (_, usage) = lru
+            .get_with_usage(&query, now + Duration::from_secs(9))
+            .unwrap();
+        assert_eq!(usage.hits, 2);
+        assert_eq!(usage.remaining_ttl, Duration::from_secs(1));
+
+        // the counter starts over for a new value
+        lru.insert(query.clone(), records, now);
+        let (_, usage) = lru.get_with_usage(&query, now).unwrap();
+        assert_eq!(usage.hits, 1);
+    }
+
+    #[test]
+    fn test_save_restore() {
+        use crate::cache_file::{CacheEntry, CacheFileReader};
+
+        let now = Instant::now();
+        let name = Name::from_str("www.example.com.").unwrap();
+        let query = Query::query(name.clone(), RecordType::A);
+        let records = vec![(
+            Record::from_rdata(name.clone(), 300, RData::A(A::new(127, 0, 0, 1))),
+            300,
+        )];
+        let nx_query = Query::query(name, RecordType::AAAA);
+        let nx_error = ProtoError::nx_error(
+            Box::new(nx_query.clone()),
+            None,
+            None,
+            Some(60),
+            ResponseCode::NoError,
+            true,
+            None,
+        );
+
+        let lru = DnsLru::new(2, TtlConfig::default());
+        lru.insert(query.clone(), records, now);
+        lru.negative(nx_query.clone(), nx_error, now);
+
+        let mut file = CacheFileWriter::new(Vec::new()).unwrap();
+        assert_eq!(lru.save(&mut file).unwrap(), 2);
+        let bytes = file.finish().unwrap();
+
+        let restored = DnsLru::new(2, TtlConfig::default());
+        let mut reader = CacheFileReader::new(bytes.as_slice()).unwrap();
+        while let Some(entry) = reader.read_entry().unwrap() {
+            match entry {
+                CacheEntry::Response(response) => assert!(restored.restore(response, false)),
+                _ => panic!("unexpected entry"),
+            }
+        }
+
+        let lookup = restored.get(&query, Instant::now()).unwrap().unwrap();
+        assert_eq!(
+            lookup.iter().cloned().collect::<Vec<_>>(),
+            vec![RData::A(A::new(127, 0, 0, 1))]
+        );
+        assert!(lookup.valid_until() > now + Duration::from_secs(298));
+
+        let error = restored
+            .get(&nx_query, Instant::now())
+            .unwrap()
+            .unwrap_err();
+        assert!(error.is_no_records_found());
+    }
+
+    #[cfg(feature = "__dnssec")]
+    #[test]
+    fn test_save_skips_bogus() {
+        use crate::proto::dnssec::Proof;
+
+        let now = Instant::now();
+        let name = Name::from_str("www.example.com.").unwrap();
+        let mut record = Record::from_rdata(name.clone(), 300, RData::A(A::new(127, 0, 0, 1)));
+        record.set_proof(Proof::Bogus);
+
+        let lru = DnsLru::new(1, TtlConfig::default());
+        lru.insert(Query::query(name, RecordType::A), vec![(record, 300)], now);
+
+        let mut file = CacheFileWriter::new(Vec::new()).unwrap();
+        assert_eq!(lru.save(&mut file).unwrap(), 0);
+    }
 }
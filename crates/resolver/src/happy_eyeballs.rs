@@ -0,0 +1,273 @@
+// Copyright 2015-2025 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Connecting to a host over TCP with Happy Eyeballs, [RFC 8305](https://www.rfc-editor.org/rfc/rfc8305)
+//!
+//! ```rust,no_run
+//! # use hickory_resolver::{happy_eyeballs, Resolver};
+//! # use hickory_resolver::config::ResolverConfig;
+//! # use hickory_resolver::name_server::TokioConnectionProvider;
+//! # async fn doc() -> std::io::Result<()> {
+//! let resolver = Resolver::builder_with_config(
+//!     ResolverConfig::default(),
+//!     TokioConnectionProvider::default(),
+//! )
+//! .build();
+//!
+//! let stream = happy_eyeballs::connect(&resolver, "www.example.com.", 443).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::VecDeque;
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use futures_util::FutureExt;
+use futures_util::future::{self, BoxFuture, Either, OptionFuture};
+use futures_util::stream::{FuturesUnordered, StreamExt};
+use tokio::net::TcpStream;
+use tokio::time::{self, Instant};
+use tracing::debug;
+
+use crate::address_sort::{sort_by_address, sort_without_blocking};
+use crate::error::ResolveError;
+use crate::name_server::ConnectionProvider;
+use crate::proto::rr::IntoName;
+use crate::resolver::Resolver;
+
+/// Time to wait for the AAAA records after the A records were received, RFC 8305 section 3
+const RESOLUTION_DELAY: Duration = Duration::from_millis(50);
+
+/// Time to wait for a connection attempt before starting the next one, RFC 8305 section 5
+const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+type AddressesFuture<'a> = BoxFuture<'a, Result<Vec<SocketAddr>, ResolveError>>;
+
+/// Connects to `port` on `host`, returning the first connection established to any of its
+/// addresses
+///
+/// The A and AAAA records of `host` are looked up in parallel, regardless of the `ip_strategy`
+/// of the resolver. Connection attempts start once the first addresses are known, they are
+/// ordered by [RFC 6724](https://www.rfc-editor.org/rfc/rfc6724) alternating between IPv6 and
+/// IPv4, and a new attempt is started every 250ms or when an attempt fails.
+pub async fn connect<P: ConnectionProvider>(
+    resolver: &Resolver<P>,
+    host: impl IntoName,
+    port: u16,
+) -> io::Result<TcpStream> {
+    if let Some(ip) = host.to_ip() {
+        return TcpStream::connect(SocketAddr::new(ip, port)).await;
+    }
+
+    let name = host.into_name().map_err(ResolveError::from)?;
+    let ipv6: AddressesFuture<'_> = resolver
+        .ipv6_lookup(name.clone())
+        .map(move |lookup| {
+            Ok(lookup?
+                .into_iter()
+                .map(|aaaa| SocketAddr::new(aaaa.0.into(), port))
+                .collect())
+        })
+        .boxed();
+    let ipv4: AddressesFuture<'_> = resolver
+        .ipv4_lookup(name)
+        .map(move |lookup| {
+            Ok(lookup?
+                .into_iter()
+                .map(|a| SocketAddr::new(a.0.into(), port))
+                .collect())
+        })
+        .boxed();
+
+    // connections are attempted as soon as the AAAA records are received, after the A records
+    // only if the AAAA records don't follow shortly
+    let (addresses, remaining) = match future::select(ipv6, ipv4).await {
+        Either::Left((ipv6, ipv4)) => (ipv6, Some(ipv4)),
+        Either::Right((ipv4, mut ipv6)) => match time::timeout(RESOLUTION_DELAY, &mut ipv6).await {
+            Ok(ipv6) => match (ipv6, ipv4) {
+                (Ok(mut ipv6), Ok(ipv4)) => {
+                    ipv6.extend(ipv4);
+                    (Ok(ipv6), None)
+                }
+                (Ok(addresses), Err(e)) | (Err(e), Ok(addresses)) => {
+                    debug!("address lookup failed: {e}");
+                    (Ok(addresses), None)
+                }
+                (Err(e), Err(_)) => (Err(e), None),
+            },
+            Err(_) => (ipv4, Some(ipv6)),
+        },
+    };
+
+    race(addresses, remaining).await
+}
+
+/// Connects to the addresses, returning the first connection established
+///
+/// The addresses are sorted and attempted like the addresses of a host in [`connect`].
+pub async fn connect_addrs(
+    addresses: impl IntoIterator<Item = SocketAddr>,
+) -> io::Result<TcpStream> {
+    race(Ok(addresses.into_iter().collect()), None).await
+}
+
+/// Races the connection attempts to `addresses` and to the addresses returned by `remaining`
+async fn race(
+    addresses: Result<Vec<SocketAddr>, ResolveError>,
+    mut remaining: Option<AddressesFuture<'_>>,
+) -> io::Result<TcpStream> {
+    let mut last_error = None;
+    let mut queue = match addresses {
+        Ok(addresses) => order_without_blocking(addresses).await,
+        Err(e) => {
+            last_error = Some(io::Error::from(e));
+            VecDeque::new()
+        }
+    };
+
+    let mut attempts = FuturesUnordered::new();
+    let delay = time::sleep(Duration::ZERO);
+    tokio::pin!(delay);
+
+    let mut start_next = true;
+    loop {
+        if start_next || attempts.is_empty() {
+            if let Some(addr) = queue.pop_front() {
+                debug!("connecting to {addr}");
+                attempts.push(TcpStream::connect(addr));
+                delay
+                    .as_mut()
+                    .reset(Instant::now() + CONNECTION_ATTEMPT_DELAY);
+            }
+            start_next = false;
+        }
+
+        if attempts.is_empty() && queue.is_empty() && remaining.is_none() {
+            return Err(last_error.unwrap_or_else(|| {
+                io::Error::new(io::ErrorKind::NotFound, "no addresses to connect to")
+            }));
+        }
+
+        tokio::select! {
+            Some(result) = attempts.next() => match result {
+                Ok(stream) => return Ok(stream),
+                Err(e) => {
+                    debug!("connection attempt failed: {e}");
+                    last_error = Some(e);
+                    start_next = true;
+                }
+            },
+            () = &mut delay, if !queue.is_empty() => start_next = true,
+            Some(result) = OptionFuture::from(remaining.as_mut()) => {
+                remaining = None;
+                match result {
+                    Ok(addresses) => {
+                        let addresses = queue.drain(..).chain(addresses).collect();
+                        queue = order_without_blocking(addresses).await;
+                    }
+                    Err(e) => {
+                        debug!("address lookup failed: {e}");
+                        last_error.get_or_insert(io::Error::from(e));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Orders the addresses like [`order`], looking up their source addresses off the async workers
+async fn order_without_blocking(addresses: Vec<SocketAddr>) -> VecDeque<SocketAddr> {
+    let ips = addresses.iter().map(SocketAddr::ip).collect();
+    sort_without_blocking(ips, move || order(addresses)).await
+}
+
+/// Orders the addresses by RFC 6724 and interleaves their address families, RFC 8305 section 4
+fn order(mut addresses: Vec<SocketAddr>) -> VecDeque<SocketAddr> {
+    sort_by_address(&mut addresses, |addr| addr.ip());
+
+    let Some(first) = addresses.first() else {
+        return VecDeque::new();
+    };
+
+    let first_ipv6 = first.is_ipv6();
+    let (first, second): (Vec<_>, Vec<_>) = addresses
+        .into_iter()
+        .partition(|addr| addr.is_ipv6() == first_ipv6);
+
+    let mut first = first.into_iter();
+    let mut second = second.into_iter();
+    let mut ordered = VecDeque::with_capacity(first.len() + second.len());
+    loop {
+        match (first.next(), second.next()) {
+            (None, None) => return ordered,
+            (a, b) => ordered.extend(a.into_iter().chain(b)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use test_support::subscribe;
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    #[test]
+    fn test_order_interleaves_families() {
+        let v4 = |last| SocketAddr::new(Ipv4Addr::new(127, 0, 0, last).into(), 443);
+        let v6 = SocketAddr::new(Ipv6Addr::LOCALHOST.into(), 443);
+
+        let ordered = order(vec![v4(1), v4(2), v4(3), v6]);
+        assert_eq!(ordered.len(), 4);
+
+        // ::1 is preferred, unless IPv6 is not available
+        if ordered[0].is_ipv6() {
+            assert_eq!(ordered, [v6, v4(1), v4(2), v4(3)]);
+        } else {
+            assert_eq!(ordered, [v4(1), v6, v4(2), v4(3)]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_connect_addrs_skips_failed_attempts() {
+        subscribe();
+
+        // a port nothing listens on anymore
+        let closed = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let closed_addr = closed.local_addr().unwrap();
+        drop(closed);
+
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let stream = connect_addrs([closed_addr, addr]).await.unwrap();
+        assert_eq!(stream.peer_addr().unwrap(), addr);
+    }
+
+    #[tokio::test]
+    async fn test_connect_addrs_fails() {
+        subscribe();
+
+        assert_eq!(
+            connect_addrs([]).await.unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+
+        let closed = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let closed_addr = closed.local_addr().unwrap();
+        drop(closed);
+
+        assert_eq!(
+            connect_addrs([closed_addr]).await.unwrap_err().kind(),
+            io::ErrorKind::ConnectionRefused
+        );
+    }
+}
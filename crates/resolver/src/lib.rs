@@ -196,8 +196,11 @@ pub use hickory_proto as proto;
 // reexports from proto
 pub use proto::rr::{IntoName, Name};
 
+pub mod address_sort;
+pub mod cache_file;
 pub mod caching_client;
 pub mod config;
+mod dns64;
 pub mod dns_lru;
 mod error;
 pub use error::{ResolveError, ResolveErrorKind};
@@ -205,10 +208,13 @@ pub use error::{ResolveError, ResolveErrorKind};
 mod h2;
 #[cfg(feature = "__h3")]
 mod h3;
+#[cfg(feature = "tokio")]
+pub mod happy_eyeballs;
 mod hosts;
 pub use hosts::Hosts;
 pub mod lookup;
 pub mod lookup_ip;
+pub mod lookup_svcb;
 // TODO: consider #[doc(hidden)]
 pub mod name_server;
 #[cfg(feature = "tokio")]
@@ -216,7 +222,10 @@ use name_server::TokioConnectionProvider;
 #[cfg(feature = "__quic")]
 mod quic;
 mod resolver;
+mod routing;
 pub use resolver::LookupFuture;
+#[cfg(feature = "smol")]
+pub use resolver::SmolResolver;
 #[cfg(feature = "tokio")]
 pub use resolver::TokioResolver;
 pub use resolver::{Resolver, ResolverBuilder};
@@ -33,7 +33,7 @@ use crate::{
 };
 
 #[cfg(feature = "__dnssec")]
-use crate::proto::dnssec::{DnssecDnsHandle, Proven};
+use crate::proto::dnssec::{DnssecDnsHandle, Proven, ValidationTrace};
 
 /// Result of a DNS query when querying for any record type supported by the Hickory DNS Proto library.
 ///
@@ -231,6 +231,19 @@ pub enum LookupEither<P: ConnectionProvider + Send> {
     Secure(DnssecDnsHandle<RetryDnsHandle<NameServerPool<P>>>),
 }
 
+impl<P: ConnectionProvider> LookupEither<P> {
+    /// Records the DNSSEC validation of the lookups in `trace`, if they are validated
+    #[cfg(feature = "__dnssec")]
+    pub(crate) fn with_validation_trace(&self, trace: &ValidationTrace) -> Self {
+        match self {
+            Self::Retry(client) => Self::Retry(client.clone()),
+            Self::Secure(client) => {
+                Self::Secure(client.clone().with_validation_trace(trace.clone()))
+            }
+        }
+    }
+}
+
 impl<P: ConnectionProvider> DnsHandle for LookupEither<P> {
     type Response = Pin<Box<dyn Stream<Item = Result<DnsResponse, ProtoError>> + Send>>;
 
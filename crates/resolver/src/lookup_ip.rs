@@ -23,6 +23,7 @@ use crate::proto::op::Query;
 use crate::proto::rr::{Name, RData, Record, RecordType};
 use crate::proto::xfer::{DnsHandle, DnsRequestOptions};
 
+use crate::address_sort::sort_by_address;
 use crate::caching_client::CachingClient;
 use crate::config::LookupIpStrategy;
 use crate::dns_lru::MAX_TTL;
@@ -54,6 +55,32 @@ impl LookupIp {
         self.0.valid_until()
     }
 
+    /// Sorts the addresses by the destination address selection of
+    /// [RFC 6724](https://www.rfc-editor.org/rfc/rfc6724), see
+    /// [`sort_addresses`](crate::address_sort::sort_addresses)
+    ///
+    /// Other records, e.g. the CNAME records leading to the addresses, are moved before the
+    /// addresses.
+    pub fn sort_addresses(self) -> Self {
+        let lookup = self.0;
+        let (mut addresses, mut records): (Vec<Record>, Vec<Record>) = lookup
+            .records()
+            .iter()
+            .cloned()
+            .partition(|record| record.data().ip_addr().is_some());
+
+        sort_by_address(&mut addresses, |record| {
+            record.data().ip_addr().expect("only address records")
+        });
+        records.extend(addresses);
+
+        Self(Lookup::new_with_deadline(
+            lookup.query().clone(),
+            Arc::from(records),
+            lookup.valid_until(),
+        ))
+    }
+
     /// Return a reference to the inner lookup
     ///
     /// This can be useful for getting all records from the request
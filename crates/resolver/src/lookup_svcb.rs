@@ -0,0 +1,564 @@
+// Copyright 2015-2025 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! SvcbLookup result from a resolution of SVCB and HTTPS records with a Resolver.
+//!
+//! The lookup follows AliasMode records to the ServiceMode records of a service and resolves the
+//! addresses of their targets, see [RFC 9460](https://www.rfc-editor.org/rfc/rfc9460).
+
+use std::cmp::min;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::slice::Iter;
+use std::sync::Arc;
+use std::time::Instant;
+
+use tracing::debug;
+
+use crate::caching_client::CachingClient;
+use crate::config::LookupIpStrategy;
+use crate::error::*;
+use crate::hosts::Hosts;
+use crate::lookup::Lookup;
+use crate::lookup_ip::LookupIpFuture;
+use crate::proto::op::Query;
+use crate::proto::rr::rdata::svcb::{EchConfigList, SVCB, SvcParamKey, SvcParamValue};
+use crate::proto::rr::{Name, RData, RecordType};
+use crate::proto::xfer::{DnsHandle, DnsRequestOptions};
+use crate::resolver::LookupFuture;
+
+/// Maximum number of AliasMode records followed for a single lookup
+const MAX_ALIAS_CHAIN: usize = 8;
+
+/// Result of a lookup for SVCB or HTTPS records.
+///
+/// The endpoints are ordered by their priority, the most preferred endpoint first.
+#[derive(Debug, Clone)]
+pub struct SvcbLookup {
+    query: Query,
+    endpoints: Vec<SvcbEndpoint>,
+    valid_until: Instant,
+}
+
+impl SvcbLookup {
+    /// Returns an iterator over the endpoints of the service
+    pub fn iter(&self) -> Iter<'_, SvcbEndpoint> {
+        self.endpoints.iter()
+    }
+
+    /// Returns the endpoints of the service, the most preferred endpoint first
+    ///
+    /// An empty list means that the service is not available, i.e. an AliasMode record with the
+    /// root name as target was found.
+    pub fn endpoints(&self) -> &[SvcbEndpoint] {
+        &self.endpoints
+    }
+
+    /// Returns a reference to the Query that was used to produce this result.
+    pub fn query(&self) -> &Query {
+        &self.query
+    }
+
+    /// Returns the `Instant` at which this result is no longer valid.
+    pub fn valid_until(&self) -> Instant {
+        self.valid_until
+    }
+}
+
+impl IntoIterator for SvcbLookup {
+    type Item = SvcbEndpoint;
+    type IntoIter = std::vec::IntoIter<SvcbEndpoint>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.endpoints.into_iter()
+    }
+}
+
+/// An endpoint of a service, from a ServiceMode SVCB or HTTPS record
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SvcbEndpoint {
+    priority: u16,
+    target: Name,
+    port: Option<u16>,
+    alpn: Vec<String>,
+    no_default_alpn: bool,
+    ech_config: Option<EchConfigList>,
+    addresses: Vec<IpAddr>,
+}
+
+impl SvcbEndpoint {
+    /// The SvcPriority of the record, lower values are preferred
+    ///
+    /// This is 0 for the fallback endpoint of an AliasMode record whose target has no SVCB
+    /// records, which has no parameters.
+    pub fn priority(&self) -> u16 {
+        self.priority
+    }
+
+    /// The name of the endpoint, the owner of the record if its TargetName is the root
+    pub fn target(&self) -> &Name {
+        &self.target
+    }
+
+    /// The port of the endpoint, if it differs from the default port of the protocol
+    pub fn port(&self) -> Option<u16> {
+        self.port
+    }
+
+    /// The ALPN protocol identifiers supported by the endpoint, in addition to the default one
+    pub fn alpn(&self) -> &[String] {
+        &self.alpn
+    }
+
+    /// Returns true if the endpoint doesn't support the default protocol, e.g. `http/1.1`
+    pub fn no_default_alpn(&self) -> bool {
+        self.no_default_alpn
+    }
+
+    /// The Encrypted Client Hello configuration of the endpoint
+    pub fn ech_config(&self) -> Option<&EchConfigList> {
+        self.ech_config.as_ref()
+    }
+
+    /// The addresses of the target
+    ///
+    /// These are the A and AAAA records of the target, or the `ipv4hint` and `ipv6hint`
+    /// parameters if the target has no addresses.
+    pub fn addresses(&self) -> &[IpAddr] {
+        &self.addresses
+    }
+
+    fn from_svcb(svcb: &SVCB, target: Name) -> Self {
+        let mut endpoint = Self::fallback(target);
+        endpoint.priority = svcb.svc_priority();
+
+        for (_, value) in svcb.svc_params() {
+            match value {
+                SvcParamValue::Alpn(alpn) => endpoint.alpn.clone_from(&alpn.0),
+                SvcParamValue::NoDefaultAlpn => endpoint.no_default_alpn = true,
+                SvcParamValue::Port(port) => endpoint.port = Some(*port),
+                SvcParamValue::EchConfigList(ech) => endpoint.ech_config = Some(ech.clone()),
+                SvcParamValue::Mandatory(_)
+                | SvcParamValue::Ipv4Hint(_)
+                | SvcParamValue::Ipv6Hint(_)
+                | SvcParamValue::Unknown(_) => {}
+            }
+        }
+
+        endpoint
+    }
+
+    fn fallback(target: Name) -> Self {
+        Self {
+            priority: 0,
+            target,
+            port: None,
+            alpn: Vec::new(),
+            no_default_alpn: false,
+            ech_config: None,
+            addresses: Vec::new(),
+        }
+    }
+}
+
+/// Returns the hinted addresses of the record, matching the lookup IP strategy
+fn hints(svcb: &SVCB, strategy: LookupIpStrategy) -> Vec<IpAddr> {
+    let mut addresses = Vec::new();
+    for (_, value) in svcb.svc_params() {
+        match value {
+            SvcParamValue::Ipv4Hint(hint) if strategy != LookupIpStrategy::Ipv6Only => {
+                addresses.extend(hint.0.iter().map(|a| IpAddr::from(Ipv4Addr::from(*a))))
+            }
+            SvcParamValue::Ipv6Hint(hint) if strategy != LookupIpStrategy::Ipv4Only => addresses
+                .extend(
+                    hint.0
+                        .iter()
+                        .map(|aaaa| IpAddr::from(Ipv6Addr::from(*aaaa))),
+                ),
+            _ => {}
+        }
+    }
+
+    addresses
+}
+
+/// Returns true if all the mandatory keys of the record are supported
+fn is_supported(svcb: &SVCB) -> bool {
+    svcb.svc_params().iter().all(|(_, value)| match value {
+        SvcParamValue::Mandatory(mandatory) => mandatory.0.iter().all(|key| {
+            matches!(
+                key,
+                SvcParamKey::Alpn
+                    | SvcParamKey::NoDefaultAlpn
+                    | SvcParamKey::Port
+                    | SvcParamKey::Ipv4Hint
+                    | SvcParamKey::EchConfigList
+                    | SvcParamKey::Ipv6Hint
+            )
+        }),
+        _ => true,
+    })
+}
+
+/// Returns the SVCB records of the lookup with their owners
+fn svcb_records(lookup: &Lookup) -> Vec<(&Name, &SVCB)> {
+    lookup
+        .record_iter()
+        .filter_map(|record| match record.data() {
+            RData::SVCB(svcb) => Some((record.name(), svcb)),
+            RData::HTTPS(https) => Some((record.name(), &https.0)),
+            _ => None,
+        })
+        .collect()
+}
+
+#[derive(Clone)]
+pub(crate) struct SvcbLookupContext<C: DnsHandle + 'static> {
+    pub(crate) client: CachingClient<C>,
+    pub(crate) options: DnsRequestOptions,
+    pub(crate) hosts: Arc<Hosts>,
+    pub(crate) strategy: LookupIpStrategy,
+}
+
+impl<C: DnsHandle + 'static> SvcbLookupContext<C> {
+    /// Looks up the SVCB or HTTPS records of the first name of `names` that has any
+    ///
+    /// AliasMode records are followed up to `MAX_ALIAS_CHAIN` times, then the addresses of the
+    /// targets of the ServiceMode records are resolved.
+    pub(crate) async fn lookup(
+        self,
+        names: Vec<Name>,
+        record_type: RecordType,
+    ) -> Result<SvcbLookup, ResolveError> {
+        let mut lookup = self.lookup_records(names, record_type).await?;
+        let query = lookup.query().clone();
+        let mut valid_until = lookup.valid_until();
+        let mut aliases = vec![query.name().clone()];
+
+        let records = loop {
+            let records = svcb_records(&lookup);
+
+            // if an RRSet contains a record in AliasMode, the ServiceMode records are ignored
+            let Some((_, alias)) = records.iter().find(|(_, svcb)| svcb.svc_priority() == 0) else {
+                break records;
+            };
+
+            let target = alias.target_name().clone();
+            if target.is_root() {
+                debug!("service {} is not available", query.name());
+                return Ok(SvcbLookup {
+                    query,
+                    endpoints: Vec::new(),
+                    valid_until,
+                });
+            }
+
+            if aliases.contains(&target) {
+                return Err(ResolveErrorKind::Msg(format!(
+                    "loop in the AliasMode records of {} at {target}",
+                    query.name()
+                ))
+                .into());
+            }
+
+            if aliases.len() > MAX_ALIAS_CHAIN {
+                return Err(ResolveErrorKind::Msg(format!(
+                    "more than {MAX_ALIAS_CHAIN} AliasMode records for {}",
+                    query.name()
+                ))
+                .into());
+            }
+
+            aliases.push(target.clone());
+            lookup = match self.lookup_records(vec![target.clone()], record_type).await {
+                Ok(lookup) => lookup,
+                // without ServiceMode records, the target of the alias is used like the origin
+                Err(e) if e.is_no_records_found() => {
+                    let mut endpoint = SvcbEndpoint::fallback(target.clone());
+                    endpoint.addresses = self.lookup_addresses(&target, &mut valid_until).await;
+                    return Ok(SvcbLookup {
+                        query,
+                        endpoints: vec![endpoint],
+                        valid_until,
+                    });
+                }
+                Err(e) => return Err(e),
+            };
+            valid_until = min(valid_until, lookup.valid_until());
+        };
+
+        let mut records = records
+            .into_iter()
+            .filter(|(_, svcb)| is_supported(svcb))
+            .collect::<Vec<_>>();
+        records.sort_by_key(|(_, svcb)| svcb.svc_priority());
+
+        let mut resolved: Vec<(Name, Vec<IpAddr>)> = Vec::new();
+        let mut endpoints = Vec::with_capacity(records.len());
+        for (owner, svcb) in records {
+            let target = match svcb.target_name().is_root() {
+                true => owner.clone(),
+                false => svcb.target_name().clone(),
+            };
+
+            let addresses = match resolved.iter().find(|(name, _)| *name == target) {
+                Some((_, addresses)) => addresses.clone(),
+                None => {
+                    let addresses = self.lookup_addresses(&target, &mut valid_until).await;
+                    resolved.push((target.clone(), addresses.clone()));
+                    addresses
+                }
+            };
+
+            let mut endpoint = SvcbEndpoint::from_svcb(svcb, target);
+            endpoint.addresses = match addresses.is_empty() {
+                true => hints(svcb, self.strategy),
+                false => addresses,
+            };
+            endpoints.push(endpoint);
+        }
+
+        Ok(SvcbLookup {
+            query,
+            endpoints,
+            valid_until,
+        })
+    }
+
+    async fn lookup_records(
+        &self,
+        names: Vec<Name>,
+        record_type: RecordType,
+    ) -> Result<Lookup, ResolveError> {
+        LookupFuture::lookup_with_hosts(
+            names,
+            record_type,
+            self.options,
+            self.client.clone(),
+            self.hosts.clone(),
+        )
+        .await
+    }
+
+    /// Resolves the addresses of `target`, failures result in no addresses
+    async fn lookup_addresses(&self, target: &Name, valid_until: &mut Instant) -> Vec<IpAddr> {
+        let lookup = LookupIpFuture::lookup(
+            vec![target.clone()],
+            self.strategy,
+            self.client.clone(),
+            self.options,
+            self.hosts.clone(),
+            None,
+        )
+        .await;
+
+        match lookup {
+            Ok(lookup) => {
+                *valid_until = min(*valid_until, lookup.valid_until());
+                lookup.iter().collect()
+            }
+            Err(e) => {
+                debug!("failed to resolve the addresses of {target}: {e}");
+                Vec::new()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::str::FromStr;
+
+    use futures_executor::block_on;
+    use test_support::subscribe;
+
+    use crate::lookup_ip::tests::{empty, mock};
+    use crate::proto::ProtoError;
+    use crate::proto::op::Message;
+    use crate::proto::rr::Record;
+    use crate::proto::rr::rdata::svcb::{Alpn, IpHint, Mandatory};
+    use crate::proto::rr::rdata::{A, HTTPS};
+    use crate::proto::xfer::DnsResponse;
+
+    use super::*;
+
+    fn https_message(name: &str, records: Vec<SVCB>) -> Result<DnsResponse, ProtoError> {
+        let name = Name::from_str(name).unwrap();
+        let mut message = Message::new();
+        message.add_query(Query::query(name.clone(), RecordType::HTTPS));
+        message.insert_answers(
+            records
+                .into_iter()
+                .map(|svcb| Record::from_rdata(name.clone(), 300, RData::HTTPS(HTTPS(svcb))))
+                .collect(),
+        );
+
+        Ok(DnsResponse::from_message(message).unwrap())
+    }
+
+    fn a_message(name: &str, ip: Ipv4Addr) -> Result<DnsResponse, ProtoError> {
+        let name = Name::from_str(name).unwrap();
+        let mut message = Message::new();
+        message.add_query(Query::query(name.clone(), RecordType::A));
+        message.insert_answers(vec![Record::from_rdata(name, 300, RData::A(A(ip)))]);
+
+        Ok(DnsResponse::from_message(message).unwrap())
+    }
+
+    fn alias(target: &str) -> SVCB {
+        SVCB::new(0, Name::from_str(target).unwrap(), Vec::new())
+    }
+
+    fn context(
+        messages: Vec<Result<DnsResponse, ProtoError>>,
+    ) -> SvcbLookupContext<impl DnsHandle> {
+        SvcbLookupContext {
+            client: CachingClient::new(0, mock(messages), false),
+            options: DnsRequestOptions::default(),
+            hosts: Arc::new(Hosts::default()),
+            strategy: LookupIpStrategy::Ipv4Only,
+        }
+    }
+
+    #[test]
+    fn test_service_mode() {
+        subscribe();
+
+        let cx = context(vec![
+            // the mock returns the messages from last to first
+            a_message("svc.example.com.", Ipv4Addr::new(192, 0, 2, 2)),
+            a_message("example.com.", Ipv4Addr::new(192, 0, 2, 1)),
+            https_message(
+                "example.com.",
+                vec![
+                    SVCB::new(
+                        2,
+                        Name::from_str("svc.example.com.").unwrap(),
+                        vec![(SvcParamKey::Port, SvcParamValue::Port(8443))],
+                    ),
+                    SVCB::new(
+                        1,
+                        Name::root(),
+                        vec![(
+                            SvcParamKey::Alpn,
+                            SvcParamValue::Alpn(Alpn(vec!["h3".to_string(), "h2".to_string()])),
+                        )],
+                    ),
+                ],
+            ),
+        ]);
+
+        let lookup = block_on(cx.lookup(
+            vec![Name::from_str("example.com.").unwrap()],
+            RecordType::HTTPS,
+        ))
+        .unwrap();
+
+        let endpoints = lookup.endpoints();
+        assert_eq!(endpoints.len(), 2);
+        assert_eq!(endpoints[0].priority(), 1);
+        assert_eq!(
+            endpoints[0].target(),
+            &Name::from_str("example.com.").unwrap()
+        );
+        assert_eq!(endpoints[0].alpn(), &["h3".to_string(), "h2".to_string()]);
+        assert_eq!(endpoints[0].port(), None);
+        assert_eq!(
+            endpoints[0].addresses(),
+            &[IpAddr::from(Ipv4Addr::new(192, 0, 2, 1))]
+        );
+        assert_eq!(endpoints[1].priority(), 2);
+        assert_eq!(endpoints[1].port(), Some(8443));
+        assert_eq!(
+            endpoints[1].addresses(),
+            &[IpAddr::from(Ipv4Addr::new(192, 0, 2, 2))]
+        );
+    }
+
+    #[test]
+    fn test_alias_mode_with_hints() {
+        subscribe();
+
+        let cx = context(vec![
+            // no A records for the target
+            empty(),
+            https_message(
+                "svc.example.net.",
+                vec![
+                    SVCB::new(
+                        1,
+                        Name::root(),
+                        vec![(
+                            SvcParamKey::Ipv4Hint,
+                            SvcParamValue::Ipv4Hint(IpHint(vec![A::new(192, 0, 2, 3)])),
+                        )],
+                    ),
+                    // not supported, so it's ignored
+                    SVCB::new(
+                        1,
+                        Name::root(),
+                        vec![(
+                            SvcParamKey::Mandatory,
+                            SvcParamValue::Mandatory(Mandatory(vec![SvcParamKey::Key(65000)])),
+                        )],
+                    ),
+                ],
+            ),
+            https_message("example.com.", vec![alias("svc.example.net.")]),
+        ]);
+
+        let lookup = block_on(cx.lookup(
+            vec![Name::from_str("example.com.").unwrap()],
+            RecordType::HTTPS,
+        ))
+        .unwrap();
+
+        let endpoints = lookup.endpoints();
+        assert_eq!(endpoints.len(), 1);
+        assert_eq!(
+            endpoints[0].target(),
+            &Name::from_str("svc.example.net.").unwrap()
+        );
+        assert_eq!(
+            endpoints[0].addresses(),
+            &[IpAddr::from(Ipv4Addr::new(192, 0, 2, 3))]
+        );
+    }
+
+    #[test]
+    fn test_alias_mode_unavailable() {
+        subscribe();
+
+        let cx = context(vec![https_message("example.com.", vec![alias(".")])]);
+        let lookup = block_on(cx.lookup(
+            vec![Name::from_str("example.com.").unwrap()],
+            RecordType::HTTPS,
+        ))
+        .unwrap();
+
+        assert!(lookup.endpoints().is_empty());
+    }
+
+    #[test]
+    fn test_alias_mode_loop() {
+        subscribe();
+
+        let cx = context(vec![
+            https_message("b.example.com.", vec![alias("a.example.com.")]),
+            https_message("a.example.com.", vec![alias("b.example.com.")]),
+            https_message("example.com.", vec![alias("a.example.com.")]),
+        ]);
+
+        assert!(
+            block_on(cx.lookup(
+                vec![Name::from_str("example.com.").unwrap()],
+                RecordType::HTTPS
+            ))
+            .is_err()
+        );
+    }
+}
@@ -15,16 +15,14 @@ use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context, Poll};
 
+#[cfg(feature = "smol")]
+use crate::proto::runtime::SmolRuntimeProvider;
 use crate::proto::runtime::Spawn;
 #[cfg(feature = "tokio")]
 use crate::proto::runtime::TokioRuntimeProvider;
-#[cfg(feature = "__tls")]
-use crate::proto::runtime::iocompat::AsyncIoStdAsTokio;
 use futures_util::future::FutureExt;
 use futures_util::ready;
 use futures_util::stream::{Stream, StreamExt};
-#[cfg(feature = "__tls")]
-use tokio_rustls::client::TlsStream as TokioTlsStream;
 
 use crate::config::{NameServerConfig, ResolverOpts};
 #[cfg(any(feature = "__h3", feature = "__https"))]
@@ -39,7 +37,7 @@ use crate::proto::quic::{QuicClientConnect, QuicClientStream};
 #[allow(unused_imports)] // Complicated cfg for which protocols are enabled
 use crate::proto::runtime::TokioTime;
 #[cfg(feature = "__tls")]
-use crate::proto::runtime::iocompat::AsyncIoTokioAsStd;
+use crate::proto::rustls::TlsClientStream;
 use crate::proto::{
     ProtoError,
     runtime::RuntimeProvider,
@@ -67,11 +65,15 @@ pub trait ConnectionProvider: 'static + Clone + Send + Sync + Unpin {
         config: &NameServerConfig,
         options: &ResolverOpts,
     ) -> Result<Self::FutureConn, io::Error>;
-}
 
-#[cfg(feature = "__tls")]
-/// Predefined type for TLS client stream
-type TlsClientStream<S> = TcpClientStream<AsyncIoTokioAsStd<TokioTlsStream<AsyncIoStdAsTokio<S>>>>;
+    /// Create a handle to the runtime, for spawning background tasks
+    ///
+    /// Features which need background tasks, like prefetching, are disabled if this returns
+    /// `None`, which is the default.
+    fn create_handle(&self) -> Option<<Self::RuntimeProvider as RuntimeProvider>::Handle> {
+        None
+    }
+}
 
 /// The variants of all supported connections for the Resolver
 #[allow(clippy::large_enum_variant, clippy::type_complexity)]
@@ -105,7 +107,7 @@ pub(crate) enum ConnectionConnect<R: RuntimeProvider> {
                 TlsClientStream<<R as RuntimeProvider>::Tcp>,
             >,
             DnsMultiplexer<TlsClientStream<<R as RuntimeProvider>::Tcp>>,
-            TokioTime,
+            R::Timer,
         >,
     ),
     #[cfg(all(feature = "__https", feature = "tokio"))]
@@ -182,6 +184,10 @@ impl DnsHandle for GenericConnection {
 #[cfg(feature = "tokio")]
 pub type TokioConnectionProvider = GenericConnector<TokioRuntimeProvider>;
 
+/// ConnectionProvider for the smol runtime, supports UDP, TCP and TLS.
+#[cfg(feature = "smol")]
+pub type SmolConnectionProvider = GenericConnector<SmolRuntimeProvider>;
+
 /// Default connector for `GenericConnection`
 #[derive(Clone)]
 pub struct GenericConnector<P: RuntimeProvider> {
@@ -331,6 +337,10 @@ impl<P: RuntimeProvider> ConnectionProvider for GenericConnector<P> {
             spawner: self.runtime_provider.create_handle(),
         })
     }
+
+    fn create_handle(&self) -> Option<P::Handle> {
+        Some(self.runtime_provider.create_handle())
+    }
 }
 
 /// A stream of response to a DNS request.
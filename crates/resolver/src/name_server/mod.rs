@@ -19,6 +19,10 @@ pub use self::name_server::{GenericNameServer, NameServer};
 pub use self::name_server_pool::{GenericNameServerPool, NameServerPool};
 use self::name_server_state::NameServerState;
 use self::name_server_stats::NameServerStats;
+#[cfg(feature = "metrics")]
+use self::name_server_stats::UpstreamMetrics;
 
+#[cfg(feature = "smol")]
+pub use self::connection_provider::SmolConnectionProvider;
 #[cfg(feature = "tokio")]
 pub use self::connection_provider::TokioConnectionProvider;
@@ -9,14 +9,24 @@ use std::fmt::{self, Debug, Formatter};
 use std::pin::Pin;
 use std::sync::Arc;
 use std::time::Instant;
+#[cfg(feature = "dnstap")]
+use std::time::SystemTime;
 
 use futures_util::lock::Mutex;
 use futures_util::stream::{Stream, once};
 use tracing::debug;
+#[cfg(feature = "__tls")]
+use tracing::warn;
 
 use crate::config::{NameServerConfig, ResolverOpts};
+#[cfg(feature = "metrics")]
+use crate::name_server::UpstreamMetrics;
 use crate::name_server::connection_provider::{ConnectionProvider, GenericConnector};
 use crate::name_server::{NameServerState, NameServerStats};
+#[cfg(feature = "metrics")]
+use crate::proto::ProtoErrorKind;
+#[cfg(feature = "dnstap")]
+use crate::proto::dnstap::{Dnstap, DnstapMessage, DnstapMessageType};
 use crate::proto::{
     ProtoError,
     xfer::{DnsHandle, DnsRequest, DnsResponse, FirstAnswer},
@@ -30,7 +40,12 @@ pub struct NameServer<P: ConnectionProvider> {
     client: Arc<Mutex<Option<P::Conn>>>,
     state: Arc<NameServerState>,
     pub(crate) stats: Arc<NameServerStats>,
+    #[cfg(feature = "metrics")]
+    metrics: Arc<UpstreamMetrics>,
     connection_provider: P,
+    /// Why the pinned certificates of the configuration can't be enforced, connections fail then
+    #[cfg(feature = "__tls")]
+    pin_error: Option<ProtoError>,
 }
 
 /// Specifies the details of a remote NameServer used for lookups
@@ -50,31 +65,53 @@ where
     P: ConnectionProvider + Send,
 {
     /// Construct a new Nameserver with the configuration and options. The connection provider will create UDP and TCP sockets
-    pub fn new(config: NameServerConfig, options: ResolverOpts, connection_provider: P) -> Self {
+    pub fn new(
+        config: NameServerConfig,
+        #[cfg_attr(not(feature = "__tls"), allow(unused_mut))] mut options: ResolverOpts,
+        connection_provider: P,
+    ) -> Self {
+        #[cfg(feature = "metrics")]
+        let metrics = Arc::new(UpstreamMetrics::new(&config, options.per_upstream_metrics));
+        #[cfg(feature = "__tls")]
+        let pin_error = pin_certificates(&config, &mut options).err();
+
         Self {
             config,
             options,
             client: Arc::new(Mutex::new(None)),
             state: Arc::new(NameServerState::init(None)),
             stats: Arc::new(NameServerStats::default()),
+            #[cfg(feature = "metrics")]
+            metrics,
             connection_provider,
+            #[cfg(feature = "__tls")]
+            pin_error,
         }
     }
 
     #[doc(hidden)]
     pub fn from_conn(
         config: NameServerConfig,
-        options: ResolverOpts,
+        #[cfg_attr(not(feature = "__tls"), allow(unused_mut))] mut options: ResolverOpts,
         client: P::Conn,
         connection_provider: P,
     ) -> Self {
+        #[cfg(feature = "metrics")]
+        let metrics = Arc::new(UpstreamMetrics::new(&config, options.per_upstream_metrics));
+        #[cfg(feature = "__tls")]
+        let pin_error = pin_certificates(&config, &mut options).err();
+
         Self {
             config,
             options,
             client: Arc::new(Mutex::new(Some(client))),
             state: Arc::new(NameServerState::init(None)),
             stats: Arc::new(NameServerStats::default()),
+            #[cfg(feature = "metrics")]
+            metrics,
             connection_provider,
+            #[cfg(feature = "__tls")]
+            pin_error,
         }
     }
 
@@ -100,6 +137,11 @@ where
         if self.state.is_failed() || client.is_none() {
             debug!("reconnecting: {:?}", self.config);
 
+            #[cfg(feature = "__tls")]
+            if let Some(error) = &self.pin_error {
+                return Err(error.clone());
+            }
+
             // TODO: we need the local EDNS options
             self.state.reinit(None);
 
@@ -125,14 +167,36 @@ where
         request: R,
     ) -> Result<DnsResponse, ProtoError> {
         let client = self.connected_mut_client().await?;
+        let request: DnsRequest = request.into();
+        #[cfg(feature = "dnstap")]
+        let tap = self
+            .options
+            .dnstap
+            .clone()
+            .map(|dnstap| (dnstap, request.clone(), SystemTime::now()));
+
+        #[cfg(feature = "metrics")]
+        self.metrics.queries.increment(1);
+
+        if let Some(circuit_breaker) = &self.options.circuit_breaker {
+            self.stats.record_probe(circuit_breaker);
+        }
+
         let now = Instant::now();
         let response = client.send(request).first_answer().await;
         let rtt = now.elapsed();
 
+        #[cfg(feature = "dnstap")]
+        if let Some((dnstap, query, query_time)) = tap {
+            self.tap(&dnstap, query, query_time, response.as_ref().ok());
+        }
+
         match response {
             Ok(response) => {
                 // Record the measured latency.
                 self.stats.record_rtt(rtt);
+                #[cfg(feature = "metrics")]
+                self.metrics.srtt.set(self.stats.srtt().as_secs_f64());
 
                 // First evaluate if the message succeeded.
                 let response =
@@ -154,6 +218,13 @@ where
 
                 // record the failure
                 self.stats.record_connection_failure();
+                #[cfg(feature = "metrics")]
+                {
+                    if matches!(error.kind(), ProtoErrorKind::Timeout) {
+                        self.metrics.timeouts.increment(1);
+                    }
+                    self.metrics.srtt.set(self.stats.srtt().as_secs_f64());
+                }
 
                 // These are connection failures, not lookup failures, that is handled in the resolver layer
                 Err(error)
@@ -161,10 +232,83 @@ where
         }
     }
 
+    /// Logs an exchange with the name server as dnstap messages
+    #[cfg(feature = "dnstap")]
+    fn tap(
+        &self,
+        dnstap: &Dnstap,
+        mut query: DnsRequest,
+        query_time: SystemTime,
+        response: Option<&DnsResponse>,
+    ) {
+        let (server, protocol) = (self.config.socket_addr, self.config.protocol);
+
+        // the message id is assigned by the connection, take it from the response
+        if let Some(response) = response {
+            query.set_id(response.id());
+        }
+
+        match query.to_vec() {
+            Ok(bytes) => dnstap.log(
+                DnstapMessage::query(DnstapMessageType::ResolverQuery, protocol, bytes)
+                    .with_query_time(query_time)
+                    .with_response_address(server),
+            ),
+            Err(e) => debug!("failed to encode query for dnstap: {e}"),
+        }
+
+        if let Some(response) = response {
+            dnstap.log(
+                DnstapMessage::response(
+                    DnstapMessageType::ResolverResponse,
+                    protocol,
+                    response.as_buffer().to_vec(),
+                )
+                .with_query_time(query_time)
+                .with_response_address(server),
+            );
+        }
+    }
+
     /// Specifies that this NameServer will treat negative responses as permanent failures and will not retry
     pub fn trust_nx_responses(&self) -> bool {
         self.config.trust_negative_responses
     }
+
+    /// Returns the config of this NameServer
+    pub fn config(&self) -> &NameServerConfig {
+        &self.config
+    }
+}
+
+/// Replaces the TLS configuration in `options` with one enforcing the pinned certificates of
+/// `config`, once for all connections to the name server
+#[cfg(feature = "__tls")]
+fn pin_certificates(
+    config: &NameServerConfig,
+    options: &mut ResolverOpts,
+) -> Result<(), ProtoError> {
+    if config.tls_pins.is_empty() {
+        return Ok(());
+    }
+
+    let result = match &options.tls_verifier {
+        Some(verifier) => crate::proto::rustls::pin_certificates(
+            &mut options.tls_config,
+            verifier.clone(),
+            config.tls_pins.clone(),
+        )
+        .map_err(|e| ProtoError::from(format!("failed to pin certificates: {e}"))),
+        None => Err(ProtoError::from(
+            "pinned certificates require the tls_verifier option",
+        )),
+    };
+
+    if let Err(e) = &result {
+        warn!(%config, "{e}");
+    }
+
+    result
 }
 
 impl<P> DnsHandle for NameServer<P>
@@ -215,6 +359,7 @@ mod tests {
             protocol: Protocol::Udp,
             tls_dns_name: None,
             http_endpoint: None,
+            tls_pins: Vec::new(),
             trust_negative_responses: false,
             bind_addr: None,
         };
@@ -236,6 +381,36 @@ mod tests {
         assert_eq!(response.response_code(), ResponseCode::NoError);
     }
 
+    #[cfg(feature = "__tls")]
+    #[tokio::test]
+    async fn test_pins_require_verifier() {
+        subscribe();
+
+        let options = ResolverOpts::default();
+        let config = NameServerConfig {
+            tls_dns_name: Some("dns.example".to_owned()),
+            tls_pins: vec![[0; 32]],
+            ..NameServerConfig::new(
+                SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 252)), 853),
+                Protocol::Tls,
+            )
+        };
+        let name_server =
+            GenericNameServer::new(config, options, TokioConnectionProvider::default());
+        assert!(name_server.pin_error.is_some());
+
+        let name = Name::parse("www.example.com.", None).unwrap();
+        let error = name_server
+            .lookup(
+                Query::query(name, RecordType::A),
+                DnsRequestOptions::default(),
+            )
+            .first_answer()
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("tls_verifier"), "{error}");
+    }
+
     #[tokio::test]
     async fn test_failed_name_server() {
         subscribe();
@@ -249,6 +424,7 @@ mod tests {
             protocol: Protocol::Udp,
             tls_dns_name: None,
             http_endpoint: None,
+            tls_pins: Vec::new(),
             trust_negative_responses: false,
             bind_addr: None,
         };
@@ -12,16 +12,20 @@ use std::sync::{
     atomic::{AtomicUsize, Ordering as AtomicOrdering},
 };
 use std::task::{Context, Poll};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use futures_util::future::FutureExt;
+use futures_util::future::{self, Either, FutureExt};
 use futures_util::stream::{FuturesUnordered, Stream, StreamExt, once};
 use smallvec::SmallVec;
 use tracing::debug;
 
-use crate::config::{NameServerConfigGroup, ResolverConfig, ResolverOpts, ServerOrderingStrategy};
+use crate::config::{
+    CircuitBreakerConfig, HedgingConfig, NameServerConfig, NameServerConfigGroup, ResolverConfig,
+    ResolverOpts, ServerOrderingStrategy,
+};
 use crate::name_server::connection_provider::{ConnectionProvider, GenericConnector};
 use crate::name_server::name_server::NameServer;
+use crate::name_server::name_server_stats::NameServerStats;
 use crate::proto::runtime::{RuntimeProvider, Time};
 use crate::proto::xfer::{DnsHandle, DnsRequest, DnsResponse, FirstAnswer};
 use crate::proto::{ProtoError, ProtoErrorKind};
@@ -124,6 +128,14 @@ where
         &self.options
     }
 
+    /// Returns the configs of the pool's name servers, the datagram ones first
+    pub fn name_server_configs(&self) -> impl Iterator<Item = &NameServerConfig> {
+        self.datagram_conns
+            .iter()
+            .chain(self.stream_conns.iter())
+            .map(NameServer::config)
+    }
+
     #[cfg(test)]
     #[allow(dead_code)]
     fn from_nameservers_test(
@@ -148,6 +160,23 @@ where
     ) -> Result<DnsResponse, ProtoError> {
         let mut conns: Vec<NameServer<P>> = conns.to_vec();
 
+        // name servers are only used while all of them are out of rotation, otherwise a
+        // single query claims the probe of a name server when it is sent, see `claim_probe`
+        let mut circuit_breaker = opts.circuit_breaker;
+        if let Some(config) = &circuit_breaker {
+            let available = conns
+                .iter()
+                .filter(|conn| conn.stats.is_available(config))
+                .cloned()
+                .collect::<Vec<_>>();
+            if !available.is_empty() {
+                conns = available;
+            } else {
+                debug!("all name servers are out of rotation");
+                circuit_breaker = None;
+            }
+        }
+
         match opts.server_ordering_strategy {
             // select the highest priority connection
             //   reorder the connections based on current view...
@@ -171,7 +200,10 @@ where
         }
         let request_loop = request.clone();
 
-        parallel_conn_loop(conns, request_loop, opts).await
+        match opts.hedging {
+            Some(hedging) => hedged_conn_loop(conns, request_loop, hedging, circuit_breaker).await,
+            None => parallel_conn_loop(conns, request_loop, opts, circuit_breaker).await,
+        }
     }
 }
 
@@ -243,6 +275,7 @@ async fn parallel_conn_loop<P>(
     mut conns: Vec<NameServer<P>>,
     request: DnsRequest,
     opts: ResolverOpts,
+    circuit_breaker: Option<CircuitBreakerConfig>,
 ) -> Result<DnsResponse, ProtoError>
 where
     P: ConnectionProvider + 'static,
@@ -267,11 +300,14 @@ where
 
         // construct the parallel requests, 2 is the default
         let mut par_conns = SmallVec::<[NameServer<P>; 2]>::new();
-        let count = conns.len().min(opts.num_concurrent_reqs.max(1));
+        let count = opts.num_concurrent_reqs.max(1);
 
         // Shuffe DNS NameServers to avoid overloads to the first configured ones
-        for conn in conns.drain(..count) {
-            par_conns.push(conn);
+        while par_conns.len() < count && !conns.is_empty() {
+            let conn = conns.remove(0);
+            if claim_probe(&conn, circuit_breaker.as_ref()) {
+                par_conns.push(conn);
+            }
         }
 
         if par_conns.is_empty() {
@@ -326,6 +362,133 @@ where
     }
 }
 
+/// Sends the request to the conns one after another, each time the previous ones failed or did not
+/// answer within the hedging percentile of their RTTs, returning the first answer
+async fn hedged_conn_loop<P>(
+    conns: Vec<NameServer<P>>,
+    request: DnsRequest,
+    hedging: HedgingConfig,
+    circuit_breaker: Option<CircuitBreakerConfig>,
+) -> Result<DnsResponse, ProtoError>
+where
+    P: ConnectionProvider + 'static,
+{
+    let mut err = ProtoError::from(ProtoErrorKind::NoConnections);
+    let mut conns = conns.into_iter();
+    let mut requests = FuturesUnordered::new();
+
+    loop {
+        let next = conns
+            .by_ref()
+            .find(|conn| claim_probe(conn, circuit_breaker.as_ref()));
+        let delay = match next {
+            Some(conn) => {
+                let delay = conn
+                    .stats
+                    .rtt_percentile(hedging.rtt_percentile)
+                    .unwrap_or(hedging.initial_delay);
+                let pending = PendingRtt::new(&conn);
+                let response = conn.send(request.clone()).first_answer();
+                requests.push(async move {
+                    let result = response.await;
+                    pending.finish();
+                    result
+                });
+                Some(delay)
+            }
+            None if requests.is_empty() => return Err(err),
+            None => None,
+        };
+
+        // once all conns are used, wait for the remaining requests
+        let mut delay = match delay {
+            Some(delay) => {
+                <<P as ConnectionProvider>::RuntimeProvider as RuntimeProvider>::Timer::delay_for(
+                    delay,
+                )
+                .boxed()
+            }
+            None => future::pending().boxed(),
+        };
+
+        loop {
+            let (result, pending) = match future::select(requests.next(), delay).await {
+                Either::Left((Some(result), pending)) => (result, pending),
+                Either::Left((None, _)) => return Err(err),
+                Either::Right(((), _)) => {
+                    debug!("no answer within the hedging delay, sending to the next name server");
+                    break;
+                }
+            };
+
+            let e = match result {
+                Ok(response) => return Ok(response),
+                Err(e) => e,
+            };
+
+            match e.kind() {
+                ProtoErrorKind::NoRecordsFound {
+                    trusted, soa, ns, ..
+                } if *trusted || soa.is_some() || ns.is_some() => {
+                    return Err(e);
+                }
+                _ if matches!(err.kind(), ProtoErrorKind::NoConnections)
+                    || err.cmp_specificity(&e) == Ordering::Less =>
+                {
+                    err = e;
+                }
+                _ => {}
+            }
+
+            if !conns.as_slice().is_empty() {
+                debug!("request failed, sending to the next name server");
+                break;
+            }
+            delay = pending;
+        }
+    }
+}
+
+/// Returns false if `conn` is out of rotation and another query claimed its probe already, see
+/// `NameServerStats::claim_probe`
+fn claim_probe<P: ConnectionProvider>(
+    conn: &NameServer<P>,
+    circuit_breaker: Option<&CircuitBreakerConfig>,
+) -> bool {
+    circuit_breaker.map_or(true, |config| conn.stats.claim_probe(config))
+}
+
+/// Records the time a request has been pending as a lower bound of the RTT of its name server,
+/// when the request is cancelled because another name server answered first
+///
+/// Otherwise slow name servers would keep their RTT, and be tried first again.
+struct PendingRtt {
+    stats: Option<Arc<NameServerStats>>,
+    sent: Instant,
+}
+
+impl PendingRtt {
+    fn new<P: ConnectionProvider>(conn: &NameServer<P>) -> Self {
+        Self {
+            stats: Some(conn.stats.clone()),
+            sent: Instant::now(),
+        }
+    }
+
+    /// The request completed, the name server recorded its RTT itself
+    fn finish(mut self) {
+        self.stats = None;
+    }
+}
+
+impl Drop for PendingRtt {
+    fn drop(&mut self) {
+        if let Some(stats) = &self.stats {
+            stats.record_pending_rtt(self.sent.elapsed());
+        }
+    }
+}
+
 #[allow(clippy::large_enum_variant)]
 pub(crate) enum Local {
     #[allow(dead_code)]
@@ -405,6 +568,7 @@ mod tests {
             protocol: Protocol::Udp,
             tls_dns_name: None,
             http_endpoint: None,
+            tls_pins: Vec::new(),
             trust_negative_responses: false,
             bind_addr: None,
         };
@@ -414,6 +578,7 @@ mod tests {
             protocol: Protocol::Udp,
             tls_dns_name: None,
             http_endpoint: None,
+            tls_pins: Vec::new(),
             trust_negative_responses: false,
             bind_addr: None,
         };
@@ -476,6 +641,7 @@ mod tests {
             protocol: Protocol::Tcp,
             tls_dns_name: None,
             http_endpoint: None,
+            tls_pins: Vec::new(),
             trust_negative_responses: false,
             bind_addr: None,
         };
@@ -5,13 +5,20 @@
 // https://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use std::collections::VecDeque;
 use std::sync::{
     Arc,
     atomic::{self, AtomicU32},
 };
 
+#[cfg(feature = "metrics")]
+use metrics::{Counter, Gauge, Unit, counter, describe_counter, describe_gauge, gauge};
 use parking_lot::Mutex;
 
+use crate::config::CircuitBreakerConfig;
+#[cfg(feature = "metrics")]
+use crate::config::NameServerConfig;
+
 #[cfg(not(test))]
 use std::time::{Duration, Instant};
 #[cfg(test)]
@@ -49,6 +56,15 @@ pub(crate) struct NameServerStats {
 
     /// The last time the `srtt_microseconds` value was updated.
     last_update: Arc<Mutex<Option<Instant>>>,
+
+    /// The most recent RTTs, for the percentiles used to hedge queries.
+    recent_rtts: Mutex<VecDeque<Duration>>,
+
+    /// The number of connection failures since the last successful query.
+    consecutive_failures: AtomicU32,
+
+    /// The last time a query failed, or was sent to probe whether the server recovered.
+    last_failure: Mutex<Option<Instant>>,
 }
 
 impl Default for NameServerStats {
@@ -76,16 +92,30 @@ fn compute_srtt_factor(last_update: Instant, weight: u32) -> f64 {
 impl NameServerStats {
     const CONNECTION_FAILURE_PENALTY: u32 = Duration::from_millis(150).as_micros() as u32;
     const MAX_SRTT_MICROS: u32 = Duration::from_secs(5).as_micros() as u32;
+    const RECENT_RTTS: usize = 32;
 
     pub(crate) fn new(initial_srtt: Duration) -> Self {
         Self {
             srtt_microseconds: AtomicU32::new(initial_srtt.as_micros() as u32),
             last_update: Arc::new(Mutex::new(None)),
+            recent_rtts: Mutex::new(VecDeque::with_capacity(Self::RECENT_RTTS)),
+            consecutive_failures: AtomicU32::new(0),
+            last_failure: Mutex::new(None),
         }
     }
 
     /// Records the measured `rtt` for a particular query.
     pub(crate) fn record_rtt(&self, rtt: Duration) {
+        self.record_pending_rtt(rtt);
+        self.consecutive_failures
+            .store(0, atomic::Ordering::Release);
+    }
+
+    /// Records `rtt` as a lower bound of the RTT of a query which was cancelled before it was
+    /// answered.
+    ///
+    /// Unlike `record_rtt`, this doesn't count as a successful query for the circuit breaker.
+    pub(crate) fn record_pending_rtt(&self, rtt: Duration) {
         // If the cast on the result does overflow (it shouldn't), then the
         // value is saturated to u32::MAX, which is above the `MAX_SRTT_MICROS`
         // limit (meaning that any potential overflow is inconsequential).
@@ -102,10 +132,20 @@ impl NameServerStats {
                 new_srtt.round() as u32
             },
         );
+
+        let mut recent_rtts = self.recent_rtts.lock();
+        if recent_rtts.len() == Self::RECENT_RTTS {
+            recent_rtts.pop_front();
+        }
+        recent_rtts.push_back(rtt);
     }
 
     /// Records a connection failure for a particular query.
     pub(crate) fn record_connection_failure(&self) {
+        self.consecutive_failures
+            .fetch_add(1, atomic::Ordering::AcqRel);
+        *self.last_failure.lock() = Some(Instant::now());
+
         self.update_srtt(
             Self::CONNECTION_FAILURE_PENALTY,
             |cur_srtt_microseconds, _last_update| {
@@ -114,11 +154,71 @@ impl NameServerStats {
         );
     }
 
+    /// Returns the `percentile` of the recent RTTs, or `None` if no RTT was recorded yet.
+    pub(crate) fn rtt_percentile(&self, percentile: u8) -> Option<Duration> {
+        let mut rtts = self.recent_rtts.lock().iter().copied().collect::<Vec<_>>();
+        if rtts.is_empty() {
+            return None;
+        }
+
+        // the nearest-rank method
+        rtts.sort_unstable();
+        let rank = (rtts.len() * usize::from(percentile.min(100)) + 99) / 100;
+        Some(rtts[rank.saturating_sub(1)])
+    }
+
+    /// Returns true unless the circuit breaker took the server out of rotation, and it's not yet
+    /// time to probe it again.
+    pub(crate) fn is_available(&self, config: &CircuitBreakerConfig) -> bool {
+        if !self.is_out_of_rotation(config) {
+            return true;
+        }
+
+        self.last_failure.lock().map_or(true, |last_failure| {
+            last_failure.elapsed() >= config.probe_interval
+        })
+    }
+
+    /// Claims the probe of a server which is out of rotation, returns true if a query may be
+    /// sent to the server.
+    ///
+    /// This is true for servers in rotation. Otherwise only a single query claims the probe, other
+    /// queries aren't sent to the server until the probe failed for the `probe_interval`, or it
+    /// succeeded and put the server back into rotation.
+    pub(crate) fn claim_probe(&self, config: &CircuitBreakerConfig) -> bool {
+        if !self.is_out_of_rotation(config) {
+            return true;
+        }
+
+        let mut last_failure = self.last_failure.lock();
+        match *last_failure {
+            Some(last_failure) if last_failure.elapsed() < config.probe_interval => false,
+            _ => {
+                *last_failure = Some(Instant::now());
+                true
+            }
+        }
+    }
+
+    /// Records that a query is sent, which probes the server if it is out of rotation.
+    ///
+    /// This is for queries that are sent regardless of `claim_probe`, e.g. because all servers
+    /// are out of rotation.
+    pub(crate) fn record_probe(&self, config: &CircuitBreakerConfig) {
+        if self.is_out_of_rotation(config) {
+            *self.last_failure.lock() = Some(Instant::now());
+        }
+    }
+
+    fn is_out_of_rotation(&self, config: &CircuitBreakerConfig) -> bool {
+        self.consecutive_failures.load(atomic::Ordering::Acquire) >= config.failure_threshold
+    }
+
     /// Returns the raw SRTT value.
     ///
     /// Prefer to use `decayed_srtt` when ordering name servers.
-    #[cfg(test)]
-    fn srtt(&self) -> Duration {
+    #[cfg(any(test, feature = "metrics"))]
+    pub(crate) fn srtt(&self) -> Duration {
         Duration::from_micros(u64::from(
             self.srtt_microseconds.load(atomic::Ordering::Acquire),
         ))
@@ -172,6 +272,68 @@ impl NameServerStats {
     }
 }
 
+/// Metrics of the exchanges with a single upstream name server
+#[cfg(feature = "metrics")]
+pub(crate) struct UpstreamMetrics {
+    pub(crate) queries: Counter,
+    pub(crate) timeouts: Counter,
+    pub(crate) srtt: Gauge,
+}
+
+#[cfg(feature = "metrics")]
+impl UpstreamMetrics {
+    pub(crate) fn new(config: &NameServerConfig, per_upstream: bool) -> Self {
+        let upstream = match per_upstream {
+            true => config.socket_addr.to_string(),
+            false => "all".to_string(),
+        };
+        let protocol = config.protocol.to_string();
+
+        let queries = counter!(
+            "hickory_upstream_queries_total",
+            "upstream" => upstream.clone(),
+            "protocol" => protocol.clone()
+        );
+        describe_counter!(
+            "hickory_upstream_queries_total",
+            Unit::Count,
+            "number of queries sent to an upstream name server"
+        );
+
+        let timeouts = counter!(
+            "hickory_upstream_timeouts_total",
+            "upstream" => upstream.clone(),
+            "protocol" => protocol.clone()
+        );
+        describe_counter!(
+            "hickory_upstream_timeouts_total",
+            Unit::Count,
+            "number of queries to an upstream name server that timed out"
+        );
+
+        // the SRTTs of different name servers can't be aggregated
+        let srtt = match per_upstream {
+            true => gauge!(
+                "hickory_upstream_srtt_seconds",
+                "upstream" => upstream,
+                "protocol" => protocol
+            ),
+            false => Gauge::noop(),
+        };
+        describe_gauge!(
+            "hickory_upstream_srtt_seconds",
+            Unit::Seconds,
+            "smoothed round-trip time of an upstream name server"
+        );
+
+        Self {
+            queries,
+            timeouts,
+            srtt,
+        }
+    }
+}
+
 #[cfg(test)]
 #[allow(clippy::extra_unused_type_parameters)]
 mod tests {
@@ -301,6 +463,68 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_rtt_percentile() {
+        let server = NameServerStats::new(Duration::from_micros(10));
+        assert_eq!(server.rtt_percentile(95), None);
+
+        for millis in (1..=20).rev() {
+            server.record_rtt(Duration::from_millis(millis));
+        }
+
+        assert_eq!(server.rtt_percentile(0), Some(Duration::from_millis(1)));
+        assert_eq!(server.rtt_percentile(50), Some(Duration::from_millis(10)));
+        assert_eq!(server.rtt_percentile(95), Some(Duration::from_millis(19)));
+        assert_eq!(server.rtt_percentile(100), Some(Duration::from_millis(20)));
+
+        // only the most recent RTTs are used
+        for _ in 0..NameServerStats::RECENT_RTTS {
+            server.record_rtt(Duration::from_millis(30));
+        }
+        assert_eq!(server.rtt_percentile(0), Some(Duration::from_millis(30)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_circuit_breaker() {
+        let config = CircuitBreakerConfig {
+            failure_threshold: 2,
+            probe_interval: Duration::from_secs(10),
+        };
+        let server = NameServerStats::new(Duration::from_micros(10));
+
+        server.record_connection_failure();
+        assert!(server.is_available(&config));
+        server.record_connection_failure();
+        assert!(!server.is_available(&config));
+
+        // a single probe is sent after the interval
+        tokio::time::advance(config.probe_interval).await;
+        assert!(server.is_available(&config));
+        assert!(server.claim_probe(&config));
+        assert!(!server.claim_probe(&config));
+        assert!(!server.is_available(&config));
+
+        // a failed probe keeps the server out of rotation for another interval
+        server.record_connection_failure();
+        tokio::time::advance(config.probe_interval / 2).await;
+        assert!(!server.is_available(&config));
+        tokio::time::advance(config.probe_interval / 2).await;
+        assert!(server.is_available(&config));
+
+        // a cancelled request doesn't put it back into rotation
+        assert!(server.claim_probe(&config));
+        server.record_pending_rtt(Duration::from_millis(10));
+        assert!(!server.is_available(&config));
+
+        // a successful probe puts it back into rotation
+        tokio::time::advance(config.probe_interval).await;
+        assert!(server.claim_probe(&config));
+        server.record_rtt(Duration::from_millis(10));
+        assert!(server.is_available(&config));
+        assert!(server.claim_probe(&config));
+        assert!(server.claim_probe(&config));
+    }
+
     #[tokio::test(start_paused = true)]
     async fn test_decayed_srtt() {
         let initial_srtt = 10;
@@ -8,7 +8,9 @@
 //! Structs for creating and using a Resolver
 use std::fmt;
 use std::future::Future;
+use std::io;
 use std::net::IpAddr;
+use std::path::Path;
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context, Poll};
@@ -16,6 +18,8 @@ use std::task::{Context, Poll};
 use futures_util::{FutureExt, future};
 use tracing::debug;
 
+use crate::address_sort::sort_without_blocking;
+use crate::cache_file::{self, CacheEntry};
 use crate::caching_client::CachingClient;
 use crate::config::{ResolveHosts, ResolverConfig, ResolverOpts};
 use crate::dns_lru::{self, DnsLru, TtlConfig};
@@ -23,15 +27,19 @@ use crate::error::{ResolveError, ResolveErrorKind};
 use crate::hosts::Hosts;
 use crate::lookup::{self, Lookup, LookupEither};
 use crate::lookup_ip::{LookupIp, LookupIpFuture};
+use crate::lookup_svcb::{SvcbLookup, SvcbLookupContext};
+#[cfg(feature = "smol")]
+use crate::name_server::SmolConnectionProvider;
 #[cfg(feature = "tokio")]
 use crate::name_server::TokioConnectionProvider;
 use crate::name_server::{ConnectionProvider, NameServerPool};
 #[cfg(feature = "__dnssec")]
-use crate::proto::dnssec::{DnssecDnsHandle, TrustAnchors};
+use crate::proto::dnssec::{DnssecDnsHandle, TrustAnchors, ValidationTrace};
 use crate::proto::op::Query;
 use crate::proto::rr::domain::usage::ONION;
 use crate::proto::rr::{IntoName, Name, RData, Record, RecordType};
 use crate::proto::xfer::{DnsHandle, DnsRequestOptions, RetryDnsHandle};
+use crate::routing::RoutingHandle;
 
 /// A builder to construct a [`Resolver`].
 ///
@@ -84,30 +92,62 @@ where
             trust_anchor,
         } = self;
 
-        let pool = NameServerPool::from_config_with_provider(&config, options.clone(), provider);
-        let client = RetryDnsHandle::new(pool, options.attempts);
-        let either;
+        #[cfg(feature = "__dnssec")]
+        let trust_anchor = trust_anchor.unwrap_or_else(|| Arc::new(TrustAnchors::default()));
 
-        if options.validate {
-            #[cfg(feature = "__dnssec")]
-            {
-                let trust_anchor =
-                    trust_anchor.unwrap_or_else(|| Arc::new(TrustAnchors::default()));
-                either =
-                    LookupEither::Secure(DnssecDnsHandle::with_trust_anchor(client, trust_anchor));
-            }
+        let handle = provider.create_handle();
+        let routes = config
+            .routes()
+            .iter()
+            .map(|route| {
+                let mut route_options = route.options.clone().unwrap_or_else(|| options.clone());
+                if route_options.validate != options.validate {
+                    tracing::warn!(
+                        "the validate option of the route for {:?} differs from the resolver's, using the resolver's",
+                        route.domains
+                    );
+                    route_options.validate = options.validate;
+                }
 
-            #[cfg(not(feature = "__dnssec"))]
-            {
-                tracing::warn!("validate option is only available with dnssec features");
-                either = LookupEither::Retry(client);
-            }
-        } else {
-            either = LookupEither::Retry(client);
-        }
+                let pool = NameServerPool::from_config(
+                    route.name_servers.clone(),
+                    route_options.clone(),
+                    provider.clone(),
+                );
+                let client = Self::lookup_client(
+                    pool,
+                    &route_options,
+                    #[cfg(feature = "__dnssec")]
+                    &trust_anchor,
+                );
+                (route.clone(), client)
+            })
+            .collect::<Vec<_>>();
+
+        let pool = NameServerPool::from_config_with_provider(&config, options.clone(), provider);
+        let either = RoutingHandle::new(
+            Self::lookup_client(
+                pool,
+                &options,
+                #[cfg(feature = "__dnssec")]
+                &trust_anchor,
+            ),
+            routes,
+        );
 
         let lru = DnsLru::new(options.cache_size, TtlConfig::from_opts(&options));
-        let client_cache = CachingClient::with_cache(lru, either, options.preserve_intermediates);
+        let mut client_cache =
+            CachingClient::with_cache(lru, either, options.preserve_intermediates);
+        if let Some(dns64) = &options.dns64 {
+            client_cache.set_dns64(dns64.clone());
+        }
+        match (options.prefetch, handle) {
+            (Some(prefetch), Some(handle)) => client_cache.set_prefetch(prefetch, handle),
+            (Some(_), None) => {
+                tracing::warn!("the connection provider can't spawn tasks, prefetching is disabled")
+            }
+            (None, _) => {}
+        }
 
         let hosts = Arc::new(match options.use_hosts_file {
             ResolveHosts::Always | ResolveHosts::Auto => Hosts::from_system().unwrap_or_default(),
@@ -121,6 +161,32 @@ where
             hosts,
         }
     }
+
+    fn lookup_client(
+        pool: NameServerPool<P>,
+        options: &ResolverOpts,
+        #[cfg(feature = "__dnssec")] trust_anchor: &Arc<TrustAnchors>,
+    ) -> LookupEither<P> {
+        let client = RetryDnsHandle::new(pool, options.attempts);
+        if !options.validate {
+            return LookupEither::Retry(client);
+        }
+
+        #[cfg(feature = "__dnssec")]
+        {
+            let negative_trust_anchors = options.negative_trust_anchors.iter().cloned();
+            LookupEither::Secure(
+                DnssecDnsHandle::with_trust_anchor(client, trust_anchor.clone())
+                    .with_negative_trust_anchors(negative_trust_anchors.collect()),
+            )
+        }
+
+        #[cfg(not(feature = "__dnssec"))]
+        {
+            tracing::warn!("validate option is only available with dnssec features");
+            LookupEither::Retry(client)
+        }
+    }
 }
 
 /// An asynchronous resolver for DNS generic over async Runtimes.
@@ -140,7 +206,7 @@ where
 pub struct Resolver<P: ConnectionProvider> {
     config: ResolverConfig,
     options: ResolverOpts,
-    client_cache: CachingClient<LookupEither<P>>,
+    client_cache: CachingClient<RoutingHandle<P>>,
     hosts: Arc<Hosts>,
 }
 
@@ -148,6 +214,10 @@ pub struct Resolver<P: ConnectionProvider> {
 #[cfg(feature = "tokio")]
 pub type TokioResolver = Resolver<TokioConnectionProvider>;
 
+/// A Resolver used with smol
+#[cfg(feature = "smol")]
+pub type SmolResolver = Resolver<SmolConnectionProvider>;
+
 macro_rules! lookup_fn {
     ($p:ident, $l:ty, $r:path) => {
         /// Performs a lookup for the associated type.
@@ -225,6 +295,53 @@ impl<R: ConnectionProvider> Resolver<R> {
         self.client_cache.clear_cache();
     }
 
+    /// Removes the cached entries for `name` and the names below it
+    pub fn clear_cache_subdomains(&self, name: &Name) {
+        self.client_cache.clear_cache_subdomains(name);
+    }
+
+    /// Returns the records of all cached positive responses, with their remaining TTL
+    pub fn cached_records(&self) -> Vec<Record> {
+        self.client_cache.cached_records()
+    }
+
+    /// Writes the current entries of the cache to the file at `path`, returns the number of
+    /// written entries
+    ///
+    /// The entries are stored in wire format with their absolute expiry time, the file is
+    /// replaced atomically.
+    pub fn save_cache(&self, path: &Path) -> io::Result<usize> {
+        cache_file::write_file(path, |file| self.client_cache.lru().save(file))
+    }
+
+    /// Inserts the entries of a file written by [`Self::save_cache`] into the cache, returns the
+    /// number of restored entries
+    ///
+    /// Expired entries are discarded. Unless `preserve_proofs` is set, the DNSSEC validation state
+    /// of the records is not restored, they are `Indeterminate`. Records in the cache aren't
+    /// validated again, so nothing is restored in that case if the resolver validates.
+    pub fn load_cache(&self, path: &Path, preserve_proofs: bool) -> io::Result<usize> {
+        if self.options.validate && !preserve_proofs {
+            tracing::warn!(
+                "not restoring the cache from {}, the DNSSEC validation state is not preserved",
+                path.display()
+            );
+            return Ok(0);
+        }
+
+        let cache = self.client_cache.lru();
+        let mut restored = 0;
+        cache_file::read_file(path, |entry| {
+            if let CacheEntry::Response(response) = entry {
+                if cache.restore(response, preserve_proofs) {
+                    restored += 1;
+                }
+            }
+        })?;
+
+        Ok(restored)
+    }
+
     /// Read the config for this resolver.
     pub fn config(&self) -> &ResolverConfig {
         &self.config
@@ -273,6 +390,50 @@ impl<P: ConnectionProvider> Resolver<P> {
             .await
     }
 
+    /// Same as [`Resolver::lookup`], but also returns a trace of the DNSSEC validation
+    ///
+    /// The trace lists every DS, DNSKEY and RRSIG that was considered while walking the chain of
+    /// trust, and where validation failed, see [`ValidationTrace`]. The lookup bypasses the cache
+    /// so that the whole chain is walked. This requires [`ResolverOpts::validate`] to be enabled.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - name of the record to lookup, if name is not a valid domain name, an error will be returned
+    /// * `record_type` - type of record to lookup
+    #[cfg(feature = "__dnssec")]
+    pub async fn lookup_with_validation_trace<N: IntoName>(
+        &self,
+        name: N,
+        record_type: RecordType,
+    ) -> (Result<Lookup, ResolveError>, ValidationTrace) {
+        let trace = ValidationTrace::new();
+        let name = match name.into_name() {
+            Ok(name) => name,
+            Err(err) => return (Err(err.into()), trace),
+        };
+
+        if !self.client_cache.client().is_verifying_dnssec() {
+            return (
+                Err(ResolveError::from(
+                    "a validation trace requires the validate option",
+                )),
+                trace,
+            );
+        }
+        let client = self.client_cache.client().with_validation_trace(&trace);
+
+        let client_cache = CachingClient::with_cache(
+            DnsLru::new(self.options.cache_size, TtlConfig::from_opts(&self.options)),
+            client,
+            self.options.preserve_intermediates,
+        );
+
+        let names = self.build_names(name);
+        let result =
+            LookupFuture::lookup(names, record_type, self.request_options(), client_cache).await;
+        (result, trace)
+    }
+
     fn push_name(name: Name, names: &mut Vec<Name>) {
         if !names.contains(&name) {
             names.push(name);
@@ -412,7 +573,7 @@ impl<P: ConnectionProvider> Resolver<P> {
         let names = self.build_names(name);
         let hosts = self.hosts.clone();
 
-        LookupIpFuture::lookup(
+        let lookup = LookupIpFuture::lookup(
             names,
             self.options.ip_strategy,
             self.client_cache.clone(),
@@ -420,6 +581,57 @@ impl<P: ConnectionProvider> Resolver<P> {
             hosts,
             finally_ip_addr.map(Record::into_data),
         )
+        .await?;
+
+        match self.options.sort_addresses {
+            true => {
+                let addresses = lookup.iter().collect();
+                Ok(sort_without_blocking(addresses, move || lookup.sort_addresses()).await)
+            }
+            false => Ok(lookup),
+        }
+    }
+
+    /// Performs a lookup of the HTTPS records of the given hostname, for connecting to it with
+    /// HTTP.
+    ///
+    /// See [`Self::svcb_lookup`] for how the records are resolved to endpoints. The
+    /// [RFC 9460](https://www.rfc-editor.org/rfc/rfc9460#section-9.1) name of a service on a
+    /// port other than 443 is `_<port>._https.<host>`.
+    ///
+    /// # Arguments
+    /// * `host` - string hostname, if this is an invalid hostname, an error will be returned.
+    pub async fn https_lookup(&self, host: impl IntoName) -> Result<SvcbLookup, ResolveError> {
+        self.inner_svcb_lookup(host.into_name()?, RecordType::HTTPS)
+            .await
+    }
+
+    /// Performs a lookup of the SVCB records of the given service name, e.g. `_dns.example.com`.
+    ///
+    /// AliasMode records are followed to the ServiceMode records of the service, which are
+    /// returned as endpoints ordered by their priority. The addresses of an endpoint are
+    /// resolved with the configured `ip_strategy`, if the target has no addresses the
+    /// `ipv4hint` and `ipv6hint` parameters are used.
+    ///
+    /// # Arguments
+    /// * `query` - a string which parses to a domain name, failure to parse will return an error
+    pub async fn svcb_lookup(&self, query: impl IntoName) -> Result<SvcbLookup, ResolveError> {
+        self.inner_svcb_lookup(query.into_name()?, RecordType::SVCB)
+            .await
+    }
+
+    async fn inner_svcb_lookup(
+        &self,
+        name: Name,
+        record_type: RecordType,
+    ) -> Result<SvcbLookup, ResolveError> {
+        SvcbLookupContext {
+            client: self.client_cache.clone(),
+            options: self.request_options(),
+            hosts: self.hosts.clone(),
+            strategy: self.options.ip_strategy,
+        }
+        .lookup(self.build_names(name), record_type)
         .await
     }
 
@@ -0,0 +1,286 @@
+// Copyright 2015-2025 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Sending queries for names in routing domains to the name servers of those domains
+
+use std::cmp::Reverse;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use futures_util::stream::{self, Stream};
+use tracing::debug;
+
+use crate::config::DomainRoute;
+use crate::lookup::LookupEither;
+use crate::name_server::ConnectionProvider;
+#[cfg(feature = "__dnssec")]
+use crate::proto::dnssec::ValidationTrace;
+use crate::proto::op::ResponseCode;
+use crate::proto::rr::Name;
+use crate::proto::xfer::{DnsHandle, DnsRequest, DnsResponse, FirstAnswer};
+use crate::proto::{ProtoError, ProtoErrorKind};
+
+/// Sends queries to the name servers of the closest routing domain of the query name, or to the
+/// default name servers
+#[derive(Clone)]
+pub(crate) struct RoutingHandle<P: ConnectionProvider> {
+    default: LookupEither<P>,
+    routes: Arc<[Route<P>]>,
+    /// The routing domains with the index of their route, the longest domains first
+    domains: Arc<[(Name, usize)]>,
+}
+
+impl<P: ConnectionProvider> RoutingHandle<P> {
+    pub(crate) fn new(
+        default: LookupEither<P>,
+        routes: impl IntoIterator<Item = (DomainRoute, LookupEither<P>)>,
+    ) -> Self {
+        let mut domains = Vec::new();
+        let mut handles = Vec::new();
+        for (index, (route, handle)) in routes.into_iter().enumerate() {
+            domains.extend(route.domains.into_iter().map(|domain| (domain, index)));
+            handles.push(Route {
+                handle,
+                exclusive: route.exclusive,
+            });
+        }
+
+        domains.sort_by_key(|(domain, _)| Reverse(domain.num_labels()));
+        Self {
+            default,
+            routes: Arc::from(handles),
+            domains: Arc::from(domains),
+        }
+    }
+
+    /// Returns the route of the closest routing domain of `name`
+    fn route(&self, name: &Name) -> Option<&Route<P>> {
+        self.domains
+            .iter()
+            .find(|(domain, _)| domain.zone_of(name))
+            .map(|(_, index)| &self.routes[*index])
+    }
+
+    #[cfg(feature = "__dnssec")]
+    pub(crate) fn with_validation_trace(&self, trace: &ValidationTrace) -> Self {
+        Self {
+            default: self.default.with_validation_trace(trace),
+            routes: self
+                .routes
+                .iter()
+                .map(|route| Route {
+                    handle: route.handle.with_validation_trace(trace),
+                    exclusive: route.exclusive,
+                })
+                .collect(),
+            domains: self.domains.clone(),
+        }
+    }
+}
+
+impl<P: ConnectionProvider> DnsHandle for RoutingHandle<P> {
+    type Response = Pin<Box<dyn Stream<Item = Result<DnsResponse, ProtoError>> + Send>>;
+
+    // the resolver builder gives all routes the `validate` option of the default handle
+    fn is_verifying_dnssec(&self) -> bool {
+        self.default.is_verifying_dnssec()
+    }
+
+    fn send<R: Into<DnsRequest> + Unpin + Send + 'static>(&self, request: R) -> Self::Response {
+        let request = request.into();
+        let route = request
+            .queries()
+            .first()
+            .and_then(|query| self.route(query.name()));
+
+        let route = match route {
+            Some(route) if route.exclusive => return route.handle.send(request),
+            Some(route) => route,
+            None => return self.default.send(request),
+        };
+
+        let routed = route.handle.send(request.clone()).first_answer();
+        let default = self.default.clone();
+        Box::pin(stream::once(async move {
+            match routed.await {
+                Err(e) if is_failure(&e) => {
+                    debug!("falling back to the default name servers: {e}");
+                    default.send(request).first_answer().await
+                }
+                result => result,
+            }
+        }))
+    }
+}
+
+#[derive(Clone)]
+struct Route<P: ConnectionProvider> {
+    handle: LookupEither<P>,
+    exclusive: bool,
+}
+
+/// Whether the name servers failed to answer, rather than answering that there are no records
+fn is_failure(error: &ProtoError) -> bool {
+    match error.kind() {
+        ProtoErrorKind::NoRecordsFound { response_code, .. } => {
+            *response_code == ResponseCode::ServFail
+        }
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "tokio")]
+mod tests {
+    use std::net::Ipv4Addr;
+    use std::str::FromStr;
+
+    use test_support::subscribe;
+    use tokio::net::UdpSocket;
+    use tokio::spawn;
+
+    use crate::Resolver;
+    use crate::config::{NameServerConfig, ResolveHosts, ResolverConfig, ResolverOpts};
+    use crate::name_server::TokioConnectionProvider;
+    use crate::proto::op::{Message, MessageType};
+    use crate::proto::rr::rdata::A;
+    use crate::proto::rr::{RData, Record};
+    use crate::proto::xfer::Protocol;
+
+    use super::*;
+
+    /// Starts a name server which answers A queries for `names` with `address`, other queries with
+    /// `failure`
+    async fn name_server(
+        names: &[&str],
+        address: Ipv4Addr,
+        failure: ResponseCode,
+    ) -> NameServerConfig {
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let socket_addr = socket.local_addr().unwrap();
+        let names = names
+            .iter()
+            .map(|name| Name::from_str(name).unwrap())
+            .collect::<Vec<_>>();
+
+        spawn(async move {
+            let mut buffer = [0_u8; 512];
+            loop {
+                let (len, addr) = socket.recv_from(&mut buffer).await.unwrap();
+                let request = Message::from_vec(&buffer[..len]).unwrap();
+                let mut response = Message::new();
+                response
+                    .set_id(request.id())
+                    .set_message_type(MessageType::Response)
+                    .add_queries(request.queries().to_vec());
+
+                let query_name = request.queries()[0].name();
+                if names.contains(query_name) {
+                    response.add_answer(Record::from_rdata(
+                        query_name.clone(),
+                        300,
+                        RData::A(A(address)),
+                    ));
+                } else {
+                    response.set_response_code(failure);
+                }
+
+                socket
+                    .send_to(&response.to_vec().unwrap(), addr)
+                    .await
+                    .unwrap();
+            }
+        });
+
+        NameServerConfig::new(socket_addr, Protocol::Udp)
+    }
+
+    async fn routing_resolver(exclusive: bool) -> Resolver<TokioConnectionProvider> {
+        let default = name_server(
+            &["public.corp.example.", "www.lab.example."],
+            Ipv4Addr::new(192, 0, 2, 1),
+            ResponseCode::NXDomain,
+        )
+        .await;
+        let corp = name_server(
+            &["www.corp.example."],
+            Ipv4Addr::new(10, 0, 0, 1),
+            ResponseCode::NXDomain,
+        )
+        .await;
+        let dev = name_server(
+            &["www.dev.corp.example."],
+            Ipv4Addr::new(10, 0, 1, 1),
+            ResponseCode::NXDomain,
+        )
+        .await;
+        let lab = name_server(&[], Ipv4Addr::new(10, 0, 2, 1), ResponseCode::ServFail).await;
+
+        let mut config = ResolverConfig::from_parts(None, vec![], vec![default]);
+        config.add_route(DomainRoute::new(
+            vec![Name::from_str("corp.example.").unwrap()],
+            vec![corp],
+        ));
+        config.add_route(DomainRoute::new(
+            vec![Name::from_str("dev.corp.example.").unwrap()],
+            vec![dev],
+        ));
+        let mut route = DomainRoute::new(vec![Name::from_str("lab.example.").unwrap()], vec![lab]);
+        route.exclusive = exclusive;
+        config.add_route(route);
+
+        let options = ResolverOpts {
+            use_hosts_file: ResolveHosts::Never,
+            ..ResolverOpts::default()
+        };
+        Resolver::builder_with_config(config, TokioConnectionProvider::default())
+            .with_options(options)
+            .build()
+    }
+
+    async fn lookup(resolver: &Resolver<TokioConnectionProvider>, name: &str) -> Option<Ipv4Addr> {
+        let lookup = resolver.ipv4_lookup(name).await.ok()?;
+        lookup.iter().next().map(|a| a.0)
+    }
+
+    #[tokio::test]
+    async fn test_longest_domain() {
+        subscribe();
+        let resolver = routing_resolver(false).await;
+
+        assert_eq!(
+            lookup(&resolver, "www.corp.example.").await,
+            Some(Ipv4Addr::new(10, 0, 0, 1))
+        );
+        assert_eq!(
+            lookup(&resolver, "www.dev.corp.example.").await,
+            Some(Ipv4Addr::new(10, 0, 1, 1))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fall_back_unless_exclusive() {
+        subscribe();
+
+        let resolver = routing_resolver(false).await;
+        assert_eq!(
+            lookup(&resolver, "www.lab.example.").await,
+            Some(Ipv4Addr::new(192, 0, 2, 1))
+        );
+
+        let resolver = routing_resolver(true).await;
+        assert_eq!(lookup(&resolver, "www.lab.example.").await, None);
+    }
+
+    #[tokio::test]
+    async fn test_no_fall_back_on_nxdomain() {
+        subscribe();
+        let resolver = routing_resolver(false).await;
+
+        assert_eq!(lookup(&resolver, "public.corp.example.").await, None);
+    }
+}
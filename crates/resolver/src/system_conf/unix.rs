@@ -75,6 +75,7 @@ fn into_resolver_config(
             protocol: Protocol::Udp,
             tls_dns_name: None,
             http_endpoint: None,
+            tls_pins: Vec::new(),
             trust_negative_responses: false,
             bind_addr: None,
         });
@@ -83,6 +84,7 @@ fn into_resolver_config(
             protocol: Protocol::Tcp,
             tls_dns_name: None,
             http_endpoint: None,
+            tls_pins: Vec::new(),
             trust_negative_responses: false,
             bind_addr: None,
         });
@@ -143,6 +145,7 @@ mod tests {
                 protocol: Protocol::Udp,
                 tls_dns_name: None,
                 http_endpoint: None,
+                tls_pins: Vec::new(),
                 trust_negative_responses: false,
                 bind_addr: None,
             },
@@ -151,6 +154,7 @@ mod tests {
                 protocol: Protocol::Tcp,
                 tls_dns_name: None,
                 http_endpoint: None,
+                tls_pins: Vec::new(),
                 trust_negative_responses: false,
                 bind_addr: None,
             },
@@ -34,6 +34,7 @@ fn get_name_servers() -> Result<Vec<NameServerConfig>, ResolveError> {
             protocol: Protocol::Udp,
             tls_dns_name: None,
             http_endpoint: None,
+            tls_pins: Vec::new(),
             trust_negative_responses: false,
             bind_addr: None,
         });
@@ -42,6 +43,7 @@ fn get_name_servers() -> Result<Vec<NameServerConfig>, ResolveError> {
             protocol: Protocol::Tcp,
             tls_dns_name: None,
             http_endpoint: None,
+            tls_pins: Vec::new(),
             trust_negative_responses: false,
             bind_addr: None,
         });
@@ -10,7 +10,7 @@ use prefix_trie::{Prefix, PrefixSet};
 ///   the allowed list will effectively deny access to anything that's not in the allowed list. On the other hand, if
 ///   denied networks are specified, then allowed networks will only apply if the deny rule matched, but otherwise the
 ///   address will be allowed.
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub(crate) struct AccessControl {
     ipv4: InnerAccessControl<Ipv4Net>,
     ipv6: InnerAccessControl<Ipv6Net>,
@@ -71,7 +71,7 @@ impl AccessControl {
     }
 }
 
-#[derive(Default)]
+#[derive(Clone, Default)]
 struct InnerAccessControl<I: Prefix> {
     allow: PrefixSet<I>,
     deny: PrefixSet<I>,
@@ -8,11 +8,11 @@
 //! All authority related types
 
 use cfg_if::cfg_if;
-use std::fmt;
+use std::{fmt, io};
 
 use crate::{
     authority::{LookupError, LookupObject, MessageRequest, UpdateResult, ZoneType},
-    proto::rr::{LowerName, RecordSet, RecordType, RrsetRecords},
+    proto::rr::{LowerName, Name, Record, RecordSet, RecordType, RrsetRecords},
     server::RequestInfo,
 };
 #[cfg(feature = "__dnssec")]
@@ -21,7 +21,6 @@ use crate::{
     proto::{
         ProtoError,
         dnssec::{DnsSecResult, Nsec3HashAlgorithm, SigSigner, crypto::Digest, rdata::key::KEY},
-        rr::Name,
     },
 };
 
@@ -31,6 +30,7 @@ use crate::{
 #[derive(Clone, Copy, Debug, Default)]
 pub struct LookupOptions {
     dnssec_ok: bool,
+    omit_additionals: bool,
 }
 
 /// Lookup Options for the request to the authority
@@ -38,7 +38,10 @@ impl LookupOptions {
     /// Return a new LookupOptions
     #[cfg(feature = "__dnssec")]
     pub fn for_dnssec(dnssec_ok: bool) -> Self {
-        Self { dnssec_ok }
+        Self {
+            dnssec_ok,
+            ..Self::default()
+        }
     }
 
     /// Specify that this lookup should return DNSSEC related records as well, e.g. RRSIG
@@ -55,6 +58,20 @@ impl LookupOptions {
         self.dnssec_ok
     }
 
+    /// Specify that this lookup should not search for optional additional records, e.g. the
+    /// addresses of MX and SRV targets; the records of a CNAME chain are still returned
+    pub fn set_omit_additionals(self, val: bool) -> Self {
+        Self {
+            omit_additionals: val,
+            ..self
+        }
+    }
+
+    /// If true this lookup should not search for optional additional records
+    pub fn omit_additionals(&self) -> bool {
+        self.omit_additionals
+    }
+
     /// Returns the rrset's records with or without RRSIGs, depending on the DO flag.
     pub fn rrset_with_rrigs<'r>(&self, record_set: &'r RecordSet) -> RrsetRecords<'r> {
         cfg_if! {
@@ -211,6 +228,27 @@ pub trait Authority: Send + Sync {
     /// Returns the kind of non-existence proof used for this zone.
     #[cfg(feature = "__dnssec")]
     fn nx_proof_kind(&self) -> Option<&NxProofKind>;
+
+    /// Removes the cached records for `name` and the names below it, or all of them if `name` is
+    /// `None`
+    ///
+    /// Returns `false` if the authority doesn't cache records.
+    fn clear_cache(&self, name: Option<&Name>) -> bool {
+        let _ = name;
+        false
+    }
+
+    /// Returns the cached records, `None` if the authority doesn't cache records
+    fn cached_records(&self) -> Option<Vec<Record>> {
+        None
+    }
+
+    /// Writes the cache to the configured cache file, returns the number of saved entries
+    ///
+    /// Returns `None` if the authority doesn't persist its cache.
+    fn save_cache(&self) -> Option<io::Result<usize>> {
+        None
+    }
 }
 
 /// Extension to Authority to allow for DNSSEC features
@@ -7,6 +7,8 @@
 
 //! Object-safe authority and lookup traits
 
+use std::io;
+
 use tracing::debug;
 
 #[cfg(feature = "__dnssec")]
@@ -15,7 +17,7 @@ use crate::{
     authority::{
         Authority, LookupControlFlow, LookupOptions, MessageRequest, UpdateResult, ZoneType,
     },
-    proto::rr::{LowerName, Record, RecordType},
+    proto::rr::{LowerName, Name, Record, RecordType},
     server::RequestInfo,
 };
 
@@ -159,6 +161,27 @@ pub trait AuthorityObject: Send + Sync {
     /// Returns the kind of non-existence proof used for this zone.
     #[cfg(feature = "__dnssec")]
     fn nx_proof_kind(&self) -> Option<&NxProofKind>;
+
+    /// Removes the cached records for `name` and the names below it, or all of them if `name` is
+    /// `None`
+    ///
+    /// Returns `false` if the authority doesn't cache records.
+    fn clear_cache(&self, name: Option<&Name>) -> bool {
+        let _ = name;
+        false
+    }
+
+    /// Returns the cached records, `None` if the authority doesn't cache records
+    fn cached_records(&self) -> Option<Vec<Record>> {
+        None
+    }
+
+    /// Writes the cache to the configured cache file, returns the number of saved entries
+    ///
+    /// Returns `None` if the authority doesn't persist its cache.
+    fn save_cache(&self) -> Option<io::Result<usize>> {
+        None
+    }
 }
 
 #[async_trait::async_trait]
@@ -310,6 +333,26 @@ where
     fn nx_proof_kind(&self) -> Option<&NxProofKind> {
         Authority::nx_proof_kind(self)
     }
+
+    /// Removes the cached records for `name` and the names below it, or all of them if `name` is
+    /// `None`
+    ///
+    /// Returns `false` if the authority doesn't cache records.
+    fn clear_cache(&self, name: Option<&Name>) -> bool {
+        Authority::clear_cache(self, name)
+    }
+
+    /// Returns the cached records, `None` if the authority doesn't cache records
+    fn cached_records(&self) -> Option<Vec<Record>> {
+        Authority::cached_records(self)
+    }
+
+    /// Writes the cache to the configured cache file, returns the number of saved entries
+    ///
+    /// Returns `None` if the authority doesn't persist its cache.
+    fn save_cache(&self) -> Option<io::Result<usize>> {
+        Authority::save_cache(self)
+    }
 }
 
 /// DNSSEC status of an answer
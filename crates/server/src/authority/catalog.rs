@@ -11,15 +11,18 @@
 use std::{borrow::Borrow, collections::HashMap, io, sync::Arc};
 
 use cfg_if::cfg_if;
+use ipnet::IpNet;
+use serde::Deserialize;
 use tracing::{debug, error, info, trace, warn};
 
-#[cfg(feature = "__dnssec")]
-use crate::{authority::Nsec3QueryInfo, dnssec::NxProofKind};
+#[cfg(feature = "metrics")]
+use crate::server::metrics;
 use crate::{
+    access::AccessControl,
     authority::{
         AuthLookup, AuthorityObject, EmptyLookup, LookupControlFlow, LookupError, LookupObject,
         LookupOptions, LookupRecords, MessageResponse, MessageResponseBuilder, ZoneType,
-        authority_object::DnssecSummary,
+        authority_object::DnssecSummary, catalog_zone::MemberZones,
     },
     proto::{
         op::{Edns, Header, LowerQuery, MessageType, OpCode, ResponseCode},
@@ -27,11 +30,55 @@ use crate::{
     },
     server::{Request, RequestHandler, RequestInfo, ResponseHandler, ResponseInfo},
 };
+#[cfg(feature = "__dnssec")]
+use crate::{authority::Nsec3QueryInfo, dnssec::NxProofKind};
 
 /// Set of authorities, zones, available to this server.
 #[derive(Default)]
 pub struct Catalog {
     authorities: HashMap<LowerName, Vec<Arc<dyn AuthorityObject>>>,
+    query_access: HashMap<LowerName, AccessControl>,
+    recursion_access: AccessControl,
+    member_zones: Option<Arc<MemberZones>>,
+    minimal_responses: MinimalResponses,
+    zone_minimal_responses: HashMap<LowerName, MinimalResponses>,
+}
+
+/// Which optional records are added to the authority and additional sections of authoritative
+/// responses, like the `minimal-responses` option of BIND
+///
+/// Records required by a response, e.g. the SOA and NSEC records of a negative response, are
+/// always added.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum MinimalResponses {
+    /// Add the NS records of the zone and the additional records, e.g. the addresses of MX and
+    /// SRV targets
+    #[default]
+    No,
+    /// Add neither the NS records nor the additional records
+    Yes,
+    /// Add the additional records but not the NS records
+    NoAuth,
+    /// Like `NoAuth` for requests with recursion desired, like `No` otherwise
+    NoAuthRecursive,
+}
+
+impl MinimalResponses {
+    /// Returns true if the NS records of the zone should be omitted
+    fn omit_authority(self, recursion_desired: bool) -> bool {
+        match self {
+            Self::No => false,
+            Self::Yes | Self::NoAuth => true,
+            Self::NoAuthRecursive => recursion_desired,
+        }
+    }
+
+    /// Returns true if the additional records should be omitted, the records of a CNAME chain
+    /// are always added
+    fn omit_additionals(self) -> bool {
+        self == Self::Yes
+    }
 }
 
 #[allow(unused_mut, unused_variables)]
@@ -161,9 +208,7 @@ impl RequestHandler for Catalog {
 impl Catalog {
     /// Constructs a new Catalog
     pub fn new() -> Self {
-        Self {
-            authorities: HashMap::new(),
-        }
+        Self::default()
     }
 
     /// Insert or update a zone authority
@@ -178,9 +223,55 @@ impl Catalog {
 
     /// Remove a zone from the catalog
     pub fn remove(&mut self, name: &LowerName) -> Option<Vec<Arc<dyn AuthorityObject>>> {
+        self.query_access.remove(name);
+        self.zone_minimal_responses.remove(name);
         self.authorities.remove(name)
     }
 
+    /// Restrict which clients may query the zone `name`, other clients are refused
+    ///
+    /// The networks are evaluated the same way as the server wide networks, see
+    /// [`ServerFuture::with_access`](crate::ServerFuture::with_access).
+    pub fn set_query_access(
+        &mut self,
+        name: LowerName,
+        denied_networks: &[IpNet],
+        allowed_networks: &[IpNet],
+    ) {
+        let mut access = AccessControl::default();
+        access.insert_deny(denied_networks);
+        access.insert_allow(allowed_networks);
+        self.query_access.insert(name, access);
+    }
+
+    /// Restrict which clients may use recursion, i.e. the external zones of forwarders and
+    /// recursors
+    ///
+    /// Other clients are only answered from authoritative zones and refused otherwise. By default
+    /// all clients may use recursion.
+    pub fn set_recursion_access(&mut self, denied_networks: &[IpNet], allowed_networks: &[IpNet]) {
+        let mut access = AccessControl::default();
+        access.insert_deny(denied_networks);
+        access.insert_allow(allowed_networks);
+        self.recursion_access = access;
+    }
+
+    /// Set which optional records are added to the responses of the zones, see [`MinimalResponses`]
+    ///
+    /// A zone can override this with [`Self::set_zone_minimal_responses`].
+    pub fn set_minimal_responses(&mut self, minimal_responses: MinimalResponses) {
+        self.minimal_responses = minimal_responses;
+    }
+
+    /// Set which optional records are added to the responses of the zone `name`
+    pub fn set_zone_minimal_responses(
+        &mut self,
+        name: LowerName,
+        minimal_responses: MinimalResponses,
+    ) {
+        self.zone_minimal_responses.insert(name, minimal_responses);
+    }
+
     /// Update the zone given the Update request.
     ///
     /// [RFC 2136](https://tools.ietf.org/html/rfc2136), DNS Update, April 1997
@@ -264,7 +355,7 @@ impl Catalog {
         };
 
         // verify the zone type and number of zones in request, then find the zone to update
-        if let Some(authorities) = self.find(verify_request.query.name()) {
+        if let Some((_, authorities)) = self.find_zone(verify_request.query.name()) {
             #[allow(clippy::never_loop)]
             for authority in authorities {
                 #[allow(deprecated)]
@@ -346,10 +437,51 @@ impl Catalog {
                 Ok(r) => return r,
             }
         };
-        let authorities = self.find(request_info.query.name());
+        let client = request.src().ip();
+        let recursion_allowed = self.recursion_access.allow(client);
+        let authorities = self
+            .find_zone(request_info.query.name())
+            .filter(|(zone, _)| {
+                let allowed = self
+                    .query_access
+                    .get(zone)
+                    .map_or(true, |access| access.allow(client));
+                if !allowed {
+                    info!(
+                        "request:{} for zone {zone} refused by allow-query",
+                        request.id()
+                    );
+                    #[cfg(feature = "metrics")]
+                    metrics::refused("allow_query");
+                }
+                allowed
+            })
+            .map(|(zone, authorities)| {
+                let minimal_responses = self
+                    .zone_minimal_responses
+                    .get(&zone)
+                    .copied()
+                    .unwrap_or(self.minimal_responses);
+                let authorities = authorities
+                    .into_iter()
+                    .filter(|authority| {
+                        recursion_allowed || authority.zone_type().is_authoritative()
+                    })
+                    .collect::<Vec<_>>();
+                (authorities, minimal_responses)
+            })
+            .filter(|(authorities, _)| {
+                // authorities are only filtered out if the client may not use recursion
+                #[cfg(feature = "metrics")]
+                if authorities.is_empty() {
+                    metrics::refused("allow_recursion");
+                }
+                !authorities.is_empty()
+            });
 
-        let Some(authorities) = authorities else {
-            // There are no authorities registered that can handle the request
+        let Some((authorities, minimal_responses)) = authorities else {
+            // There are no authorities registered that can handle the request, or the client may
+            // not use them
             let response = MessageResponseBuilder::new(request.raw_queries());
 
             let result = send_response(
@@ -370,7 +502,8 @@ impl Catalog {
 
         let result = lookup(
             request_info.clone(),
-            authorities,
+            &authorities,
+            minimal_responses,
             request,
             response_edns
                 .as_ref()
@@ -386,12 +519,55 @@ impl Catalog {
     }
 
     /// Recursively searches the catalog for a matching authority
+    ///
+    /// Member zones of catalog zones are not searched.
     pub fn find(&self, name: &LowerName) -> Option<&Vec<Arc<(dyn AuthorityObject + 'static)>>> {
+        self.find_configured_zone(name)
+            .map(|(_, authorities)| authorities)
+    }
+
+    /// Returns the authorities of all zones of the catalog, without the member zones of catalog
+    /// zones
+    pub fn authorities(&self) -> impl Iterator<Item = &Arc<dyn AuthorityObject>> {
+        self.authorities.values().flatten()
+    }
+
+    /// Serves the member zones of catalog zones in addition to the zones of the catalog
+    ///
+    /// A configured zone is preferred to a member zone of the same name.
+    pub fn set_member_zones(&mut self, member_zones: Arc<MemberZones>) {
+        self.member_zones = Some(member_zones);
+    }
+
+    /// Searches the zones and the member zones for the closest zone of `name`, returning the zone
+    /// name with its authorities
+    fn find_zone(&self, name: &LowerName) -> Option<(LowerName, Vec<Arc<dyn AuthorityObject>>)> {
+        let zone = self.find_configured_zone(name);
+        let member = self
+            .member_zones
+            .as_ref()
+            .and_then(|member_zones| member_zones.find(name));
+
+        match (zone, member) {
+            (Some((zone, _)), Some(member)) if member.0.num_labels() > zone.num_labels() => {
+                Some(member)
+            }
+            (Some((zone, authorities)), _) => Some((zone.clone(), authorities.clone())),
+            (None, member) => member,
+        }
+    }
+
+    /// Recursively searches the configured zones for a matching authority, returning the zone
+    /// name with it
+    fn find_configured_zone(
+        &self,
+        name: &LowerName,
+    ) -> Option<(&LowerName, &Vec<Arc<dyn AuthorityObject + 'static>>)> {
         debug!("searching authorities for: {name}");
-        self.authorities.get(name).or_else(|| {
+        self.authorities.get_key_value(name).or_else(|| {
             if !name.is_root() {
                 let name = name.base_name();
-                self.find(&name)
+                self.find_configured_zone(&name)
             } else {
                 None
             }
@@ -402,12 +578,14 @@ impl Catalog {
 async fn lookup<R: ResponseHandler + Unpin>(
     request_info: RequestInfo<'_>,
     authorities: &[Arc<dyn AuthorityObject>],
+    minimal_responses: MinimalResponses,
     request: &Request,
     response_edns: Option<Edns>,
     response_handle: R,
 ) -> Result<ResponseInfo, LookupError> {
     let edns = request.edns();
-    let lookup_options = lookup_options_for_edns(edns);
+    let lookup_options =
+        lookup_options_for_edns(edns).set_omit_additionals(minimal_responses.omit_additionals());
     let request_id = request.id();
 
     // log algorithms being requested
@@ -466,6 +644,7 @@ async fn lookup<R: ResponseHandler + Unpin>(
         let (response_header, sections) = build_response(
             result,
             &**authority,
+            minimal_responses,
             request_id,
             request.header(),
             query,
@@ -516,6 +695,7 @@ fn lookup_options_for_edns(edns: Option<&Edns>) -> LookupOptions {
 async fn build_response(
     result: Result<Box<dyn LookupObject>, LookupError>,
     authority: &dyn AuthorityObject,
+    minimal_responses: MinimalResponses,
     request_id: u16,
     request_header: &Header,
     query: &LowerQuery,
@@ -534,6 +714,7 @@ async fn build_response(
                 authority,
                 &mut response_header,
                 lookup_options,
+                minimal_responses.omit_authority(request_header.recursion_desired()),
                 request_id,
                 query,
             )
@@ -561,6 +742,7 @@ async fn build_authoritative_response(
     authority: &dyn AuthorityObject,
     response_header: &mut Header,
     lookup_options: LookupOptions,
+    omit_authority: bool,
     _request_id: u16,
     query: &LowerQuery,
 ) -> LookupSections {
@@ -597,20 +779,24 @@ async fn build_authoritative_response(
     };
 
     let (ns, soa) = if answers.is_some() {
-        // SOA queries should return the NS records as well.
+        // SOA queries should return the NS records as well, unless responses are minimal.
         if query.query_type().is_soa() {
-            // This was a successful authoritative lookup for SOA:
-            //   get the NS records as well.
-
-            match authority.ns(lookup_options).await.map_result() {
-                Some(Ok(ns)) => (Some(ns), None),
-                Some(Err(e)) => {
-                    warn!("ns_lookup errored: {e}");
-                    (None, None)
-                }
-                None => {
-                    warn!("ns_lookup unexpected skip");
-                    (None, None)
+            if omit_authority {
+                (None, None)
+            } else {
+                // This was a successful authoritative lookup for SOA:
+                //   get the NS records as well.
+
+                match authority.ns(lookup_options).await.map_result() {
+                    Some(Ok(ns)) => (Some(ns), None),
+                    Some(Err(e)) => {
+                        warn!("ns_lookup errored: {e}");
+                        (None, None)
+                    }
+                    None => {
+                        warn!("ns_lookup unexpected skip");
+                        (None, None)
+                    }
                 }
             }
         } else {
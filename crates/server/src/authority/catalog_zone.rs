@@ -0,0 +1,528 @@
+// Copyright 2015-2025 Benjamin Fry <benjaminfry@me.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// https://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// https://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Catalog zones, [RFC 9432](https://www.rfc-editor.org/rfc/rfc9432), listing the member zones
+//! a server should serve
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    mem,
+    sync::{Arc, RwLock},
+};
+
+use tracing::{info, warn};
+
+use crate::{
+    authority::{AuthorityObject, LookupControlFlow, LookupOptions},
+    proto::rr::{LowerName, Name, RData, Record, RecordType},
+};
+
+/// The only version of the catalog zone schema which is supported
+const SCHEMA_VERSION: &str = "2";
+
+/// The contents of a catalog zone
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CatalogZone {
+    origin: Name,
+    members: Vec<MemberZone>,
+}
+
+impl CatalogZone {
+    /// Reads the catalog zone served by `authority`
+    pub async fn from_authority(authority: &dyn AuthorityObject) -> Result<Self, String> {
+        let origin = Name::from(authority.origin());
+        let lookup = match authority
+            .lookup(
+                authority.origin(),
+                RecordType::AXFR,
+                LookupOptions::default(),
+            )
+            .await
+        {
+            LookupControlFlow::Continue(Ok(lookup)) | LookupControlFlow::Break(Ok(lookup)) => {
+                lookup
+            }
+            LookupControlFlow::Continue(Err(e)) | LookupControlFlow::Break(Err(e)) => {
+                return Err(format!("failed to read catalog zone {origin}: {e}"));
+            }
+            LookupControlFlow::Skip => {
+                return Err(format!("catalog zone {origin} has no records"));
+            }
+        };
+
+        Self::from_records(origin, lookup.iter())
+    }
+
+    /// Parses the records of the catalog zone `origin`
+    ///
+    /// An error is returned if the catalog zone is broken, e.g. it has an unsupported schema
+    /// version or a member node with more than one PTR record. A zone listed more than once is
+    /// returned for each of its member nodes, see [`MemberZones::changes`].
+    pub fn from_records<'r>(
+        origin: Name,
+        records: impl IntoIterator<Item = &'r Record>,
+    ) -> Result<Self, String> {
+        let version_node = origin
+            .prepend_label("version")
+            .map_err(|e| format!("invalid catalog zone name {origin}: {e}"))?;
+        let zones_node = origin
+            .prepend_label("zones")
+            .map_err(|e| format!("invalid catalog zone name {origin}: {e}"))?;
+
+        let mut versions = Vec::new();
+        let mut nodes = BTreeMap::<String, MemberNode>::new();
+        for record in records {
+            let name = record.name();
+            if *name == version_node {
+                if let RData::TXT(txt) = record.data() {
+                    versions.push(txt.to_string());
+                }
+                continue;
+            }
+
+            if !zones_node.zone_of(name) {
+                continue;
+            }
+
+            // the labels of the name below the `zones` node, starting with the leftmost
+            let labels = name
+                .iter()
+                .take((name.num_labels() - zones_node.num_labels()) as usize)
+                .map(|label| String::from_utf8_lossy(label).to_ascii_lowercase())
+                .collect::<Vec<_>>();
+            match (labels.as_slice(), record.data()) {
+                ([id], RData::PTR(ptr)) => {
+                    let node = nodes.entry(id.clone()).or_default();
+                    if node.zone.replace(ptr.0.clone()).is_some() {
+                        return Err(format!(
+                            "catalog zone {origin} has more than one PTR record for member {id}"
+                        ));
+                    }
+                }
+                ([property, id], RData::PTR(ptr)) if property == "coo" => {
+                    nodes.entry(id.clone()).or_default().coo = Some(ptr.0.clone());
+                }
+                ([property, id], RData::TXT(txt)) if property == "group" => {
+                    nodes.entry(id.clone()).or_default().group = Some(txt.to_string());
+                }
+                // unknown properties are ignored
+                _ => {}
+            }
+        }
+
+        match versions.as_slice() {
+            [version] if version == SCHEMA_VERSION => {}
+            [version] => {
+                return Err(format!(
+                    "catalog zone {origin} has unsupported schema version {version}"
+                ));
+            }
+            [] => return Err(format!("catalog zone {origin} has no schema version")),
+            _ => {
+                return Err(format!(
+                    "catalog zone {origin} has more than one schema version"
+                ));
+            }
+        }
+
+        let mut members = Vec::<MemberZone>::with_capacity(nodes.len());
+        for (id, node) in nodes {
+            // properties of a member which isn't listed are ignored
+            let Some(zone) = node.zone else {
+                continue;
+            };
+
+            members.push(MemberZone {
+                id,
+                zone,
+                group: node.group,
+                coo: node.coo,
+            });
+        }
+
+        Ok(Self { origin, members })
+    }
+
+    /// The name of the catalog zone
+    pub fn origin(&self) -> &Name {
+        &self.origin
+    }
+
+    /// The member zones listed in the catalog zone
+    pub fn members(&self) -> &[MemberZone] {
+        &self.members
+    }
+}
+
+#[derive(Default)]
+struct MemberNode {
+    zone: Option<Name>,
+    group: Option<String>,
+    coo: Option<Name>,
+}
+
+/// A member zone listed in a catalog zone
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemberZone {
+    /// The unique label of the member in the catalog zone
+    pub id: String,
+    /// The name of the member zone
+    pub zone: Name,
+    /// The group of the member, selecting how the zone is served
+    pub group: Option<String>,
+    /// The catalog zone the member is being migrated to, see the change of ownership in RFC 9432
+    pub coo: Option<Name>,
+}
+
+/// The member zones of the catalog zones served by a [`Catalog`](crate::authority::Catalog)
+///
+/// A zone is owned by the first catalog zone listing it. Another catalog zone only takes over the
+/// zone if the owner has a change of ownership property pointing to it, or after the owner stopped
+/// listing the zone.
+#[derive(Default)]
+pub struct MemberZones {
+    zones: RwLock<HashMap<LowerName, Member>>,
+}
+
+impl MemberZones {
+    /// Returns the changes needed for the served member zones to match `catalog`
+    ///
+    /// Only the change of ownership properties of the zones are updated, the authorities of the
+    /// added zones must be created and inserted with [`Self::insert`], and the removed zones
+    /// removed with [`Self::remove`].
+    ///
+    /// A zone listed by more than one member node of `catalog` keeps being served from the node
+    /// already in use, or from the first one, the other nodes are ignored (RFC 9432 section 5.4).
+    pub fn changes(&self, catalog: &CatalogZone) -> MemberChanges {
+        let mut zones = self.zones.write().expect("member zones lock poisoned");
+        let mut changes = MemberChanges::default();
+
+        let mut listed = HashMap::<LowerName, &MemberZone>::new();
+        let mut members = Vec::with_capacity(catalog.members().len());
+        for member in catalog.members() {
+            let name = LowerName::from(member.zone.clone());
+            let Some(first) = listed.get_mut(&name) else {
+                listed.insert(name.clone(), member);
+                members.push(name);
+                continue;
+            };
+
+            let served = zones
+                .get(&name)
+                .is_some_and(|owned| owned.catalog == *catalog.origin() && owned.id == member.id);
+            let ignored = if served {
+                mem::replace(first, member)
+            } else {
+                member
+            };
+            warn!(
+                "ignoring member {} of catalog zone {}, zone {} is listed more than once",
+                ignored.id,
+                catalog.origin(),
+                member.zone
+            );
+        }
+
+        for name in members {
+            let member = listed[&name];
+            match zones.get_mut(&name) {
+                None => changes.added.push(member.clone()),
+                Some(owned) if owned.catalog == *catalog.origin() => {
+                    // a new unique label resets the member zone
+                    if owned.id != member.id || owned.group != member.group {
+                        changes.removed.push(member.zone.clone());
+                        changes.added.push(member.clone());
+                    } else {
+                        owned.coo = member.coo.clone();
+                    }
+                }
+                Some(owned) if owned.coo.as_ref() == Some(catalog.origin()) => {
+                    info!(
+                        "member zone {} changes ownership from catalog zone {} to {}",
+                        member.zone,
+                        owned.catalog,
+                        catalog.origin()
+                    );
+                    changes.removed.push(member.zone.clone());
+                    changes.added.push(member.clone());
+                }
+                Some(owned) => warn!(
+                    "ignoring member zone {} of catalog zone {}, it belongs to catalog zone {}",
+                    member.zone,
+                    catalog.origin(),
+                    owned.catalog
+                ),
+            }
+        }
+
+        for (name, owned) in zones.iter() {
+            if owned.catalog == *catalog.origin() && !listed.contains_key(name) {
+                changes.removed.push(Name::from(name));
+            }
+        }
+
+        changes
+    }
+
+    /// Serves the member zone of `catalog` with `authorities`, replacing any previous member
+    pub fn insert(
+        &self,
+        catalog: &Name,
+        member: &MemberZone,
+        authorities: Vec<Arc<dyn AuthorityObject>>,
+    ) {
+        self.zones
+            .write()
+            .expect("member zones lock poisoned")
+            .insert(
+                LowerName::from(member.zone.clone()),
+                Member {
+                    catalog: catalog.clone(),
+                    id: member.id.clone(),
+                    group: member.group.clone(),
+                    coo: member.coo.clone(),
+                    authorities,
+                },
+            );
+    }
+
+    /// Stops serving the member zone `zone`
+    pub fn remove(&self, zone: &Name) {
+        self.zones
+            .write()
+            .expect("member zones lock poisoned")
+            .remove(&LowerName::from(zone.clone()));
+    }
+
+    /// Returns the names of the served member zones and the catalog zones owning them
+    pub fn zones(&self) -> Vec<(Name, Name)> {
+        self.zones
+            .read()
+            .expect("member zones lock poisoned")
+            .iter()
+            .map(|(name, member)| (Name::from(name), member.catalog.clone()))
+            .collect()
+    }
+
+    /// Finds the member zone `name` is in, returning the zone name with its authorities
+    pub(crate) fn find(
+        &self,
+        name: &LowerName,
+    ) -> Option<(LowerName, Vec<Arc<dyn AuthorityObject>>)> {
+        let zones = self.zones.read().expect("member zones lock poisoned");
+        let mut name = name.clone();
+        loop {
+            if let Some(member) = zones.get(&name) {
+                return Some((name, member.authorities.clone()));
+            }
+
+            if name.is_root() {
+                return None;
+            }
+            name = name.base_name();
+        }
+    }
+}
+
+struct Member {
+    catalog: Name,
+    id: String,
+    group: Option<String>,
+    coo: Option<Name>,
+    authorities: Vec<Arc<dyn AuthorityObject>>,
+}
+
+/// Member zones to add and remove, see [`MemberZones::changes`]
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct MemberChanges {
+    /// Zones which must be removed, before the added ones are inserted
+    pub removed: Vec<Name>,
+    /// Zones which must be created and inserted
+    pub added: Vec<MemberZone>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::proto::rr::rdata::{PTR, TXT};
+
+    fn ptr(name: &str, target: &str) -> Record {
+        Record::from_rdata(
+            Name::from_str(name).unwrap(),
+            0,
+            RData::PTR(PTR(Name::from_str(target).unwrap())),
+        )
+    }
+
+    fn txt(name: &str, value: &str) -> Record {
+        Record::from_rdata(
+            Name::from_str(name).unwrap(),
+            0,
+            RData::TXT(TXT::new(vec![value.to_string()])),
+        )
+    }
+
+    fn catalog(origin: &str, records: &[Record]) -> Result<CatalogZone, String> {
+        CatalogZone::from_records(Name::from_str(origin).unwrap(), records)
+    }
+
+    #[test]
+    fn test_parse_catalog_zone() {
+        let zone = catalog(
+            "catalog.example.",
+            &[
+                txt("version.catalog.example.", "2"),
+                ptr("a1.zones.catalog.example.", "example.com."),
+                txt("group.a1.zones.catalog.example.", "forwarded"),
+                ptr("b2.zones.catalog.example.", "example.net."),
+                ptr("coo.b2.zones.catalog.example.", "other.example."),
+                ptr("coo.c3.zones.catalog.example.", "other.example."),
+                txt("unknown.a1.zones.catalog.example.", "ignored"),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(
+            zone.members(),
+            &[
+                MemberZone {
+                    id: "a1".to_string(),
+                    zone: Name::from_str("example.com.").unwrap(),
+                    group: Some("forwarded".to_string()),
+                    coo: None,
+                },
+                MemberZone {
+                    id: "b2".to_string(),
+                    zone: Name::from_str("example.net.").unwrap(),
+                    group: None,
+                    coo: Some(Name::from_str("other.example.").unwrap()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_broken_catalog_zones() {
+        let member = ptr("a1.zones.catalog.example.", "example.com.");
+        assert!(catalog("catalog.example.", std::slice::from_ref(&member)).is_err());
+        assert!(
+            catalog(
+                "catalog.example.",
+                &[txt("version.catalog.example.", "1"), member.clone()]
+            )
+            .is_err()
+        );
+        assert!(
+            catalog(
+                "catalog.example.",
+                &[
+                    txt("version.catalog.example.", "2"),
+                    member,
+                    ptr("a1.zones.catalog.example.", "example.net."),
+                ]
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_duplicate_member_zone() {
+        let members = MemberZones::default();
+        let version = txt("version.catalog.example.", "2");
+        let first = ptr("b2.zones.catalog.example.", "example.com.");
+
+        let zone = catalog("catalog.example.", &[version.clone(), first.clone()]).unwrap();
+        let changes = members.changes(&zone);
+        assert_eq!(changes.added.len(), 1);
+        members.insert(zone.origin(), &changes.added[0], vec![]);
+
+        // a member node listing the zone again is ignored, even if it sorts first
+        let zone = catalog(
+            "catalog.example.",
+            &[
+                version.clone(),
+                first,
+                ptr("a1.zones.catalog.example.", "example.com."),
+            ],
+        )
+        .unwrap();
+        assert_eq!(zone.members().len(), 2);
+        assert_eq!(members.changes(&zone), MemberChanges::default());
+
+        // without a served node, the first one is used
+        let changes = MemberZones::default().changes(&zone);
+        assert_eq!(changes.removed, vec![]);
+        assert_eq!(changes.added.len(), 1);
+        assert_eq!(changes.added[0].id, "a1");
+    }
+
+    #[test]
+    fn test_change_of_ownership() {
+        let members = MemberZones::default();
+        let apply = |catalog: &CatalogZone| {
+            let changes = members.changes(catalog);
+            for zone in &changes.removed {
+                members.remove(zone);
+            }
+            for member in &changes.added {
+                members.insert(catalog.origin(), member, vec![]);
+            }
+            changes
+        };
+        let owner = |zone: &str| {
+            members
+                .zones()
+                .into_iter()
+                .find(|(name, _)| *name == Name::from_str(zone).unwrap())
+                .map(|(_, catalog)| catalog.to_string())
+        };
+
+        let old = [
+            txt("version.old.example.", "2"),
+            ptr("a1.zones.old.example.", "example.com."),
+        ];
+        let new = [
+            txt("version.new.example.", "2"),
+            ptr("b2.zones.new.example.", "example.com."),
+        ];
+
+        let old_catalog = catalog("old.example.", &old).unwrap();
+        let new_catalog = catalog("new.example.", &new).unwrap();
+        assert_eq!(apply(&old_catalog).added.len(), 1);
+        assert_eq!(owner("example.com."), Some("old.example.".to_string()));
+
+        // the zone belongs to the old catalog zone
+        assert_eq!(apply(&new_catalog), MemberChanges::default());
+        assert_eq!(owner("example.com."), Some("old.example.".to_string()));
+
+        // until it points to the new one
+        let old_catalog = catalog(
+            "old.example.",
+            &[
+                old[0].clone(),
+                old[1].clone(),
+                ptr("coo.a1.zones.old.example.", "new.example."),
+            ],
+        )
+        .unwrap();
+        assert_eq!(apply(&old_catalog), MemberChanges::default());
+        assert_eq!(apply(&new_catalog).added.len(), 1);
+        assert_eq!(owner("example.com."), Some("new.example.".to_string()));
+
+        // the old catalog zone doesn't remove the zone anymore
+        let empty = catalog("old.example.", &[old[0].clone()]).unwrap();
+        assert_eq!(apply(&empty), MemberChanges::default());
+        assert_eq!(owner("example.com."), Some("new.example.".to_string()));
+
+        // but the new one does
+        let empty = catalog("new.example.", &[new[0].clone()]).unwrap();
+        assert_eq!(apply(&empty).removed.len(), 1);
+        assert_eq!(owner("example.com."), None);
+    }
+}
@@ -5,12 +5,14 @@
 // https://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+#[cfg(feature = "dnstap")]
+use crate::server::ClientTap;
 use crate::{
     authority::{Queries, message_request::MessageRequest},
     proto::{
         ProtoError,
         op::{Edns, Header, ResponseCode, message},
-        rr::Record,
+        rr::{Name, Record},
         serialize::binary::BinEncoder,
     },
     server::ResponseInfo,
@@ -33,9 +35,11 @@ where
     additionals: Additionals,
     sig0: Vec<Record>,
     edns: Option<Edns>,
+    #[cfg(feature = "dnstap")]
+    tap: Option<ClientTap>,
 }
 
-impl<'a, A, N, S, D> MessageResponse<'_, 'a, A, N, S, D>
+impl<'q, 'a, A, N, S, D> MessageResponse<'q, 'a, A, N, S, D>
 where
     A: Iterator<Item = &'a Record> + Send + 'a,
     N: Iterator<Item = &'a Record> + Send + 'a,
@@ -63,15 +67,57 @@ where
         &self.edns
     }
 
+    /// Returns the queries the response is for
+    pub(crate) fn queries(&self) -> &'q Queries {
+        self.queries
+    }
+
+    /// Sets the tap logging the response once it's emitted
+    #[cfg(feature = "dnstap")]
+    pub(crate) fn set_tap(&mut self, tap: ClientTap) {
+        self.tap = Some(tap);
+    }
+
+    /// Takes the tap, for response handlers deciding themselves what is sent
+    #[cfg(feature = "dnstap")]
+    pub(crate) fn take_tap(&mut self) -> Option<ClientTap> {
+        self.tap.take()
+    }
+
     /// Consumes self, and emits to the encoder.
     pub fn destructive_emit(
-        mut self,
+        self,
         encoder: &mut BinEncoder<'_>,
     ) -> Result<ResponseInfo, ProtoError> {
+        #[cfg(feature = "dnstap")]
+        let (tap, start) = (self.tap.clone(), encoder.offset());
+
+        let (info, _) = self.destructive_emit_with_authority(encoder)?;
+
+        #[cfg(feature = "dnstap")]
+        if let Some(tap) = tap {
+            tap.log_response(encoder.slice_of(start, encoder.offset()));
+        }
+
+        Ok(info)
+    }
+
+    /// Consumes self, and emits to the encoder, also returning the owner name of the first record
+    /// of the authority section.
+    pub(crate) fn destructive_emit_with_authority(
+        mut self,
+        encoder: &mut BinEncoder<'_>,
+    ) -> Result<(ResponseInfo, Option<Name>), ProtoError> {
+        let mut authority = None;
+
         // soa records are part of the nameserver section
-        let mut name_servers = self.name_servers.chain(self.soa);
+        let mut name_servers = self.name_servers.chain(self.soa).inspect(|record| {
+            if authority.is_none() {
+                authority = Some(record.name().clone());
+            }
+        });
 
-        message::emit_message_parts(
+        let info = message::emit_message_parts(
             &self.header,
             &mut self.queries.as_emit_and_count(),
             &mut self.answers,
@@ -80,8 +126,10 @@ where
             self.edns.as_ref(),
             &self.sig0,
             encoder,
-        )
-        .map(Into::into)
+        )?;
+
+        drop(name_servers);
+        Ok((info.into(), authority))
     }
 }
 
@@ -153,6 +201,8 @@ impl<'q> MessageResponseBuilder<'q> {
             additionals: additionals.into_iter(),
             sig0: self.sig0.unwrap_or_default(),
             edns: self.edns,
+            #[cfg(feature = "dnstap")]
+            tap: None,
         }
     }
 
@@ -177,6 +227,8 @@ impl<'q> MessageResponseBuilder<'q> {
             additionals: Box::new(None.into_iter()),
             sig0: self.sig0.unwrap_or_default(),
             edns: self.edns,
+            #[cfg(feature = "dnstap")]
+            tap: None,
         }
     }
 
@@ -211,6 +263,8 @@ impl<'q> MessageResponseBuilder<'q> {
             additionals: Box::new(None.into_iter()),
             sig0: self.sig0.unwrap_or_default(),
             edns: self.edns,
+            #[cfg(feature = "dnstap")]
+            tap: None,
         }
     }
 }
@@ -251,6 +305,8 @@ mod tests {
                 additionals: iter::once(&answer),
                 sig0: vec![],
                 edns: None,
+                #[cfg(feature = "dnstap")]
+                tap: None,
             };
 
             message
@@ -289,6 +345,8 @@ mod tests {
                 additionals: iter::repeat(&answer),
                 sig0: vec![],
                 edns: None,
+                #[cfg(feature = "dnstap")]
+                tap: None,
             };
 
             message
@@ -25,8 +25,10 @@ mod auth_lookup;
 mod authority;
 pub(crate) mod authority_object;
 mod catalog;
+pub mod catalog_zone;
 pub(crate) mod message_request;
 mod message_response;
+mod views;
 
 pub use self::auth_lookup::{
     AnyRecords, AuthLookup, AuthLookupIter, LookupRecords, LookupRecordsIter,
@@ -35,9 +37,10 @@ pub use self::authority::{Authority, LookupControlFlow, LookupOptions};
 #[cfg(feature = "__dnssec")]
 pub use self::authority::{DnssecAuthority, Nsec3QueryInfo};
 pub use self::authority_object::{AuthorityObject, DnssecSummary, EmptyLookup, LookupObject};
-pub use self::catalog::Catalog;
+pub use self::catalog::{Catalog, MinimalResponses};
 pub use self::message_request::{MessageRequest, Queries, UpdateRequest};
 pub use self::message_response::{MessageResponse, MessageResponseBuilder};
+pub use self::views::{View, Views};
 
 /// Result of an Update operation
 pub type UpdateResult<T> = Result<T, ResponseCode>;
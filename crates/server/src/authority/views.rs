@@ -0,0 +1,332 @@
+//! Split-horizon views, serving different zone data depending on who is asking
+
+use std::net::IpAddr;
+#[cfg(feature = "__dnssec")]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ipnet::IpNet;
+use tracing::{debug, error};
+
+#[cfg(feature = "__dnssec")]
+use crate::proto::dnssec::tsig::TSigner;
+#[cfg(feature = "metrics")]
+use crate::server::metrics;
+use crate::{
+    authority::{Catalog, MessageResponseBuilder},
+    proto::op::ResponseCode,
+    server::{Request, RequestHandler, ResponseHandler, ResponseInfo},
+};
+
+/// A named [`Catalog`] and the requests it answers
+///
+/// A request matches the view if it matches every criterion that is set: its source address is in
+/// one of the client networks, the address it was received on is in one of the destination
+/// networks and it is signed with one of the TSIG keys. A view without any criteria matches every
+/// request.
+pub struct View {
+    name: String,
+    match_clients: Vec<IpNet>,
+    match_destinations: Vec<IpNet>,
+    #[cfg(feature = "__dnssec")]
+    match_keys: Vec<TSigner>,
+    catalog: Catalog,
+}
+
+impl View {
+    /// Creates a view serving `catalog` to all requests
+    pub fn new(name: impl Into<String>, catalog: Catalog) -> Self {
+        Self {
+            name: name.into(),
+            match_clients: Vec::new(),
+            match_destinations: Vec::new(),
+            #[cfg(feature = "__dnssec")]
+            match_keys: Vec::new(),
+            catalog,
+        }
+    }
+
+    /// Only match requests from these networks
+    pub fn with_match_clients(mut self, networks: Vec<IpNet>) -> Self {
+        self.match_clients = networks;
+        self
+    }
+
+    /// Only match requests received on addresses in these networks
+    ///
+    /// The address is the one the server socket is bound to, so sockets bound to the unspecified
+    /// address will not match specific addresses.
+    pub fn with_match_destinations(mut self, networks: Vec<IpNet>) -> Self {
+        self.match_destinations = networks;
+        self
+    }
+
+    /// Only match requests signed with one of these TSIG keys
+    ///
+    /// The signature only selects the view, responses are not signed.
+    #[cfg(feature = "__dnssec")]
+    pub fn with_match_keys(mut self, keys: Vec<TSigner>) -> Self {
+        self.match_keys = keys;
+        self
+    }
+
+    /// The name of the view
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The zones served by the view
+    pub fn catalog(&self) -> &Catalog {
+        &self.catalog
+    }
+
+    /// Mutable access to the zones served by the view
+    pub fn catalog_mut(&mut self) -> &mut Catalog {
+        &mut self.catalog
+    }
+
+    /// Returns true if the view should answer the request
+    pub fn matches(&self, request: &Request) -> bool {
+        let in_networks =
+            |networks: &[IpNet], ip: IpAddr| networks.iter().any(|net| net.contains(&ip));
+
+        if !self.match_clients.is_empty() && !in_networks(&self.match_clients, request.src().ip()) {
+            return false;
+        }
+
+        if !self.match_destinations.is_empty()
+            && !request
+                .local_addr()
+                .is_some_and(|local| in_networks(&self.match_destinations, local.ip()))
+        {
+            return false;
+        }
+
+        #[cfg(feature = "__dnssec")]
+        if !self.match_keys.is_empty() && !self.signed_with_key(request) {
+            return false;
+        }
+
+        true
+    }
+
+    #[cfg(feature = "__dnssec")]
+    fn signed_with_key(&self, request: &Request) -> bool {
+        let Some(bytes) = request.signed_bytes() else {
+            return false;
+        };
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |now| now.as_secs());
+
+        self.match_keys
+            .iter()
+            .any(|key| match key.verify_message_byte(None, bytes, true) {
+                Ok((_, valid, _)) => valid.contains(&now),
+                Err(e) => {
+                    debug!("request does not verify with {}: {e}", key.signer_name());
+                    false
+                }
+            })
+    }
+}
+
+/// A [`RequestHandler`] dispatching requests to the first matching [`View`]
+///
+/// Requests matching no view are refused.
+#[derive(Default)]
+pub struct Views {
+    views: Vec<View>,
+}
+
+impl Views {
+    /// Creates an empty set of views, refusing all requests
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a view, it is only used for requests not matching any of the views added before it
+    pub fn push(&mut self, view: View) {
+        self.views.push(view);
+    }
+
+    /// The views, in the order they are matched
+    pub fn views(&self) -> &[View] {
+        &self.views
+    }
+
+    /// Mutable access to the views
+    pub fn views_mut(&mut self) -> &mut [View] {
+        &mut self.views
+    }
+
+    /// Returns the first view matching the request
+    pub fn find(&self, request: &Request) -> Option<&View> {
+        self.views.iter().find(|view| view.matches(request))
+    }
+}
+
+impl From<Catalog> for Views {
+    fn from(catalog: Catalog) -> Self {
+        Self {
+            views: vec![View::new("default", catalog)],
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl RequestHandler for Views {
+    async fn handle_request<R: ResponseHandler>(
+        &self,
+        request: &Request,
+        mut response_handle: R,
+    ) -> ResponseInfo {
+        if let Some(view) = self.find(request) {
+            debug!("request:{} matched view: {}", request.id(), view.name());
+            return view.catalog.handle_request(request, response_handle).await;
+        }
+
+        debug!("request:{} matched no view, refusing", request.id());
+        #[cfg(feature = "metrics")]
+        metrics::refused("view");
+        let response = MessageResponseBuilder::from_message_request(request);
+        let result = response_handle
+            .send_response(response.error_msg(request.header(), ResponseCode::Refused))
+            .await;
+
+        match result {
+            Ok(info) => info,
+            Err(e) => {
+                error!("failed to send response: {e}");
+                ResponseInfo::serve_failed()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use super::*;
+    use crate::{
+        authority::MessageRequest,
+        proto::{
+            op::{Message, Query},
+            rr::{Name, RecordType},
+            serialize::binary::{BinDecodable, BinEncodable},
+            xfer::Protocol,
+        },
+    };
+
+    fn request(src: &str, local: &str) -> Request {
+        let mut message = Message::new();
+        message.add_query(Query::query(Name::root(), RecordType::NS));
+        let message = MessageRequest::from_bytes(&message.to_bytes().unwrap()).unwrap();
+
+        Request::new(message, src.parse::<SocketAddr>().unwrap(), Protocol::Udp)
+            .with_local_addr(local.parse().unwrap())
+    }
+
+    #[test]
+    fn test_first_matching_view() {
+        let mut views = Views::new();
+        views.push(
+            View::new("internal", Catalog::new())
+                .with_match_clients(vec!["10.0.0.0/8".parse().unwrap()]),
+        );
+        views.push(
+            View::new("service", Catalog::new())
+                .with_match_destinations(vec!["192.0.2.53/32".parse().unwrap()]),
+        );
+        views.push(View::new("external", Catalog::new()));
+
+        let name = |src, local| views.find(&request(src, local)).map(View::name);
+        assert_eq!(name("10.1.2.3:5353", "192.0.2.53:53"), Some("internal"));
+        assert_eq!(name("198.51.100.1:5353", "192.0.2.53:53"), Some("service"));
+        assert_eq!(name("198.51.100.1:5353", "192.0.2.54:53"), Some("external"));
+    }
+
+    #[test]
+    fn test_all_criteria_must_match() {
+        let mut views = Views::new();
+        views.push(
+            View::new("internal", Catalog::new())
+                .with_match_clients(vec!["10.0.0.0/8".parse().unwrap()])
+                .with_match_destinations(vec!["10.0.0.53/32".parse().unwrap()]),
+        );
+
+        assert!(
+            views
+                .find(&request("10.1.2.3:5353", "10.0.0.53:53"))
+                .is_some()
+        );
+        assert!(
+            views
+                .find(&request("10.1.2.3:5353", "192.0.2.53:53"))
+                .is_none()
+        );
+        assert!(
+            views
+                .find(&request("192.0.2.1:5353", "10.0.0.53:53"))
+                .is_none()
+        );
+    }
+
+    #[cfg(feature = "__dnssec")]
+    #[test]
+    fn test_match_keys() {
+        use crate::proto::{dnssec::rdata::tsig::TsigAlgorithm, op::MessageFinalizer};
+
+        let key = |name: &str, secret: &[u8]| {
+            TSigner::new(
+                secret.to_vec(),
+                TsigAlgorithm::HmacSha256,
+                Name::from_ascii(name).unwrap(),
+                300,
+            )
+            .unwrap()
+        };
+        let internal = key("internal-key", b"internal secret");
+
+        let mut views = Views::new();
+        views.push(View::new("internal", Catalog::new()).with_match_keys(vec![internal.clone()]));
+        views.push(View::new("external", Catalog::new()));
+
+        let signed = |signer: &TSigner| {
+            let mut message = Message::new();
+            message.add_query(Query::query(Name::root(), RecordType::NS));
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs() as u32;
+            let (records, _) = signer.finalize_message(&message, now).unwrap();
+            for record in records {
+                message.add_tsig(record);
+            }
+
+            let bytes = message.to_bytes().unwrap();
+            Request::new(
+                MessageRequest::from_bytes(&bytes).unwrap(),
+                "192.0.2.1:5353".parse().unwrap(),
+                Protocol::Udp,
+            )
+            .with_signed_bytes(&bytes)
+        };
+
+        let name = |request: Request| views.find(&request).map(View::name);
+        assert_eq!(name(signed(&internal)), Some("internal"));
+        assert_eq!(
+            name(signed(&key("internal-key", b"wrong secret"))),
+            Some("external")
+        );
+        assert_eq!(
+            name(signed(&key("other-key", b"internal secret"))),
+            Some("external")
+        );
+        assert_eq!(
+            name(request("192.0.2.1:5353", "192.0.2.53:53")),
+            Some("external")
+        );
+    }
+}
@@ -0,0 +1,350 @@
+//! Limits on the connections of stream based listeners
+//!
+//! Every TCP, TLS and HTTPS connection holds a permit from the server's [`ConnectionLimiter`], so
+//! that a single client, or many of them, can't exhaust the server's sockets and tasks. Queries
+//! pipelined on a TCP or TLS connection are processed concurrently, up to a per-connection limit,
+//! and answered in the order they complete.
+//!
+//! Connections are closed after being idle for the timeout of their listener. Clients that send
+//! the edns-tcp-keepalive option ([RFC 7828](https://tools.ietf.org/html/rfc7828)) get the
+//! keepalive timeout instead, which is advertised to them in the option of the response.
+
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use serde::Deserialize;
+use tracing::debug;
+
+use crate::proto::{
+    op::Edns,
+    rr::rdata::opt::{EdnsCode, EdnsOption, TcpKeepalive},
+};
+#[cfg(feature = "metrics")]
+use crate::server::metrics::ConnectionMetrics;
+
+/// Configuration of the [`ConnectionLimiter`]
+///
+/// A limit of `0` does not limit.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConnectionLimitConfig {
+    /// Maximum number of concurrent connections over all TCP, TLS and HTTPS listeners. Defaults
+    /// to `0`.
+    pub max_connections: usize,
+
+    /// Maximum number of concurrent connections from a single client address. Defaults to `0`.
+    pub max_connections_per_ip: usize,
+
+    /// Maximum number of pipelined queries processed concurrently on a TCP or TLS connection,
+    /// further queries are not read until one of them is answered. Defaults to 16.
+    pub max_in_flight: usize,
+
+    /// Idle timeout in seconds for connections of clients sending the edns-tcp-keepalive option,
+    /// advertised in the responses to them. Defaults to the timeout of the listener, limited to
+    /// 6553.5 seconds, the largest timeout the option can carry.
+    pub keepalive_timeout: Option<u64>,
+}
+
+impl Default for ConnectionLimitConfig {
+    fn default() -> Self {
+        Self {
+            max_connections: 0,
+            max_connections_per_ip: 0,
+            max_in_flight: 16,
+            keepalive_timeout: None,
+        }
+    }
+}
+
+/// Counts the connections of all stream based listeners of a server
+#[derive(Debug, Default)]
+pub struct ConnectionLimiter {
+    config: ConnectionLimitConfig,
+    connections: Mutex<ConnectionCounts>,
+    #[cfg(feature = "metrics")]
+    metrics: ConnectionMetrics,
+}
+
+impl ConnectionLimiter {
+    /// Creates a connection limiter from its configuration
+    pub fn new(config: ConnectionLimitConfig) -> Self {
+        Self {
+            config,
+            connections: Mutex::default(),
+            #[cfg(feature = "metrics")]
+            metrics: ConnectionMetrics::new(),
+        }
+    }
+
+    /// Accounts for a new connection from `client`, returns `None` if a limit has been reached
+    ///
+    /// The connection is counted until the returned permit is dropped.
+    pub fn try_acquire(self: &Arc<Self>, client: IpAddr) -> Option<ConnectionPermit> {
+        let mut permit = self.try_acquire_connection()?;
+        permit.try_set_client(client).then_some(permit)
+    }
+
+    /// Accounts for a new connection whose client isn't known yet, returns `None` if the limit
+    /// of all connections has been reached
+    ///
+    /// This is meant to be called before any work is done for the connection, the limit per
+    /// client is applied once it's known, see [`ConnectionPermit::try_set_client`].
+    pub fn try_acquire_connection(self: &Arc<Self>) -> Option<ConnectionPermit> {
+        let mut connections = self.connections.lock().expect("poisoned connection counts");
+        if self.config.max_connections > 0 && connections.total >= self.config.max_connections {
+            debug!("connection limit reached, refusing connection");
+            #[cfg(feature = "metrics")]
+            self.metrics.rejected.increment(1);
+            return None;
+        }
+
+        connections.total += 1;
+        #[cfg(feature = "metrics")]
+        self.metrics.open.increment(1);
+        Some(ConnectionPermit {
+            limiter: self.clone(),
+            client: None,
+        })
+    }
+
+    /// The number of open connections
+    pub fn connections(&self) -> usize {
+        self.connections
+            .lock()
+            .expect("poisoned connection counts")
+            .total
+    }
+
+    /// The number of pipelined queries processed concurrently on a connection, `0` if unlimited
+    pub(crate) fn max_in_flight(&self) -> usize {
+        self.config.max_in_flight
+    }
+
+    /// The idle timeout of a new connection on a listener with the given timeout
+    ///
+    /// The keepalive timeout is limited to what the edns-tcp-keepalive option can advertise, so
+    /// that connections are kept open exactly as long as clients are told.
+    pub(crate) fn idle_timeout(&self, timeout: Duration) -> Arc<IdleTimeout> {
+        let keepalive = self
+            .config
+            .keepalive_timeout
+            .map_or(timeout, Duration::from_secs);
+        Arc::new(IdleTimeout::new(
+            timeout,
+            TcpKeepalive::with_timeout(keepalive),
+        ))
+    }
+}
+
+#[derive(Debug, Default)]
+struct ConnectionCounts {
+    total: usize,
+    per_ip: HashMap<IpAddr, usize>,
+}
+
+impl ConnectionCounts {
+    fn remove_client(&mut self, client: IpAddr) {
+        if let Some(per_ip) = self.per_ip.get_mut(&client) {
+            *per_ip -= 1;
+            if *per_ip == 0 {
+                self.per_ip.remove(&client);
+            }
+        }
+    }
+}
+
+/// A connection counted by the [`ConnectionLimiter`], until this is dropped
+#[derive(Debug)]
+pub struct ConnectionPermit {
+    limiter: Arc<ConnectionLimiter>,
+    client: Option<IpAddr>,
+}
+
+impl ConnectionPermit {
+    /// Accounts for the connection against the limit of `client`, returns `false` if it has been
+    /// reached
+    ///
+    /// The permit should be dropped then, to close the connection. If a client was set already,
+    /// it's replaced.
+    pub fn try_set_client(&mut self, client: IpAddr) -> bool {
+        let limiter = &self.limiter;
+        let mut connections = limiter
+            .connections
+            .lock()
+            .expect("poisoned connection counts");
+        if let Some(previous) = self.client.take() {
+            connections.remove_client(previous);
+        }
+
+        let per_ip = connections.per_ip.entry(client).or_default();
+        if limiter.config.max_connections_per_ip > 0
+            && *per_ip >= limiter.config.max_connections_per_ip
+        {
+            debug!("connection limit per client reached, refusing {client}");
+            #[cfg(feature = "metrics")]
+            limiter.metrics.rejected.increment(1);
+            if *per_ip == 0 {
+                connections.per_ip.remove(&client);
+            }
+            return false;
+        }
+
+        *per_ip += 1;
+        self.client = Some(client);
+        true
+    }
+}
+
+impl Drop for ConnectionPermit {
+    fn drop(&mut self) {
+        let mut connections = self
+            .limiter
+            .connections
+            .lock()
+            .expect("poisoned connection counts");
+        connections.total -= 1;
+        #[cfg(feature = "metrics")]
+        self.limiter.metrics.open.decrement(1);
+        if let Some(client) = self.client {
+            connections.remove_client(client);
+        }
+    }
+}
+
+/// Returns true if the EDNS of a request carries the edns-tcp-keepalive option
+pub(crate) fn requests_keepalive(edns: Option<&Edns>) -> bool {
+    edns.is_some_and(|edns| edns.option(EdnsCode::Keepalive).is_some())
+}
+
+/// The idle timeout of a TCP or TLS connection, negotiated with edns-tcp-keepalive
+#[derive(Debug)]
+pub(crate) struct IdleTimeout {
+    millis: AtomicU64,
+    keepalive: TcpKeepalive,
+}
+
+impl IdleTimeout {
+    fn new(timeout: Duration, keepalive: TcpKeepalive) -> Self {
+        Self {
+            millis: AtomicU64::new(timeout.as_millis() as u64),
+            keepalive,
+        }
+    }
+
+    /// The current timeout, `0` if the connection is never idle
+    pub(crate) fn get(&self) -> Duration {
+        Duration::from_millis(self.millis.load(Ordering::Relaxed))
+    }
+
+    /// Switches the connection to the keepalive timeout, advertising it in the response
+    ///
+    /// Nothing is negotiated if the response has no EDNS to carry the option.
+    pub(crate) fn negotiate(&self, response: Option<&Edns>) -> Option<Edns> {
+        let mut edns = response?.clone();
+        edns.options_mut()
+            .insert(EdnsOption::Keepalive(self.keepalive));
+
+        let keepalive = self.keepalive.timeout().unwrap_or_default();
+        self.millis
+            .store(keepalive.as_millis() as u64, Ordering::Relaxed);
+        Some(edns)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+
+    #[test]
+    fn test_connection_limits() {
+        let limiter = Arc::new(ConnectionLimiter::new(ConnectionLimitConfig {
+            max_connections: 3,
+            max_connections_per_ip: 2,
+            ..ConnectionLimitConfig::default()
+        }));
+        let client = IpAddr::from(Ipv4Addr::new(192, 0, 2, 1));
+        let other = IpAddr::from(Ipv4Addr::new(192, 0, 2, 2));
+
+        let first = limiter.try_acquire(client).unwrap();
+        let _second = limiter.try_acquire(client).unwrap();
+        assert!(limiter.try_acquire(client).is_none());
+
+        let _third = limiter.try_acquire(other).unwrap();
+        assert!(limiter.try_acquire(other).is_none());
+        assert_eq!(limiter.connections(), 3);
+
+        drop(first);
+        assert_eq!(limiter.connections(), 2);
+        assert!(limiter.try_acquire(client).is_some());
+    }
+
+    #[test]
+    fn test_connection_limits_before_client() {
+        let limiter = Arc::new(ConnectionLimiter::new(ConnectionLimitConfig {
+            max_connections: 2,
+            max_connections_per_ip: 1,
+            ..ConnectionLimitConfig::default()
+        }));
+        let proxy = IpAddr::from(Ipv4Addr::new(192, 0, 2, 1));
+        let client = IpAddr::from(Ipv4Addr::new(192, 0, 2, 2));
+
+        // connections count against the total limit before their client is known
+        let mut first = limiter.try_acquire_connection().unwrap();
+        let mut second = limiter.try_acquire_connection().unwrap();
+        assert!(limiter.try_acquire_connection().is_none());
+
+        // the client from the PROXY header replaces the address of the proxy
+        assert!(first.try_set_client(proxy));
+        assert!(first.try_set_client(client));
+        assert!(!second.try_set_client(client));
+        drop(second);
+        assert_eq!(limiter.connections(), 1);
+
+        assert!(limiter.try_acquire(proxy).is_some());
+        drop(first);
+        assert_eq!(limiter.connections(), 0);
+        assert!(limiter.connections.lock().unwrap().per_ip.is_empty());
+    }
+
+    #[test]
+    fn test_negotiate_keepalive() {
+        let limiter = ConnectionLimiter::new(ConnectionLimitConfig {
+            keepalive_timeout: Some(120),
+            ..ConnectionLimitConfig::default()
+        });
+        let idle_timeout = limiter.idle_timeout(Duration::from_secs(5));
+        assert_eq!(idle_timeout.get(), Duration::from_secs(5));
+
+        assert!(idle_timeout.negotiate(None).is_none());
+        assert_eq!(idle_timeout.get(), Duration::from_secs(5));
+
+        let edns = idle_timeout.negotiate(Some(&Edns::new())).unwrap();
+        assert_eq!(
+            edns.option(EdnsCode::Keepalive),
+            Some(&EdnsOption::Keepalive(TcpKeepalive::new(Some(1200))))
+        );
+        assert_eq!(idle_timeout.get(), Duration::from_secs(120));
+
+        // the connection stays open as long as advertised, even above the largest option value
+        let limiter = ConnectionLimiter::new(ConnectionLimitConfig {
+            keepalive_timeout: Some(24 * 60 * 60),
+            ..ConnectionLimitConfig::default()
+        });
+        let idle_timeout = limiter.idle_timeout(Duration::from_secs(5));
+        let edns = idle_timeout.negotiate(Some(&Edns::new())).unwrap();
+        assert_eq!(
+            edns.option(EdnsCode::Keepalive),
+            Some(&EdnsOption::Keepalive(TcpKeepalive::new(Some(u16::MAX))))
+        );
+        assert_eq!(idle_timeout.get(), Duration::from_millis(6_553_500));
+    }
+}
@@ -0,0 +1,64 @@
+//! dnstap logging of the messages exchanged with clients
+
+use std::{net::SocketAddr, time::SystemTime};
+
+use crate::proto::{
+    dnstap::{Dnstap, DnstapMessage, DnstapMessageType},
+    xfer::Protocol,
+};
+
+/// Logs the query of a client and the response sent to it
+#[derive(Clone, Debug)]
+pub(crate) struct ClientTap {
+    dnstap: Dnstap,
+    protocol: Protocol,
+    src_addr: SocketAddr,
+    local_addr: SocketAddr,
+    query_time: SystemTime,
+}
+
+impl ClientTap {
+    /// Starts an exchange with the client at `src_addr`, received now on `local_addr`
+    pub(crate) fn new(
+        dnstap: Dnstap,
+        protocol: Protocol,
+        src_addr: SocketAddr,
+        local_addr: SocketAddr,
+    ) -> Self {
+        Self {
+            dnstap,
+            protocol,
+            src_addr,
+            local_addr,
+            query_time: SystemTime::now(),
+        }
+    }
+
+    /// Logs the query as received from the client
+    pub(crate) fn log_query(&self, message: &[u8]) {
+        self.dnstap.log(
+            DnstapMessage::query(
+                DnstapMessageType::ClientQuery,
+                self.protocol,
+                message.to_vec(),
+            )
+            .with_query_time(self.query_time)
+            .with_query_address(self.src_addr)
+            .with_response_address(self.local_addr),
+        );
+    }
+
+    /// Logs the response as sent to the client
+    pub(crate) fn log_response(&self, message: &[u8]) {
+        self.dnstap.log(
+            DnstapMessage::response(
+                DnstapMessageType::ClientResponse,
+                self.protocol,
+                message.to_vec(),
+            )
+            .with_query_time(self.query_time)
+            .with_query_address(self.src_addr)
+            .with_response_address(self.local_addr),
+        );
+    }
+}
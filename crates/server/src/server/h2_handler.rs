@@ -15,19 +15,25 @@ use tokio::io::{AsyncRead, AsyncWrite};
 use tokio_util::sync::CancellationToken;
 use tracing::{debug, warn};
 
+#[cfg(feature = "metrics")]
+use crate::server::metrics;
 use crate::{
-    access::AccessControl,
     authority::MessageResponse,
     proto::h2::h2_server,
     proto::xfer::Protocol,
-    server::{ResponseInfo, request_handler::RequestHandler, response_handler::ResponseHandler},
+    server::{
+        ResponseInfo, ServerContext, request_handler::RequestHandler,
+        response_handler::ResponseHandler,
+    },
 };
 
+#[allow(clippy::too_many_arguments)]
 pub(crate) async fn h2_handler<T, I>(
-    access: Arc<AccessControl>,
+    context: Arc<ServerContext>,
     handler: Arc<T>,
     io: I,
     src_addr: SocketAddr,
+    local_addr: SocketAddr,
     dns_hostname: Option<Arc<str>>,
     http_endpoint: Arc<str>,
     shutdown: CancellationToken,
@@ -71,7 +77,7 @@ pub(crate) async fn h2_handler<T, I>(
         let dns_hostname = dns_hostname.clone();
         let http_endpoint = http_endpoint.clone();
         let handler = handler.clone();
-        let access = access.clone();
+        let context = context.clone();
         let responder = HttpsResponseHandle(Arc::new(Mutex::new(respond)));
 
         tokio::spawn(async move {
@@ -83,8 +89,17 @@ pub(crate) async fn h2_handler<T, I>(
                 }
             };
 
-            super::handle_request(&body, src_addr, Protocol::Https, access, handler, responder)
-                .await
+            super::handle_request(
+                &body,
+                src_addr,
+                local_addr,
+                Protocol::Https,
+                context,
+                handler,
+                responder,
+                None,
+            )
+            .await
         });
 
         // we'll continue handling requests from here.
@@ -119,6 +134,8 @@ impl ResponseHandler for HttpsResponseHandle {
         };
         let bytes = Bytes::from(bytes);
         let response = response::new(Version::Http2, bytes.len())?;
+        #[cfg(feature = "metrics")]
+        metrics::response_size(Protocol::Https, bytes.len());
 
         debug!("sending response: {:#?}", response);
         let mut stream = self
@@ -14,10 +14,14 @@ use h3_quinn::BidiStream;
 use tokio_util::sync::CancellationToken;
 use tracing::{debug, warn};
 
+#[cfg(feature = "metrics")]
+use crate::server::metrics;
 use crate::{
-    access::AccessControl,
     authority::MessageResponse,
-    server::{ResponseInfo, request_handler::RequestHandler, response_handler::ResponseHandler},
+    server::{
+        ResponseInfo, ServerContext, request_handler::RequestHandler,
+        response_handler::ResponseHandler,
+    },
 };
 use hickory_proto::{
     ProtoError,
@@ -28,10 +32,11 @@ use hickory_proto::{
 };
 
 pub(crate) async fn h3_handler<T>(
-    access: Arc<AccessControl>,
+    context: Arc<ServerContext>,
     handler: Arc<T>,
     mut connection: H3Connection,
     src_addr: SocketAddr,
+    local_addr: SocketAddr,
     _dns_hostname: Option<Arc<str>>,
     shutdown: CancellationToken,
 ) -> Result<(), ProtoError>
@@ -74,13 +79,22 @@ where
             request.remaining()
         );
         let handler = handler.clone();
-        let access = access.clone();
+        let context = context.clone();
         let stream = Arc::new(Mutex::new(stream));
         let responder = H3ResponseHandle(stream.clone());
 
         tokio::spawn(async move {
-            super::handle_request(&request, src_addr, Protocol::H3, access, handler, responder)
-                .await
+            super::handle_request(
+                &request,
+                src_addr,
+                local_addr,
+                Protocol::H3,
+                context,
+                handler,
+                responder,
+                None,
+            )
+            .await
         });
 
         max_requests -= 1;
@@ -122,6 +136,8 @@ impl ResponseHandler for H3ResponseHandle {
         };
         let bytes = Bytes::from(bytes);
         let response = response::new(Version::Http3, bytes.len())?;
+        #[cfg(feature = "metrics")]
+        metrics::response_size(Protocol::H3, bytes.len());
 
         debug!("sending response: {:#?}", response);
         let mut stream = self.0.lock().await;
@@ -0,0 +1,413 @@
+//! Metrics of the requests handled by the server
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use metrics::{
+    Counter, Gauge, Histogram, Unit, counter, describe_counter, describe_gauge, describe_histogram,
+    gauge, histogram,
+};
+
+use crate::{
+    proto::{
+        op::{Header, LowerQuery, OpCode, ResponseCode},
+        rr::RecordType,
+        xfer::Protocol,
+    },
+    server::ResponseInfo,
+};
+
+/// Counters and histograms of the requests answered by a server, labelled by their protocol
+#[derive(Clone)]
+pub(crate) struct RequestMetrics {
+    protocols: Arc<[ProtocolMetrics]>,
+    other: Arc<ProtocolMetrics>,
+    refused_access: Counter,
+}
+
+impl RequestMetrics {
+    pub(crate) fn new() -> Self {
+        describe_counter!(
+            REQUESTS,
+            Unit::Count,
+            "number of answered requests by protocol and opcode"
+        );
+        describe_counter!(
+            QUERIES,
+            Unit::Count,
+            "number of answered queries by protocol and query type"
+        );
+        describe_counter!(
+            RESPONSES,
+            Unit::Count,
+            "number of sent responses by protocol and response code"
+        );
+        describe_counter!(
+            TRUNCATED,
+            Unit::Count,
+            "number of sent responses with the truncation flag set"
+        );
+        describe_histogram!(
+            "hickory_response_size_bytes",
+            Unit::Bytes,
+            "size of the serialized responses"
+        );
+        describe_histogram!(
+            DURATION,
+            Unit::Seconds,
+            "time from receiving a request to sending its response"
+        );
+
+        let refused_access = counter!(REFUSED, "reason" => "access");
+        describe_counter!(
+            REFUSED,
+            Unit::Count,
+            "number of requests refused by an access control list"
+        );
+
+        let protocols = [
+            Protocol::Udp,
+            Protocol::Tcp,
+            #[cfg(feature = "__tls")]
+            Protocol::Tls,
+            #[cfg(feature = "__https")]
+            Protocol::Https,
+            #[cfg(feature = "__quic")]
+            Protocol::Quic,
+            #[cfg(feature = "__h3")]
+            Protocol::H3,
+        ];
+
+        Self {
+            protocols: protocols
+                .into_iter()
+                .map(|protocol| ProtocolMetrics::new(Some(protocol), protocol.to_string()))
+                .collect(),
+            other: Arc::new(ProtocolMetrics::new(None, OTHER.to_owned())),
+            refused_access,
+        }
+    }
+
+    /// Accounts for a response to a request
+    pub(crate) fn response(
+        &self,
+        protocol: Protocol,
+        request: &Header,
+        queries: &[LowerQuery],
+        response: &ResponseInfo,
+        duration: Duration,
+    ) {
+        let metrics = self
+            .protocols
+            .iter()
+            .find(|metrics| metrics.protocol == Some(protocol))
+            .unwrap_or(&self.other);
+
+        metrics
+            .requests
+            .get(&request.op_code())
+            .unwrap_or(&metrics.other_requests)
+            .increment(1);
+
+        for query in queries {
+            metrics
+                .queries
+                .get(&query.query_type())
+                .unwrap_or(&metrics.other_queries)
+                .increment(1);
+        }
+
+        metrics
+            .responses
+            .get(&response.response_code())
+            .unwrap_or(&metrics.other_responses)
+            .increment(1);
+
+        if response.truncated() {
+            metrics.truncated.increment(1);
+        }
+
+        metrics.duration.record(duration.as_secs_f64());
+    }
+
+    /// Accounts for a request refused by the access control list of the server
+    pub(crate) fn refused_access(&self) {
+        self.refused_access.increment(1);
+    }
+}
+
+impl Default for RequestMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Handles of the series of one protocol, opcodes, query types and response codes without their
+/// own series are counted as `other`
+struct ProtocolMetrics {
+    protocol: Option<Protocol>,
+    requests: HashMap<OpCode, Counter>,
+    other_requests: Counter,
+    queries: HashMap<RecordType, Counter>,
+    other_queries: Counter,
+    responses: HashMap<ResponseCode, Counter>,
+    other_responses: Counter,
+    truncated: Counter,
+    duration: Histogram,
+}
+
+impl ProtocolMetrics {
+    fn new(protocol: Option<Protocol>, label: String) -> Self {
+        let requests = [
+            OpCode::Query,
+            OpCode::Status,
+            OpCode::Notify,
+            OpCode::Update,
+        ]
+        .into_iter()
+        .map(|op_code| {
+            let counter = counter!(
+                REQUESTS,
+                "protocol" => label.clone(),
+                "opcode" => op_code.to_string()
+            );
+            (op_code, counter)
+        })
+        .collect();
+
+        let queries = QUERY_TYPES
+            .iter()
+            .map(|&query_type| {
+                let counter = counter!(
+                    QUERIES,
+                    "protocol" => label.clone(),
+                    "type" => <&'static str>::from(query_type)
+                );
+                (query_type, counter)
+            })
+            .collect();
+
+        let responses = RESPONSE_CODES
+            .iter()
+            .map(|&response_code| {
+                let counter = counter!(
+                    RESPONSES,
+                    "protocol" => label.clone(),
+                    "rcode" => u16::from(response_code).to_string()
+                );
+                (response_code, counter)
+            })
+            .collect();
+
+        Self {
+            protocol,
+            requests,
+            other_requests: counter!(REQUESTS, "protocol" => label.clone(), "opcode" => OTHER),
+            queries,
+            other_queries: counter!(QUERIES, "protocol" => label.clone(), "type" => OTHER),
+            responses,
+            other_responses: counter!(RESPONSES, "protocol" => label.clone(), "rcode" => OTHER),
+            truncated: counter!(TRUNCATED, "protocol" => label.clone()),
+            duration: histogram!(DURATION, "protocol" => label),
+        }
+    }
+}
+
+/// Accounts for the size of a serialized response
+pub(crate) fn response_size(protocol: Protocol, len: usize) {
+    histogram!("hickory_response_size_bytes", "protocol" => protocol.to_string())
+        .record(len as f64);
+}
+
+/// Accounts for a request refused for the given reason, by a zone or view of the catalog
+pub(crate) fn refused(reason: &'static str) {
+    counter!(REFUSED, "reason" => reason).increment(1);
+}
+
+/// Gauge and counter of the connections of the stream based listeners
+#[derive(Debug)]
+pub(crate) struct ConnectionMetrics {
+    pub(crate) open: Gauge,
+    pub(crate) rejected: Counter,
+}
+
+impl ConnectionMetrics {
+    pub(crate) fn new() -> Self {
+        let open = gauge!("hickory_stream_connections");
+        describe_gauge!(
+            "hickory_stream_connections",
+            Unit::Count,
+            "number of open TCP, TLS and HTTPS connections"
+        );
+
+        let rejected = counter!("hickory_stream_connections_rejected_total");
+        describe_counter!(
+            "hickory_stream_connections_rejected_total",
+            Unit::Count,
+            "number of connections closed because a connection limit was reached"
+        );
+
+        Self { open, rejected }
+    }
+}
+
+impl Default for ConnectionMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Query types with their own series
+const QUERY_TYPES: &[RecordType] = &[
+    RecordType::A,
+    RecordType::AAAA,
+    RecordType::ANY,
+    RecordType::AXFR,
+    RecordType::CAA,
+    RecordType::CDNSKEY,
+    RecordType::CDS,
+    RecordType::CNAME,
+    RecordType::DNSKEY,
+    RecordType::DS,
+    RecordType::HINFO,
+    RecordType::HTTPS,
+    RecordType::IXFR,
+    RecordType::MX,
+    RecordType::NAPTR,
+    RecordType::NS,
+    RecordType::NSEC,
+    RecordType::NSEC3PARAM,
+    RecordType::NULL,
+    RecordType::OPENPGPKEY,
+    RecordType::PTR,
+    RecordType::SOA,
+    RecordType::SRV,
+    RecordType::SSHFP,
+    RecordType::SVCB,
+    RecordType::TLSA,
+    RecordType::TXT,
+];
+
+/// Response codes with their own series, labelled by their numeric value
+const RESPONSE_CODES: &[ResponseCode] = &[
+    ResponseCode::NoError,
+    ResponseCode::FormErr,
+    ResponseCode::ServFail,
+    ResponseCode::NXDomain,
+    ResponseCode::NotImp,
+    ResponseCode::Refused,
+    ResponseCode::YXDomain,
+    ResponseCode::YXRRSet,
+    ResponseCode::NXRRSet,
+    ResponseCode::NotAuth,
+    ResponseCode::NotZone,
+    ResponseCode::BADVERS,
+    ResponseCode::BADKEY,
+    ResponseCode::BADTIME,
+    ResponseCode::BADMODE,
+    ResponseCode::BADNAME,
+    ResponseCode::BADALG,
+    ResponseCode::BADTRUNC,
+    ResponseCode::BADCOOKIE,
+];
+
+const REQUESTS: &str = "hickory_requests_total";
+const QUERIES: &str = "hickory_request_queries_total";
+const RESPONSES: &str = "hickory_responses_total";
+const TRUNCATED: &str = "hickory_responses_truncated_total";
+const DURATION: &str = "hickory_request_duration_seconds";
+const REFUSED: &str = "hickory_requests_refused_total";
+const OTHER: &str = "other";
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder, Snapshotter};
+
+    use super::*;
+    use crate::proto::{op::Query, rr::Name};
+
+    #[test]
+    fn test_response() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+
+        metrics::with_local_recorder(&recorder, || {
+            let metrics = RequestMetrics::new();
+
+            let mut request = Header::new();
+            request.set_op_code(OpCode::Query);
+            let name = Name::from_str("www.example.com.").unwrap();
+            let queries = [
+                LowerQuery::from(Query::query(name.clone(), RecordType::A)),
+                LowerQuery::from(Query::query(name, RecordType::Unknown(65280))),
+            ];
+
+            let mut response = Header::response_from_request(&request);
+            response
+                .set_response_code(ResponseCode::NXDomain)
+                .set_truncated(true);
+
+            metrics.response(
+                Protocol::Udp,
+                &request,
+                &queries,
+                &ResponseInfo::from(response),
+                Duration::from_millis(5),
+            );
+        });
+
+        let udp = [("protocol", "udp")];
+        assert_eq!(
+            counter(&snapshotter, REQUESTS, &[udp[0], ("opcode", "QUERY")]),
+            Some(1)
+        );
+        assert_eq!(
+            counter(&snapshotter, QUERIES, &[udp[0], ("type", "A")]),
+            Some(1)
+        );
+        assert_eq!(
+            counter(&snapshotter, QUERIES, &[udp[0], ("type", "other")]),
+            Some(1)
+        );
+        assert_eq!(
+            counter(&snapshotter, RESPONSES, &[udp[0], ("rcode", "3")]),
+            Some(1)
+        );
+        assert_eq!(counter(&snapshotter, TRUNCATED, &udp), Some(1));
+
+        // series are registered up front
+        assert_eq!(
+            counter(
+                &snapshotter,
+                RESPONSES,
+                &[("protocol", "tcp"), ("rcode", "0")]
+            ),
+            Some(0)
+        );
+        assert_eq!(
+            counter(&snapshotter, QUERIES, &[udp[0], ("type", "Unknown")]),
+            None
+        );
+    }
+
+    fn counter(snapshotter: &Snapshotter, name: &str, labels: &[(&str, &str)]) -> Option<u64> {
+        snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .find_map(|(key, _, _, value)| {
+                let key = key.key();
+                let matches = key.name() == name
+                    && key.labels().count() == labels.len()
+                    && key
+                        .labels()
+                        .all(|label| labels.contains(&(label.key(), label.value())));
+
+                match value {
+                    DebugValue::Counter(count) if matches => Some(count),
+                    _ => None,
+                }
+            })
+    }
+}
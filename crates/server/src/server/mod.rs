@@ -7,14 +7,16 @@
 
 //! `Server` component for hosting a domain name servers operations.
 
+#[cfg(feature = "metrics")]
+use std::time::Instant;
 use std::{
     io,
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
-    sync::Arc,
+    sync::{Arc, RwLock},
     time::Duration,
 };
 
-use futures_util::{FutureExt, StreamExt};
+use futures_util::{AsyncRead, FutureExt, Stream, StreamExt, stream};
 use ipnet::IpNet;
 #[cfg(feature = "__tls")]
 use rustls::{ServerConfig, server::ResolvesServerCert};
@@ -24,6 +26,10 @@ use tokio::{net, task::JoinSet};
 use tokio_util::sync::CancellationToken;
 use tracing::{debug, info, warn};
 
+#[cfg(feature = "dnstap")]
+use crate::proto::dnstap::Dnstap;
+#[cfg(feature = "testing")]
+use crate::proto::runtime::sim::{SimRuntimeProvider, SimTcpListener, SimUdpSocket};
 #[cfg(feature = "__tls")]
 use crate::proto::rustls::default_provider;
 use crate::{
@@ -33,26 +39,104 @@ use crate::{
         BufDnsStreamHandle, ProtoError,
         op::{Header, LowerQuery, MessageType, Query, ResponseCode},
         rr::Record,
-        runtime::{TokioRuntimeProvider, iocompat::AsyncIoTokioAsStd},
+        runtime::{RuntimeProvider, TokioRuntimeProvider, iocompat::AsyncIoTokioAsStd},
         serialize::binary::{BinDecodable, BinDecoder},
-        tcp::TcpStream,
+        tcp::{DnsTcpStream, TcpStream},
         udp::UdpStream,
         xfer::{Protocol, SerialMessage},
     },
 };
 
+mod connection_limit;
 #[cfg(feature = "__https")]
 mod h2_handler;
 #[cfg(feature = "__h3")]
 mod h3_handler;
 #[cfg(feature = "__quic")]
 mod quic_handler;
+use connection_limit::IdleTimeout;
+pub use connection_limit::{ConnectionLimitConfig, ConnectionLimiter, ConnectionPermit};
 mod request_handler;
 pub use request_handler::{Request, RequestHandler, RequestInfo, ResponseInfo};
+mod proxy_protocol;
+pub use proxy_protocol::ProxyProtocolConfig;
 mod response_handler;
 pub use response_handler::{ResponseHandle, ResponseHandler};
+mod response_rate_limit;
+pub use response_rate_limit::{
+    RateLimitAction, ResponseKind, ResponseRateLimitConfig, ResponseRateLimiter,
+};
 mod timeout_stream;
 pub use timeout_stream::TimeoutStream;
+#[cfg(feature = "dnstap")]
+mod dnstap;
+#[cfg(feature = "dnstap")]
+pub(crate) use dnstap::ClientTap;
+#[cfg(feature = "metrics")]
+pub(crate) mod metrics;
+#[cfg(feature = "metrics")]
+use metrics::RequestMetrics;
+
+/// State shared by the request handling of all listeners
+///
+/// The settings behind locks can be changed at any time, listeners read them for every new
+/// connection or UDP request.
+#[derive(Default)]
+pub(crate) struct ServerContext {
+    access: AccessControl,
+    rate_limiter: RwLock<Option<Arc<ResponseRateLimiter>>>,
+    connection_limiter: RwLock<Arc<ConnectionLimiter>>,
+    proxy_protocol: RwLock<Option<Arc<ProxyProtocolConfig>>>,
+    #[cfg(feature = "dnstap")]
+    dnstap: RwLock<Option<Dnstap>>,
+    #[cfg(feature = "metrics")]
+    metrics: RequestMetrics,
+}
+
+impl ServerContext {
+    fn new(access: AccessControl) -> Self {
+        Self {
+            access,
+            ..Self::default()
+        }
+    }
+
+    /// The limiter of responses to UDP requests, if response rate limiting is enabled
+    fn rate_limiter(&self) -> Option<Arc<ResponseRateLimiter>> {
+        self.rate_limiter
+            .read()
+            .expect("rate limiter lock poisoned")
+            .clone()
+    }
+
+    /// The limiter of TCP, TLS and HTTPS connections
+    fn connection_limiter(&self) -> Arc<ConnectionLimiter> {
+        self.connection_limiter
+            .read()
+            .expect("connection limiter lock poisoned")
+            .clone()
+    }
+
+    /// The PROXY protocol configuration, if connections from proxies are accepted
+    fn proxy_protocol(&self) -> Option<Arc<ProxyProtocolConfig>> {
+        self.proxy_protocol
+            .read()
+            .expect("proxy protocol lock poisoned")
+            .clone()
+    }
+
+    /// A tap for the exchange with a client, if dnstap is enabled
+    #[cfg(feature = "dnstap")]
+    fn client_tap(
+        &self,
+        protocol: Protocol,
+        src_addr: SocketAddr,
+        local_addr: SocketAddr,
+    ) -> Option<ClientTap> {
+        let dnstap = self.dnstap.read().expect("dnstap lock poisoned").clone()?;
+        Some(ClientTap::new(dnstap, protocol, src_addr, local_addr))
+    }
+}
 
 // TODO, would be nice to have a Slab for buffers here...
 /// A Futures based implementation of a DNS server
@@ -60,7 +144,7 @@ pub struct ServerFuture<T: RequestHandler> {
     handler: Arc<T>,
     join_set: JoinSet<Result<(), ProtoError>>,
     shutdown_token: CancellationToken,
-    access: Arc<AccessControl>,
+    context: Arc<ServerContext>,
 }
 
 impl<T: RequestHandler> ServerFuture<T> {
@@ -79,21 +163,78 @@ impl<T: RequestHandler> ServerFuture<T> {
             handler: Arc::new(handler),
             join_set: JoinSet::new(),
             shutdown_token: CancellationToken::new(),
-            access: Arc::new(access),
+            context: Arc::new(ServerContext::new(access)),
         }
     }
 
+    /// Enables response rate limiting for the requests received on UDP sockets from now on
+    ///
+    /// All sockets share the same limits, see [`ResponseRateLimiter`].
+    pub fn set_response_rate_limit(&mut self, config: ResponseRateLimitConfig) {
+        *self
+            .context
+            .rate_limiter
+            .write()
+            .expect("rate limiter lock poisoned") =
+            Some(Arc::new(ResponseRateLimiter::new(config)));
+    }
+
+    /// Limits the connections accepted by TCP, TLS and HTTPS listeners from now on
+    ///
+    /// All listeners share the same limits, see [`ConnectionLimiter`]. Connections accepted
+    /// before keep counting against the limits they were accepted with.
+    pub fn set_connection_limits(&mut self, config: ConnectionLimitConfig) {
+        *self
+            .context
+            .connection_limiter
+            .write()
+            .expect("connection limiter lock poisoned") = Arc::new(ConnectionLimiter::new(config));
+    }
+
+    /// Reads the PROXY protocol header of the connections accepted from trusted proxies by TCP,
+    /// TLS and HTTPS listeners from now on
+    ///
+    /// The client address from the header replaces the proxy's for access control, views,
+    /// logging and everything else that looks at the source of a request.
+    pub fn set_proxy_protocol(&mut self, config: ProxyProtocolConfig) {
+        *self
+            .context
+            .proxy_protocol
+            .write()
+            .expect("proxy protocol lock poisoned") = Some(Arc::new(config));
+    }
+
+    /// Logs the queries received from now on, and the responses sent to them, to dnstap
+    #[cfg(feature = "dnstap")]
+    pub fn set_dnstap(&mut self, dnstap: Dnstap) {
+        *self.context.dnstap.write().expect("dnstap lock poisoned") = Some(dnstap);
+    }
+
     /// Register a UDP socket. Should be bound before calling this function.
     pub fn register_socket(&mut self, socket: net::UdpSocket) {
         debug!("registering udp: {:?}", socket);
+        let local_addr = socket
+            .local_addr()
+            .unwrap_or_else(|_| (Ipv4Addr::UNSPECIFIED, 0).into());
+        self.register_udp::<TokioRuntimeProvider>(socket, local_addr);
+    }
 
+    /// Register a UDP socket of a simulated network, see [`sim`](crate::proto::runtime::sim)
+    #[cfg(feature = "testing")]
+    pub fn register_sim_socket(&mut self, socket: SimUdpSocket) {
+        debug!("registering simulated udp: {}", socket.local_addr());
+        let local_addr = socket.local_addr();
+        self.register_udp::<SimRuntimeProvider>(socket, local_addr);
+    }
+
+    fn register_udp<P: RuntimeProvider>(&mut self, socket: P::Udp, local_addr: SocketAddr) {
         // create the new UdpStream, the IP address isn't relevant, and ideally goes essentially no where.
         //   the address used is acquired from the inbound queries
         let (mut stream, stream_handle) =
-            UdpStream::<TokioRuntimeProvider>::with_bound(socket, ([127, 255, 255, 254], 0).into());
+            UdpStream::<P>::with_bound(socket, ([127, 255, 255, 254], 0).into());
         let shutdown = self.shutdown_token.clone();
         let handler = self.handler.clone();
-        let access = self.access.clone();
+        let context = self.context.clone();
 
         // this spawns a ForEach future which handles all the requests into a Handler.
         self.join_set.spawn({
@@ -133,12 +274,22 @@ impl<T: RequestHandler> ServerFuture<T> {
                     }
 
                     let handler = handler.clone();
-                    let access = access.clone();
+                    let rate_limiter = context.rate_limiter();
+                    let context = context.clone();
                     let stream_handle = stream_handle.with_remote_addr(src_addr);
 
                     inner_join_set.spawn(async move {
-                        handle_raw_request(message, Protocol::Udp, access, handler, stream_handle)
-                            .await;
+                        handle_raw_request(
+                            message,
+                            local_addr,
+                            Protocol::Udp,
+                            context,
+                            handler,
+                            stream_handle,
+                            rate_limiter,
+                            None,
+                        )
+                        .await;
                     });
 
                     reap_tasks(&mut inner_join_set);
@@ -170,24 +321,56 @@ impl<T: RequestHandler> ServerFuture<T> {
     /// # Arguments
     /// * `listener` - a bound TCP socket
     /// * `timeout` - timeout duration of incoming requests, any connection that does not send
-    ///   requests within this time period will be closed. In the future it should be
-    ///   possible to create long-lived queries, but these should be from trusted sources
-    ///   only, this would require some type of whitelisting.
+    ///   requests within this time period will be closed. Clients negotiating edns-tcp-keepalive
+    ///   get the keepalive timeout of the [`ConnectionLimitConfig`] instead.
     pub fn register_listener(&mut self, listener: net::TcpListener, timeout: Duration) {
         debug!("register tcp: {:?}", listener);
 
+        let incoming = stream::unfold(listener, |listener| async move {
+            let accepted = listener.accept().await.and_then(|(tcp_stream, src_addr)| {
+                let local_addr = tcp_stream.local_addr()?;
+                Ok((AsyncIoTokioAsStd(tcp_stream), src_addr, local_addr))
+            });
+            Some((accepted, listener))
+        });
+
+        self.register_tcp(Box::pin(incoming), timeout);
+    }
+
+    /// Register a TCP listener of a simulated network, see [`sim`](crate::proto::runtime::sim)
+    ///
+    /// See [`Self::register_listener`] for the meaning of `timeout`.
+    #[cfg(feature = "testing")]
+    pub fn register_sim_listener(&mut self, listener: SimTcpListener, timeout: Duration) {
+        debug!("register simulated tcp: {}", listener.local_addr());
+        let local_addr = listener.local_addr();
+        let incoming = listener.map(move |accepted| {
+            accepted.map(|(tcp_stream, src_addr)| (tcp_stream, src_addr, local_addr))
+        });
+        self.register_tcp(incoming, timeout);
+    }
+
+    fn register_tcp<S: DnsTcpStream>(
+        &mut self,
+        mut incoming: impl Stream<Item = io::Result<(S, SocketAddr, SocketAddr)>>
+        + Send
+        + Unpin
+        + 'static,
+        timeout: Duration,
+    ) {
         let handler = self.handler.clone();
-        let access = self.access.clone();
+        let context = self.context.clone();
 
         // for each incoming request...
         let shutdown = self.shutdown_token.clone();
         self.join_set.spawn(async move {
             let mut inner_join_set = JoinSet::new();
             loop {
-                let (tcp_stream, src_addr) = tokio::select! {
-                    tcp_stream = listener.accept() => match tcp_stream {
-                        Ok((t, s)) => (t, s),
-                        Err(e) => {
+                let (tcp_stream, src_addr, local_addr) = tokio::select! {
+                    tcp_stream = incoming.next() => match tcp_stream {
+                        Some(Ok(accepted)) => accepted,
+                        None => break,
+                        Some(Err(e)) => {
                             debug!("error receiving TCP tcp_stream error: {}", e);
                             if is_unrecoverable_socket_error(&e) {
                                 break;
@@ -211,40 +394,49 @@ impl<T: RequestHandler> ServerFuture<T> {
                     continue;
                 }
 
+                // connections over the limits are closed right away, before a task is spawned
+                let connection_limiter = context.connection_limiter();
+                let proxy_protocol = context.proxy_protocol();
+                let Some(permit) =
+                    acquire_permit(&connection_limiter, src_addr, proxy_protocol.is_some())
+                else {
+                    continue;
+                };
+
                 let handler = handler.clone();
-                let access = access.clone();
+                let context = context.clone();
 
                 // and spawn to the io_loop
                 inner_join_set.spawn(async move {
+                    let mut tcp_stream = tcp_stream;
+                    let Some((src_addr, local_addr, permit)) = accept_connection(
+                        &mut tcp_stream,
+                        src_addr,
+                        local_addr,
+                        proxy_protocol.as_deref(),
+                        permit,
+                        timeout,
+                    )
+                    .await
+                    else {
+                        return;
+                    };
+
                     debug!("accepted request from: {}", src_addr);
                     // take the created stream...
-                    let (buf_stream, stream_handle) =
-                        TcpStream::from_stream(AsyncIoTokioAsStd(tcp_stream), src_addr);
-                    let mut timeout_stream = TimeoutStream::new(buf_stream, timeout);
-
-                    while let Some(message) = timeout_stream.next().await {
-                        let message = match message {
-                            Ok(message) => message,
-                            Err(e) => {
-                                debug!(
-                                    "error in TCP request_stream src: {} error: {}",
-                                    src_addr, e
-                                );
-                                // we're going to bail on this connection...
-                                return;
-                            }
-                        };
-
-                        // we don't spawn here to limit clients from getting too many resources
-                        handle_raw_request(
-                            message,
-                            Protocol::Tcp,
-                            access.clone(),
-                            handler.clone(),
-                            stream_handle.clone(),
-                        )
-                        .await;
-                    }
+                    let (buf_stream, stream_handle) = TcpStream::from_stream(tcp_stream, src_addr);
+                    handle_stream_connection(
+                        buf_stream,
+                        stream_handle,
+                        local_addr,
+                        Protocol::Tcp,
+                        context,
+                        handler,
+                        &connection_limiter,
+                        timeout,
+                    )
+                    .await;
+                    drop(permit);
                 });
 
                 reap_tasks(&mut inner_join_set);
@@ -304,7 +496,7 @@ impl<T: RequestHandler> ServerFuture<T> {
         use tokio_rustls::TlsAcceptor;
 
         let handler = self.handler.clone();
-        let access = self.access.clone();
+        let context = self.context.clone();
 
         debug!("registered tcp: {:?}", listener);
 
@@ -342,12 +534,43 @@ impl<T: RequestHandler> ServerFuture<T> {
                     continue;
                 }
 
+                let local_addr = match tcp_stream.local_addr() {
+                    Ok(local_addr) => local_addr,
+                    Err(e) => {
+                        debug!("error reading local address of tcp_stream: {e}");
+                        continue;
+                    }
+                };
+
+                // connections over the limits are closed right away, before a task is spawned
+                let connection_limiter = context.connection_limiter();
+                let proxy_protocol = context.proxy_protocol();
+                let Some(permit) =
+                    acquire_permit(&connection_limiter, src_addr, proxy_protocol.is_some())
+                else {
+                    continue;
+                };
+
                 let handler = handler.clone();
-                let access = access.clone();
+                let context = context.clone();
                 let tls_acceptor = tls_acceptor.clone();
 
                 // kick out to a different task immediately, let them do the TLS handshake
                 inner_join_set.spawn(async move {
+                    let mut tcp_stream = tcp_stream;
+                    let Some((src_addr, local_addr, permit)) = accept_connection(
+                        &mut AsyncIoTokioAsStd(&mut tcp_stream),
+                        src_addr,
+                        local_addr,
+                        proxy_protocol.as_deref(),
+                        permit,
+                        handshake_timeout,
+                    )
+                    .await
+                    else {
+                        return;
+                    };
+
                     debug!("starting TLS request from: {}", src_addr);
 
                     // perform the TLS
@@ -367,30 +590,18 @@ impl<T: RequestHandler> ServerFuture<T> {
                     };
                     debug!("accepted TLS request from: {}", src_addr);
                     let (buf_stream, stream_handle) = tls_from_stream(tls_stream, src_addr);
-                    let mut timeout_stream = TimeoutStream::new(buf_stream, handshake_timeout);
-                    while let Some(message) = timeout_stream.next().await {
-                        let message = match message {
-                            Ok(message) => message,
-                            Err(e) => {
-                                debug!(
-                                    "error in TLS request_stream src: {:?} error: {}",
-                                    src_addr, e
-                                );
-
-                                // kill this connection
-                                return;
-                            }
-                        };
-
-                        handle_raw_request(
-                            message,
-                            Protocol::Tls,
-                            access.clone(),
-                            handler.clone(),
-                            stream_handle.clone(),
-                        )
-                        .await;
-                    }
+                    handle_stream_connection(
+                        buf_stream,
+                        stream_handle,
+                        local_addr,
+                        Protocol::Tls,
+                        context,
+                        handler,
+                        &connection_limiter,
+                        handshake_timeout,
+                    )
+                    .await;
+                    drop(permit);
                 });
 
                 reap_tasks(&mut inner_join_set);
@@ -460,7 +671,7 @@ impl<T: RequestHandler> ServerFuture<T> {
         let http_endpoint: Arc<str> = Arc::from(http_endpoint);
 
         let handler = self.handler.clone();
-        let access = self.access.clone();
+        let context = self.context.clone();
         debug!("registered https: {listener:?}");
 
         let tls_acceptor =
@@ -495,13 +706,44 @@ impl<T: RequestHandler> ServerFuture<T> {
                     continue;
                 }
 
+                let local_addr = match tcp_stream.local_addr() {
+                    Ok(local_addr) => local_addr,
+                    Err(e) => {
+                        debug!("error reading local address of tcp_stream: {e}");
+                        continue;
+                    }
+                };
+
+                // connections over the limits are closed right away, before a task is spawned
+                let connection_limiter = context.connection_limiter();
+                let proxy_protocol = context.proxy_protocol();
+                let Some(permit) =
+                    acquire_permit(&connection_limiter, src_addr, proxy_protocol.is_some())
+                else {
+                    continue;
+                };
+
                 let handler = handler.clone();
-                let access = access.clone();
+                let context = context.clone();
                 let tls_acceptor = tls_acceptor.clone();
                 let dns_hostname = dns_hostname.clone();
                 let http_endpoint = http_endpoint.clone();
 
                 inner_join_set.spawn(async move {
+                    let mut tcp_stream = tcp_stream;
+                    let Some((src_addr, local_addr, permit)) = accept_connection(
+                        &mut AsyncIoTokioAsStd(&mut tcp_stream),
+                        src_addr,
+                        local_addr,
+                        proxy_protocol.as_deref(),
+                        permit,
+                        handshake_timeout,
+                    )
+                    .await
+                    else {
+                        return;
+                    };
+
                     debug!("starting HTTPS request from: {src_addr}");
 
                     // TODO: need to consider timeout of total connect...
@@ -523,15 +765,17 @@ impl<T: RequestHandler> ServerFuture<T> {
                     debug!("accepted HTTPS request from: {src_addr}");
 
                     h2_handler(
-                        access,
+                        context,
                         handler,
                         tls_stream,
                         src_addr,
+                        local_addr,
                         dns_hostname,
                         http_endpoint,
                         shutdown.clone(),
                     )
                     .await;
+                    drop(permit);
                 });
 
                 reap_tasks(&mut inner_join_set);
@@ -575,9 +819,10 @@ impl<T: RequestHandler> ServerFuture<T> {
         let dns_hostname: Option<Arc<str>> = dns_hostname.map(|n| n.into());
 
         let handler = self.handler.clone();
-        let access = self.access.clone();
+        let context = self.context.clone();
 
         debug!("registered quic: {:?}", socket);
+        let local_addr = socket.local_addr()?;
         let mut server = QuicServer::with_socket(socket, server_cert_resolver)?;
 
         // for each incoming request...
@@ -613,7 +858,7 @@ impl<T: RequestHandler> ServerFuture<T> {
                 }
 
                 let handler = handler.clone();
-                let access = access.clone();
+                let context = context.clone();
                 let dns_hostname = dns_hostname.clone();
 
                 inner_join_set.spawn(async move {
@@ -621,10 +866,11 @@ impl<T: RequestHandler> ServerFuture<T> {
 
                     // TODO: need to consider timeout of total connect...
                     let result = quic_handler(
-                        access,
+                        context,
                         handler,
                         streams,
                         src_addr,
+                        local_addr,
                         dns_hostname,
                         shutdown.clone(),
                     )
@@ -672,9 +918,10 @@ impl<T: RequestHandler> ServerFuture<T> {
         let dns_hostname: Option<Arc<str>> = dns_hostname.map(|n| n.into());
 
         let handler = self.handler.clone();
-        let access = self.access.clone();
+        let context = self.context.clone();
 
         debug!("registered h3: {:?}", socket);
+        let local_addr = socket.local_addr()?;
         let mut server = H3Server::with_socket(socket, server_cert_resolver)?;
 
         // for each incoming request...
@@ -710,7 +957,7 @@ impl<T: RequestHandler> ServerFuture<T> {
                 }
 
                 let handler = handler.clone();
-                let access = access.clone();
+                let context = context.clone();
                 let dns_hostname = dns_hostname.clone();
 
                 inner_join_set.spawn(async move {
@@ -718,10 +965,11 @@ impl<T: RequestHandler> ServerFuture<T> {
 
                     // TODO: need to consider timeout of total connect...
                     let result = h3_handler(
-                        access,
+                        context,
                         handler,
                         streams,
                         src_addr,
+                        local_addr,
                         dns_hostname,
                         shutdown.clone(),
                     )
@@ -800,23 +1048,146 @@ fn reap_tasks(join_set: &mut JoinSet<()>) {
     {}
 }
 
+/// Counts a new connection against the limits, returns `None` if it should be closed
+///
+/// The limit per client is only applied once the client address is known, after the PROXY header
+/// of a connection from a trusted proxy was read, see [`accept_connection`].
+fn acquire_permit(
+    connection_limiter: &Arc<ConnectionLimiter>,
+    src_addr: SocketAddr,
+    proxied: bool,
+) -> Option<ConnectionPermit> {
+    let mut permit = connection_limiter.try_acquire_connection()?;
+    if !proxied && !permit.try_set_client(src_addr.ip()) {
+        return None;
+    }
+
+    Some(permit)
+}
+
+/// Recovers the addresses of a connection from a trusted proxy and counts the connection against
+/// the limit of its client, returns `None` if it should be closed
+async fn accept_connection(
+    stream: &mut (impl AsyncRead + Unpin),
+    src_addr: SocketAddr,
+    local_addr: SocketAddr,
+    proxy_protocol: Option<&ProxyProtocolConfig>,
+    mut permit: ConnectionPermit,
+    timeout: Duration,
+) -> Option<(SocketAddr, SocketAddr, ConnectionPermit)> {
+    let Some(proxy_protocol) = proxy_protocol else {
+        return Some((src_addr, local_addr, permit));
+    };
+
+    let (src_addr, local_addr) = match proxy_protocol
+        .read_addresses(stream, src_addr, local_addr, timeout)
+        .await
+    {
+        Ok(addresses) => addresses,
+        Err(e) => {
+            debug!("error reading PROXY header from {src_addr}: {e}");
+            return None;
+        }
+    };
+
+    // connections over the limits are closed right away, by dropping the stream
+    if !permit.try_set_client(src_addr.ip()) {
+        return None;
+    }
+
+    Some((src_addr, local_addr, permit))
+}
+
+/// Reads the queries pipelined on a TCP or TLS connection, processing up to the in-flight limit of
+/// them concurrently, until the client closes the connection or it has been idle for too long
+#[allow(clippy::too_many_arguments)]
+async fn handle_stream_connection<T: RequestHandler>(
+    mut stream: impl Stream<Item = io::Result<SerialMessage>> + Unpin,
+    stream_handle: BufDnsStreamHandle,
+    local_addr: SocketAddr,
+    protocol: Protocol,
+    context: Arc<ServerContext>,
+    handler: Arc<T>,
+    connection_limiter: &ConnectionLimiter,
+    timeout: Duration,
+) {
+    let idle_timeout = connection_limiter.idle_timeout(timeout);
+    let max_in_flight = connection_limiter.max_in_flight();
+    let mut in_flight = JoinSet::new();
+
+    loop {
+        // responses are written while the stream is polled, the connection is only idle once
+        //  all of them have been sent
+        let idle = idle_timeout.get();
+        let message = tokio::select! {
+            message = stream.next(), if max_in_flight == 0 || in_flight.len() < max_in_flight => message,
+            Some(_) = in_flight.join_next(), if !in_flight.is_empty() => continue,
+            _ = tokio::time::sleep(idle), if in_flight.is_empty() && !idle.is_zero() => {
+                debug!("{protocol} connection idle for {idle:?}, closing");
+                return;
+            }
+        };
+
+        let message = match message {
+            Some(Ok(message)) => message,
+            Some(Err(e)) => {
+                debug!("error in {protocol} request_stream error: {e}");
+                // we're going to bail on this connection...
+                return;
+            }
+            None => {
+                // the client is done sending, answer the queries it is still waiting for
+                while in_flight.join_next().await.is_some() {}
+                let _ =
+                    tokio::time::timeout(timeout.max(Duration::from_secs(1)), stream.next()).await;
+                return;
+            }
+        };
+
+        let context = context.clone();
+        let handler = handler.clone();
+        let stream_handle = stream_handle.clone();
+        let idle_timeout = idle_timeout.clone();
+        in_flight.spawn(async move {
+            handle_raw_request(
+                message,
+                local_addr,
+                protocol,
+                context,
+                handler,
+                stream_handle,
+                None,
+                Some(idle_timeout),
+            )
+            .await;
+        });
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub(crate) async fn handle_raw_request<T: RequestHandler>(
     message: SerialMessage,
+    local_addr: SocketAddr,
     protocol: Protocol,
-    access: Arc<AccessControl>,
+    context: Arc<ServerContext>,
     request_handler: Arc<T>,
     response_handler: BufDnsStreamHandle,
+    rate_limiter: Option<Arc<ResponseRateLimiter>>,
+    idle_timeout: Option<Arc<IdleTimeout>>,
 ) {
     let src_addr = message.addr();
-    let response_handler = ResponseHandle::new(message.addr(), response_handler, protocol);
+    let response_handler = ResponseHandle::new(message.addr(), response_handler, protocol)
+        .with_rate_limiter(rate_limiter);
 
     handle_request(
         message.bytes(),
         src_addr,
+        local_addr,
         protocol,
-        access,
+        context,
         request_handler,
         response_handler,
+        idle_timeout,
     )
     .await;
 }
@@ -848,6 +1219,13 @@ struct ReportingResponseHandler<R: ResponseHandler> {
     protocol: Protocol,
     src_addr: SocketAddr,
     handler: R,
+    idle_timeout: Option<Arc<IdleTimeout>>,
+    #[cfg(feature = "dnstap")]
+    tap: Option<ClientTap>,
+    #[cfg(feature = "metrics")]
+    metrics: RequestMetrics,
+    #[cfg(feature = "metrics")]
+    received: Instant,
 }
 
 #[async_trait::async_trait]
@@ -864,8 +1242,33 @@ impl<R: ResponseHandler> ResponseHandler for ReportingResponseHandler<R> {
             impl Iterator<Item = &'a Record> + Send + 'a,
         >,
     ) -> io::Result<ResponseInfo> {
+        let mut response = response;
+        if let Some(idle_timeout) = &self.idle_timeout {
+            if let Some(edns) = idle_timeout.negotiate(response.get_edns().as_ref()) {
+                response.set_edns(edns);
+            }
+        }
+
+        #[cfg(feature = "dnstap")]
+        let response = {
+            let mut response = response;
+            if let Some(tap) = &self.tap {
+                response.set_tap(tap.clone());
+            }
+            response
+        };
+
         let response_info = self.handler.send_response(response).await?;
 
+        #[cfg(feature = "metrics")]
+        self.metrics.response(
+            self.protocol,
+            &self.request_header,
+            &self.queries,
+            &response_info,
+            self.received.elapsed(),
+        );
+
         let id = self.request_header.id();
         let rid = response_info.id();
         if id != rid {
@@ -906,17 +1309,33 @@ impl<R: ResponseHandler> ResponseHandler for ReportingResponseHandler<R> {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub(crate) async fn handle_request<R: ResponseHandler, T: RequestHandler>(
     // TODO: allow Message here...
     message_bytes: &[u8],
     src_addr: SocketAddr,
+    local_addr: SocketAddr,
     protocol: Protocol,
-    access: Arc<AccessControl>,
+    context: Arc<ServerContext>,
     request_handler: Arc<T>,
     response_handler: R,
+    idle_timeout: Option<Arc<IdleTimeout>>,
 ) {
+    #[cfg(feature = "metrics")]
+    let received = Instant::now();
     let mut decoder = BinDecoder::new(message_bytes);
 
+    #[cfg(feature = "dnstap")]
+    let tap = context.client_tap(protocol, src_addr, local_addr);
+    #[cfg(feature = "dnstap")]
+    if let Some(tap) = &tap {
+        tap.log_query(message_bytes);
+    }
+    #[cfg(feature = "dnstap")]
+    let tap = &tap;
+    #[cfg(feature = "metrics")]
+    let metrics = &context.metrics;
+
     // method to handle the request
     let inner_handle_request = |message: MessageRequest, response_handler: R| async move {
         if message.message_type() == MessageType::Response {
@@ -929,8 +1348,12 @@ pub(crate) async fn handle_request<R: ResponseHandler, T: RequestHandler>(
         let qop_code = message.op_code();
         let message_type = message.message_type();
         let is_dnssec = message.edns().is_some_and(|edns| edns.flags().dnssec_ok);
+        let idle_timeout =
+            idle_timeout.filter(|_| connection_limit::requests_keepalive(message.edns()));
 
-        let request = Request::new(message, src_addr, protocol);
+        let request = Request::new(message, src_addr, protocol).with_local_addr(local_addr);
+        #[cfg(feature = "__dnssec")]
+        let request = request.with_signed_bytes(message_bytes);
 
         debug!(
             "request:{id} src:{proto}://{addr}#{port} type:{message_type} dnssec:{is_dnssec} {op} qflags:{qflags}",
@@ -960,6 +1383,13 @@ pub(crate) async fn handle_request<R: ResponseHandler, T: RequestHandler>(
             protocol,
             src_addr,
             handler: response_handler,
+            idle_timeout,
+            #[cfg(feature = "dnstap")]
+            tap: tap.clone(),
+            #[cfg(feature = "metrics")]
+            metrics: metrics.clone(),
+            #[cfg(feature = "metrics")]
+            received,
         };
 
         request_handler.handle_request(&request, reporter).await;
@@ -993,6 +1423,13 @@ pub(crate) async fn handle_request<R: ResponseHandler, T: RequestHandler>(
             protocol,
             src_addr,
             handler: response_handler,
+            idle_timeout: None,
+            #[cfg(feature = "dnstap")]
+            tap: tap.clone(),
+            #[cfg(feature = "metrics")]
+            metrics: metrics.clone(),
+            #[cfg(feature = "metrics")]
+            received,
         };
 
         let queries = Queries::empty();
@@ -1006,13 +1443,47 @@ pub(crate) async fn handle_request<R: ResponseHandler, T: RequestHandler>(
         }
     };
 
-    if !access.allow(src_addr.ip()) {
+    if !context.access.allow(src_addr.ip()) {
+        #[cfg(feature = "metrics")]
+        context.metrics.refused_access();
+
         info!(
             "request:Refused src:{proto}://{addr}#{port}",
             proto = protocol,
             addr = src_addr.ip(),
             port = src_addr.port(),
         );
+
+        // anything that isn't a valid request is dropped without a response
+        match MessageRequest::read(&mut decoder) {
+            Ok(message) if message.message_type() == MessageType::Query => {
+                let mut reporter = ReportingResponseHandler {
+                    request_header: *message.header(),
+                    queries: message.queries().to_vec(),
+                    protocol,
+                    src_addr,
+                    handler: response_handler,
+                    idle_timeout: None,
+                    #[cfg(feature = "dnstap")]
+                    tap: tap.clone(),
+                    #[cfg(feature = "metrics")]
+                    metrics: metrics.clone(),
+                    #[cfg(feature = "metrics")]
+                    received,
+                };
+
+                let response = MessageResponseBuilder::new(message.raw_queries());
+                let result = reporter
+                    .send_response(response.error_msg(message.header(), ResponseCode::Refused))
+                    .await;
+
+                if let Err(e) = result {
+                    warn!("failed to return Refused to client: {}", e);
+                }
+            }
+            _ => {}
+        }
+
         return;
     }
 
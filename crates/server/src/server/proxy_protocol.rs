@@ -0,0 +1,311 @@
+//! PROXY protocol, recovering the addresses of clients connecting through a load balancer
+//!
+//! Load balancers such as HAProxy send a header with the addresses of the client's connection
+//! before relaying its stream. Both the text version 1 and the binary version 2 of the
+//! [protocol](https://www.haproxy.org/download/2.9/doc/proxy-protocol.txt) are accepted.
+//!
+//! Anybody could claim any address in a header, so it is only read on connections from trusted
+//! proxies, where it is then required.
+
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    str,
+    time::Duration,
+};
+
+use futures_util::{AsyncRead, AsyncReadExt};
+use ipnet::IpNet;
+use serde::Deserialize;
+
+/// Signature starting a version 2 header
+const V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+
+/// The longest version 1 header, including the CRLF
+const V1_MAX_LENGTH: usize = 107;
+
+/// Configuration of the PROXY protocol on TCP, TLS and HTTPS listeners
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProxyProtocolConfig {
+    /// Networks of the proxies sending a PROXY header, connections from them must start with one
+    pub trusted_proxies: Vec<IpNet>,
+}
+
+impl ProxyProtocolConfig {
+    /// Returns the addresses of the client and of the server it connected to
+    ///
+    /// The header is read from connections of trusted proxies, other connections keep their own
+    /// addresses. So do connections for which the proxy doesn't know the client, e.g. its health
+    /// checks.
+    pub(crate) async fn read_addresses<S: AsyncRead + Unpin>(
+        &self,
+        stream: &mut S,
+        src_addr: SocketAddr,
+        local_addr: SocketAddr,
+        timeout: Duration,
+    ) -> io::Result<(SocketAddr, SocketAddr)> {
+        if !self
+            .trusted_proxies
+            .iter()
+            .any(|net| net.contains(&src_addr.ip()))
+        {
+            return Ok((src_addr, local_addr));
+        }
+
+        let addresses = tokio::time::timeout(timeout, read_header(stream))
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "timed out reading header"))??;
+        Ok(addresses.unwrap_or((src_addr, local_addr)))
+    }
+}
+
+/// Reads a header, returning the source and destination addresses it carries
+///
+/// Exactly the header is read from the stream, the client's data follows it.
+async fn read_header<S: AsyncRead + Unpin>(
+    stream: &mut S,
+) -> io::Result<Option<(SocketAddr, SocketAddr)>> {
+    // long enough to tell the versions apart, and shorter than any header
+    let mut start = [0; 6];
+    stream.read_exact(&mut start).await?;
+
+    if start == V2_SIGNATURE[..6] {
+        let mut header = [0; 10];
+        stream.read_exact(&mut header).await?;
+        if header[..6] != V2_SIGNATURE[6..] {
+            return Err(invalid_header("bad version 2 signature"));
+        }
+
+        let length = u16::from_be_bytes([header[8], header[9]]);
+        let mut payload = vec![0; usize::from(length)];
+        stream.read_exact(&mut payload).await?;
+        parse_v2(header[6], header[7], &payload)
+    } else if &start == b"PROXY " {
+        let mut line = start.to_vec();
+        while !line.ends_with(b"\r\n") {
+            if line.len() >= V1_MAX_LENGTH {
+                return Err(invalid_header("version 1 header too long"));
+            }
+
+            let mut byte = [0];
+            stream.read_exact(&mut byte).await?;
+            line.push(byte[0]);
+        }
+
+        parse_v1(&line[..line.len() - 2])
+    } else {
+        Err(invalid_header("missing header"))
+    }
+}
+
+/// Parses a version 1 header without its CRLF, e.g. `PROXY TCP4 192.0.2.1 192.0.2.2 56324 53`
+fn parse_v1(line: &[u8]) -> io::Result<Option<(SocketAddr, SocketAddr)>> {
+    let line = str::from_utf8(line).map_err(|_| invalid_header("version 1 header not ASCII"))?;
+    let mut fields = line.split(' ').skip(1);
+
+    match fields.next() {
+        Some("UNKNOWN") => return Ok(None),
+        Some("TCP4" | "TCP6") => {}
+        _ => return Err(invalid_header("unsupported version 1 protocol")),
+    }
+
+    let mut next = || {
+        fields
+            .next()
+            .ok_or_else(|| invalid_header("version 1 header too short"))
+    };
+    let src_ip = next()?.parse::<IpAddr>();
+    let dst_ip = next()?.parse::<IpAddr>();
+    let src_port = next()?.parse::<u16>();
+    let dst_port = next()?.parse::<u16>();
+    if fields.next().is_some() {
+        return Err(invalid_header("version 1 header too long"));
+    }
+
+    match (src_ip, dst_ip, src_port, dst_port) {
+        (Ok(src_ip), Ok(dst_ip), Ok(src_port), Ok(dst_port)) => Ok(Some((
+            SocketAddr::new(src_ip, src_port),
+            SocketAddr::new(dst_ip, dst_port),
+        ))),
+        _ => Err(invalid_header("bad version 1 address")),
+    }
+}
+
+/// Parses the rest of a version 2 header following its length
+fn parse_v2(
+    version_command: u8,
+    family_protocol: u8,
+    payload: &[u8],
+) -> io::Result<Option<(SocketAddr, SocketAddr)>> {
+    if version_command >> 4 != 2 {
+        return Err(invalid_header("unsupported version"));
+    }
+
+    match version_command & 0x0f {
+        // LOCAL, the proxy's own connection
+        0 => return Ok(None),
+        // PROXY
+        1 => {}
+        _ => return Err(invalid_header("unsupported version 2 command")),
+    }
+
+    // only the addresses are of interest, not the TLVs following them
+    match family_protocol >> 4 {
+        // INET
+        1 => {
+            let Some(addresses) = payload.get(..12) else {
+                return Err(invalid_header("version 2 header too short"));
+            };
+            let src_ip = Ipv4Addr::new(addresses[0], addresses[1], addresses[2], addresses[3]);
+            let dst_ip = Ipv4Addr::new(addresses[4], addresses[5], addresses[6], addresses[7]);
+            Ok(Some((
+                SocketAddr::new(
+                    src_ip.into(),
+                    u16::from_be_bytes([addresses[8], addresses[9]]),
+                ),
+                SocketAddr::new(
+                    dst_ip.into(),
+                    u16::from_be_bytes([addresses[10], addresses[11]]),
+                ),
+            )))
+        }
+        // INET6
+        2 => {
+            let Some(addresses) = payload.get(..36) else {
+                return Err(invalid_header("version 2 header too short"));
+            };
+            let mut src_ip = [0; 16];
+            src_ip.copy_from_slice(&addresses[..16]);
+            let mut dst_ip = [0; 16];
+            dst_ip.copy_from_slice(&addresses[16..32]);
+            Ok(Some((
+                SocketAddr::new(
+                    Ipv6Addr::from(src_ip).into(),
+                    u16::from_be_bytes([addresses[32], addresses[33]]),
+                ),
+                SocketAddr::new(
+                    Ipv6Addr::from(dst_ip).into(),
+                    u16::from_be_bytes([addresses[34], addresses[35]]),
+                ),
+            )))
+        }
+        // UNSPEC and UNIX, there is no client address to use
+        _ => Ok(None),
+    }
+}
+
+fn invalid_header(reason: &'static str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("invalid PROXY header: {reason}"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_executor::block_on;
+    use futures_util::io::Cursor;
+
+    use super::*;
+
+    fn read(bytes: &[u8]) -> (io::Result<Option<(SocketAddr, SocketAddr)>>, Vec<u8>) {
+        let mut stream = Cursor::new(bytes.to_vec());
+        let addresses = block_on(read_header(&mut stream));
+        let position = stream.position() as usize;
+        (addresses, bytes[position..].to_vec())
+    }
+
+    #[test]
+    fn test_v1() {
+        let (addresses, rest) = read(b"PROXY TCP4 192.0.2.1 192.0.2.53 56324 53\r\nquery");
+        assert_eq!(
+            addresses.unwrap(),
+            Some((
+                "192.0.2.1:56324".parse().unwrap(),
+                "192.0.2.53:53".parse().unwrap()
+            ))
+        );
+        assert_eq!(rest, b"query");
+
+        let (addresses, _) = read(b"PROXY TCP6 2001:db8::1 2001:db8::53 56324 53\r\n");
+        assert_eq!(
+            addresses.unwrap(),
+            Some((
+                "[2001:db8::1]:56324".parse().unwrap(),
+                "[2001:db8::53]:53".parse().unwrap()
+            ))
+        );
+
+        let (addresses, _) = read(b"PROXY UNKNOWN\r\n");
+        assert_eq!(addresses.unwrap(), None);
+
+        assert!(
+            read(b"PROXY TCP4 192.0.2.1 192.0.2.53 56324\r\n")
+                .0
+                .is_err()
+        );
+        assert!(
+            read(b"PROXY TCP4 192.0.2.1 example 56324 53\r\n")
+                .0
+                .is_err()
+        );
+        assert!(read(&[b'A'; 200]).0.is_err());
+        assert!(
+            read(&[b"PROXY ".as_slice(), &[b' '; 200]].concat())
+                .0
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_v2() {
+        let mut header = V2_SIGNATURE.to_vec();
+        // PROXY over TCP/IPv4, with a NOOP TLV after the addresses
+        header.extend_from_slice(&[0x21, 0x11, 0, 16]);
+        header.extend_from_slice(&[192, 0, 2, 1, 192, 0, 2, 53, 0xdc, 0x04, 0, 53]);
+        header.extend_from_slice(&[0x04, 0, 1, 0]);
+        header.extend_from_slice(b"query");
+
+        let (addresses, rest) = read(&header);
+        assert_eq!(
+            addresses.unwrap(),
+            Some((
+                "192.0.2.1:56324".parse().unwrap(),
+                "192.0.2.53:53".parse().unwrap()
+            ))
+        );
+        assert_eq!(rest, b"query");
+
+        // LOCAL
+        let mut header = V2_SIGNATURE.to_vec();
+        header.extend_from_slice(&[0x20, 0x00, 0, 0]);
+        assert_eq!(read(&header).0.unwrap(), None);
+
+        // version 1 of the binary format doesn't exist
+        let mut header = V2_SIGNATURE.to_vec();
+        header.extend_from_slice(&[0x11, 0x11, 0, 0]);
+        assert!(read(&header).0.is_err());
+    }
+
+    #[test]
+    fn test_untrusted_source() {
+        let config = ProxyProtocolConfig {
+            trusted_proxies: vec!["10.0.0.0/8".parse().unwrap()],
+        };
+        let src_addr = "192.0.2.1:56324".parse().unwrap();
+        let local_addr = "192.0.2.53:53".parse().unwrap();
+
+        // the header isn't consumed and the connection's own addresses are kept
+        let mut stream = Cursor::new(b"PROXY UNKNOWN\r\n".to_vec());
+        let addresses = block_on(config.read_addresses(
+            &mut stream,
+            src_addr,
+            local_addr,
+            Duration::from_secs(5),
+        ))
+        .unwrap();
+        assert_eq!(addresses, (src_addr, local_addr));
+        assert_eq!(stream.position(), 0);
+    }
+}
@@ -12,8 +12,9 @@ use futures_util::lock::Mutex;
 use tokio_util::sync::CancellationToken;
 use tracing::{debug, warn};
 
+#[cfg(feature = "metrics")]
+use crate::server::metrics;
 use crate::{
-    access::AccessControl,
     authority::MessageResponse,
     proto::{
         ProtoError,
@@ -22,14 +23,18 @@ use crate::{
         rr::Record,
         xfer::Protocol,
     },
-    server::{ResponseInfo, request_handler::RequestHandler, response_handler::ResponseHandler},
+    server::{
+        ResponseInfo, ServerContext, request_handler::RequestHandler,
+        response_handler::ResponseHandler,
+    },
 };
 
 pub(crate) async fn quic_handler<T>(
-    access: Arc<AccessControl>,
+    context: Arc<ServerContext>,
     handler: Arc<T>,
     mut quic_streams: QuicStreams,
     src_addr: SocketAddr,
+    local_addr: SocketAddr,
     _dns_hostname: Option<Arc<str>>,
     shutdown: CancellationToken,
 ) -> Result<(), ProtoError>
@@ -65,17 +70,19 @@ where
             request.len()
         );
         let handler = handler.clone();
-        let access = access.clone();
+        let context = context.clone();
         let stream = Arc::new(Mutex::new(request_stream));
         let responder = QuicResponseHandle(stream.clone());
 
         super::handle_request(
             &request,
             src_addr,
+            local_addr,
             Protocol::Quic,
-            access,
+            context,
             handler,
             responder,
+            None,
         )
         .await;
 
@@ -122,6 +129,8 @@ impl ResponseHandler for QuicResponseHandle {
         let bytes = Bytes::from(bytes);
 
         debug!("sending quic response: {}", bytes.len());
+        #[cfg(feature = "metrics")]
+        metrics::response_size(Protocol::Quic, bytes.len());
         let mut lock = self.0.lock().await;
         lock.send_bytes(bytes).await?;
         lock.finish().await?;
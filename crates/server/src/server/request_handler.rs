@@ -11,6 +11,8 @@ use std::net::SocketAddr;
 
 use hickory_proto::ProtoError;
 
+#[cfg(feature = "__dnssec")]
+use crate::proto::rr::{Record, RecordType};
 use crate::{
     authority::MessageRequest,
     proto::{
@@ -27,8 +29,13 @@ pub struct Request {
     message: MessageRequest,
     /// Source address of the Client
     src: SocketAddr,
+    /// Address of the server socket the request was received on, if known
+    local: Option<SocketAddr>,
     /// Protocol of the request
     protocol: Protocol,
+    /// The message as received, kept for requests signed with TSIG to verify the MAC
+    #[cfg(feature = "__dnssec")]
+    signed_bytes: Option<Box<[u8]>>,
 }
 
 impl Request {
@@ -39,10 +46,35 @@ impl Request {
         Self {
             message,
             src,
+            local: None,
             protocol,
+            #[cfg(feature = "__dnssec")]
+            signed_bytes: None,
         }
     }
 
+    /// Set the address of the server socket the request was received on
+    pub fn with_local_addr(mut self, local: SocketAddr) -> Self {
+        self.local = Some(local);
+        self
+    }
+
+    /// Keep the message as received if it is signed with TSIG, so the signature can be verified
+    #[cfg(feature = "__dnssec")]
+    pub(crate) fn with_signed_bytes(mut self, bytes: &[u8]) -> Self {
+        let is_tsig = |record: &Record| record.record_type() == RecordType::TSIG;
+        if self.message.sig0().iter().any(is_tsig) {
+            self.signed_bytes = Some(bytes.into());
+        }
+        self
+    }
+
+    /// The message as received, if it is signed with TSIG
+    #[cfg(feature = "__dnssec")]
+    pub(crate) fn signed_bytes(&self) -> Option<&[u8]> {
+        self.signed_bytes.as_deref()
+    }
+
     /// Return just the header and request information from the Request Message
     ///
     /// Returns an error if there is not exactly one query
@@ -60,6 +92,13 @@ impl Request {
         self.src
     }
 
+    /// The address of the server socket on which the request was received, if known
+    ///
+    /// For sockets bound to an unspecified address this is the unspecified address.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local
+    }
+
     /// The protocol that was used for the request
     pub fn protocol(&self) -> Protocol {
         self.protocol
@@ -5,19 +5,24 @@
 // https://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
-use std::{io, net::SocketAddr};
+use std::{io, net::SocketAddr, sync::Arc};
 
-use hickory_proto::rr::Record;
+use hickory_proto::rr::{LowerName, Name, Record};
 use tracing::{debug, trace};
 
+#[cfg(feature = "metrics")]
+use crate::server::metrics;
 use crate::{
-    authority::MessageResponse,
+    authority::{MessageResponse, MessageResponseBuilder},
     proto::{
         BufDnsStreamHandle, DnsStreamHandle,
         serialize::binary::BinEncoder,
         xfer::{Protocol, SerialMessage},
     },
-    server::ResponseInfo,
+    server::{
+        ResponseInfo,
+        response_rate_limit::{RateLimitAction, ResponseKind, ResponseRateLimiter},
+    },
 };
 
 /// A handler for send a response to a client
@@ -49,6 +54,7 @@ pub struct ResponseHandle {
     dst: SocketAddr,
     stream_handle: BufDnsStreamHandle,
     protocol: Protocol,
+    rate_limiter: Option<Arc<ResponseRateLimiter>>,
 }
 
 impl ResponseHandle {
@@ -58,9 +64,19 @@ impl ResponseHandle {
             dst,
             stream_handle,
             protocol,
+            rate_limiter: None,
         }
     }
 
+    /// Applies response rate limiting to the response, if a limiter is given
+    pub(crate) fn with_rate_limiter(
+        mut self,
+        rate_limiter: Option<Arc<ResponseRateLimiter>>,
+    ) -> Self {
+        self.rate_limiter = rate_limiter;
+        self
+    }
+
     /// Selects an appropriate maximum serialized size for the given response.
     fn max_size_for_response<'a>(
         &self,
@@ -104,11 +120,20 @@ impl ResponseHandler for ResponseHandle {
             impl Iterator<Item = &'a Record> + Send + 'a,
         >,
     ) -> io::Result<ResponseInfo> {
+        // only log what is actually sent, after rate limiting
+        #[cfg(feature = "dnstap")]
+        let (tap, response) = {
+            let mut response = response;
+            (response.take_tap(), response)
+        };
+
         debug!(
             "response: {} response_code: {}",
             response.header().id(),
             response.header().response_code(),
         );
+        let queries = response.queries();
+        let header = *response.header();
         let mut buffer = Vec::with_capacity(512);
         let encode_result = {
             let mut encoder = BinEncoder::new(&mut buffer);
@@ -121,13 +146,59 @@ impl ResponseHandler for ResponseHandle {
             );
             encoder.set_max_size(max_size);
 
-            response.destructive_emit(&mut encoder)
+            response.destructive_emit_with_authority(&mut encoder)
         };
 
-        let info = encode_result.map_err(|e| {
+        let (info, authority) = encode_result.map_err(|e| {
             io::Error::new(io::ErrorKind::Other, format!("error encoding message: {e}"))
         })?;
 
+        if let Some(rate_limiter) = &self.rate_limiter {
+            let kind = ResponseKind::from_response(&info);
+            let name = match (kind, authority) {
+                (ResponseKind::NxDomain | ResponseKind::Referral, Some(authority)) => {
+                    LowerName::from(authority)
+                }
+                _ => queries
+                    .try_as_query()
+                    .map(|query| query.name().clone())
+                    .unwrap_or_else(|_| LowerName::from(Name::root())),
+            };
+
+            match rate_limiter.check(self.dst.ip(), kind, &name) {
+                RateLimitAction::Send => {}
+                RateLimitAction::Drop => {
+                    debug!("rate limited response: {} to: {}", info.id(), self.dst);
+                    return Ok(info);
+                }
+                RateLimitAction::Slip => {
+                    debug!("slipped response: {} to: {}", info.id(), self.dst);
+                    let mut header = header;
+                    header.set_truncated(true);
+
+                    buffer.clear();
+                    let mut encoder = BinEncoder::new(&mut buffer);
+                    MessageResponseBuilder::new(queries)
+                        .build_no_records(header)
+                        .destructive_emit(&mut encoder)
+                        .map_err(|e| {
+                            io::Error::new(
+                                io::ErrorKind::Other,
+                                format!("error encoding message: {e}"),
+                            )
+                        })?;
+                }
+            }
+        }
+
+        #[cfg(feature = "dnstap")]
+        if let Some(tap) = tap {
+            tap.log_response(&buffer);
+        }
+
+        #[cfg(feature = "metrics")]
+        metrics::response_size(self.protocol, buffer.len());
+
         self.stream_handle
             .send(SerialMessage::new(buffer, self.dst))
             .map_err(|_| io::Error::new(io::ErrorKind::Other, "unknown"))?;
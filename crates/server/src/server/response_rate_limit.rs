@@ -0,0 +1,487 @@
+//! Response Rate Limiting (RRL) for UDP responses
+//!
+//! UDP source addresses can be spoofed, so an authoritative server answering every query as fast
+//! as it can be used to reflect and amplify traffic towards a victim. Like BIND's RRL, responses
+//! are counted in buckets keyed by the client's network prefix, the kind of response and the name
+//! it is about, and a bucket that runs out of credit stops getting responses. Every `slip`th
+//! limited response is still answered, but truncated and without records, so that legitimate
+//! clients behind a limited prefix can retry over TCP.
+
+use std::{net::IpAddr, sync::Mutex, time::Instant};
+
+use ipnet::IpNet;
+use lru_cache::LruCache;
+#[cfg(feature = "metrics")]
+use metrics::{Counter, Unit, counter, describe_counter};
+use serde::Deserialize;
+use tracing::debug;
+
+use crate::{
+    proto::{op::ResponseCode, rr::LowerName},
+    server::ResponseInfo,
+};
+
+/// Configuration of the [`ResponseRateLimiter`]
+///
+/// All rates are in responses per second per bucket, a rate of `0` does not limit that kind of
+/// response.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ResponseRateLimitConfig {
+    /// Limit for positive answers and empty (NODATA) responses, bucketed by the query name.
+    /// Defaults to `0`.
+    pub responses_per_second: u32,
+
+    /// Limit for NXDOMAIN responses, bucketed by the zone from the SOA in the response. Defaults
+    /// to `responses_per_second`.
+    pub nxdomains_per_second: Option<u32>,
+
+    /// Limit for referrals, bucketed by the delegation point. Defaults to `responses_per_second`.
+    pub referrals_per_second: Option<u32>,
+
+    /// Limit for any other response code, bucketed by client prefix only. Defaults to
+    /// `responses_per_second`.
+    pub errors_per_second: Option<u32>,
+
+    /// Number of seconds over which a bucket may go into debt. A client that keeps exceeding the
+    /// limit stays limited until it has been quiet for up to this long. Defaults to 15.
+    pub window: u32,
+
+    /// Every `slip`th limited response is sent truncated instead of being dropped, `0` drops all
+    /// limited responses and `1` truncates all of them. Defaults to 2.
+    pub slip: u32,
+
+    /// Prefix length IPv4 clients are grouped by. Defaults to 24.
+    pub ipv4_prefix_length: u8,
+
+    /// Prefix length IPv6 clients are grouped by. Defaults to 56.
+    pub ipv6_prefix_length: u8,
+
+    /// Maximum number of buckets tracked at once, the least recently used bucket is evicted to make
+    /// room for a new one. Defaults to 20,000.
+    pub max_table_size: usize,
+
+    /// Networks that are never limited
+    pub exempt_clients: Vec<IpNet>,
+}
+
+impl Default for ResponseRateLimitConfig {
+    fn default() -> Self {
+        Self {
+            responses_per_second: 0,
+            nxdomains_per_second: None,
+            referrals_per_second: None,
+            errors_per_second: None,
+            window: 15,
+            slip: 2,
+            ipv4_prefix_length: 24,
+            ipv6_prefix_length: 56,
+            max_table_size: 20_000,
+            exempt_clients: Vec::new(),
+        }
+    }
+}
+
+/// The kind of a response, each kind has its own limit
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ResponseKind {
+    /// A positive or empty (NODATA) response
+    Answer,
+    /// A name error
+    NxDomain,
+    /// A non-authoritative response delegating to other name servers
+    Referral,
+    /// Any other response code
+    Error,
+}
+
+impl ResponseKind {
+    /// Classifies a response from its header
+    pub fn from_response(info: &ResponseInfo) -> Self {
+        match info.response_code() {
+            ResponseCode::NoError
+                if info.answer_count() == 0
+                    && info.name_server_count() > 0
+                    && !info.authoritative() =>
+            {
+                Self::Referral
+            }
+            ResponseCode::NoError => Self::Answer,
+            ResponseCode::NXDomain => Self::NxDomain,
+            _ => Self::Error,
+        }
+    }
+}
+
+/// What to do with a response
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RateLimitAction {
+    /// Send the response
+    Send,
+    /// Send a truncated response without records instead
+    Slip,
+    /// Don't send anything
+    Drop,
+}
+
+/// BIND-style response rate limiter, shared by all UDP sockets of a server
+pub struct ResponseRateLimiter {
+    config: ResponseRateLimitConfig,
+    buckets: Mutex<LruCache<BucketKey, Bucket>>,
+    #[cfg(feature = "metrics")]
+    metrics: RateLimitMetrics,
+}
+
+impl ResponseRateLimiter {
+    /// Creates a rate limiter from its configuration
+    pub fn new(config: ResponseRateLimitConfig) -> Self {
+        Self {
+            buckets: Mutex::new(LruCache::new(config.max_table_size.max(1))),
+            config,
+            #[cfg(feature = "metrics")]
+            metrics: RateLimitMetrics::new(),
+        }
+    }
+
+    /// Accounts for a response to `client`, returning what should be sent
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - address the response is for
+    /// * `kind` - kind of the response
+    /// * `name` - query name of answers, or the owner of the first record in the authority
+    ///   section of NXDOMAIN responses and referrals; ignored for errors
+    pub fn check(&self, client: IpAddr, kind: ResponseKind, name: &LowerName) -> RateLimitAction {
+        let action = self.check_at(client, kind, name, Instant::now());
+
+        #[cfg(feature = "metrics")]
+        match action {
+            RateLimitAction::Send => self.metrics.sent.increment(1),
+            RateLimitAction::Slip => self.metrics.slipped.increment(1),
+            RateLimitAction::Drop => self.metrics.dropped.increment(1),
+        }
+
+        action
+    }
+
+    fn check_at(
+        &self,
+        client: IpAddr,
+        kind: ResponseKind,
+        name: &LowerName,
+        now: Instant,
+    ) -> RateLimitAction {
+        let rate = self.rate(kind);
+        if rate == 0
+            || self
+                .config
+                .exempt_clients
+                .iter()
+                .any(|n| n.contains(&client))
+        {
+            return RateLimitAction::Send;
+        }
+
+        let key = BucketKey {
+            prefix: self.prefix(client),
+            kind,
+            name: match kind {
+                ResponseKind::Error => None,
+                _ => Some(name.clone()),
+            },
+        };
+
+        let rate = f64::from(rate);
+        let mut buckets = self.buckets.lock().expect("poisoned rate limit table");
+        if !buckets.contains_key(&key) {
+            if buckets.len() >= buckets.capacity() {
+                debug!(
+                    "response rate limit table is full, evicting the least recently used bucket"
+                );
+            }
+
+            // evicts the least recently used bucket if the table is full
+            buckets.insert(
+                key.clone(),
+                Bucket {
+                    balance: rate,
+                    updated: now,
+                    limited: 0,
+                },
+            );
+        }
+
+        // marks the bucket as the most recently used one
+        let bucket = buckets.get_mut(&key).expect("bucket was just inserted");
+
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.balance = (bucket.balance + elapsed * rate).min(rate) - 1.0;
+        bucket.balance = bucket.balance.max(-rate * f64::from(self.config.window));
+        bucket.updated = now;
+
+        if bucket.balance >= 0.0 {
+            bucket.limited = 0;
+            return RateLimitAction::Send;
+        }
+
+        bucket.limited = bucket.limited.wrapping_add(1);
+        match self.config.slip {
+            0 => RateLimitAction::Drop,
+            slip if bucket.limited % slip == 0 => RateLimitAction::Slip,
+            _ => RateLimitAction::Drop,
+        }
+    }
+
+    fn rate(&self, kind: ResponseKind) -> u32 {
+        let config = &self.config;
+        match kind {
+            ResponseKind::Answer => Some(config.responses_per_second),
+            ResponseKind::NxDomain => config.nxdomains_per_second,
+            ResponseKind::Referral => config.referrals_per_second,
+            ResponseKind::Error => config.errors_per_second,
+        }
+        .unwrap_or(config.responses_per_second)
+    }
+
+    fn prefix(&self, client: IpAddr) -> IpNet {
+        let len = match client {
+            IpAddr::V4(_) => self.config.ipv4_prefix_length.min(32),
+            IpAddr::V6(_) => self.config.ipv6_prefix_length.min(128),
+        };
+
+        IpNet::new(client, len)
+            .expect("prefix length is in range")
+            .trunc()
+    }
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+struct BucketKey {
+    prefix: IpNet,
+    kind: ResponseKind,
+    name: Option<LowerName>,
+}
+
+struct Bucket {
+    /// Remaining credit, negative while the bucket is limited
+    balance: f64,
+    updated: Instant,
+    /// Number of responses limited in a row, for slipping
+    limited: u32,
+}
+
+#[cfg(feature = "metrics")]
+struct RateLimitMetrics {
+    sent: Counter,
+    slipped: Counter,
+    dropped: Counter,
+}
+
+#[cfg(feature = "metrics")]
+impl RateLimitMetrics {
+    fn new() -> Self {
+        let name = "hickory_response_rate_limit_total";
+        let sent = counter!(name, "action" => "sent");
+        let slipped = counter!(name, "action" => "slipped");
+        let dropped = counter!(name, "action" => "dropped");
+        describe_counter!(
+            name,
+            Unit::Count,
+            "number of rate limited UDP responses by the action taken"
+        );
+
+        Self {
+            sent,
+            slipped,
+            dropped,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::Ipv4Addr, str::FromStr, time::Duration};
+
+    use super::*;
+    use crate::proto::rr::Name;
+
+    fn limiter(config: ResponseRateLimitConfig) -> ResponseRateLimiter {
+        ResponseRateLimiter::new(config)
+    }
+
+    fn name(name: &str) -> LowerName {
+        LowerName::from(Name::from_str(name).unwrap())
+    }
+
+    const CLIENT: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+
+    #[test]
+    fn test_limits_and_slips() {
+        let limiter = limiter(ResponseRateLimitConfig {
+            responses_per_second: 2,
+            slip: 2,
+            ..ResponseRateLimitConfig::default()
+        });
+        let www = name("www.example.com.");
+        let now = Instant::now();
+
+        let actions = (0..6)
+            .map(|_| limiter.check_at(CLIENT, ResponseKind::Answer, &www, now))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            actions,
+            vec![
+                RateLimitAction::Send,
+                RateLimitAction::Send,
+                RateLimitAction::Drop,
+                RateLimitAction::Slip,
+                RateLimitAction::Drop,
+                RateLimitAction::Slip,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_buckets() {
+        let limiter = limiter(ResponseRateLimitConfig {
+            responses_per_second: 1,
+            slip: 0,
+            ..ResponseRateLimitConfig::default()
+        });
+        let www = name("www.example.com.");
+        let now = Instant::now();
+
+        assert_eq!(
+            limiter.check_at(CLIENT, ResponseKind::Answer, &www, now),
+            RateLimitAction::Send
+        );
+        assert_eq!(
+            limiter.check_at(CLIENT, ResponseKind::Answer, &www, now),
+            RateLimitAction::Drop
+        );
+
+        // same prefix, same bucket
+        let neighbour = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 200));
+        assert_eq!(
+            limiter.check_at(neighbour, ResponseKind::Answer, &www, now),
+            RateLimitAction::Drop
+        );
+
+        // other prefix, name or kind
+        let other = IpAddr::V4(Ipv4Addr::new(192, 0, 3, 1));
+        assert_eq!(
+            limiter.check_at(other, ResponseKind::Answer, &www, now),
+            RateLimitAction::Send
+        );
+        assert_eq!(
+            limiter.check_at(CLIENT, ResponseKind::Answer, &name("ftp.example.com."), now),
+            RateLimitAction::Send
+        );
+        assert_eq!(
+            limiter.check_at(CLIENT, ResponseKind::NxDomain, &www, now),
+            RateLimitAction::Send
+        );
+
+        // errors are only bucketed by client
+        assert_eq!(
+            limiter.check_at(CLIENT, ResponseKind::Error, &www, now),
+            RateLimitAction::Send
+        );
+        assert_eq!(
+            limiter.check_at(CLIENT, ResponseKind::Error, &name("ftp.example.com."), now),
+            RateLimitAction::Drop
+        );
+    }
+
+    #[test]
+    fn test_recovers_after_window() {
+        let limiter = limiter(ResponseRateLimitConfig {
+            responses_per_second: 10,
+            window: 2,
+            slip: 0,
+            ..ResponseRateLimitConfig::default()
+        });
+        let www = name("www.example.com.");
+        let start = Instant::now();
+
+        for _ in 0..100 {
+            limiter.check_at(CLIENT, ResponseKind::Answer, &www, start);
+        }
+
+        // the debt is capped at `window` seconds of credit
+        let action = limiter.check_at(
+            CLIENT,
+            ResponseKind::Answer,
+            &www,
+            start + Duration::from_millis(1900),
+        );
+        assert_eq!(action, RateLimitAction::Drop);
+        let action = limiter.check_at(
+            CLIENT,
+            ResponseKind::Answer,
+            &www,
+            start + Duration::from_millis(2200),
+        );
+        assert_eq!(action, RateLimitAction::Send);
+    }
+
+    #[test]
+    fn test_unlimited_and_exempt() {
+        let limiter = limiter(ResponseRateLimitConfig {
+            responses_per_second: 1,
+            errors_per_second: Some(0),
+            exempt_clients: vec!["192.0.2.0/28".parse().unwrap()],
+            ..ResponseRateLimitConfig::default()
+        });
+        let www = name("www.example.com.");
+        let now = Instant::now();
+        let other = IpAddr::V4(Ipv4Addr::new(198, 51, 100, 1));
+
+        for _ in 0..10 {
+            assert_eq!(
+                limiter.check_at(CLIENT, ResponseKind::Answer, &www, now),
+                RateLimitAction::Send
+            );
+            assert_eq!(
+                limiter.check_at(other, ResponseKind::Error, &www, now),
+                RateLimitAction::Send
+            );
+        }
+    }
+
+    #[test]
+    fn test_table_full() {
+        let limiter = limiter(ResponseRateLimitConfig {
+            responses_per_second: 1,
+            slip: 0,
+            max_table_size: 2,
+            ..ResponseRateLimitConfig::default()
+        });
+        let now = Instant::now();
+        let (a, b, c) = (name("a.example."), name("b.example."), name("c.example."));
+
+        for name in [&a, &b] {
+            assert_eq!(
+                limiter.check_at(CLIENT, ResponseKind::Answer, name, now),
+                RateLimitAction::Send
+            );
+        }
+        assert_eq!(
+            limiter.check_at(CLIENT, ResponseKind::Answer, &a, now),
+            RateLimitAction::Drop
+        );
+
+        // the least recently used bucket, b, is evicted to make room
+        assert_eq!(
+            limiter.check_at(CLIENT, ResponseKind::Answer, &c, now),
+            RateLimitAction::Send
+        );
+        for name in [&a, &c] {
+            assert_eq!(
+                limiter.check_at(CLIENT, ResponseKind::Answer, name, now),
+                RateLimitAction::Drop
+            );
+        }
+        assert_eq!(limiter.buckets.lock().unwrap().len(), 2);
+    }
+}
@@ -10,11 +10,12 @@
 //! Forwarding resolver related types
 
 use std::io;
+use std::path::PathBuf;
 #[cfg(feature = "__dnssec")]
 use std::sync::Arc;
 
 use serde::Deserialize;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 #[cfg(feature = "metrics")]
 use crate::store::metrics::QueryStoreMetrics;
@@ -31,7 +32,7 @@ use crate::{
     },
     resolver::{
         Resolver,
-        config::{NameServerConfigGroup, ResolveHosts, ResolverConfig, ResolverOpts},
+        config::{DomainRoute, NameServerConfigGroup, ResolveHosts, ResolverConfig, ResolverOpts},
         lookup::Lookup as ResolverLookup,
         name_server::{ConnectionProvider, TokioConnectionProvider},
     },
@@ -101,8 +102,14 @@ impl<P: ConnectionProvider> ForwardAuthorityBuilder<P> {
         } = self;
         info!(%origin, "loading forwarder config");
 
-        let name_servers = config.name_servers;
-        let mut options = config.options.unwrap_or_default();
+        let ForwardConfig {
+            name_servers,
+            routes,
+            options,
+            cache_file,
+            preserve_dnssec_proofs,
+        } = config;
+        let mut options = options.unwrap_or_default();
 
         // See RFC 1034, Section 4.3.2:
         // "If the data at the node is a CNAME, and QTYPE doesn't match
@@ -129,32 +136,67 @@ impl<P: ConnectionProvider> ForwardAuthorityBuilder<P> {
             options.use_hosts_file = ResolveHosts::Never;
         }
 
-        let config = ResolverConfig::from_parts(domain, search, name_servers);
-
-        let mut resolver_builder = Resolver::builder_with_config(config, runtime);
-
         #[cfg(feature = "__dnssec")]
-        match (trust_anchor, &options.trust_anchor) {
-            (Some(trust_anchor), _) => {
-                resolver_builder = resolver_builder.with_trust_anchor(trust_anchor);
-                options.validate = true;
-            }
+        let trust_anchor = match (trust_anchor, &options.trust_anchor) {
+            (Some(trust_anchor), _) => Some(trust_anchor),
             (None, Some(path)) => {
                 let trust_anchor = TrustAnchors::from_file(path).map_err(|err| err.to_string())?;
-                resolver_builder = resolver_builder.with_trust_anchor(Arc::new(trust_anchor));
-                options.validate = true;
+                Some(Arc::new(trust_anchor))
             }
-            (None, None) => {}
+            (None, None) => None,
+        };
+
+        #[cfg(feature = "__dnssec")]
+        if trust_anchor.is_some() {
+            options.validate = true;
+        }
+
+        // responses of all routes share the cache, which is either validating or not
+        if let Some(route) = routes.iter().find(|route| {
+            route
+                .options
+                .as_ref()
+                .is_some_and(|route_options| route_options.validate != options.validate)
+        }) {
+            return Err(format!(
+                "the validate option of the route for {:?} must match the forwarder's",
+                route.domains
+            ));
+        }
+
+        let mut config = ResolverConfig::from_parts(domain, search, name_servers);
+        for route in routes {
+            config.add_route(route);
+        }
+
+        let mut resolver_builder = Resolver::builder_with_config(config, runtime);
+
+        #[cfg(feature = "__dnssec")]
+        if let Some(trust_anchor) = trust_anchor {
+            resolver_builder = resolver_builder.with_trust_anchor(trust_anchor);
         }
 
         *resolver_builder.options_mut() = options;
         let resolver = resolver_builder.build();
 
+        if let Some(path) = &cache_file {
+            match resolver.load_cache(path, preserve_dnssec_proofs) {
+                Ok(restored) => {
+                    info!(%origin, "restored {restored} cache entries from {}", path.display())
+                }
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                    info!(%origin, "cache file {} does not exist yet", path.display())
+                }
+                Err(e) => warn!(%origin, "failed to restore cache from {}: {e}", path.display()),
+            }
+        }
+
         info!(%origin, "forward resolver configured");
 
         Ok(ForwardAuthority {
             origin: origin.into(),
             resolver,
+            cache_file,
             #[cfg(feature = "metrics")]
             metrics: QueryStoreMetrics::new("forwarder"),
         })
@@ -167,6 +209,7 @@ impl<P: ConnectionProvider> ForwardAuthorityBuilder<P> {
 pub struct ForwardAuthority<P: ConnectionProvider = TokioConnectionProvider> {
     origin: LowerName,
     resolver: Resolver<P>,
+    cache_file: Option<PathBuf>,
     #[cfg(feature = "metrics")]
     metrics: QueryStoreMetrics,
 }
@@ -178,8 +221,9 @@ impl<P: ConnectionProvider> ForwardAuthority<P> {
         let (resolver_config, options) = hickory_resolver::system_conf::read_system_conf()
             .map_err(|e| format!("error reading system configuration: {e}"))?;
         let forward_config = ForwardConfig {
-            name_servers: resolver_config.name_servers().to_vec().into(),
+            routes: resolver_config.routes().to_vec(),
             options: Some(options),
+            ..ForwardConfig::new(resolver_config.name_servers().to_vec().into())
         };
         let mut builder = Self::builder_with_config(forward_config, runtime);
         if let Some(domain) = resolver_config.domain() {
@@ -313,6 +357,23 @@ impl<P: ConnectionProvider> Authority for ForwardAuthority<P> {
     fn nx_proof_kind(&self) -> Option<&NxProofKind> {
         None
     }
+
+    fn clear_cache(&self, name: Option<&Name>) -> bool {
+        match name {
+            Some(name) => self.resolver.clear_cache_subdomains(name),
+            None => self.resolver.clear_cache(),
+        }
+        true
+    }
+
+    fn cached_records(&self) -> Option<Vec<Record>> {
+        Some(self.resolver.cached_records())
+    }
+
+    fn save_cache(&self) -> Option<io::Result<usize>> {
+        let path = self.cache_file.as_ref()?;
+        Some(self.resolver.save_cache(path))
+    }
 }
 
 /// A structure that holds the results of a forwarding lookup.
@@ -337,9 +398,37 @@ impl LookupObject for ForwardLookup {
 /// Configuration for file based zones
 #[derive(Clone, Deserialize, Debug)]
 #[serde(deny_unknown_fields)]
+#[non_exhaustive]
 pub struct ForwardConfig {
     /// upstream name_server configurations
     pub name_servers: NameServerConfigGroup,
-    /// Resolver options
+    /// Upstream name servers for names in routing domains, see [`DomainRoute`]
+    #[serde(default)]
+    pub routes: Vec<DomainRoute>,
+    /// Resolver options, e.g. `dns64` to synthesize AAAA records from A records for IPv6-only
+    /// clients, RFC 6147
     pub options: Option<ResolverOpts>,
+    /// File the cache is restored from at startup, and saved to when the server stops
+    ///
+    /// Relative paths are relative to the zone directory of the server.
+    pub cache_file: Option<PathBuf>,
+    /// Restore the DNSSEC validation state of the records from the cache file
+    ///
+    /// Otherwise the cache file isn't restored if the forwarder validates, as the records would
+    /// not be validated again.
+    #[serde(default)]
+    pub preserve_dnssec_proofs: bool,
+}
+
+impl ForwardConfig {
+    /// Creates a configuration forwarding to `name_servers`, with the default options
+    pub fn new(name_servers: NameServerConfigGroup) -> Self {
+        Self {
+            name_servers,
+            routes: Vec::new(),
+            options: None,
+            cache_file: None,
+            preserve_dnssec_proofs: false,
+        }
+    }
 }
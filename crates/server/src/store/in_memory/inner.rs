@@ -1,9 +1,12 @@
-#[cfg(feature = "__dnssec")]
-use std::collections::{BTreeSet, HashMap, hash_map::Entry};
 use std::{
     collections::{BTreeMap, HashSet},
     sync::Arc,
 };
+#[cfg(feature = "__dnssec")]
+use std::{
+    collections::{BTreeSet, HashMap, hash_map::Entry},
+    ops::Bound,
+};
 
 use cfg_if::cfg_if;
 #[cfg(feature = "__dnssec")]
@@ -33,7 +36,11 @@ use crate::{
     },
 };
 
-#[derive(Default)]
+/// RRsets replaced in the zone, by key, `None` if there was none
+#[cfg(feature = "__dnssec")]
+pub(super) type Replaced = BTreeMap<RrKey, Option<Arc<RecordSet>>>;
+
+#[derive(Clone, Default)]
 pub(super) struct InnerInMemory {
     pub(super) records: BTreeMap<RrKey, Arc<RecordSet>>,
     // Private key mapped to the Record of the DNSKey
@@ -42,7 +49,7 @@ pub(super) struct InnerInMemory {
     //   may not support dynamic updates to register the new key... Hickory DNS will provide support
     //   for this, in some form, perhaps alternate root zones...
     #[cfg(feature = "__dnssec")]
-    pub(super) secure_keys: Vec<SigSigner>,
+    pub(super) secure_keys: Vec<Arc<SigSigner>>,
 }
 
 impl InnerInMemory {
@@ -478,6 +485,52 @@ impl InnerInMemory {
         origin: &LowerName,
         dns_class: DNSClass,
         nx_proof_kind: Option<&NxProofKind>,
+    ) -> DnsSecResult<()> {
+        self.nx_proof_zone(origin, dns_class, nx_proof_kind)?;
+
+        // need to resign any records at the current serial number and bump the number.
+        // first bump the serial number on the SOA, so that it is resigned with the new serial.
+        self.increment_soa_serial(origin, dns_class);
+
+        // TODO: should we auto sign here? or maybe up a level...
+        self.sign_zone(origin, dns_class)
+    }
+
+    /// Same as `secure_zone_mut`, but leaves signing to the caller, see `sign_rrsets`
+    ///
+    /// The RRsets as they were before are added to `replaced`, see `restore`.
+    #[cfg(feature = "__dnssec")]
+    pub(super) fn prepare_secure_zone(
+        &mut self,
+        origin: &LowerName,
+        dns_class: DNSClass,
+        nx_proof_kind: Option<&NxProofKind>,
+        replaced: &mut Replaced,
+    ) -> DnsSecResult<()> {
+        for (rr_key, rr_set) in &self.records {
+            replaced
+                .entry(rr_key.clone())
+                .or_insert_with(|| Some(rr_set.clone()));
+        }
+
+        let result = self.nx_proof_zone(origin, dns_class, nx_proof_kind);
+        if result.is_ok() {
+            self.increment_soa_serial(origin, dns_class);
+        }
+
+        for rr_key in self.records.keys() {
+            replaced.entry(rr_key.clone()).or_insert(None);
+        }
+        result
+    }
+
+    /// (Re)generates the nsec or nsec3 records of the zone
+    #[cfg(feature = "__dnssec")]
+    fn nx_proof_zone(
+        &mut self,
+        origin: &LowerName,
+        dns_class: DNSClass,
+        nx_proof_kind: Option<&NxProofKind>,
     ) -> DnsSecResult<()> {
         // TODO: only call nsec_zone after adds/deletes
         // needs to be called before incrementing the soa serial, to make sure IXFR works properly
@@ -492,12 +545,546 @@ impl InnerInMemory {
             None => (),
         }
 
-        // need to resign any records at the current serial number and bump the number.
-        // first bump the serial number on the SOA, so that it is resigned with the new serial.
+        Ok(())
+    }
+
+    /// Patches the nsec records around the changed RRsets and increments the serial number,
+    /// returns the keys of the RRsets that need to be signed again
+    ///
+    /// This is the incremental counterpart of `prepare_secure_zone`, for use after a dynamic
+    /// update. Adding or removing a delegation point in an NSEC3 zone can change which names below
+    /// it are authoritative, in that case the whole chain is regenerated and `None` is returned,
+    /// meaning that all the RRsets need to be signed. The RRsets as they were before are added to
+    /// `replaced`, see `restore`.
+    #[cfg(feature = "__dnssec")]
+    pub(super) fn prepare_zone_update(
+        &mut self,
+        origin: &LowerName,
+        dns_class: DNSClass,
+        nx_proof_kind: Option<&NxProofKind>,
+        changed: &BTreeSet<RrKey>,
+        replaced: &mut Replaced,
+    ) -> DnsSecResult<Option<BTreeSet<RrKey>>> {
+        let mut to_sign = changed.clone();
+
+        // needs to be called before incrementing the soa serial, to make sure IXFR works properly
+        match nx_proof_kind {
+            Some(NxProofKind::Nsec) => {
+                to_sign.extend(self.update_nsec_chain(origin, dns_class, changed, replaced))
+            }
+            Some(NxProofKind::Nsec3 { .. })
+                if changed
+                    .iter()
+                    .any(|key| key.record_type == RecordType::NS && key.name != *origin) =>
+            {
+                debug!("delegation changed, regenerating nsec3 records: {origin}");
+                self.prepare_secure_zone(origin, dns_class, nx_proof_kind, replaced)?;
+                return Ok(None);
+            }
+            Some(NxProofKind::Nsec3 {
+                algorithm,
+                salt,
+                iterations,
+                opt_out,
+            }) => to_sign.extend(self.update_nsec3_chain(
+                origin,
+                dns_class,
+                *algorithm,
+                salt,
+                *iterations,
+                *opt_out,
+                changed,
+                replaced,
+            )?),
+            None => (),
+        }
+
+        let soa_key = RrKey::new(origin.clone(), RecordType::SOA);
+        let soa = self.records.get(&soa_key).cloned();
+        replaced.entry(soa_key.clone()).or_insert(soa);
         self.increment_soa_serial(origin, dns_class);
+        to_sign.insert(soa_key);
 
-        // TODO: should we auto sign here? or maybe up a level...
-        self.sign_zone(origin, dns_class)
+        Ok(Some(to_sign))
+    }
+
+    /// Regenerates the NSEC records at the owner names of the changed RRsets and at their
+    /// predecessors in the chain, returns the keys of the NSEC records that need to be signed
+    #[cfg(feature = "__dnssec")]
+    fn update_nsec_chain(
+        &mut self,
+        origin: &LowerName,
+        dns_class: DNSClass,
+        changed: &BTreeSet<RrKey>,
+        replaced: &mut Replaced,
+    ) -> Vec<RrKey> {
+        // only create nsec records for secure zones
+        if self.secure_keys.is_empty() {
+            return vec![];
+        }
+        debug!("updating nsec records: {origin}");
+
+        let ttl = self.minimum_ttl(origin);
+        let serial = self.serial(origin);
+
+        // Added names need a new record and change the next name of their predecessor, removed names
+        // lose their record and also change the next name of their predecessor.
+        let mut affected = BTreeSet::new();
+        for key in changed {
+            if let Some(previous) = self.previous_nsec_owner(&key.name) {
+                affected.insert(previous);
+            }
+            affected.insert(key.name.clone());
+        }
+
+        let mut updated = vec![];
+        for name in affected {
+            let rr_key = RrKey::new(name.clone(), RecordType::NSEC);
+            self.take_rrset(&rr_key, replaced);
+
+            let types = self
+                .records_at(&name)
+                .map(|key| key.record_type)
+                .filter(|record_type| *record_type != RecordType::NSEC)
+                .collect::<BTreeSet<_>>();
+            if types.is_empty() {
+                // the name was removed from the zone
+                continue;
+            }
+
+            let next = self
+                .next_nsec_owner(&name)
+                .unwrap_or_else(|| origin.clone());
+            let rdata = NSEC::new_cover_self(next.into(), types);
+            let record = Record::from_rdata(name.into(), ttl, rdata);
+            let upserted = self.upsert(record.into_record_of_rdata(), serial, dns_class);
+            debug_assert!(upserted);
+
+            updated.push(rr_key);
+        }
+
+        updated
+    }
+
+    /// Regenerates the NSEC3 records of the owner names of the changed RRsets and of all of their
+    /// ancestors (which may be empty non-terminals), then relinks the hash order around them.
+    /// Returns the keys of the NSEC3 records that need to be signed.
+    #[cfg(feature = "__dnssec")]
+    #[allow(clippy::too_many_arguments)]
+    fn update_nsec3_chain(
+        &mut self,
+        origin: &LowerName,
+        dns_class: DNSClass,
+        hash_alg: Nsec3HashAlgorithm,
+        salt: &[u8],
+        iterations: u16,
+        opt_out: bool,
+        changed: &BTreeSet<RrKey>,
+        replaced: &mut Replaced,
+    ) -> DnsSecResult<Vec<RrKey>> {
+        // only create nsec records for secure zones
+        if self.secure_keys.is_empty() {
+            return Ok(vec![]);
+        }
+        debug!("updating nsec3 records: {origin}");
+
+        let ttl = self.minimum_ttl(origin);
+        let serial = self.serial(origin);
+
+        let mut names = BTreeSet::new();
+        for key in changed {
+            let mut name = key.name.clone();
+            while origin.zone_of(&name) && names.insert(name.clone()) && name != *origin {
+                name = name.base_name();
+            }
+        }
+
+        // Replace the NSEC3 record of every name, the next hashed owner names are fixed up below.
+        let mut type_bit_maps = HashMap::new();
+        let mut touched = BTreeSet::new();
+        for name in names {
+            let hashed_name = hash_alg.hash(salt, &name, iterations)?;
+            let owner =
+                origin.prepend_label(data_encoding::BASE32_DNSSEC.encode(hashed_name.as_ref()))?;
+            let rr_key = RrKey::new(owner.into(), RecordType::NSEC3);
+
+            self.take_rrset(&rr_key, replaced);
+            if let Some(types) = self.nsec3_type_bit_maps(origin, &name, opt_out) {
+                type_bit_maps.insert(rr_key.name.clone(), types);
+            }
+
+            touched.insert(rr_key);
+        }
+
+        // Every touched record, and the record preceding it in hash order, needs to point at its
+        // current successor.
+        let mut relink = BTreeSet::new();
+        for rr_key in touched {
+            if let Some(previous) = self.previous_nsec3_owner(&rr_key, &type_bit_maps) {
+                relink.insert(previous);
+            }
+            if type_bit_maps.contains_key(&rr_key.name) {
+                relink.insert(rr_key.name);
+            }
+        }
+
+        let mut updated = vec![];
+        for owner in relink {
+            let rr_key = RrKey::new(owner.clone(), RecordType::NSEC3);
+            let types = match type_bit_maps.remove(&owner) {
+                Some(types) => types,
+                None => self
+                    .take_rrset(&rr_key, replaced)
+                    .and_then(|rr_set| rr_set.records_without_rrsigs().next().cloned())
+                    .and_then(|record| match record.into_data() {
+                        RData::DNSSEC(DNSSECRData::NSEC3(nsec3)) => {
+                            Some(nsec3.type_bit_maps().collect())
+                        }
+                        _ => None,
+                    })
+                    .unwrap_or_default(),
+            };
+
+            let next_owner = self.next_nsec3_owner(&owner, &type_bit_maps);
+            let next_hashed_name = data_encoding::BASE32_DNSSEC
+                .decode(next_owner.iter().next().unwrap_or_default())
+                .map_err(|e| ProtoError::from(format!("invalid nsec3 owner name: {e}")))?;
+
+            let rdata = NSEC3::new(
+                hash_alg,
+                opt_out,
+                iterations,
+                salt.to_vec(),
+                next_hashed_name,
+                types,
+            );
+            let record = Record::from_rdata(owner.into(), ttl, rdata);
+            let upserted = self.upsert(record.into_record_of_rdata(), serial, dns_class);
+            debug_assert!(upserted);
+
+            updated.push(rr_key);
+        }
+
+        Ok(updated)
+    }
+
+    /// Returns the types for the NSEC3 record of `name`, following the same rules as
+    /// `nsec3_zone`, or `None` if the name should not have an NSEC3 record
+    #[cfg(feature = "__dnssec")]
+    fn nsec3_type_bit_maps(
+        &self,
+        origin: &LowerName,
+        name: &LowerName,
+        opt_out: bool,
+    ) -> Option<Vec<RecordType>> {
+        // Non-authoritative record outside of zone or below zone cut
+        if !origin.zone_of(name) || self.is_below_delegation(origin, name) {
+            return None;
+        }
+
+        let mut types = self
+            .records_at(name)
+            .map(|key| key.record_type)
+            .filter(|record_type| *record_type != RecordType::NSEC3)
+            .collect::<BTreeSet<_>>();
+        if name == origin {
+            types.insert(RecordType::NSEC3PARAM);
+        }
+
+        if opt_out && types.len() == 1 && types.contains(&RecordType::NS) {
+            // unsigned delegation
+            return None;
+        }
+
+        if !types.is_empty() {
+            return Some(types.into_iter().chain([RecordType::RRSIG]).collect());
+        }
+
+        // An empty non-terminal needs a record if any name below it has one.
+        let mut descendants = self
+            .records
+            .range((
+                Bound::Excluded(RrKey::new(name.clone(), RecordType::Unknown(u16::MAX))),
+                Bound::Unbounded,
+            ))
+            .map(|(key, _)| &key.name)
+            .take_while(|descendant| name.zone_of(descendant))
+            .collect::<Vec<_>>();
+        descendants.dedup();
+
+        descendants
+            .into_iter()
+            .any(|descendant| {
+                self.nsec3_type_bit_maps(origin, descendant, opt_out)
+                    .is_some_and(|types| !types.is_empty())
+            })
+            .then(Vec::new)
+    }
+
+    /// Returns true if an ancestor of `name` below `origin` is a delegation point
+    #[cfg(feature = "__dnssec")]
+    fn is_below_delegation(&self, origin: &LowerName, name: &LowerName) -> bool {
+        let mut parent = name.base_name();
+        while parent.num_labels() > origin.num_labels() {
+            if self
+                .records
+                .contains_key(&RrKey::new(parent.clone(), RecordType::NS))
+            {
+                return true;
+            }
+            parent = parent.base_name();
+        }
+
+        false
+    }
+
+    /// All the keys of the records at `name`
+    #[cfg(feature = "__dnssec")]
+    fn records_at<'a>(&'a self, name: &LowerName) -> impl Iterator<Item = &'a RrKey> + 'a {
+        let start_range_key = RrKey::new(name.clone(), RecordType::Unknown(u16::MIN));
+        let end_range_key = RrKey::new(name.clone(), RecordType::Unknown(u16::MAX));
+
+        self.records
+            .range(start_range_key..end_range_key)
+            .map(|(key, _)| key)
+    }
+
+    /// The closest name before `name` that has records other than NSEC
+    #[cfg(feature = "__dnssec")]
+    fn previous_nsec_owner(&self, name: &LowerName) -> Option<LowerName> {
+        self.records
+            .range(..RrKey::new(name.clone(), RecordType::Unknown(u16::MIN)))
+            .rev()
+            .find(|(key, _)| key.record_type != RecordType::NSEC)
+            .map(|(key, _)| key.name.clone())
+    }
+
+    /// The closest name after `name` that has records other than NSEC
+    #[cfg(feature = "__dnssec")]
+    fn next_nsec_owner(&self, name: &LowerName) -> Option<LowerName> {
+        self.records
+            .range((
+                Bound::Excluded(RrKey::new(name.clone(), RecordType::Unknown(u16::MAX))),
+                Bound::Unbounded,
+            ))
+            .find(|(key, _)| key.record_type != RecordType::NSEC)
+            .map(|(key, _)| key.name.clone())
+    }
+
+    /// The owner of the NSEC3 record preceding `rr_key` in hash order, wrapping around to the last
+    /// one. `pending` holds the owners of records that are about to be inserted.
+    #[cfg(feature = "__dnssec")]
+    fn previous_nsec3_owner(
+        &self,
+        rr_key: &RrKey,
+        pending: &HashMap<LowerName, Vec<RecordType>>,
+    ) -> Option<LowerName> {
+        let is_nsec3 = |key: &&RrKey| key.record_type == RecordType::NSEC3;
+        let before = |owner: &&LowerName| *owner < &rr_key.name;
+
+        let existing = self.records.range(..rr_key).map(|(key, _)| key);
+        let previous = existing
+            .filter(is_nsec3)
+            .map(|key| &key.name)
+            .next_back()
+            .into_iter()
+            .chain(pending.keys().filter(before))
+            .max();
+
+        previous
+            .or_else(|| {
+                let all = self.records.keys().filter(is_nsec3).map(|key| &key.name);
+                all.chain(pending.keys()).max()
+            })
+            .filter(|previous| **previous != rr_key.name)
+            .cloned()
+    }
+
+    /// The owner of the NSEC3 record following `owner` in hash order, wrapping around to the first
+    /// one. `pending` holds the owners of records that are about to be inserted.
+    #[cfg(feature = "__dnssec")]
+    fn next_nsec3_owner(
+        &self,
+        owner: &LowerName,
+        pending: &HashMap<LowerName, Vec<RecordType>>,
+    ) -> LowerName {
+        let is_nsec3 = |key: &&RrKey| key.record_type == RecordType::NSEC3;
+        let rr_key = RrKey::new(owner.clone(), RecordType::NSEC3);
+
+        let existing = self
+            .records
+            .range((Bound::Excluded(&rr_key), Bound::Unbounded))
+            .map(|(key, _)| key);
+        let next = existing
+            .filter(is_nsec3)
+            .map(|key| &key.name)
+            .next()
+            .into_iter()
+            .chain(pending.keys().filter(|pending| *pending > owner))
+            .min();
+
+        next.or_else(|| {
+            let all = self.records.keys().filter(is_nsec3).map(|key| &key.name);
+            all.chain(pending.keys()).min()
+        })
+        .unwrap_or(owner)
+        .clone()
+    }
+
+    /// Removes the RRset at `rr_key`, and adds it to `replaced` unless it's already there
+    #[cfg(feature = "__dnssec")]
+    fn take_rrset(&mut self, rr_key: &RrKey, replaced: &mut Replaced) -> Option<Arc<RecordSet>> {
+        let rr_set = self.records.remove(rr_key);
+        replaced
+            .entry(rr_key.clone())
+            .or_insert_with(|| rr_set.clone());
+        rr_set
+    }
+
+    /// Puts back the RRsets replaced by `prepare_secure_zone` or `prepare_zone_update`
+    ///
+    /// `prepared` are the RRsets at the same keys right after they were replaced, RRsets which
+    /// changed in the zone since then are left alone.
+    #[cfg(feature = "__dnssec")]
+    pub(super) fn restore(&mut self, replaced: Replaced, prepared: &Replaced) {
+        for (rr_key, rr_set) in replaced {
+            let unchanged = match (
+                prepared.get(&rr_key).and_then(Option::as_ref),
+                self.records.get(&rr_key),
+            ) {
+                (Some(prepared), Some(current)) => Arc::ptr_eq(prepared, current),
+                (None, None) => true,
+                _ => false,
+            };
+            if !unchanged {
+                continue;
+            }
+
+            match rr_set {
+                Some(rr_set) => self.records.insert(rr_key, rr_set),
+                None => self.records.remove(&rr_key),
+            };
+        }
+    }
+
+    /// The current RRsets at the keys of `replaced`, see `restore`
+    #[cfg(feature = "__dnssec")]
+    pub(super) fn prepared(&self, replaced: &Replaced) -> Replaced {
+        replaced
+            .keys()
+            .map(|rr_key| (rr_key.clone(), self.records.get(rr_key).cloned()))
+            .collect()
+    }
+
+    /// The given RRsets of the zone, or all of them if `rr_keys` is `None`
+    ///
+    /// Only the `Arc`s are cloned, the result is meant to be signed with `sign_rrsets` and put
+    /// back with `apply_signed`.
+    #[cfg(feature = "__dnssec")]
+    pub(super) fn rrsets(
+        &self,
+        rr_keys: Option<&BTreeSet<RrKey>>,
+    ) -> BTreeMap<RrKey, Arc<RecordSet>> {
+        match rr_keys {
+            Some(rr_keys) => rr_keys
+                .iter()
+                .filter_map(|rr_key| {
+                    let rr_set = self.records.get(rr_key)?;
+                    Some((rr_key.clone(), rr_set.clone()))
+                })
+                .collect(),
+            None => self.records.clone(),
+        }
+    }
+
+    /// Signs the given RRsets of the zone
+    ///
+    /// The RRsets are sent to each key in a single batch, see `SigSigner::sign_batch`. This
+    /// doesn't need access to the zone, so that it isn't locked while a remote signer is busy.
+    #[cfg(feature = "__dnssec")]
+    pub(super) async fn sign_rrsets(
+        records: &mut BTreeMap<RrKey, Arc<RecordSet>>,
+        secure_keys: &[Arc<SigSigner>],
+        origin: &LowerName,
+        minimum_ttl: u32,
+        dns_class: DNSClass,
+    ) -> DnsSecResult<()> {
+        debug!("signing {} rrsets in zone: {}", records.len(), origin);
+        if secure_keys.is_empty() {
+            warn!(
+                "attempt to sign_zone {} for dnssec, but no keys available!",
+                origin
+            )
+        }
+
+        for rr_set in records.values_mut() {
+            // because the rrset is an Arc, it must be cloned before mutated
+            Arc::make_mut(rr_set).clear_rrsigs();
+        }
+
+        let inception = OffsetDateTime::now_utc();
+        for signer in secure_keys.iter() {
+            let expiration = inception + signer.sig_duration();
+
+            let tbs = records
+                .values()
+                .map(|rr_set| TBS::from_rrset(rr_set, dns_class, inception, expiration, signer))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            debug!(
+                "signing {} rrsets with: {}",
+                tbs.len(),
+                signer.key().algorithm()
+            );
+            let signatures = signer.sign_batch(&tbs).await.map_err(|err| {
+                error!("could not sign rrsets: {}", err);
+                err
+            })?;
+
+            for (rr_set, signature) in records.values_mut().zip(signatures) {
+                let rr_set = Arc::make_mut(rr_set);
+                let rrsig = Self::rrsig(
+                    rr_set,
+                    signer,
+                    minimum_ttl,
+                    inception,
+                    expiration,
+                    signature,
+                )?;
+                rr_set.insert_rrsig(rrsig);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Puts the RRsets signed with `sign_rrsets` back into the zone
+    ///
+    /// `base` are the RRsets as they were taken from the zone with `rrsets`, and `signed` the same
+    /// RRsets after they were signed. RRsets which changed in the zone since they were taken are
+    /// left alone, their keys are returned so that they can be signed again. RRsets which were
+    /// removed in the meantime are dropped.
+    #[cfg(feature = "__dnssec")]
+    pub(super) fn apply_signed(
+        &mut self,
+        base: &BTreeMap<RrKey, Arc<RecordSet>>,
+        signed: BTreeMap<RrKey, Arc<RecordSet>>,
+    ) -> BTreeSet<RrKey> {
+        let mut conflicts = BTreeSet::new();
+        for (rr_key, signed_rr_set) in signed {
+            let Some(rr_set) = self.records.get_mut(&rr_key) else {
+                continue;
+            };
+
+            match base.get(&rr_key) {
+                Some(base_rr_set) if Arc::ptr_eq(base_rr_set, rr_set) => *rr_set = signed_rr_set,
+                _ => {
+                    conflicts.insert(rr_key);
+                }
+            }
+        }
+
+        conflicts
     }
 
     #[cfg(feature = "__dnssec")]
@@ -583,7 +1170,9 @@ impl InnerInMemory {
         let delete_keys = self
             .records
             .keys()
-            .filter(|k| k.record_type == RecordType::NSEC3)
+            .filter(|k| {
+                k.record_type == RecordType::NSEC3 || k.record_type == RecordType::NSEC3PARAM
+            })
             .cloned()
             .collect::<Vec<_>>();
 
@@ -715,7 +1304,7 @@ impl InnerInMemory {
     #[cfg(feature = "__dnssec")]
     pub(super) fn sign_rrset(
         rr_set: &mut RecordSet,
-        secure_keys: &[SigSigner],
+        secure_keys: &[Arc<SigSigner>],
         zone_ttl: u32,
         zone_class: DNSClass,
     ) -> DnsSecResult<()> {
@@ -723,8 +1312,6 @@ impl InnerInMemory {
 
         rr_set.clear_rrsigs();
 
-        let rrsig_temp = Record::update0(rr_set.name().clone(), zone_ttl, RecordType::RRSIG);
-
         for signer in secure_keys {
             debug!(
                 "signing rr_set: {}, {} with: {}",
@@ -754,34 +1341,48 @@ impl InnerInMemory {
                 }
             };
 
-            let mut rrsig = rrsig_temp.clone();
-            rrsig.set_data(RData::DNSSEC(DNSSECRData::RRSIG(RRSIG::new(
-                // type_covered: RecordType,
-                rr_set.record_type(),
-                // algorithm: Algorithm,
-                signer.key().algorithm(),
-                // num_labels: u8,
-                rr_set.name().num_labels(),
-                // original_ttl: u32,
-                rr_set.ttl(),
-                // sig_expiration: u32,
-                expiration.unix_timestamp() as u32,
-                // sig_inception: u32,
-                inception.unix_timestamp() as u32,
-                // key_tag: u16,
-                signer.calculate_key_tag()?,
-                // signer_name: Name,
-                signer.signer_name().clone(),
-                // sig: Vec<u8>
-                signature,
-            ))));
-
+            let rrsig = Self::rrsig(rr_set, signer, zone_ttl, inception, expiration, signature)?;
             rr_set.insert_rrsig(rrsig);
         }
 
         Ok(())
     }
 
+    /// Builds the RRSIG record covering `rr_set` from a signature made by `signer`
+    #[cfg(feature = "__dnssec")]
+    fn rrsig(
+        rr_set: &RecordSet,
+        signer: &SigSigner,
+        zone_ttl: u32,
+        inception: OffsetDateTime,
+        expiration: OffsetDateTime,
+        signature: Vec<u8>,
+    ) -> DnsSecResult<Record> {
+        let mut rrsig = Record::update0(rr_set.name().clone(), zone_ttl, RecordType::RRSIG);
+        rrsig.set_data(RData::DNSSEC(DNSSECRData::RRSIG(RRSIG::new(
+            // type_covered: RecordType,
+            rr_set.record_type(),
+            // algorithm: Algorithm,
+            signer.key().algorithm(),
+            // num_labels: u8,
+            rr_set.name().num_labels(),
+            // original_ttl: u32,
+            rr_set.ttl(),
+            // sig_expiration: u32,
+            expiration.unix_timestamp() as u32,
+            // sig_inception: u32,
+            inception.unix_timestamp() as u32,
+            // key_tag: u16,
+            signer.calculate_key_tag()?,
+            // signer_name: Name,
+            signer.signer_name().clone(),
+            // sig: Vec<u8>
+            signature,
+        ))));
+
+        Ok(rrsig)
+    }
+
     /// Signs all records in the zone.
     #[cfg(feature = "__dnssec")]
     fn sign_zone(&mut self, origin: &LowerName, dns_class: DNSClass) -> DnsSecResult<()> {
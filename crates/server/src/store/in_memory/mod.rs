@@ -7,10 +7,14 @@
 
 //! Zone file based serving with Dynamic DNS and journaling support
 
+#[cfg(feature = "__dnssec")]
+use std::collections::BTreeSet;
 #[cfg(all(feature = "__dnssec", feature = "testing"))]
 use std::ops::Deref;
 use std::{collections::BTreeMap, ops::DerefMut, sync::Arc};
 
+#[cfg(feature = "__dnssec")]
+use tokio::sync::Mutex;
 use tokio::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
 use tracing::debug;
 #[cfg(feature = "__dnssec")]
@@ -39,6 +43,8 @@ use crate::{
 
 mod inner;
 use inner::InnerInMemory;
+#[cfg(feature = "__dnssec")]
+use inner::Replaced;
 
 /// InMemoryAuthority is responsible for storing the resource records for a particular zone.
 ///
@@ -52,6 +58,9 @@ pub struct InMemoryAuthority {
     inner: RwLock<InnerInMemory>,
     #[cfg(feature = "__dnssec")]
     nx_proof_kind: Option<NxProofKind>,
+    /// Held while the zone is signed, so that concurrent updates are signed one after the other
+    #[cfg(feature = "__dnssec")]
+    signing: Mutex<()>,
 }
 
 impl InMemoryAuthority {
@@ -134,6 +143,8 @@ impl InMemoryAuthority {
 
             #[cfg(feature = "__dnssec")]
             nx_proof_kind,
+            #[cfg(feature = "__dnssec")]
+            signing: Mutex::new(()),
         }
     }
 
@@ -155,7 +166,7 @@ impl InMemoryAuthority {
 
     /// Retrieve the Signer, which contains the private keys, for this zone
     #[cfg(all(feature = "__dnssec", feature = "testing"))]
-    pub async fn secure_keys(&self) -> impl Deref<Target = [SigSigner]> + '_ {
+    pub async fn secure_keys(&self) -> impl Deref<Target = [Arc<SigSigner>]> + '_ {
         RwLockWriteGuard::map(self.inner.write().await, |i| i.secure_keys.as_mut_slice())
     }
 
@@ -276,7 +287,7 @@ impl InMemoryAuthority {
         // TODO: also generate the CDS and CDNSKEY
         let serial = inner.serial(origin);
         inner.upsert(dnskey, serial, dns_class);
-        inner.secure_keys.push(signer);
+        inner.secure_keys.push(Arc::new(signer));
         Ok(())
     }
 
@@ -302,6 +313,101 @@ impl InMemoryAuthority {
             .secure_zone_mut(origin, self.class, self.nx_proof_kind.as_ref())
     }
 
+    /// Updates the nsec records around the changed RRsets, increments the serial number and
+    /// re-signs only the affected RRsets
+    ///
+    /// This is meant to be used after a dynamic update on a zone that was already secured with
+    /// `secure_zone`, so that the whole zone doesn't need to be signed again.
+    ///
+    /// # Arguments
+    ///
+    /// * `changed` - The keys of all the RRsets that were added, modified or removed.
+    #[cfg(feature = "__dnssec")]
+    pub async fn secure_zone_update(&self, changed: &BTreeSet<RrKey>) -> DnsSecResult<()> {
+        self.sign_copy(Some(changed)).await
+    }
+
+    /// Updates the nsec records and the serial number in place, then signs the affected RRsets
+    ///
+    /// The zone isn't locked while the keys sign the RRsets, which may involve a remote signer.
+    /// The signed RRsets are swapped in afterwards, and those that changed in the zone in the
+    /// meantime are signed again. If signing fails, the nsec records and the serial number are
+    /// put back as they were.
+    #[cfg(feature = "__dnssec")]
+    async fn sign_copy(&self, changed: Option<&BTreeSet<RrKey>>) -> DnsSecResult<()> {
+        let _signing = self.signing.lock().await;
+        let nx_proof_kind = self.nx_proof_kind.as_ref();
+
+        let mut replaced = Replaced::new();
+        let (rr_keys, prepared) = {
+            let mut inner = self.inner.write().await;
+            let rr_keys = match changed {
+                Some(changed) => inner.prepare_zone_update(
+                    self.origin(),
+                    self.class,
+                    nx_proof_kind,
+                    changed,
+                    &mut replaced,
+                ),
+                None => inner
+                    .prepare_secure_zone(self.origin(), self.class, nx_proof_kind, &mut replaced)
+                    .map(|()| None),
+            };
+
+            let prepared = inner.prepared(&replaced);
+            match rr_keys {
+                Ok(rr_keys) => (rr_keys, prepared),
+                Err(e) => {
+                    inner.restore(replaced, &prepared);
+                    return Err(e);
+                }
+            }
+        };
+
+        let result = self.sign_rrsets(rr_keys).await;
+        if result.is_err() {
+            self.inner.write().await.restore(replaced, &prepared);
+        }
+        result
+    }
+
+    /// Signs the given RRsets, or all of them, and swaps them into the zone
+    #[cfg(feature = "__dnssec")]
+    async fn sign_rrsets(&self, mut rr_keys: Option<BTreeSet<RrKey>>) -> DnsSecResult<()> {
+        loop {
+            let (base, secure_keys, minimum_ttl) = {
+                let inner = self.inner.read().await;
+                (
+                    inner.rrsets(rr_keys.as_ref()),
+                    inner.secure_keys.clone(),
+                    inner.minimum_ttl(self.origin()),
+                )
+            };
+
+            let mut signed = base.clone();
+            InnerInMemory::sign_rrsets(
+                &mut signed,
+                &secure_keys,
+                self.origin(),
+                minimum_ttl,
+                self.class,
+            )
+            .await?;
+
+            let conflicts = self.inner.write().await.apply_signed(&base, signed);
+            if conflicts.is_empty() {
+                return Ok(());
+            }
+
+            debug!(
+                "{} rrsets changed while signing, signing them again: {}",
+                conflicts.len(),
+                self.origin
+            );
+            rr_keys = Some(conflicts);
+        }
+    }
+
     /// (Re)generates the nsec records, increments the serial number and signs the zone
     #[cfg(not(feature = "__dnssec"))]
     pub fn secure_zone_mut(&mut self) -> Result<(), &str> {
@@ -431,10 +537,15 @@ impl Authority for InMemoryAuthority {
                     // perform the lookup
                     let answer = inner.inner_lookup(name, query_type, lookup_options);
 
-                    // evaluate any cnames for additional inclusion
+                    // evaluate any cnames for additional inclusion, the records of a CNAME or
+                    // ANAME chain are part of the answer and always added
                     let additionals_root_chain_type: Option<(_, _)> = answer
                         .as_ref()
                         .and_then(|a| maybe_next_name(a, query_type))
+                        .filter(|(_, search_type)| {
+                            !lookup_options.omit_additionals()
+                                || matches!(search_type, RecordType::CNAME | RecordType::ANAME)
+                        })
                         .and_then(|(search_name, search_type)| {
                             inner
                                 .additional_search(
@@ -752,9 +863,7 @@ impl DnssecAuthority for InMemoryAuthority {
 
     /// Sign the zone for DNSSEC
     async fn secure_zone(&self) -> DnsSecResult<()> {
-        let mut inner = self.inner.write().await;
-
-        inner.secure_zone_mut(self.origin(), self.class, self.nx_proof_kind.as_ref())
+        self.sign_copy(None).await
     }
 }
 
@@ -9,8 +9,6 @@
 
 //! Recursive resolver related types
 
-#[cfg(feature = "__dnssec")]
-use std::sync::Arc;
 use std::{
     borrow::Cow,
     collections::HashSet,
@@ -20,13 +18,21 @@ use std::{
     path::{Path, PathBuf},
     time::Instant,
 };
+#[cfg(feature = "__dnssec")]
+use std::{sync::Arc, time::Duration};
 
 use ipnet::IpNet;
 use serde::Deserialize;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
+#[cfg(feature = "dnstap")]
+use crate::proto::dnstap::Dnstap;
 #[cfg(feature = "__dnssec")]
-use crate::{authority::Nsec3QueryInfo, dnssec::NxProofKind, proto::dnssec::TrustAnchors};
+use crate::{
+    authority::Nsec3QueryInfo,
+    dnssec::NxProofKind,
+    proto::dnssec::{NegativeTrustAnchor, TrustAnchors},
+};
 use crate::{
     authority::{
         Authority, LookupControlFlow, LookupError, LookupObject, LookupOptions, MessageRequest,
@@ -54,6 +60,7 @@ use crate::{
 pub struct RecursiveAuthority {
     origin: LowerName,
     recursor: Recursor,
+    cache_file: Option<PathBuf>,
 }
 
 impl RecursiveAuthority {
@@ -79,6 +86,7 @@ impl RecursiveAuthority {
                 protocol: Protocol::Tcp,
                 tls_dns_name: None,
                 http_endpoint: None,
+                tls_pins: Vec::new(),
                 trust_negative_responses: false,
                 bind_addr: None, // TODO: need to support bind addresses
             });
@@ -88,6 +96,7 @@ impl RecursiveAuthority {
                 protocol: Protocol::Udp,
                 tls_dns_name: None,
                 http_endpoint: None,
+                tls_pins: Vec::new(),
                 trust_negative_responses: false,
                 bind_addr: None,
             });
@@ -101,6 +110,21 @@ impl RecursiveAuthority {
             builder = builder.record_cache_size(record_cache_size);
         }
 
+        #[cfg(feature = "__dnssec")]
+        {
+            builder = builder.negative_trust_anchors(
+                config
+                    .negative_trust_anchors
+                    .iter()
+                    .map(NegativeTrustAnchorConfig::load),
+            );
+        }
+
+        #[cfg(feature = "dnstap")]
+        if let Some(dnstap) = &config.dnstap {
+            builder = builder.dnstap(dnstap.clone());
+        }
+
         let recursor = builder
             .dnssec_policy(config.dnssec_policy.load().map_err(|e| e.to_string())?)
             .nameserver_filter(config.allow_server.iter(), config.deny_server.iter())
@@ -118,9 +142,26 @@ impl RecursiveAuthority {
             .build(roots)
             .map_err(|e| format!("failed to initialize recursor: {e}"))?;
 
+        let cache_file = config.cache_file.as_ref().map(|path| match root_dir {
+            Some(root_dir) => root_dir.join(path),
+            None => path.clone(),
+        });
+        if let Some(path) = &cache_file {
+            match recursor.load_cache(path, config.preserve_dnssec_proofs) {
+                Ok(restored) => {
+                    info!(%origin, "restored {restored} cache entries from {}", path.display())
+                }
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                    info!(%origin, "cache file {} does not exist yet", path.display())
+                }
+                Err(e) => warn!(%origin, "failed to restore cache from {}: {e}", path.display()),
+            }
+        }
+
         Ok(Self {
             origin: origin.into(),
             recursor,
+            cache_file,
         })
     }
 }
@@ -220,6 +261,23 @@ impl Authority for RecursiveAuthority {
     fn nx_proof_kind(&self) -> Option<&NxProofKind> {
         None
     }
+
+    fn clear_cache(&self, name: Option<&Name>) -> bool {
+        match name {
+            Some(name) => self.recursor.clear_cache_subdomains(name),
+            None => self.recursor.clear_cache(),
+        }
+        true
+    }
+
+    fn cached_records(&self) -> Option<Vec<Record>> {
+        Some(self.recursor.cached_records())
+    }
+
+    fn save_cache(&self) -> Option<io::Result<usize>> {
+        let path = self.cache_file.as_ref()?;
+        Some(self.recursor.save_cache(path))
+    }
 }
 
 /// A Lookup object for the recursive resolver
@@ -289,6 +347,28 @@ pub struct RecursiveConfig {
     /// [draft-vixie-dnsext-dns0x20-00](https://datatracker.ietf.org/doc/html/draft-vixie-dnsext-dns0x20-00).
     #[serde(default)]
     pub case_randomization: bool,
+
+    /// File the record and name server caches are restored from at startup, and saved to when the
+    /// server stops
+    ///
+    /// Relative paths are relative to the zone directory.
+    pub cache_file: Option<PathBuf>,
+
+    /// Restore the DNSSEC validation state of the records from the cache file
+    ///
+    /// Otherwise restored records are validated again when they are used.
+    #[serde(default)]
+    pub preserve_dnssec_proofs: bool,
+
+    /// Negative trust anchors, domains for which DNSSEC validation is skipped
+    #[cfg(feature = "__dnssec")]
+    #[serde(default)]
+    pub negative_trust_anchors: Vec<NegativeTrustAnchorConfig>,
+
+    /// Log the queries sent to authoritative servers and their responses to dnstap
+    #[cfg(feature = "dnstap")]
+    #[serde(skip)]
+    pub dnstap: Option<Dnstap>,
 }
 
 impl RecursiveConfig {
@@ -367,6 +447,27 @@ impl DnssecPolicyConfig {
     }
 }
 
+/// A negative trust anchor, see [RFC 7646](https://datatracker.ietf.org/doc/html/rfc7646)
+///
+/// DNSSEC validation is skipped for `name` and every name below it, their records are treated as
+/// insecure.
+#[cfg(feature = "__dnssec")]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct NegativeTrustAnchorConfig {
+    /// Domain for which validation is skipped
+    pub name: Name,
+    /// Lifetime in seconds, counted from startup; unset for a permanent anchor
+    pub lifetime: Option<u64>,
+}
+
+#[cfg(feature = "__dnssec")]
+impl NegativeTrustAnchorConfig {
+    fn load(&self) -> NegativeTrustAnchor {
+        NegativeTrustAnchor::new(self.name.clone(), self.lifetime.map(Duration::from_secs))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     #[cfg(all(feature = "__dnssec", feature = "toml"))]
@@ -387,6 +488,37 @@ dnssec_policy.ValidateWithStaticKey.path = "/etc/trusted-key.key""#;
         }
     }
 
+    #[cfg(all(feature = "__dnssec", feature = "toml"))]
+    #[test]
+    fn can_parse_negative_trust_anchors() {
+        let input = r#"roots = "/etc/root.hints"
+dnssec_policy.ValidateWithStaticKey.path = "/etc/trusted-key.key"
+
+[[negative_trust_anchors]]
+name = "example.com."
+lifetime = 3600
+
+[[negative_trust_anchors]]
+name = "example.net.""#;
+
+        let config: RecursiveConfig = toml::from_str(input).unwrap();
+
+        assert_eq!(
+            config
+                .negative_trust_anchors
+                .iter()
+                .map(NegativeTrustAnchorConfig::load)
+                .collect::<Vec<_>>(),
+            [
+                NegativeTrustAnchor::new(
+                    Name::from_ascii("example.com.").unwrap(),
+                    Some(Duration::from_secs(3600))
+                ),
+                NegativeTrustAnchor::new(Name::from_ascii("example.net.").unwrap(), None),
+            ]
+        );
+    }
+
     #[cfg(all(feature = "recursor", feature = "toml"))]
     #[test]
     fn can_parse_recursor_cache_policy() {
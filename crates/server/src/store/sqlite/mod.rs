@@ -8,6 +8,7 @@
 //! SQLite serving with Dynamic DNS and journaling support
 
 use std::{
+    collections::BTreeSet,
     ops::{Deref, DerefMut},
     path::{Path, PathBuf},
     sync::Arc,
@@ -680,6 +681,8 @@ impl SqliteAuthority {
         auto_signing_and_increment: bool,
     ) -> UpdateResult<bool> {
         let mut updated = false;
+        // the RRsets that were touched by this update, these are the only ones that need resigning
+        let mut changed = BTreeSet::new();
         let serial: u32 = self.in_memory.serial().await;
 
         // the persistence act as a write-ahead log. The WAL will also be used for recovery of a zone
@@ -749,7 +752,10 @@ impl SqliteAuthority {
 
                     // zone     rrset    rr       Add to an RRset
                     info!("upserting record: {:?}", rr);
-                    updated = self.in_memory.upsert(rr.clone(), serial).await || updated;
+                    if self.in_memory.upsert(rr.clone(), serial).await {
+                        changed.insert(rr_key);
+                        updated = true;
+                    }
                 }
                 DNSClass::ANY => {
                     // This is a delete of entire RRSETs, either many or one. In either case, the spec is clear:
@@ -787,6 +793,7 @@ impl SqliteAuthority {
 
                             for delete in to_delete {
                                 self.in_memory.records_mut().await.remove(&delete);
+                                changed.insert(delete);
                                 updated = true;
                             }
                         }
@@ -800,7 +807,10 @@ impl SqliteAuthority {
                             if let RData::Update0(_) | RData::NULL(..) = rr.data() {
                                 let deleted = self.in_memory.records_mut().await.remove(&rr_key);
                                 info!("deleted rrset: {:?}", deleted);
-                                updated = updated || deleted.is_some();
+                                if deleted.is_some() {
+                                    changed.insert(rr_key);
+                                    updated = true;
+                                }
                             } else {
                                 info!("expected empty rdata: {:?}", rr);
                                 return Err(ResponseCode::FormErr);
@@ -820,6 +830,7 @@ impl SqliteAuthority {
 
                         if deleted {
                             *rrset = Arc::new(rrset_clone);
+                            changed.insert(rr_key);
                         }
                     }
                 }
@@ -835,7 +846,7 @@ impl SqliteAuthority {
             if self.is_dnssec_enabled {
                 cfg_if::cfg_if! {
                     if #[cfg(feature = "__dnssec")] {
-                        self.secure_zone().await.map_err(|e| {
+                        self.in_memory.secure_zone_update(&changed).await.map_err(|e| {
                             error!("failure securing zone: {}", e);
                             ResponseCode::ServFail
                         })?
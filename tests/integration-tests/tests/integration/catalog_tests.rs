@@ -9,7 +9,7 @@ use hickory_proto::{
 #[cfg(feature = "__dnssec")]
 use hickory_server::dnssec::NxProofKind;
 use hickory_server::{
-    authority::{Authority, Catalog, MessageRequest, ZoneType},
+    authority::{Authority, Catalog, MessageRequest, MinimalResponses, ZoneType},
     server::Request,
     store::in_memory::InMemoryAuthority,
 };
@@ -355,6 +355,93 @@ async fn test_non_authoritive_nx_refused() {
     assert_eq!(result.additionals().len(), 0);
 }
 
+async fn query_from(catalog: &Catalog, name: &Name, src: [u8; 4]) -> Message {
+    let mut question = Message::new();
+    question
+        .add_query(Query::query(name.clone(), RecordType::A))
+        .set_recursion_desired(true);
+
+    let question_bytes = question.to_bytes().unwrap();
+    let question_req = MessageRequest::from_bytes(&question_bytes).unwrap();
+    let question_req = Request::new(question_req, (src, 5553).into(), Protocol::Udp);
+
+    let response_handler = TestResponseHandler::new();
+    catalog
+        .lookup(&question_req, None, response_handler.clone())
+        .await;
+    response_handler.into_message().await
+}
+
+#[tokio::test]
+async fn test_query_access() {
+    subscribe();
+
+    let example = create_example();
+    let origin = example.origin().clone();
+    let test = create_test();
+    let test_origin = test.origin().clone();
+
+    let mut catalog = Catalog::new();
+    catalog.upsert(origin.clone(), vec![Arc::new(example)]);
+    catalog.upsert(test_origin.clone(), vec![Arc::new(test)]);
+    catalog.set_query_access(origin.clone(), &[], &["10.0.0.0/8".parse().unwrap()]);
+
+    let www = Name::parse("www.example.com.", None).unwrap();
+    let result = query_from(&catalog, &www, [10, 1, 2, 3]).await;
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+    assert_eq!(result.answers().len(), 1);
+
+    let result = query_from(&catalog, &www, [192, 0, 2, 1]).await;
+    assert_eq!(result.response_code(), ResponseCode::Refused);
+    assert!(result.answers().is_empty());
+
+    // other zones are not affected
+    let result = query_from(&catalog, &test_origin.into(), [192, 0, 2, 1]).await;
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+    assert_eq!(result.answers().len(), 1);
+}
+
+#[tokio::test]
+async fn test_recursion_access() {
+    subscribe();
+
+    let example = create_example();
+    let origin = example.origin().clone();
+
+    // stands in for a forwarder, answering from its records
+    let test_origin = Name::parse("test.com.", None).unwrap();
+    let mut forwarder = InMemoryAuthority::empty(
+        test_origin.clone(),
+        ZoneType::External,
+        false,
+        #[cfg(feature = "__dnssec")]
+        None,
+    );
+    create_records(&mut forwarder);
+
+    let mut catalog = Catalog::new();
+    catalog.upsert(origin.clone(), vec![Arc::new(example)]);
+    catalog.upsert(test_origin.clone().into(), vec![Arc::new(forwarder)]);
+    catalog.set_recursion_access(&[], &["10.0.0.0/8".parse().unwrap()]);
+
+    let result = query_from(&catalog, &test_origin, [10, 1, 2, 3]).await;
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+    assert!(result.header().recursion_available());
+    assert_eq!(result.answers().len(), 1);
+
+    let result = query_from(&catalog, &test_origin, [192, 0, 2, 1]).await;
+    assert_eq!(result.response_code(), ResponseCode::Refused);
+    assert!(!result.header().recursion_available());
+    assert!(result.answers().is_empty());
+
+    // authoritative zones are still answered
+    let www = Name::parse("www.example.com.", None).unwrap();
+    let result = query_from(&catalog, &www, [192, 0, 2, 1]).await;
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+    assert!(result.header().authoritative());
+    assert_eq!(result.answers().len(), 1);
+}
+
 #[tokio::test]
 #[allow(clippy::unreadable_literal)]
 async fn test_axfr() {
@@ -581,6 +668,70 @@ async fn test_cname_additionals() {
     );
 }
 
+async fn query_type(catalog: &Catalog, name: &str, query_type: RecordType) -> Message {
+    let mut question = Message::new();
+    question.add_query(Query::query(Name::from_str(name).unwrap(), query_type));
+
+    let question_bytes = question.to_bytes().unwrap();
+    let question_req = MessageRequest::from_bytes(&question_bytes).unwrap();
+    let question_req = Request::new(question_req, ([127, 0, 0, 1], 5553).into(), Protocol::Udp);
+
+    let response_handler = TestResponseHandler::new();
+    catalog
+        .lookup(&question_req, None, response_handler.clone())
+        .await;
+    response_handler.into_message().await
+}
+
+#[tokio::test]
+async fn test_minimal_responses() {
+    subscribe();
+
+    let mut example = create_example();
+    let origin = example.origin().clone();
+    example.upsert_mut(
+        Record::from_rdata(
+            origin.clone().into(),
+            86400,
+            RData::MX(MX::new(10, Name::from_str("www.example.com.").unwrap())),
+        )
+        .set_dns_class(DNSClass::IN)
+        .clone(),
+        0,
+    );
+
+    let mut catalog = Catalog::new();
+    catalog.upsert(origin.clone(), vec![Arc::new(example)]);
+    catalog.set_minimal_responses(MinimalResponses::Yes);
+
+    // neither NS records nor additionals
+    let result = query_type(&catalog, "example.com.", RecordType::SOA).await;
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+    assert_eq!(result.answers().len(), 1);
+    assert!(result.name_servers().is_empty());
+
+    let result = query_type(&catalog, "example.com.", RecordType::MX).await;
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+    assert_eq!(result.answers().len(), 1);
+    assert!(result.additionals().is_empty());
+
+    // the records of a CNAME chain are part of the answer
+    let result = query_type(&catalog, "alias.example.com.", RecordType::A).await;
+    assert_eq!(result.response_code(), ResponseCode::NoError);
+    assert_eq!(result.answers().len(), 1);
+    assert_eq!(result.additionals().len(), 1);
+    assert_eq!(result.additionals()[0].record_type(), RecordType::A);
+
+    // the zone overrides the default of the catalog
+    catalog.set_zone_minimal_responses(origin, MinimalResponses::NoAuth);
+
+    let result = query_type(&catalog, "example.com.", RecordType::SOA).await;
+    assert!(result.name_servers().is_empty());
+
+    let result = query_type(&catalog, "example.com.", RecordType::MX).await;
+    assert!(!result.additionals().is_empty());
+}
+
 #[tokio::test]
 async fn test_multiple_cname_additionals() {
     subscribe();
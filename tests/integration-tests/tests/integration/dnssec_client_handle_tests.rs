@@ -7,7 +7,9 @@ use std::sync::{Arc, Mutex as StdMutex};
 use futures::executor::block_on;
 
 use hickory_client::client::{Client, ClientHandle, MemoizeClientHandle};
-use hickory_proto::dnssec::{DnssecDnsHandle, TrustAnchors};
+use hickory_proto::dnssec::{
+    DnssecDnsHandle, NegativeTrustAnchors, Proof, TrustAnchors, ValidationEvent, ValidationTrace,
+};
 use hickory_proto::op::ResponseCode;
 use hickory_proto::rr::Name;
 use hickory_proto::rr::{DNSClass, RecordType};
@@ -95,6 +97,109 @@ where
     assert_eq!(response.response_code(), ResponseCode::NXDomain);
 }
 
+#[tokio::test]
+async fn test_validation_trace_nonet() {
+    subscribe();
+    let client = nonet_client().await;
+
+    let trace = ValidationTrace::new();
+    let mut client = client.with_validation_trace(trace.clone());
+    let name = Name::from_str("www.example.com.").unwrap();
+    client
+        .query(name.clone(), DNSClass::IN, RecordType::A)
+        .await
+        .expect("query failed");
+
+    let events = trace.events();
+    assert!(events.iter().any(|event| matches!(
+        event,
+        ValidationEvent::TrustAnchor { name, .. } if name == &Name::from_str("example.com.").unwrap()
+    )));
+    assert!(events.iter().any(|event| matches!(
+        event,
+        ValidationEvent::Rrsig { name: rrsig_name, record_type: RecordType::A, result: Ok(Proof::Secure), .. }
+            if rrsig_name == &name
+    )));
+    assert!(events.iter().any(|event| matches!(
+        event,
+        ValidationEvent::Rrset { name: rrset_name, record_type: RecordType::A, result: Ok(Proof::Secure) }
+            if rrset_name == &name
+    )));
+    assert!(
+        trace
+            .to_string()
+            .contains("RRset www.example.com. A: Secure")
+    );
+
+    let trace = ValidationTrace::new();
+    let mut client = client.with_validation_trace(trace.clone());
+    let name = Name::from_str("none.example.com.").unwrap();
+    let response = client
+        .query(name.clone(), DNSClass::IN, RecordType::A)
+        .await
+        .expect("query failed");
+    assert_eq!(response.response_code(), ResponseCode::NXDomain);
+
+    assert!(trace.events().iter().any(|event| matches!(
+        event,
+        ValidationEvent::Nsec { query, proof: Proof::Secure } if query.name() == &name
+    )));
+}
+
+#[tokio::test]
+async fn test_negative_trust_anchor_nonet() {
+    subscribe();
+
+    let negative_trust_anchors = NegativeTrustAnchors::new();
+    let trace = ValidationTrace::new();
+    let client = nonet_client()
+        .await
+        .with_negative_trust_anchors(negative_trust_anchors.clone())
+        .with_validation_trace(trace.clone());
+
+    let name = Name::from_str("www.example.com.").unwrap();
+    assert_eq!(a_proofs(&client, &name).await, [Proof::Secure]);
+
+    // the zone validates, but the negative trust anchor skips validation altogether
+    negative_trust_anchors.insert(Name::from_str("example.com.").unwrap(), None);
+    assert_eq!(a_proofs(&client, &name).await, [Proof::Insecure]);
+    assert!(trace.events().iter().any(|event| matches!(
+        event,
+        ValidationEvent::NegativeTrustAnchor { name: rrset_name, record_type: RecordType::A, anchor }
+            if rrset_name == &name && anchor == &Name::from_str("example.com.").unwrap()
+    )));
+
+    let response = client
+        .clone()
+        .query(
+            Name::from_str("none.example.com.").unwrap(),
+            DNSClass::IN,
+            RecordType::A,
+        )
+        .await
+        .expect("query failed");
+    assert_eq!(response.response_code(), ResponseCode::NXDomain);
+
+    assert!(negative_trust_anchors.remove(&Name::from_str("example.com.").unwrap()));
+    assert_eq!(a_proofs(&client, &name).await, [Proof::Secure]);
+}
+
+async fn a_proofs<H>(client: &DnssecDnsHandle<H>, name: &Name) -> Vec<Proof>
+where
+    H: ClientHandle + Sync + 'static,
+{
+    client
+        .clone()
+        .query(name.clone(), DNSClass::IN, RecordType::A)
+        .await
+        .expect("query failed")
+        .answers()
+        .iter()
+        .filter(|record| record.record_type() == RecordType::A)
+        .map(|record| record.proof())
+        .collect()
+}
+
 // TODO: NSEC response code wrong in Hickory DNS? Issue #53
 // #[test]
 // fn test_nsec_query_type_nonet() {
@@ -214,6 +319,14 @@ where
         })
         .unwrap();
 
+    let secure_client = nonet_client().await;
+
+    test(secure_client);
+    succeeded.store(true, std::sync::atomic::Ordering::Relaxed);
+    join.join().unwrap();
+}
+
+async fn nonet_client() -> DnssecDnsHandle<MemoizeClientHandle<Client>> {
     let authority = create_secure_example();
 
     let trust_anchor = {
@@ -241,11 +354,7 @@ where
 
     tokio::spawn(bg);
     let client = MemoizeClientHandle::new(client);
-    let secure_client = DnssecDnsHandle::with_trust_anchor(client, trust_anchor);
-
-    test(secure_client);
-    succeeded.store(true, std::sync::atomic::Ordering::Relaxed);
-    join.join().unwrap();
+    DnssecDnsHandle::with_trust_anchor(client, trust_anchor)
 }
 
 async fn with_udp<F, Fut>(test: F)
@@ -0,0 +1,276 @@
+use std::io::{self, Write};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::net::UdpSocket;
+
+use hickory_integration::example_authority::create_example;
+use hickory_integration::mock_client::*;
+use hickory_proto::dnstap::Dnstap;
+use hickory_proto::op::{Message, Query, ResponseCode};
+use hickory_proto::rr::{Name, RecordType};
+use hickory_proto::xfer::{DnsHandle, DnsResponse, FirstAnswer, Protocol};
+use hickory_resolver::config::{NameServerConfig, ResolverOpts};
+use hickory_resolver::name_server::NameServer;
+use hickory_server::ServerFuture;
+use hickory_server::authority::{Authority, Catalog};
+use test_support::subscribe;
+
+// dnstap message types
+const RESOLVER_QUERY: u64 = 3;
+const RESOLVER_RESPONSE: u64 = 4;
+const CLIENT_QUERY: u64 = 5;
+const CLIENT_RESPONSE: u64 = 6;
+
+#[tokio::test]
+async fn test_server_dnstap() {
+    subscribe();
+
+    let output = SharedOutput::default();
+    let udp_socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let server_addr = udp_socket.local_addr().unwrap();
+
+    let example = create_example();
+    let mut catalog = Catalog::new();
+    catalog.upsert(example.origin().clone(), vec![Arc::new(example)]);
+
+    let mut server = ServerFuture::new(catalog);
+    server.set_dnstap(Dnstap::builder().to_writer(output.clone()).unwrap());
+    server.register_socket(udp_socket);
+
+    let client_socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let client_addr = client_socket.local_addr().unwrap();
+    let mut message = Message::new();
+    message.set_id(42).add_query(Query::query(
+        Name::from_str("www.example.com.").unwrap(),
+        RecordType::A,
+    ));
+    let query = message.to_vec().unwrap();
+    client_socket.send_to(&query, server_addr).await.unwrap();
+
+    let mut response_buf = [0; 512];
+    let (len, _) = tokio::time::timeout(
+        Duration::from_secs(5),
+        client_socket.recv_from(&mut response_buf),
+    )
+    .await
+    .unwrap()
+    .unwrap();
+    let response = &response_buf[..len];
+    assert_eq!(
+        Message::from_vec(response).unwrap().response_code(),
+        ResponseCode::NoError
+    );
+
+    let messages = output.wait_for_messages(2).await;
+    let (client_query, client_response) = (&messages[0], &messages[1]);
+
+    assert_eq!(client_query.message_type, CLIENT_QUERY);
+    assert_eq!(client_query.query_message.as_deref(), Some(&query[..]));
+    assert_eq!(client_query.query_address(), Some(client_addr));
+    assert_eq!(client_query.response_address(), Some(server_addr));
+
+    assert_eq!(client_response.message_type, CLIENT_RESPONSE);
+    assert_eq!(client_response.response_message.as_deref(), Some(response));
+    assert_eq!(client_response.query_address(), Some(client_addr));
+    assert!(client_response.query_time_sec.is_some());
+
+    server.shutdown_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_resolver_dnstap() {
+    subscribe();
+
+    let output = SharedOutput::default();
+    let mut options = ResolverOpts::default();
+    options.dnstap = Some(Dnstap::builder().to_writer(output.clone()).unwrap());
+
+    let query = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+    let record = v4_record(query.name().clone(), Ipv4Addr::LOCALHOST);
+    let upstream =
+        DnsResponse::from_message(message(query.clone(), vec![record], vec![], vec![])).unwrap();
+    let upstream_bytes = upstream.as_buffer().to_vec();
+
+    let server_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 53)), 53);
+    let name_server = NameServer::from_conn(
+        NameServerConfig::new(server_addr, Protocol::Udp),
+        options,
+        MockClientHandle::mock(vec![Ok(upstream)]),
+        MockConnProvider {
+            on_send: DefaultOnSend,
+        },
+    );
+
+    let mut request = Message::new();
+    request.add_query(query);
+    name_server.send(request).first_answer().await.unwrap();
+
+    let messages = output.wait_for_messages(2).await;
+    let (resolver_query, resolver_response) = (&messages[0], &messages[1]);
+
+    assert_eq!(resolver_query.message_type, RESOLVER_QUERY);
+    let sent = Message::from_vec(resolver_query.query_message.as_ref().unwrap()).unwrap();
+    assert_eq!(
+        sent.queries()[0].name(),
+        &Name::from_str("www.example.com.").unwrap()
+    );
+    assert_eq!(resolver_query.response_address(), Some(server_addr));
+
+    assert_eq!(resolver_response.message_type, RESOLVER_RESPONSE);
+    assert_eq!(
+        resolver_response.response_message.as_deref(),
+        Some(&upstream_bytes[..])
+    );
+    assert_eq!(resolver_response.response_address(), Some(server_addr));
+}
+
+/// A dnstap output collecting the written Frame Stream
+#[derive(Clone, Default)]
+struct SharedOutput(Arc<Mutex<Vec<u8>>>);
+
+impl SharedOutput {
+    /// Waits until at least `count` messages were written, and decodes them
+    async fn wait_for_messages(&self, count: usize) -> Vec<TapMessage> {
+        for _ in 0..100 {
+            let messages = data_frames(&self.0.lock().unwrap())
+                .iter()
+                .map(|frame| TapMessage::decode(frame))
+                .collect::<Vec<_>>();
+            if messages.len() >= count {
+                return messages;
+            }
+
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+
+        panic!("timed out waiting for {count} dnstap messages");
+    }
+}
+
+impl Write for SharedOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Returns the data frames of a Frame Stream, skipping control frames
+fn data_frames(mut buf: &[u8]) -> Vec<Vec<u8>> {
+    let mut frames = Vec::new();
+    while buf.len() >= 4 {
+        let len = u32::from_be_bytes(buf[..4].try_into().unwrap()) as usize;
+        buf = &buf[4..];
+        if len == 0 {
+            let len = u32::from_be_bytes(buf[..4].try_into().unwrap()) as usize;
+            buf = &buf[4 + len..];
+            continue;
+        }
+
+        frames.push(buf[..len].to_vec());
+        buf = &buf[len..];
+    }
+    frames
+}
+
+/// The fields of a dnstap `Message` checked by the tests
+#[derive(Debug, Default)]
+struct TapMessage {
+    message_type: u64,
+    query_address: Option<Vec<u8>>,
+    response_address: Option<Vec<u8>>,
+    query_port: Option<u64>,
+    response_port: Option<u64>,
+    query_time_sec: Option<u64>,
+    query_message: Option<Vec<u8>>,
+    response_message: Option<Vec<u8>>,
+}
+
+impl TapMessage {
+    fn decode(frame: &[u8]) -> Self {
+        let (_, message) = protobuf_fields(frame)
+            .into_iter()
+            .find(|(field, _)| *field == 14)
+            .expect("dnstap frame without a message");
+        let FieldValue::Bytes(message) = message else {
+            panic!("message is not length delimited");
+        };
+
+        let mut decoded = Self::default();
+        for (field, value) in protobuf_fields(&message) {
+            match (field, value) {
+                (1, FieldValue::Varint(value)) => decoded.message_type = value,
+                (4, FieldValue::Bytes(value)) => decoded.query_address = Some(value),
+                (5, FieldValue::Bytes(value)) => decoded.response_address = Some(value),
+                (6, FieldValue::Varint(value)) => decoded.query_port = Some(value),
+                (7, FieldValue::Varint(value)) => decoded.response_port = Some(value),
+                (8, FieldValue::Varint(value)) => decoded.query_time_sec = Some(value),
+                (10, FieldValue::Bytes(value)) => decoded.query_message = Some(value),
+                (14, FieldValue::Bytes(value)) => decoded.response_message = Some(value),
+                _ => {}
+            }
+        }
+        decoded
+    }
+
+    fn query_address(&self) -> Option<SocketAddr> {
+        socket_addr(self.query_address.as_deref()?, self.query_port?)
+    }
+
+    fn response_address(&self) -> Option<SocketAddr> {
+        socket_addr(self.response_address.as_deref()?, self.response_port?)
+    }
+}
+
+fn socket_addr(ip: &[u8], port: u64) -> Option<SocketAddr> {
+    let ip = <[u8; 4]>::try_from(ip).ok()?;
+    Some(SocketAddr::new(IpAddr::from(ip), port as u16))
+}
+
+enum FieldValue {
+    Varint(u64),
+    Bytes(Vec<u8>),
+    Fixed32,
+}
+
+fn protobuf_fields(mut buf: &[u8]) -> Vec<(u64, FieldValue)> {
+    fn varint(buf: &mut &[u8]) -> u64 {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = buf[0];
+            *buf = &buf[1..];
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+        value
+    }
+
+    let mut fields = Vec::new();
+    while !buf.is_empty() {
+        let key = varint(&mut buf);
+        let value = match key & 0x7 {
+            0 => FieldValue::Varint(varint(&mut buf)),
+            2 => {
+                let len = varint(&mut buf) as usize;
+                let value = buf[..len].to_vec();
+                buf = &buf[len..];
+                FieldValue::Bytes(value)
+            }
+            5 => {
+                buf = &buf[4..];
+                FieldValue::Fixed32
+            }
+            wire_type => panic!("unexpected wire type {wire_type}"),
+        };
+        fields.push((key >> 3, value));
+    }
+    fields
+}
@@ -3,11 +3,16 @@ mod chained_authority_tests;
 mod client_future_tests;
 mod client_tests;
 mod dnssec_client_handle_tests;
+#[cfg(feature = "dnstap")]
+mod dnstap_tests;
 mod invalid_nsec3_tests;
 mod lookup_tests;
 mod name_server_pool_tests;
 mod retry_dns_handle_tests;
 mod server_future_tests;
+mod sim_network_tests;
+#[cfg(feature = "smol")]
+mod smol_runtime_tests;
 mod sqlite_authority_tests;
 mod truncation_tests;
 mod validating_forwarder_tests;
@@ -4,18 +4,22 @@ use std::pin::Pin;
 use std::str::FromStr;
 use std::sync::{
     Arc,
-    atomic::{AtomicIsize, Ordering},
+    atomic::{AtomicIsize, AtomicUsize, Ordering},
 };
 use std::task::Poll;
+use std::time::Duration;
 
 use futures::executor::block_on;
+use futures::future;
 
 use hickory_integration::mock_client::*;
 use hickory_proto::op::{Query, ResponseCode};
 use hickory_proto::rr::{Name, RecordType};
 use hickory_proto::xfer::{DnsHandle, DnsResponse, FirstAnswer, Protocol};
 use hickory_proto::{ProtoError, ProtoErrorKind};
-use hickory_resolver::config::{NameServerConfig, ResolverOpts, ServerOrderingStrategy};
+use hickory_resolver::config::{
+    CircuitBreakerConfig, HedgingConfig, NameServerConfig, ResolverOpts, ServerOrderingStrategy,
+};
 use hickory_resolver::name_server::{NameServer, NameServerPool};
 use test_support::subscribe;
 
@@ -84,6 +88,7 @@ fn mock_nameserver_on_send_nx<O: OnSend + Unpin>(
             protocol: Protocol::Udp,
             tls_dns_name: None,
             http_endpoint: None,
+            tls_pins: Vec::new(),
             trust_negative_responses,
             bind_addr: None,
         },
@@ -975,3 +980,164 @@ fn test_concurrent_requests_0_conn() {
     let response = block_on(future).unwrap();
     assert_eq!(response.answers()[0], udp_record);
 }
+
+#[tokio::test]
+async fn test_hedged_requests() {
+    subscribe();
+
+    let mut options = ResolverOpts::default();
+    options.server_ordering_strategy = ServerOrderingStrategy::UserProvidedOrder;
+    options.num_concurrent_reqs = 1;
+    options.hedging = Some(HedgingConfig {
+        initial_delay: Duration::from_millis(10),
+        ..HedgingConfig::default()
+    });
+
+    // the first name server only answers once the request was also sent to the second one
+    let on_send = OnSendBarrier::new(2);
+
+    let query = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+
+    let udp_record = v4_record(query.name().clone(), Ipv4Addr::LOCALHOST);
+
+    let udp_message = message(query.clone(), vec![udp_record.clone()], vec![], vec![]);
+
+    let slow_nameserver = mock_nameserver_on_send(vec![], options.clone(), on_send.clone());
+    let udp_nameserver = mock_nameserver_on_send(
+        vec![Ok(DnsResponse::from_message(udp_message).unwrap())],
+        options.clone(),
+        on_send,
+    );
+
+    let pool =
+        mock_nameserver_pool_on_send(vec![slow_nameserver, udp_nameserver], vec![], None, options);
+
+    let request = message(query, vec![], vec![], vec![]);
+    let response = pool.send(request).first_answer().await.unwrap();
+    assert_eq!(response.answers()[0], udp_record);
+}
+
+/// Never answers if `hang` is set, counting the requests
+#[derive(Clone)]
+struct HangingOnSend {
+    hang: bool,
+    sent: Arc<AtomicUsize>,
+}
+
+impl OnSend for HangingOnSend {
+    fn on_send<E>(
+        &self,
+        response: Result<DnsResponse, E>,
+    ) -> Pin<Box<dyn Future<Output = Result<DnsResponse, E>> + Send>>
+    where
+        E: From<ProtoError> + Send + 'static,
+    {
+        self.sent.fetch_add(1, Ordering::Relaxed);
+        match self.hang {
+            true => Box::pin(future::pending()),
+            false => Box::pin(future::ready(response)),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_hedged_requests_demote_slow_server() {
+    subscribe();
+
+    let mut options = ResolverOpts::default();
+    options.server_ordering_strategy = ServerOrderingStrategy::QueryStatistics;
+    options.num_concurrent_reqs = 1;
+    options.hedging = Some(HedgingConfig {
+        initial_delay: Duration::from_millis(10),
+        ..HedgingConfig::default()
+    });
+
+    let query = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+    let udp_record = v4_record(query.name().clone(), Ipv4Addr::LOCALHOST);
+    let udp_message = message(query.clone(), vec![udp_record.clone()], vec![], vec![]);
+
+    let slow = HangingOnSend {
+        hang: true,
+        sent: Arc::new(AtomicUsize::new(0)),
+    };
+    let slow_nameserver = mock_nameserver_on_send(vec![], options.clone(), slow.clone());
+    let fast_nameserver = mock_nameserver_on_send(
+        (0..5)
+            .map(|_| Ok(DnsResponse::from_message(udp_message.clone()).unwrap()))
+            .collect(),
+        options.clone(),
+        HangingOnSend {
+            hang: false,
+            sent: Arc::new(AtomicUsize::new(0)),
+        },
+    );
+
+    let pool = mock_nameserver_pool_on_send(
+        vec![slow_nameserver, fast_nameserver],
+        vec![],
+        None,
+        options,
+    );
+
+    for _ in 0..5 {
+        let request = message(query.clone(), vec![], vec![], vec![]);
+        let response = pool.send(request).first_answer().await.unwrap();
+        assert_eq!(response.answers()[0], udp_record);
+    }
+
+    // the cancelled request to the slow name server counts towards its RTT, so it's tried last
+    // after it was tried once
+    assert!(slow.sent.load(Ordering::Relaxed) <= 1);
+}
+
+#[test]
+fn test_circuit_breaker() {
+    subscribe();
+
+    let mut options = ResolverOpts::default();
+    options.server_ordering_strategy = ServerOrderingStrategy::UserProvidedOrder;
+    options.num_concurrent_reqs = 1;
+    options.circuit_breaker = Some(CircuitBreakerConfig {
+        failure_threshold: 1,
+        probe_interval: Duration::from_secs(3600),
+    });
+
+    let query = Query::query(Name::from_str("www.example.com.").unwrap(), RecordType::A);
+
+    let failing_record = v4_record(query.name().clone(), Ipv4Addr::new(127, 0, 0, 2));
+    let failing_message = message(query.clone(), vec![failing_record], vec![], vec![]);
+    let udp_record = v4_record(query.name().clone(), Ipv4Addr::LOCALHOST);
+    let udp_message = message(query.clone(), vec![udp_record.clone()], vec![], vec![]);
+
+    // the first name server fails once, and would answer afterwards
+    let failing_nameserver = mock_nameserver(
+        vec![
+            Ok(DnsResponse::from_message(failing_message).unwrap()),
+            error(ProtoError::from(std::io::Error::from(
+                std::io::ErrorKind::ConnectionRefused,
+            ))),
+        ],
+        options.clone(),
+    );
+    let udp_nameserver = mock_nameserver(
+        vec![
+            Ok(DnsResponse::from_message(udp_message.clone()).unwrap()),
+            Ok(DnsResponse::from_message(udp_message).unwrap()),
+        ],
+        options.clone(),
+    );
+
+    let pool = mock_nameserver_pool(
+        vec![failing_nameserver, udp_nameserver],
+        vec![],
+        None,
+        options,
+    );
+
+    // the failing name server is out of rotation after the first request
+    for _ in 0..2 {
+        let request = message(query.clone(), vec![], vec![], vec![]);
+        let response = block_on(pool.send(request).first_answer()).unwrap();
+        assert_eq!(response.answers()[0], udp_record);
+    }
+}
@@ -22,18 +22,22 @@ use rustls::{
     server::ResolvesServerCert,
     sign::{CertifiedKey, SingleCertAndKey},
 };
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpListener;
+use tokio::net::TcpStream;
 use tokio::net::UdpSocket;
 
 use hickory_integration::example_authority::create_example;
-use hickory_proto::op::{Message, MessageType, OpCode, Query, ResponseCode};
+use hickory_proto::op::{Edns, Message, MessageType, OpCode, Query, ResponseCode};
+use hickory_proto::rr::rdata::opt::{EdnsCode, EdnsOption, TcpKeepalive};
 use hickory_proto::rr::rdata::{A, OPT};
 use hickory_proto::rr::{DNSClass, Name, RData, Record, RecordType};
 #[cfg(feature = "__tls")]
 use hickory_proto::rustls::default_provider;
 use hickory_proto::xfer::{DnsHandle, DnsMultiplexer};
 use hickory_server::ServerFuture;
-use hickory_server::authority::{Authority, Catalog};
+use hickory_server::authority::{Authority, Catalog, View, Views};
+use hickory_server::server::{ConnectionLimitConfig, ProxyProtocolConfig, ResponseRateLimitConfig};
 use test_support::subscribe;
 
 #[tokio::test]
@@ -460,3 +464,298 @@ async fn edns_multiple_opt_rr() {
     server_continue.store(false, Ordering::Relaxed);
     server.await.unwrap();
 }
+
+#[tokio::test]
+async fn test_server_response_rate_limit() {
+    subscribe();
+
+    let udp_socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let local_addr = udp_socket.local_addr().unwrap();
+    let mut server = ServerFuture::new(new_catalog());
+    server.set_response_rate_limit(ResponseRateLimitConfig {
+        responses_per_second: 1,
+        slip: 2,
+        ..ResponseRateLimitConfig::default()
+    });
+    server.register_socket(udp_socket);
+
+    let client_socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    for id in 0..5 {
+        let mut message = Message::new();
+        message.set_id(id).add_query(Query::query(
+            Name::from_str("www.example.com.").unwrap(),
+            RecordType::A,
+        ));
+        client_socket
+            .send_to(&message.to_vec().unwrap(), local_addr)
+            .await
+            .unwrap();
+    }
+
+    // the first response is within the limit, the other four are limited and every second of
+    // those is sent truncated
+    let mut responses = Vec::new();
+    let mut response_buf = [0; 512];
+    while let Ok(Ok((len, _))) = tokio::time::timeout(
+        Duration::from_millis(500),
+        client_socket.recv_from(&mut response_buf),
+    )
+    .await
+    {
+        responses.push(Message::from_vec(&response_buf[..len]).unwrap());
+    }
+
+    // requests are handled concurrently, so which ones get through is not deterministic
+    let answered = responses.iter().filter(|r| r.answer_count() == 1).count();
+    let slipped = responses
+        .iter()
+        .filter(|r| r.truncated() && r.answer_count() == 0)
+        .count();
+    assert_eq!((responses.len(), answered, slipped), (3, 1, 2));
+
+    server.shutdown_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_server_views() {
+    subscribe();
+
+    let internal_socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let internal_addr = internal_socket.local_addr().unwrap();
+    let other_socket = UdpSocket::bind((Ipv4Addr::new(127, 0, 0, 2), 0))
+        .await
+        .unwrap();
+    let other_addr = other_socket.local_addr().unwrap();
+
+    let mut views = Views::new();
+    views.push(
+        View::new("internal", new_catalog())
+            .with_match_destinations(vec!["127.0.0.1/32".parse().unwrap()]),
+    );
+    let mut server = ServerFuture::new(views);
+    server.register_socket(internal_socket);
+    server.register_socket(other_socket);
+
+    let client_socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let query = |addr| {
+        let client_socket = &client_socket;
+        async move {
+            let mut message = Message::new();
+            message.add_query(Query::query(
+                Name::from_str("www.example.com.").unwrap(),
+                RecordType::A,
+            ));
+            client_socket
+                .send_to(&message.to_vec().unwrap(), addr)
+                .await
+                .unwrap();
+
+            let mut response_buf = [0; 512];
+            let (len, _) = tokio::time::timeout(
+                Duration::from_secs(5),
+                client_socket.recv_from(&mut response_buf),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+            Message::from_vec(&response_buf[..len]).unwrap()
+        }
+    };
+
+    let response = query(internal_addr).await;
+    assert_eq!(response.response_code(), ResponseCode::NoError);
+    assert_eq!(response.answer_count(), 1);
+
+    // no view matches requests received on the other address
+    let response = query(other_addr).await;
+    assert_eq!(response.response_code(), ResponseCode::Refused);
+    assert_eq!(response.answer_count(), 0);
+
+    server.shutdown_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_server_denied_network_refused() {
+    subscribe();
+
+    let udp_socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let local_addr = udp_socket.local_addr().unwrap();
+    let mut server =
+        ServerFuture::with_access(new_catalog(), &["127.0.0.0/8".parse().unwrap()], &[]);
+    server.register_socket(udp_socket);
+
+    let client_socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let mut message = Message::new();
+    message.set_id(10).add_query(Query::query(
+        Name::from_str("www.example.com.").unwrap(),
+        RecordType::A,
+    ));
+    client_socket
+        .send_to(&message.to_vec().unwrap(), local_addr)
+        .await
+        .unwrap();
+
+    let mut response_buf = [0; 512];
+    let (len, _) = tokio::time::timeout(
+        Duration::from_secs(5),
+        client_socket.recv_from(&mut response_buf),
+    )
+    .await
+    .unwrap()
+    .unwrap();
+    let response = Message::from_vec(&response_buf[..len]).unwrap();
+
+    assert_eq!(response.id(), 10);
+    assert_eq!(response.response_code(), ResponseCode::Refused);
+    assert_eq!(response.answer_count(), 0);
+
+    server.shutdown_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_server_tcp_pipelining_keepalive() {
+    subscribe();
+
+    let tcp_listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let local_addr = tcp_listener.local_addr().unwrap();
+    let mut server = ServerFuture::new(new_catalog());
+    server.set_connection_limits(ConnectionLimitConfig {
+        keepalive_timeout: Some(120),
+        ..ConnectionLimitConfig::default()
+    });
+    server.register_listener(tcp_listener, Duration::from_secs(5));
+
+    // all queries are written before any response is read
+    let mut stream = TcpStream::connect(local_addr).await.unwrap();
+    for id in 0..3 {
+        let mut message = Message::new();
+        message.set_id(id).add_query(Query::query(
+            Name::from_str("www.example.com.").unwrap(),
+            RecordType::A,
+        ));
+        if id == 0 {
+            message
+                .extensions_mut()
+                .get_or_insert_with(Edns::new)
+                .options_mut()
+                .insert(EdnsOption::Keepalive(TcpKeepalive::default()));
+        }
+
+        let bytes = message.to_vec().unwrap();
+        stream
+            .write_all(&(bytes.len() as u16).to_be_bytes())
+            .await
+            .unwrap();
+        stream.write_all(&bytes).await.unwrap();
+    }
+
+    let mut responses = Vec::new();
+    for _ in 0..3 {
+        let mut len = [0; 2];
+        let mut response_buf = Vec::new();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            stream.read_exact(&mut len).await.unwrap();
+            response_buf.resize(u16::from_be_bytes(len) as usize, 0);
+            stream.read_exact(&mut response_buf).await.unwrap();
+        })
+        .await
+        .unwrap();
+        responses.push(Message::from_vec(&response_buf).unwrap());
+    }
+
+    // responses may be sent in any order
+    responses.sort_by_key(|response| response.id());
+    assert!(responses.iter().all(|r| r.answer_count() == 1));
+    assert_eq!(
+        responses[0]
+            .extensions()
+            .as_ref()
+            .and_then(|edns| edns.option(EdnsCode::Keepalive)),
+        Some(&EdnsOption::Keepalive(TcpKeepalive::new(Some(1200))))
+    );
+    assert!(responses[1].extensions().is_none());
+
+    server.shutdown_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_server_tcp_connection_limit() {
+    subscribe();
+
+    let tcp_listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let local_addr = tcp_listener.local_addr().unwrap();
+    let mut server = ServerFuture::new(new_catalog());
+    server.register_listener(tcp_listener, Duration::from_secs(5));
+    // the limits also apply to the listeners registered before
+    server.set_connection_limits(ConnectionLimitConfig {
+        max_connections_per_ip: 1,
+        ..ConnectionLimitConfig::default()
+    });
+
+    let first = TcpStream::connect(local_addr).await.unwrap();
+    // give the server time to account for the first connection
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // the second connection from the same address is closed right away
+    let mut second = TcpStream::connect(local_addr).await.unwrap();
+    let mut buf = [0; 1];
+    let read = tokio::time::timeout(Duration::from_secs(5), second.read(&mut buf))
+        .await
+        .unwrap();
+    assert!(matches!(read, Ok(0) | Err(_)));
+
+    drop(first);
+    server.shutdown_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_server_tcp_proxy_protocol() {
+    subscribe();
+
+    let tcp_listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let local_addr = tcp_listener.local_addr().unwrap();
+    let mut server =
+        ServerFuture::with_access(new_catalog(), &["192.0.2.0/24".parse().unwrap()], &[]);
+    server.set_proxy_protocol(ProxyProtocolConfig {
+        trusted_proxies: vec!["127.0.0.0/8".parse().unwrap()],
+    });
+    server.register_listener(tcp_listener, Duration::from_secs(5));
+
+    let query = |header: &'static [u8]| async move {
+        let mut message = Message::new();
+        message.set_id(7).add_query(Query::query(
+            Name::from_str("www.example.com.").unwrap(),
+            RecordType::A,
+        ));
+        let bytes = message.to_vec().unwrap();
+
+        let mut stream = TcpStream::connect(local_addr).await.unwrap();
+        stream.write_all(header).await.unwrap();
+        stream
+            .write_all(&(bytes.len() as u16).to_be_bytes())
+            .await
+            .unwrap();
+        stream.write_all(&bytes).await.unwrap();
+
+        let mut len = [0; 2];
+        let mut response_buf = Vec::new();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            stream.read_exact(&mut len).await.unwrap();
+            response_buf.resize(u16::from_be_bytes(len) as usize, 0);
+            stream.read_exact(&mut response_buf).await.unwrap();
+        })
+        .await
+        .unwrap();
+        Message::from_vec(&response_buf).unwrap()
+    };
+
+    // the client address from the header is subject to access control
+    let response = query(b"PROXY TCP4 192.0.2.1 127.0.0.1 56324 53\r\n").await;
+    assert_eq!(response.response_code(), ResponseCode::Refused);
+
+    let response = query(b"PROXY TCP4 198.51.100.1 127.0.0.1 56324 53\r\n").await;
+    assert_eq!(response.response_code(), ResponseCode::NoError);
+    assert_eq!(response.answer_count(), 1);
+
+    server.shutdown_gracefully().await.unwrap();
+}
@@ -0,0 +1,269 @@
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use hickory_proto::{
+    op::Query,
+    rr::{
+        DNSClass, Name, RData, Record, RecordType,
+        rdata::{A, NS, SOA},
+    },
+    runtime::{
+        RuntimeProvider,
+        sim::{LinkConditions, SimNetwork, SimRuntimeProvider},
+    },
+};
+use hickory_recursor::Recursor;
+use hickory_resolver::{
+    Resolver,
+    config::{NameServerConfigGroup, ResolverConfig},
+    name_server::GenericConnector,
+};
+use hickory_server::{
+    ServerFuture,
+    authority::{Authority, Catalog, ZoneType},
+    store::in_memory::InMemoryAuthority,
+};
+use test_support::subscribe;
+
+const ROOT_SERVER: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+const COM_SERVER: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+const EXAMPLE_SERVER: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3));
+const CLIENT: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 1, 1));
+
+#[tokio::test(start_paused = true)]
+async fn test_recursion_over_sim_network() {
+    subscribe();
+
+    let network = SimNetwork::new(1);
+    network.set_default_link(LinkConditions {
+        latency: Duration::from_millis(20),
+        ..LinkConditions::default()
+    });
+    let _servers = hierarchy(&network, 0).await;
+
+    let recursor = Recursor::builder()
+        .build_with_provider(
+            NameServerConfigGroup::from_ips_clear(&[ROOT_SERVER], 53, true),
+            network.host(CLIENT),
+        )
+        .unwrap();
+
+    let started = tokio::time::Instant::now();
+    let lookup = recursor
+        .resolve(
+            Query::query(n("www.example.com."), RecordType::A),
+            Instant::now(),
+            false,
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        a_records(lookup.records()),
+        vec![Ipv4Addr::new(10, 1, 0, 1)]
+    );
+
+    // one round trip to each of the root, com and example.com servers, all in virtual time
+    assert_eq!(started.elapsed(), Duration::from_millis(120));
+    let stats = network.stats();
+    assert_eq!(stats.udp_sent, 6);
+    assert_eq!(stats.udp_delivered, 6);
+    assert_eq!(stats.tcp_connections, 0);
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_truncation_falls_back_to_tcp() {
+    subscribe();
+
+    let network = SimNetwork::new(1);
+    network.set_default_link(LinkConditions {
+        max_udp_payload: Some(512),
+        ..LinkConditions::default()
+    });
+    let _servers = hierarchy(&network, 64).await;
+
+    let resolver = stub_resolver(&network);
+    let lookup = resolver.ipv4_lookup("many.example.com.").await.unwrap();
+
+    assert_eq!(lookup.iter().count(), 64);
+    let stats = network.stats();
+    assert_eq!(stats.udp_truncated, 1);
+    assert_eq!(stats.tcp_connections, 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_packet_loss_is_retried() {
+    subscribe();
+
+    let network = SimNetwork::new(7);
+    network.set_link(
+        CLIENT,
+        EXAMPLE_SERVER,
+        LinkConditions {
+            loss: 0.5,
+            ..LinkConditions::default()
+        },
+    );
+    let _servers = hierarchy(&network, 0).await;
+
+    let resolver = stub_resolver(&network);
+    let mut resolved = 0;
+    for _ in 0..8 {
+        if resolver.ipv4_lookup("www.example.com.").await.is_ok() {
+            resolved += 1;
+        }
+        resolver.clear_cache();
+    }
+
+    // the same seed loses the same packets on every run
+    let stats = network.stats();
+    assert!(stats.udp_dropped > 0);
+    assert!(resolved > 0);
+    let rerun = {
+        let network = SimNetwork::new(7);
+        network.set_link(
+            CLIENT,
+            EXAMPLE_SERVER,
+            LinkConditions {
+                loss: 0.5,
+                ..LinkConditions::default()
+            },
+        );
+        let _servers = hierarchy(&network, 0).await;
+
+        let resolver = stub_resolver(&network);
+        for _ in 0..8 {
+            let _ = resolver.ipv4_lookup("www.example.com.").await;
+            resolver.clear_cache();
+        }
+        network.stats()
+    };
+    assert_eq!(rerun, stats);
+}
+
+/// A stub resolver on the client host, querying the example.com server directly
+fn stub_resolver(network: &SimNetwork) -> Resolver<GenericConnector<SimRuntimeProvider>> {
+    let config = ResolverConfig::from_parts(
+        None,
+        vec![],
+        NameServerConfigGroup::from_ips_clear(&[EXAMPLE_SERVER], 53, true),
+    );
+    let mut builder =
+        Resolver::builder_with_config(config, GenericConnector::new(network.host(CLIENT)));
+    builder.options_mut().edns0 = true;
+    builder.build()
+}
+
+/// Mounts the root, com and example.com zones on their servers
+///
+/// `many.example.com` has `many` A records.
+async fn hierarchy(network: &SimNetwork, many: u32) -> Vec<ServerFuture<Catalog>> {
+    let mut root = zone(".", "a.root-servers.net.");
+    root.upsert_mut(
+        Record::from_rdata(n("com."), 86400, RData::NS(NS(n("a.gtld-servers.net.")))),
+        0,
+    );
+    root.upsert_mut(a("a.gtld-servers.net.", COM_SERVER), 0);
+
+    let mut com = zone("com.", "a.gtld-servers.net.");
+    com.upsert_mut(
+        Record::from_rdata(
+            n("example.com."),
+            86400,
+            RData::NS(NS(n("ns.example.com."))),
+        ),
+        0,
+    );
+    com.upsert_mut(a("ns.example.com.", EXAMPLE_SERVER), 0);
+
+    let mut example = zone("example.com.", "ns.example.com.");
+    example.upsert_mut(a("ns.example.com.", EXAMPLE_SERVER), 0);
+    example.upsert_mut(a("www.example.com.", IpAddr::from([10, 1, 0, 1])), 0);
+    for i in 0..many {
+        example.upsert_mut(
+            a(
+                "many.example.com.",
+                IpAddr::from(Ipv4Addr::from(0x0a02_0000 + i)),
+            ),
+            0,
+        );
+    }
+
+    let mut servers = Vec::new();
+    for (ip, authority) in [
+        (ROOT_SERVER, root),
+        (COM_SERVER, com),
+        (EXAMPLE_SERVER, example),
+    ] {
+        let mut catalog = Catalog::new();
+        catalog.upsert(authority.origin().clone(), vec![Arc::new(authority)]);
+
+        let host = network.host(ip);
+        let addr = SocketAddr::new(ip, 53);
+        let mut server = ServerFuture::new(catalog);
+        server.register_sim_socket(host.bind_udp(addr, addr).await.unwrap());
+        server.register_sim_listener(host.listen_tcp(53).unwrap(), Duration::from_secs(5));
+        servers.push(server);
+    }
+
+    servers
+}
+
+fn zone(origin: &str, primary: &str) -> InMemoryAuthority {
+    let mut authority = InMemoryAuthority::empty(
+        n(origin),
+        ZoneType::Primary,
+        false,
+        #[cfg(feature = "__dnssec")]
+        None,
+    );
+    authority.upsert_mut(
+        Record::from_rdata(
+            n(origin),
+            3600,
+            RData::SOA(SOA::new(
+                n(primary),
+                n("hostmaster.example.com."),
+                1,
+                7200,
+                3600,
+                1209600,
+                3600,
+            )),
+        )
+        .set_dns_class(DNSClass::IN)
+        .clone(),
+        0,
+    );
+    authority.upsert_mut(
+        Record::from_rdata(n(origin), 86400, RData::NS(NS(n(primary)))),
+        0,
+    );
+    authority
+}
+
+fn a(name: &str, ip: IpAddr) -> Record {
+    let IpAddr::V4(ip) = ip else {
+        panic!("expected an IPv4 address: {ip}");
+    };
+
+    Record::from_rdata(n(name), 86400, RData::A(A(ip)))
+}
+
+fn a_records(records: &[Record]) -> Vec<Ipv4Addr> {
+    records
+        .iter()
+        .filter_map(|record| match record.data() {
+            RData::A(a) => Some(a.0),
+            _ => None,
+        })
+        .collect()
+}
+
+fn n(name: &str) -> Name {
+    Name::from_str(name).unwrap()
+}
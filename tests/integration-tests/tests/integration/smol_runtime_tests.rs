@@ -0,0 +1,231 @@
+//! Resolver and Client on the smol runtime, against a server running on Tokio
+
+#[cfg(feature = "__tls")]
+use std::env;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use hickory_client::client::{Client, ClientHandle};
+use hickory_integration::example_authority::create_example;
+use hickory_proto::op::ResponseCode;
+use hickory_proto::rr::{DNSClass, Name, RData, RecordType, rdata::A};
+use hickory_proto::runtime::{SmolRuntimeProvider, SmolTime};
+use hickory_proto::tcp::TcpClientStream;
+use hickory_proto::udp::UdpClientStream;
+use hickory_proto::xfer::Protocol;
+use hickory_resolver::config::{NameServerConfig, ResolverConfig, ResolverOpts};
+use hickory_resolver::{SmolResolver, name_server::SmolConnectionProvider};
+use hickory_server::ServerFuture;
+use hickory_server::authority::{Authority, Catalog};
+#[cfg(feature = "__tls")]
+use rustls::{
+    ClientConfig, RootCertStore,
+    pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject},
+    sign::{CertifiedKey, SingleCertAndKey},
+};
+use test_support::subscribe;
+use tokio::net::{TcpListener, UdpSocket};
+use tokio::runtime::Runtime;
+
+#[cfg(feature = "__tls")]
+use hickory_proto::rustls::{default_provider, tls_client_connect};
+
+const WWW_A: A = A(Ipv4Addr::new(93, 184, 215, 14));
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[test]
+fn test_resolver_udp_tcp() {
+    subscribe();
+    let server = Server::start();
+
+    smol::block_on(async {
+        for protocol in [Protocol::Udp, Protocol::Tcp] {
+            let resolver = resolver(NameServerConfig::new(server.addr, protocol), |_| {});
+            let lookup = resolver
+                .ipv4_lookup("www.example.com.")
+                .await
+                .unwrap_or_else(|e| panic!("{protocol} lookup failed: {e}"));
+
+            assert_eq!(lookup.iter().copied().collect::<Vec<_>>(), vec![WWW_A]);
+        }
+    });
+}
+
+#[test]
+fn test_client_udp() {
+    subscribe();
+    let server = Server::start();
+
+    smol::block_on(async {
+        let stream = UdpClientStream::builder(server.addr, SmolRuntimeProvider::new()).build();
+        let (client, bg) = Client::connect_with_timer::<_, _, SmolTime>(stream)
+            .await
+            .unwrap();
+        smol::spawn(bg).detach();
+
+        query_www(client).await;
+    });
+}
+
+#[test]
+fn test_client_tcp() {
+    subscribe();
+    let server = Server::start();
+
+    smol::block_on(async {
+        let (stream, sender) =
+            TcpClientStream::new(server.addr, None, None, SmolRuntimeProvider::new());
+        let (client, bg) = Client::new(stream, sender, None).await.unwrap();
+        smol::spawn(bg).detach();
+
+        query_www(client).await;
+    });
+}
+
+#[cfg(feature = "__tls")]
+#[test]
+fn test_resolver_tls() {
+    subscribe();
+    let server = Server::start();
+
+    smol::block_on(async {
+        let mut config = NameServerConfig::new(server.tls_addr, Protocol::Tls);
+        config.tls_dns_name = Some("ns.example.com".to_owned());
+
+        let resolver = resolver(config, |opts| opts.tls_config = tls_client_config());
+        let lookup = resolver.ipv4_lookup("www.example.com.").await.unwrap();
+
+        assert_eq!(lookup.iter().copied().collect::<Vec<_>>(), vec![WWW_A]);
+    });
+}
+
+#[cfg(feature = "__tls")]
+#[test]
+fn test_client_tls() {
+    subscribe();
+    let server = Server::start();
+
+    smol::block_on(async {
+        let (stream, sender) = tls_client_connect(
+            server.tls_addr,
+            "ns.example.com".to_owned(),
+            Arc::new(tls_client_config()),
+            SmolRuntimeProvider::new(),
+        );
+        let (client, bg) = Client::new(stream, sender, None).await.unwrap();
+        smol::spawn(bg).detach();
+
+        query_www(client).await;
+    });
+}
+
+/// An authoritative server for example.com, running on its own Tokio runtime
+struct Server {
+    addr: SocketAddr,
+    #[cfg(feature = "__tls")]
+    tls_addr: SocketAddr,
+    _server: ServerFuture<Catalog>,
+    _runtime: Runtime,
+}
+
+impl Server {
+    fn start() -> Self {
+        let runtime = Runtime::new().unwrap();
+        let localhost = SocketAddr::from((Ipv4Addr::LOCALHOST, 0));
+
+        let authority = create_example();
+        let mut catalog = Catalog::new();
+        catalog.upsert(authority.origin().clone(), vec![Arc::new(authority)]);
+        let mut server = ServerFuture::new(catalog);
+
+        let _guard = runtime.enter();
+        let udp = runtime.block_on(UdpSocket::bind(localhost)).unwrap();
+        let addr = udp.local_addr().unwrap();
+        server.register_socket(udp);
+        let tcp = runtime.block_on(TcpListener::bind(addr)).unwrap();
+        server.register_listener(tcp, TIMEOUT);
+
+        #[cfg(feature = "__tls")]
+        let tls_addr = {
+            let tls = runtime.block_on(TcpListener::bind(localhost)).unwrap();
+            let tls_addr = tls.local_addr().unwrap();
+            server
+                .register_tls_listener(tls, TIMEOUT, server_cert())
+                .unwrap();
+            tls_addr
+        };
+
+        drop(_guard);
+        Self {
+            addr,
+            #[cfg(feature = "__tls")]
+            tls_addr,
+            _server: server,
+            _runtime: runtime,
+        }
+    }
+}
+
+fn resolver(
+    name_server: NameServerConfig,
+    options: impl FnOnce(&mut ResolverOpts),
+) -> SmolResolver {
+    let mut config = ResolverConfig::new();
+    config.add_name_server(name_server);
+
+    let mut builder = SmolResolver::builder_with_config(config, SmolConnectionProvider::default());
+    builder.options_mut().timeout = TIMEOUT;
+    options(builder.options_mut());
+    builder.build()
+}
+
+async fn query_www(mut client: Client) {
+    let response = client
+        .query(
+            Name::from_str("www.example.com.").unwrap(),
+            DNSClass::IN,
+            RecordType::A,
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.response_code(), ResponseCode::NoError);
+    assert_eq!(response.answers()[0].data(), &RData::A(WWW_A));
+}
+
+#[cfg(feature = "__tls")]
+fn test_data(file: &str) -> String {
+    let server_path = env::var("TDNS_WORKSPACE_ROOT").unwrap_or_else(|_| "../..".to_owned());
+    format!("{server_path}/tests/test-data/{file}")
+}
+
+#[cfg(feature = "__tls")]
+fn server_cert() -> Arc<SingleCertAndKey> {
+    let cert_chain = CertificateDer::pem_file_iter(test_data("cert.pem"))
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    let key = PrivateKeyDer::from_pem_file(test_data("cert.key")).unwrap();
+
+    let certified_key = CertifiedKey::from_der(cert_chain, key, &default_provider()).unwrap();
+    Arc::new(SingleCertAndKey::from(certified_key))
+}
+
+#[cfg(feature = "__tls")]
+fn tls_client_config() -> ClientConfig {
+    let mut root_store = RootCertStore::empty();
+    let ca = CertificateDer::pem_file_iter(test_data("ca.pem"))
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    let (_, ignored) = root_store.add_parsable_certificates(ca);
+    assert_eq!(ignored, 0, "bad certificate!");
+
+    ClientConfig::builder_with_provider(Arc::new(default_provider()))
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_root_certificates(root_store)
+        .with_no_client_auth()
+}
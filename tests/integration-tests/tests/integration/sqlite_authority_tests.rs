@@ -1,8 +1,12 @@
 #![cfg(feature = "sqlite")]
 
+#[cfg(feature = "__dnssec")]
+use std::collections::BTreeMap;
 use std::net::Ipv4Addr;
 use std::net::SocketAddr;
 use std::str::FromStr;
+#[cfg(feature = "__dnssec")]
+use std::sync::Arc;
 
 use hickory_proto::rr::LowerName;
 use rusqlite::*;
@@ -10,6 +14,8 @@ use rusqlite::*;
 use hickory_proto::op::{Header, LowerQuery, Message, MessageType, OpCode, Query, ResponseCode};
 use hickory_proto::rr::rdata::{A, AAAA, NS, TXT};
 use hickory_proto::rr::{DNSClass, Name, RData, Record, RecordType};
+#[cfg(feature = "__dnssec")]
+use hickory_proto::rr::{RecordSet, RrKey};
 use hickory_proto::xfer::Protocol;
 use hickory_server::authority::LookupOptions;
 use hickory_server::authority::{Authority, ZoneType};
@@ -920,6 +926,197 @@ async fn test_zone_signing() {
     }
 }
 
+#[cfg(feature = "__dnssec")]
+#[tokio::test]
+async fn test_incremental_nsec() {
+    subscribe();
+
+    let authority = create_secure_example();
+    assert_incremental_chain(authority, RecordType::NSEC).await;
+}
+
+#[cfg(feature = "__dnssec")]
+#[tokio::test]
+async fn test_incremental_nsec3() {
+    use hickory_proto::dnssec::{SigSigner, SigningKey, crypto::Ed25519SigningKey, rdata::DNSKEY};
+
+    subscribe();
+
+    let mut example = hickory_integration::example_authority::create_example();
+    let origin = Name::from(example.origin().clone());
+    let records = example
+        .records_get_mut()
+        .iter()
+        .map(|(key, rr_set)| (key.clone(), RecordSet::clone(rr_set)))
+        .collect();
+
+    let mut authority = InMemoryAuthority::new(
+        origin.clone(),
+        records,
+        ZoneType::Primary,
+        false,
+        Some(NxProofKind::Nsec3 {
+            algorithm: Default::default(),
+            salt: Default::default(),
+            iterations: Default::default(),
+            opt_out: false,
+        }),
+    )
+    .unwrap();
+
+    let key = Ed25519SigningKey::from_pkcs8(&Ed25519SigningKey::generate_pkcs8().unwrap()).unwrap();
+    authority
+        .add_zone_signing_key_mut(SigSigner::dnssec(
+            DNSKEY::from_key(&key.to_public_key().unwrap()),
+            Box::new(key),
+            origin,
+            std::time::Duration::from_secs(3600),
+        ))
+        .unwrap();
+    authority.secure_zone_mut().unwrap();
+
+    assert_incremental_chain(
+        SqliteAuthority::new(authority, true, true),
+        RecordType::NSEC3,
+    )
+    .await;
+}
+
+#[cfg(feature = "__dnssec")]
+#[tokio::test]
+async fn test_failed_signing_keeps_signatures() {
+    use hickory_proto::dnssec::{
+        Algorithm, DnsSecResult, PublicKeyBuf, SigSigner, SigningKey, TBS,
+        crypto::Ed25519SigningKey, rdata::DNSKEY,
+    };
+    use hickory_server::authority::DnssecAuthority;
+
+    /// A key of a signer which is unavailable
+    struct UnavailableKey(Ed25519SigningKey);
+
+    impl SigningKey for UnavailableKey {
+        fn sign(&self, _: &TBS) -> DnsSecResult<Vec<u8>> {
+            Err("signer unavailable".into())
+        }
+
+        fn to_public_key(&self) -> DnsSecResult<PublicKeyBuf> {
+            self.0.to_public_key()
+        }
+
+        fn algorithm(&self) -> Algorithm {
+            self.0.algorithm()
+        }
+    }
+
+    subscribe();
+    let mut authority = create_secure_example();
+    authority.set_allow_update(true);
+
+    let key = Ed25519SigningKey::from_pkcs8(&Ed25519SigningKey::generate_pkcs8().unwrap()).unwrap();
+    authority
+        .add_zone_signing_key(SigSigner::dnssec(
+            DNSKEY::from_key(&key.to_public_key().unwrap()),
+            Box::new(UnavailableKey(key)),
+            authority.origin().clone().into(),
+            std::time::Duration::from_secs(3600),
+        ))
+        .await
+        .unwrap();
+
+    let before = authority.records().await;
+    let serial = authority.serial().await;
+    assert!(authority.secure_zone().await.is_err());
+    assert_eq!(before, authority.records().await);
+    assert_eq!(serial, authority.serial().await);
+
+    let new_name = Name::from_str("new.example.com.").unwrap();
+    let new_record = Record::from_rdata(new_name, 3600, RData::A(A::new(10, 11, 12, 13)));
+    assert!(authority.update_records(&[new_record], true).await.is_err());
+    let after = authority.records().await;
+    assert_eq!(serial, authority.serial().await);
+    for (rr_key, rr_set) in &before {
+        assert_eq!(Some(rr_set), after.get(rr_key), "{rr_key:?} changed");
+    }
+}
+
+/// Applies some dynamic updates, then verifies that the patched chain is identical to a regenerated
+/// one, and that the RRsets that weren't touched by the updates weren't signed again.
+#[cfg(feature = "__dnssec")]
+async fn assert_incremental_chain(mut authority: SqliteAuthority, chain_type: RecordType) {
+    use hickory_server::authority::DnssecAuthority;
+
+    authority.set_allow_update(true);
+
+    let new_name = Name::from_str("new.example.com.").unwrap();
+    let deep_name = Name::from_str("deep.ent.example.com.").unwrap();
+    let alias_name = Name::from_str("alias.example.com.").unwrap();
+    let www_name = Name::from_str("www.example.com.").unwrap();
+
+    let before = authority.records().await;
+    let serial = authority.serial().await;
+
+    let updates = [
+        Record::from_rdata(new_name.clone(), 86400, RData::A(A::new(10, 0, 0, 1)))
+            .set_dns_class(DNSClass::IN)
+            .clone(),
+        Record::from_rdata(deep_name.clone(), 86400, RData::A(A::new(10, 0, 0, 2)))
+            .set_dns_class(DNSClass::IN)
+            .clone(),
+        Record::update0(alias_name.clone(), 86400, RecordType::ANY)
+            .set_dns_class(DNSClass::ANY)
+            .clone(),
+        Record::from_rdata(
+            www_name.clone(),
+            86400,
+            RData::TXT(TXT::new(vec!["v=spf1 -all".to_string()])),
+        )
+        .set_dns_class(DNSClass::NONE)
+        .clone(),
+    ];
+    assert!(authority.update_records(&updates, true).await.unwrap());
+    assert_eq!(serial + 1, authority.serial().await);
+
+    let after = authority.records().await;
+    let updated_names =
+        [&new_name, &deep_name, &alias_name, &www_name].map(|name| LowerName::from(name.clone()));
+    for (rr_key, rr_set) in &after {
+        if updated_names.contains(&rr_key.name)
+            || rr_key.record_type == chain_type
+            || rr_key.record_type == RecordType::SOA
+        {
+            continue;
+        }
+
+        assert!(
+            Arc::ptr_eq(rr_set, &before[rr_key]),
+            "rrset was modified: {rr_key:?}"
+        );
+    }
+
+    let chain = |records: &BTreeMap<RrKey, Arc<RecordSet>>| {
+        records
+            .values()
+            .filter(|rr_set| rr_set.record_type() == chain_type)
+            .inspect(|rr_set| {
+                assert!(
+                    rr_set
+                        .records(true)
+                        .any(|r| r.record_type() == RecordType::RRSIG),
+                    "chain record not signed: {}",
+                    rr_set.name()
+                )
+            })
+            .flat_map(|rr_set| rr_set.records_without_rrsigs().cloned())
+            .collect::<Vec<_>>()
+    };
+
+    let incremental = chain(&after);
+    assert!(!incremental.is_empty());
+
+    authority.secure_zone().await.unwrap();
+    assert_eq!(incremental, chain(&authority.records().await));
+}
+
 #[cfg(feature = "__dnssec")]
 #[tokio::test]
 async fn test_get_nsec() {
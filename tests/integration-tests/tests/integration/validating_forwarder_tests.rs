@@ -29,7 +29,7 @@ use hickory_proto::{
 };
 use hickory_resolver::{
     Name,
-    config::{NameServerConfig, NameServerConfigGroup, ResolverOpts},
+    config::{DomainRoute, NameServerConfig, NameServerConfigGroup, ResolverOpts},
 };
 use hickory_server::{
     ServerFuture,
@@ -200,6 +200,27 @@ async fn query_validate_false_unsigned_zone_no_soa() {
     }));
 }
 
+#[test]
+fn route_validate_must_match() {
+    subscribe();
+
+    let name_servers = NameServerConfigGroup::from(vec![NameServerConfig::new(
+        SocketAddr::from((Ipv4Addr::LOCALHOST, 53)),
+        Protocol::Udp,
+    )]);
+    let mut options = ResolverOpts::default();
+    options.validate = true;
+    let mut route = DomainRoute::new(
+        vec![Name::from_ascii("example.").unwrap()],
+        name_servers.clone(),
+    );
+    route.options = Some(options);
+
+    let mut config = ForwardConfig::new(name_servers);
+    config.routes.push(route);
+    assert!(ForwardAuthority::builder_tokio(config).build().is_err());
+}
+
 async fn setup_authoritative_server(
     signed: bool,
     soa: bool,
@@ -268,13 +289,12 @@ async fn setup_client_forwarder(
     trust_anchor.insert(public_key);
     let mut options = ResolverOpts::default();
     options.validate = validate;
-    let mut authority_builder = ForwardAuthority::builder_tokio(ForwardConfig {
-        name_servers: NameServerConfigGroup::from(vec![NameServerConfig::new(
-            name_server_addr,
-            Protocol::Udp,
-        )]),
-        options: Some(options),
-    });
+    let mut config = ForwardConfig::new(NameServerConfigGroup::from(vec![NameServerConfig::new(
+        name_server_addr,
+        Protocol::Udp,
+    )]));
+    config.options = Some(options);
+    let mut authority_builder = ForwardAuthority::builder_tokio(config);
     if validate {
         authority_builder = authority_builder.with_trust_anchor(Arc::new(trust_anchor));
     }
@@ -35,7 +35,7 @@ use tracing::Level;
 
 use hickory_client::client::{Client, ClientHandle};
 #[cfg(feature = "__dnssec")]
-use hickory_proto::dnssec::rdata::DNSKEY;
+use hickory_proto::dnssec::{DnssecDnsHandle, ValidationTrace, rdata::DNSKEY};
 #[cfg(any(feature = "__tls", feature = "__https"))]
 use hickory_proto::rustls::client_config;
 #[cfg(feature = "__tls")]
@@ -149,6 +149,11 @@ struct QueryOpt {
     /// Type of DNS record to notify
     #[clap(name = "TYPE")]
     ty: RecordType,
+
+    /// Validate the response with DNSSEC, and print every step of the validation
+    #[cfg(feature = "__dnssec")]
+    #[clap(long)]
+    vtrace: bool,
 }
 
 /// Notify a nameserver that a record has been updated
@@ -452,9 +457,26 @@ async fn handle_request(
     class: DNSClass,
     zone: Option<Name>,
     command: Command,
-    mut client: impl ClientHandle,
+    mut client: impl ClientHandle + Sync + Unpin,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let response = match command {
+        #[cfg(feature = "__dnssec")]
+        Command::Query(query) if query.vtrace => {
+            let name = query.name;
+            let ty = query.ty;
+            println!("; sending validated query: {name} {class} {ty}");
+
+            let trace = ValidationTrace::new();
+            let mut client = DnssecDnsHandle::new(client).with_validation_trace(trace.clone());
+            let response = client.query(name, class, ty).await;
+
+            println!("; validation trace");
+            for event in trace.events() {
+                println!(";  {event}");
+            }
+
+            response?
+        }
         Command::Query(query) => {
             let name = query.name;
             let ty = query.ty;
@@ -127,6 +127,7 @@ pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
             protocol: Protocol::Tcp,
             tls_dns_name: None,
             http_endpoint: None,
+            tls_pins: Vec::new(),
             trust_negative_responses: false,
             bind_addr: opts.bind.map(|ip| SocketAddr::new(ip, 0)),
         });
@@ -136,6 +137,7 @@ pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
             protocol: Protocol::Udp,
             tls_dns_name: None,
             http_endpoint: None,
+            tls_pins: Vec::new(),
             trust_negative_responses: false,
             bind_addr: opts.bind.map(|ip| SocketAddr::new(ip, 0)),
         });
@@ -26,6 +26,7 @@ use std::{
     net::{IpAddr, SocketAddr},
     ops::Deref,
     path::PathBuf,
+    str::FromStr,
     sync::Arc,
     time::Duration,
 };
@@ -35,6 +36,10 @@ use console::style;
 use tokio::task::JoinSet;
 use tokio::time::MissedTickBehavior;
 
+#[cfg(feature = "__dnssec")]
+use hickory_proto::dnssec::ValidationTrace;
+#[cfg(feature = "__tls")]
+use hickory_proto::rustls::{default_provider, default_verifier};
 use hickory_proto::{
     ProtoError, ProtoErrorKind,
     rr::{Record, RecordData, RecordType},
@@ -102,10 +107,12 @@ struct Opts {
     #[clap(long)]
     quad9: bool,
 
-    /// Specify a nameserver to use, ip and port e.g. 8.8.8.8:53 or \[2001:4860:4860::8888\]:53 (port required)
-    /// ip:port are delimited by a comma like 8.8.8.8:53,1.1.1.1:53
+    /// Specify a nameserver to use, ip and port e.g. 8.8.8.8:53 or \[2001:4860:4860::8888\]:53 (port required),
+    /// a URL like tls://1.1.1.1:853#cloudflare-dns.com or https://192.0.2.1/dns-query#dns.example, or a DNS
+    /// stamp like sdns://... URLs must use an IP address, the TLS name goes in the fragment.
+    /// Nameservers are delimited by a comma like 8.8.8.8:53,1.1.1.1:53
     #[clap(short = 'n', long, use_value_delimiter = true, value_delimiter(','))]
-    nameserver: Vec<SocketAddr>,
+    nameserver: Vec<String>,
 
     /// Specify the IP address to connect from.
     #[clap(long)]
@@ -146,6 +153,16 @@ struct Opts {
     /// Set the time interval between requests (in seconds, useful with --file)
     #[clap(long, default_value = "1.0")]
     interval: f32,
+
+    /// Validate responses with DNSSEC
+    #[cfg(feature = "__dnssec")]
+    #[clap(long)]
+    validate: bool,
+
+    /// Print every step of the DNSSEC validation, implies --validate
+    #[cfg(feature = "__dnssec")]
+    #[clap(long, conflicts_with_all(&["happy", "reverse"]))]
+    vtrace: bool,
 }
 
 fn print_record<D: RecordData, R: Deref<Target = Record<D>>>(r: &R) {
@@ -196,6 +213,18 @@ fn print_result(result: Result<Lookup, ResolveError>) {
     }
 }
 
+#[cfg(feature = "__dnssec")]
+fn print_trace(name: &str, trace: &ValidationTrace) {
+    println!(
+        "{} for {}",
+        style("Validation trace").yellow(),
+        style(name).blue()
+    );
+    for event in trace.events() {
+        println!("\t{event}");
+    }
+}
+
 fn log_query(name: &str, ty: RecordType, name_servers: &str, opts: &Opts) {
     if opts.happy {
         println!(
@@ -227,7 +256,19 @@ async fn execute_query(
     happy: bool,
     reverse: bool,
     ty: RecordType,
+    vtrace: bool,
 ) -> Result<Lookup, ResolveError> {
+    #[cfg(feature = "__dnssec")]
+    if vtrace {
+        let (result, trace) = resolver
+            .lookup_with_validation_trace(name.as_str(), ty)
+            .await;
+        print_trace(&name, &trace);
+        return result;
+    }
+    #[cfg(not(feature = "__dnssec"))]
+    let _ = vtrace;
+
     if happy {
         Ok(resolver.lookup_ip(name.to_string()).await?.into())
     } else if reverse {
@@ -272,23 +313,34 @@ pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Configure all the name servers
     let mut name_servers = NameServerConfigGroup::new();
 
-    for socket_addr in &opts.nameserver {
+    for nameserver in &opts.nameserver {
+        let bind_addr = opts.bind.map(|ip| SocketAddr::new(ip, 0));
+        let Ok(socket_addr) = SocketAddr::from_str(nameserver) else {
+            let mut config = NameServerConfig::from_str(nameserver)?;
+            config.trust_negative_responses = false;
+            config.bind_addr = bind_addr;
+            name_servers.push(config);
+            continue;
+        };
+
         name_servers.push(NameServerConfig {
-            socket_addr: *socket_addr,
+            socket_addr,
             protocol: Protocol::Tcp,
             tls_dns_name: None,
             http_endpoint: None,
+            tls_pins: Vec::new(),
             trust_negative_responses: false,
-            bind_addr: opts.bind.map(|ip| SocketAddr::new(ip, 0)),
+            bind_addr,
         });
 
         name_servers.push(NameServerConfig {
-            socket_addr: *socket_addr,
+            socket_addr,
             protocol: Protocol::Udp,
             tls_dns_name: None,
             http_endpoint: None,
+            tls_pins: Vec::new(),
             trust_negative_responses: false,
-            bind_addr: opts.bind.map(|ip| SocketAddr::new(ip, 0)),
+            bind_addr,
         });
     }
 
@@ -313,8 +365,11 @@ pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     name_servers
         .retain(|ns| (ipv4 && ns.socket_addr.is_ipv4()) || (ipv6 && ns.socket_addr.is_ipv6()));
+    // the encrypted protocols are only configured explicitly
     name_servers.retain(|ns| {
-        (udp && ns.protocol == Protocol::Udp) || (tcp && ns.protocol == Protocol::Tcp)
+        ns.protocol.is_encrypted()
+            || (udp && ns.protocol == Protocol::Udp)
+            || (tcp && ns.protocol == Protocol::Tcp)
     });
 
     let mut config = sys_config.unwrap_or_else(ResolverConfig::new);
@@ -335,12 +390,26 @@ pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
     if opts.happy {
         options.ip_strategy = hickory_resolver::config::LookupIpStrategy::Ipv4AndIpv6;
     }
+    #[cfg(feature = "__dnssec")]
+    if opts.validate || opts.vtrace {
+        options.validate = true;
+    }
+    // the default tls_config is used, so pins of DNS stamps are checked on top of its verifier
+    #[cfg(feature = "__tls")]
+    {
+        options.tls_verifier = default_verifier(Arc::new(default_provider())).ok();
+    }
 
     let mut resolver_builder =
         TokioResolver::builder_with_config(config, TokioConnectionProvider::default());
     *resolver_builder.options_mut() = options;
     let resolver_arc = Arc::new(resolver_builder.build());
 
+    #[cfg(feature = "__dnssec")]
+    let vtrace = opts.vtrace;
+    #[cfg(not(feature = "__dnssec"))]
+    let vtrace = false;
+
     if let Some(domainname) = &opts.domainname {
         log_query(domainname, opts.ty, &name_servers, &opts);
         let lookup = execute_query(
@@ -349,6 +418,7 @@ pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
             opts.happy,
             opts.reverse,
             opts.ty,
+            vtrace,
         )
         .await;
         print_result(lookup);
@@ -363,7 +433,9 @@ pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
             let (happy, reverse, ty) = (opts.happy, opts.reverse, opts.ty);
             log_query(&name, ty, &name_servers, &opts);
             let resolver = resolver_arc.clone();
-            taskset.spawn(async move { execute_query(resolver, name, happy, reverse, ty).await });
+            taskset.spawn(async move {
+                execute_query(resolver, name, happy, reverse, ty, vtrace).await
+            });
             loop {
                 tokio::select! {
                     _ = timer.tick() => break,